@@ -309,6 +309,26 @@ pub async fn save_config(
         instance.axum_server.update_security(&config.proxy).await;
         // 更新 z.ai 配置
         instance.axum_server.update_zai(&config.proxy).await;
+        // 更新推理预算配置
+        instance.axum_server.update_thinking(&config.proxy).await;
+        // 更新图像存储配置
+        instance.axum_server.update_image_storage(&config.proxy).await;
+        // 更新文生图配置
+        instance.axum_server.update_image_generation(&config.proxy).await;
+        // 更新图像预处理配置
+        instance.axum_server.update_image_preprocess(&config.proxy).await;
+        // 更新远程媒体下载配置
+        instance.axum_server.update_media_fetch(&config.proxy).await;
+        // 更新模型回退链配置
+        instance.axum_server.update_model_fallback(&config.proxy).await;
+        // 更新后台任务降级规则
+        instance.axum_server.update_background_tasks(&config.proxy).await;
+        // 更新参数策略
+        instance.axum_server.update_parameter_policies(&config.proxy).await;
+        // 更新脚本钩子
+        instance.axum_server.update_hooks(&config.proxy).await;
+        // 更新上游服务商
+        instance.axum_server.update_providers(&config.proxy).await;
         tracing::debug!("已同步热更新反代服务配置");
     }
 
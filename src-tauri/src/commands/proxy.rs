@@ -84,7 +84,8 @@ pub async fn start_proxy_service(
     if active_accounts == 0 {
         let zai_enabled = config.zai.enabled
             && !matches!(config.zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
-        if !zai_enabled {
+        let has_providers = config.providers.iter().any(|p| p.enabled);
+        if !zai_enabled && !has_providers {
             return Err("没有可用账号，请先添加账号".to_string());
         }
     }
@@ -95,14 +96,24 @@ pub async fn start_proxy_service(
             config.get_bind_address().to_string(),
             config.port,
             token_manager.clone(),
-            config.custom_mapping.clone(),
+            config.routing_rules.clone(),
             config.request_timeout,
             config.upstream_proxy.clone(),
             crate::proxy::ProxySecurityConfig::from_proxy_config(&config),
             config.zai.clone(),
             monitor.clone(),
             config.experimental.clone(),
-
+            config.thinking.clone(),
+            config.image_storage.clone(),
+            config.image_generation.clone(),
+            config.image_preprocess.clone(),
+            config.media_fetch.clone(),
+            config.model_fallback.clone(),
+            config.background_tasks.clone(),
+            config.parameter_policies.clone(),
+            config.hooks.clone(),
+            config.providers.clone(),
+            config.admin_listener.clone(),
         ).await {
             Ok((server, handle)) => (server, handle),
             Err(e) => return Err(format!("启动 Axum 服务器失败: {}", e)),
@@ -259,8 +270,7 @@ pub async fn update_model_mapping(
 ) -> Result<(), String> {
     let instance_lock = state.instance.read().await;
     
-    // 1. 如果服务正在运行，立即更新内存中的映射 (这里目前只更新了 anthropic_mapping 的 RwLock, 
-    // 后续可以根据需要让 resolve_model_route 直接读取全量 config)
+    // 1. 如果服务正在运行，立即更新内存中的路由规则
     if let Some(instance) = instance_lock.as_ref() {
         instance.axum_server.update_mapping(&config).await;
         tracing::debug!("后端服务已接收全量模型映射配置");
@@ -268,7 +278,7 @@ pub async fn update_model_mapping(
     
     // 2. 无论是否运行，都保存到全局配置持久化
     let mut app_config = crate::modules::config::load_app_config().map_err(|e| e)?;
-    app_config.proxy.custom_mapping = config.custom_mapping;
+    app_config.proxy.routing_rules = config.routing_rules;
     crate::modules::config::save_app_config(&app_config).map_err(|e| e)?;
     
     Ok(())
@@ -342,9 +352,7 @@ pub async fn fetch_zai_models(
     if zai.base_url.trim().is_empty() {
         return Err("z.ai base_url is empty".to_string());
     }
-    if zai.api_key.trim().is_empty() {
-        return Err("z.ai api_key is not set".to_string());
-    }
+    let api_key = zai.all_api_keys().into_iter().next().ok_or("z.ai api_key is not set")?;
 
     let url = join_base_url(&zai.base_url, "/v1/models");
 
@@ -360,8 +368,8 @@ pub async fn fetch_zai_models(
 
     let resp = client
         .get(&url)
-        .header("Authorization", format!("Bearer {}", zai.api_key))
-        .header("x-api-key", zai.api_key)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("x-api-key", api_key)
         .header("anthropic-version", "2023-06-01")
         .header("accept", "application/json")
         .send()
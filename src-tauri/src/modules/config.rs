@@ -62,6 +62,23 @@ pub fn load_app_config() -> Result<AppConfig, String> {
         if modified {
             proxy.as_object_mut().unwrap().insert("custom_mapping".to_string(), serde_json::Value::Object(custom_mapping));
         }
+
+        // 迁移 custom_mapping 映射表 -> 有序路由规则 (追加在已有规则之后)
+        if let Some(legacy) = proxy.as_object_mut().unwrap().remove("custom_mapping") {
+            let mapping: Vec<(&str, &str)> = legacy
+                .as_object()
+                .map(|m| m.iter().filter_map(|(k, v)| v.as_str().map(|v| (k.as_str(), v))).collect())
+                .unwrap_or_default();
+            let migrated = crate::proxy::common::model_mapping::migrate_custom_mapping(mapping);
+            let mut rules = proxy
+                .get("routing_rules")
+                .and_then(|r| r.as_array())
+                .cloned()
+                .unwrap_or_default();
+            rules.extend(migrated.iter().filter_map(|r| serde_json::to_value(r).ok()));
+            proxy.as_object_mut().unwrap().insert("routing_rules".to_string(), serde_json::Value::Array(rules));
+            modified = true;
+        }
     }
 
     let config: AppConfig = serde_json::from_value(v)
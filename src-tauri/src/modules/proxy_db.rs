@@ -32,6 +32,7 @@ pub fn init_db() -> Result<(), String> {
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN output_tokens INTEGER", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN account_email TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN mapped_model TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN background_downgrade TEXT", []);
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_timestamp ON request_logs (timestamp DESC)",
@@ -46,8 +47,8 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
     conn.execute(
-        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, background_downgrade)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
         params![
             log.id,
             log.timestamp,
@@ -63,6 +64,7 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
             log.output_tokens,
             log.account_email,
             log.mapped_model,
+            log.background_downgrade,
         ],
     ).map_err(|e| e.to_string())?;
 
@@ -74,7 +76,7 @@ pub fn get_logs(limit: usize) -> Result<Vec<ProxyRequestLog>, String> {
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
     let mut stmt = conn.prepare(
-        "SELECT id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model
+        "SELECT id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, background_downgrade
          FROM request_logs 
          ORDER BY timestamp DESC 
          LIMIT ?1"
@@ -96,6 +98,7 @@ pub fn get_logs(limit: usize) -> Result<Vec<ProxyRequestLog>, String> {
             response_body: row.get(9).unwrap_or(None),
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
+            background_downgrade: row.get(14).unwrap_or(None),
         })
     }).map_err(|e| e.to_string())?;
 
@@ -0,0 +1,292 @@
+// 长音频分段
+// 在不解码的前提下按帧边界切分 WAV (PCM) 与 MP3，保证每段可独立播放，并计算精确的时间偏移。
+// 其他容器格式 (m4a/ogg/webm/flac/aiff) 需要解复用或转码，本代理不做处理：
+// 未超过单段上限时原样转录，超过时返回 413 并提示转换为 WAV / MP3。
+
+/// 可切分的格式 (超过单段上限时仍可上传)
+pub const SPLITTABLE_MIME_TYPES: [&str; 3] = ["audio/wav", "audio/mp3", "audio/mpeg"];
+
+/// 切分后的音频片段
+#[derive(Debug, Clone)]
+pub struct AudioChunk {
+    pub data: Vec<u8>,
+    /// 片段在原始音频中的起始时间 (秒)
+    pub start: f64,
+    /// 片段时长 (秒)，无法解析时为 None
+    pub duration: Option<f64>,
+}
+
+pub fn is_splittable(mime_type: &str) -> bool {
+    SPLITTABLE_MIME_TYPES.contains(&mime_type)
+}
+
+/// 不可切分格式超过单段上限时的错误信息 (列出支持自动分段的格式)
+pub fn unsplittable_error(mime_type: &str, max_chunk_bytes: usize) -> String {
+    format!(
+        "{} 格式的音频超过 {} MB，无法自动分段 (仅 wav / mp3 支持自动分段，m4a / ogg / webm / flac 等格式不会被解复用或转码)，请转换为 MP3 或 WAV 后重试",
+        mime_type,
+        max_chunk_bytes / (1024 * 1024)
+    )
+}
+
+/// 按时长与大小上限切分音频
+/// 未超过上限时原样返回单个片段；超过上限但格式无法切分时返回错误
+pub fn split_audio(
+    data: &[u8],
+    mime_type: &str,
+    max_chunk_secs: f64,
+    max_chunk_bytes: usize,
+) -> Result<Vec<AudioChunk>, String> {
+    let chunks = match mime_type {
+        "audio/wav" => split_wav(data, max_chunk_secs, max_chunk_bytes)?,
+        "audio/mp3" | "audio/mpeg" => split_mp3(data, max_chunk_secs, max_chunk_bytes)?,
+        _ => {
+            if data.len() > max_chunk_bytes {
+                return Err(unsplittable_error(mime_type, max_chunk_bytes));
+            }
+            vec![AudioChunk { data: data.to_vec(), start: 0.0, duration: None }]
+        }
+    };
+
+    if chunks.is_empty() {
+        return Err("音频不包含可识别的数据".to_string());
+    }
+    Ok(chunks)
+}
+
+struct WavInfo<'a> {
+    fmt: &'a [u8],
+    data: &'a [u8],
+    byte_rate: u32,
+    block_align: u16,
+}
+
+fn parse_wav(data: &[u8]) -> Result<WavInfo<'_>, String> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("无效的 WAV 文件头".to_string());
+    }
+
+    let mut fmt: Option<&[u8]> = None;
+    let mut pcm: Option<&[u8]> = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+        let body_start = pos + 8;
+        // 部分录音软件写入的 data 长度不准确，按实际可用长度截断
+        let body_end = body_start.saturating_add(size).min(data.len());
+        match id {
+            b"fmt " => fmt = Some(&data[body_start..body_end]),
+            b"data" => {
+                pcm = Some(&data[body_start..body_end]);
+                break;
+            }
+            _ => {}
+        }
+        pos = body_end + (size & 1);
+    }
+
+    let fmt = fmt.filter(|f| f.len() >= 16).ok_or("WAV 缺少 fmt 块")?;
+    let data = pcm.ok_or("WAV 缺少 data 块")?;
+    let byte_rate = u32::from_le_bytes([fmt[8], fmt[9], fmt[10], fmt[11]]);
+    let block_align = u16::from_le_bytes([fmt[12], fmt[13]]);
+    if byte_rate == 0 || block_align == 0 {
+        return Err("WAV fmt 块参数无效".to_string());
+    }
+    Ok(WavInfo { fmt, data, byte_rate, block_align })
+}
+
+fn build_wav(fmt: &[u8], pcm: &[u8]) -> Vec<u8> {
+    let riff_size = 4 + (8 + fmt.len()) + (8 + pcm.len());
+    let mut out = Vec::with_capacity(8 + riff_size);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(riff_size as u32).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+    out.extend_from_slice(fmt);
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(pcm.len() as u32).to_le_bytes());
+    out.extend_from_slice(pcm);
+    out
+}
+
+fn split_wav(data: &[u8], max_chunk_secs: f64, max_chunk_bytes: usize) -> Result<Vec<AudioChunk>, String> {
+    let info = parse_wav(data)?;
+    let header_len = 20 + info.fmt.len() + 8;
+    let align = info.block_align as usize;
+
+    let by_time = (info.byte_rate as f64 * max_chunk_secs) as usize;
+    let by_size = max_chunk_bytes.saturating_sub(header_len);
+    let chunk_len = (by_time.min(by_size) / align * align).max(align);
+
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    while offset < info.data.len() {
+        let end = (offset + chunk_len).min(info.data.len());
+        let pcm = &info.data[offset..end];
+        chunks.push(AudioChunk {
+            data: build_wav(info.fmt, pcm),
+            start: offset as f64 / info.byte_rate as f64,
+            duration: Some(pcm.len() as f64 / info.byte_rate as f64),
+        });
+        offset = end;
+    }
+    Ok(chunks)
+}
+
+/// MP3 帧头信息 (仅 Layer III)
+struct Mp3Frame {
+    len: usize,
+    samples: u32,
+    sample_rate: u32,
+}
+
+fn parse_mp3_frame(header: &[u8]) -> Option<Mp3Frame> {
+    const BITRATES_V1: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const BITRATES_V2: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    if header.len() < 4 || header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+    let version = (header[1] >> 3) & 0x03; // 0: MPEG2.5, 2: MPEG2, 3: MPEG1
+    let layer = (header[1] >> 1) & 0x03; // 1: Layer III
+    let bitrate_idx = (header[2] >> 4) as usize;
+    let rate_idx = ((header[2] >> 2) & 0x03) as usize;
+    let padding = ((header[2] >> 1) & 0x01) as usize;
+    if version == 1 || layer != 1 || bitrate_idx == 0 || bitrate_idx == 15 || rate_idx == 3 {
+        return None;
+    }
+
+    let (bitrate, sample_rate, samples, coeff) = match version {
+        3 => (BITRATES_V1[bitrate_idx], [44100, 48000, 32000][rate_idx], 1152, 144),
+        2 => (BITRATES_V2[bitrate_idx], [22050, 24000, 16000][rate_idx], 576, 72),
+        _ => (BITRATES_V2[bitrate_idx], [11025, 12000, 8000][rate_idx], 576, 72),
+    };
+    let len = (coeff * bitrate * 1000 / sample_rate) as usize + padding;
+    Some(Mp3Frame { len, samples, sample_rate })
+}
+
+/// 跳过 ID3v2 标签
+fn skip_id3v2(data: &[u8]) -> usize {
+    if data.len() < 10 || &data[0..3] != b"ID3" {
+        return 0;
+    }
+    let size = data[6..10].iter().fold(0usize, |acc, &b| (acc << 7) | (b & 0x7F) as usize);
+    let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+    (10 + size + footer).min(data.len())
+}
+
+fn split_mp3(data: &[u8], max_chunk_secs: f64, max_chunk_bytes: usize) -> Result<Vec<AudioChunk>, String> {
+    let mut chunks = Vec::new();
+    let mut pos = skip_id3v2(data);
+    let mut chunk_start_pos: Option<usize> = None;
+    let mut chunk_secs = 0.0;
+    let mut elapsed = 0.0;
+
+    while pos + 4 <= data.len() {
+        let Some(frame) = parse_mp3_frame(&data[pos..pos + 4]).filter(|f| pos + f.len <= data.len()) else {
+            // 非帧数据 (损坏或尾部标签)，逐字节重新同步
+            pos += 1;
+            continue;
+        };
+        let frame_secs = frame.samples as f64 / frame.sample_rate as f64;
+
+        if let Some(start) = chunk_start_pos {
+            if chunk_secs + frame_secs > max_chunk_secs || pos + frame.len - start > max_chunk_bytes {
+                chunks.push(AudioChunk {
+                    data: data[start..pos].to_vec(),
+                    start: elapsed - chunk_secs,
+                    duration: Some(chunk_secs),
+                });
+                chunk_start_pos = None;
+                chunk_secs = 0.0;
+            }
+        }
+        if chunk_start_pos.is_none() {
+            chunk_start_pos = Some(pos);
+        }
+        chunk_secs += frame_secs;
+        elapsed += frame_secs;
+        pos += frame.len;
+    }
+
+    if let Some(start) = chunk_start_pos {
+        chunks.push(AudioChunk {
+            data: data[start..pos.min(data.len())].to_vec(),
+            start: elapsed - chunk_secs,
+            duration: Some(chunk_secs),
+        });
+    }
+
+    if chunks.is_empty() {
+        return Err("无法解析 MP3 帧 (仅支持 MPEG Layer III)".to_string());
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_wav(secs: usize) -> Vec<u8> {
+        // 8kHz 16-bit mono: byte_rate = 16000, block_align = 2
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&1u16.to_le_bytes());
+        fmt.extend_from_slice(&1u16.to_le_bytes());
+        fmt.extend_from_slice(&8000u32.to_le_bytes());
+        fmt.extend_from_slice(&16000u32.to_le_bytes());
+        fmt.extend_from_slice(&2u16.to_le_bytes());
+        fmt.extend_from_slice(&16u16.to_le_bytes());
+        build_wav(&fmt, &vec![0u8; 16000 * secs])
+    }
+
+    fn make_mp3(frames: usize) -> Vec<u8> {
+        // MPEG1 Layer III, 128kbps, 44.1kHz, 无 padding: 417 字节/帧
+        let mut frame = vec![0xFF, 0xFB, 0x90, 0x00];
+        frame.resize(417, 0);
+        let mut data = b"ID3\x03\x00\x00\x00\x00\x00\x04".to_vec();
+        data.extend_from_slice(&[0u8; 4]);
+        for _ in 0..frames {
+            data.extend_from_slice(&frame);
+        }
+        data
+    }
+
+    #[test]
+    fn test_split_wav() {
+        let wav = make_wav(25);
+        let chunks = split_audio(&wav, "audio/wav", 10.0, usize::MAX).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].start, 10.0);
+        assert_eq!(chunks[2].duration, Some(5.0));
+        // 每段均为完整的 WAV 文件
+        assert!(parse_wav(&chunks[2].data).is_ok());
+        assert_eq!(parse_wav(&chunks[2].data).unwrap().data.len(), 80000);
+    }
+
+    #[test]
+    fn test_split_mp3() {
+        let mp3 = make_mp3(100); // 100 * 1152 / 44100 ≈ 2.61s
+        let chunks = split_audio(&mp3, "audio/mp3", 1.0, usize::MAX).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].data.len() % 417, 0);
+        assert!(chunks[0].data.starts_with(&[0xFF, 0xFB]));
+        let total: f64 = chunks.iter().filter_map(|c| c.duration).sum();
+        assert!((total - 100.0 * 1152.0 / 44100.0).abs() < 1e-9);
+        assert!((chunks[1].start - chunks[0].duration.unwrap()).abs() < 1e-9);
+
+        // 大小上限同样生效
+        let chunks = split_audio(&mp3, "audio/mp3", 600.0, 417 * 40).unwrap();
+        assert_eq!(chunks.len(), 3);
+    }
+
+    #[test]
+    fn test_unsplittable_format() {
+        let data = vec![0u8; 100];
+        assert_eq!(split_audio(&data, "audio/ogg", 10.0, 1000).unwrap().len(), 1);
+        let err = split_audio(&data, "audio/webm", 10.0, 50).unwrap_err();
+        assert!(err.contains("wav / mp3"), "{}", err);
+        assert!(!is_splittable("audio/mp4") && is_splittable("audio/mpeg"));
+    }
+}
@@ -1,6 +1,12 @@
+pub mod chunker;
+pub mod speech;
+pub mod transcript;
+
 use base64::{engine::general_purpose, Engine as _};
 use std::path::Path;
 
+pub const MAX_AUDIO_SIZE: usize = 15 * 1024 * 1024; // 15MB (单次请求 inlineData 上限)
+
 pub struct AudioProcessor;
 
 impl AudioProcessor {
@@ -27,10 +33,57 @@ impl AudioProcessor {
         general_purpose::STANDARD.encode(audio_data)
     }
 
+    /// 根据格式名 (如 input_audio.format 的 "wav" / "mp3") 检测 MIME 类型
+    pub fn mime_from_format(format: &str) -> Result<String, String> {
+        Self::detect_mime_type(&format!("audio.{}", format))
+    }
+
     /// 判断文件是否超过大小限制
     pub fn exceeds_size_limit(size_bytes: usize) -> bool {
-        const MAX_SIZE: usize = 15 * 1024 * 1024; // 15MB
-        size_bytes > MAX_SIZE
+        size_bytes > MAX_AUDIO_SIZE
+    }
+
+    /// Base64 数据解码后的字节数 (无需实际解码)
+    pub fn decoded_base64_len(data: &str) -> usize {
+        let padding = data.bytes().rev().take_while(|&b| b == b'=').count();
+        (data.len() / 4 * 3).saturating_sub(padding)
+    }
+
+    /// 校验消息中的音频输入 (远程 audio_url 已由 MediaFetcher 下载为 data URL)
+    /// - data URL / input_audio: 校验格式与大小限制
+    pub fn validate_audio_inputs(
+        messages: &[crate::proxy::mappers::openai::OpenAIMessage],
+    ) -> Result<(), String> {
+        use crate::proxy::mappers::openai::{OpenAIContent, OpenAIContentBlock};
+
+        for msg in messages {
+            let Some(OpenAIContent::Array(blocks)) = msg.content.as_ref() else {
+                continue;
+            };
+            for block in blocks {
+                match block {
+                    OpenAIContentBlock::AudioUrl { audio_url } => {
+                        if audio_url.url.starts_with("http://") || audio_url.url.starts_with("https://") {
+                            return Err("远程 audio_url 需要启用媒体下载 (media_fetch.enabled)".to_string());
+                        } else if let Some((_, data)) = audio_url.url.split_once(',') {
+                            if Self::exceeds_size_limit(Self::decoded_base64_len(data)) {
+                                return Err("音频数据超过 15 MB 限制".to_string());
+                            }
+                        } else {
+                            return Err("audio_url 必须为 http(s) 或 data URL".to_string());
+                        }
+                    }
+                    OpenAIContentBlock::InputAudio { input_audio } => {
+                        Self::mime_from_format(&input_audio.format)?;
+                        if Self::exceeds_size_limit(Self::decoded_base64_len(&input_audio.data)) {
+                            return Err("音频数据超过 15 MB 限制".to_string());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
     }
 }
 
@@ -59,6 +112,15 @@ mod tests {
         assert!(!AudioProcessor::exceeds_size_limit(15 * 1024 * 1024)); // 刚好等于限制
     }
 
+    #[test]
+    fn test_mime_from_format_and_decoded_len() {
+        assert_eq!(AudioProcessor::mime_from_format("wav").unwrap(), "audio/wav");
+        assert!(AudioProcessor::mime_from_format("txt").is_err());
+
+        let encoded = AudioProcessor::encode_to_base64(b"hello");
+        assert_eq!(AudioProcessor::decoded_base64_len(&encoded), 5);
+    }
+
     #[test]
     fn test_base64_encoding() {
         let data = b"test audio data";
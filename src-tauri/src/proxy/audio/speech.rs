@@ -0,0 +1,216 @@
+// 语音合成 (OpenAI /v1/audio/speech ↔ Gemini TTS)
+// Gemini TTS 返回 16-bit 单声道 PCM (audio/L16;codec=pcm;rate=24000)，此处负责音色映射与容器封装
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Gemini TTS 默认采样率
+pub const DEFAULT_SAMPLE_RATE: u32 = 24000;
+
+/// Gemini 预置音色
+const GEMINI_VOICES: [&str; 30] = [
+    "Zephyr", "Puck", "Charon", "Kore", "Fenrir", "Leda", "Orus", "Aoede", "Callirrhoe", "Autonoe",
+    "Enceladus", "Iapetus", "Umbriel", "Algieba", "Despina", "Erinome", "Algenib", "Rasalgethi",
+    "Laomedeia", "Achernar", "Alnilam", "Schedar", "Gacrux", "Pulcherrima", "Achird",
+    "Zubenelgenubi", "Vindemiatrix", "Sadachbia", "Sadaltager", "Sulafat",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpeechRequest {
+    pub model: String,
+    pub input: String,
+    #[serde(default)]
+    pub voice: Option<String>,
+    /// wav (未指定时的默认值) / pcm；显式请求 mp3/opus/aac/flac 时无编码器，返回 400
+    #[serde(default)]
+    pub response_format: Option<String>,
+    /// 0.25 - 4.0
+    #[serde(default)]
+    pub speed: Option<f32>,
+    /// gpt-4o-mini-tts 风格指令
+    #[serde(default)]
+    pub instructions: Option<String>,
+}
+
+/// 输出容器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeechFormat {
+    Wav,
+    Pcm,
+}
+
+impl SpeechFormat {
+    /// 解析 response_format
+    /// 未指定时默认 WAV (OpenAI SDK 默认不发送该字段，其默认值 mp3 无法编码)；
+    /// 显式请求 mp3/opus/aac/flac 时报错，不静默替换为 WAV
+    pub fn resolve(format: Option<&str>) -> Result<Self, String> {
+        match format.unwrap_or("wav") {
+            "wav" => Ok(Self::Wav),
+            "pcm" => Ok(Self::Pcm),
+            other @ ("mp3" | "opus" | "aac" | "flac") => Err(format!(
+                "response_format '{}' is not supported by this proxy (no {} encoder); request 'wav' or 'pcm' explicitly",
+                other, other
+            )),
+            other => Err(format!("Unsupported response_format: {}", other)),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Wav => "audio/wav",
+            Self::Pcm => "audio/pcm",
+        }
+    }
+}
+
+/// OpenAI 音色 -> Gemini 音色；已是 Gemini 音色名时直接使用
+pub fn map_voice(voice: Option<&str>) -> String {
+    let voice = voice.unwrap_or("alloy");
+    if let Some(v) = GEMINI_VOICES.iter().find(|v| v.eq_ignore_ascii_case(voice)) {
+        return v.to_string();
+    }
+    match voice.to_lowercase().as_str() {
+        "echo" => "Charon",
+        "fable" => "Puck",
+        "onyx" => "Fenrir",
+        "nova" => "Aoede",
+        "shimmer" => "Leda",
+        "ash" => "Orus",
+        "ballad" => "Umbriel",
+        "coral" => "Despina",
+        "sage" => "Iapetus",
+        "verse" => "Zephyr",
+        _ => "Kore", // alloy 及未知音色
+    }
+    .to_string()
+}
+
+/// 构建 Gemini TTS 请求体
+/// Gemini 不支持语速参数，通过自然语言指令控制
+pub fn build_tts_request(req: &SpeechRequest) -> Value {
+    let mut directions = Vec::new();
+    if let Some(inst) = req.instructions.as_deref().filter(|s| !s.trim().is_empty()) {
+        directions.push(inst.trim().to_string());
+    }
+    match req.speed {
+        Some(s) if s < 0.9 => directions.push(format!("Speak slowly, at about {:.2}x normal pace.", s)),
+        Some(s) if s > 1.1 => directions.push(format!("Speak quickly, at about {:.2}x normal pace.", s)),
+        _ => {}
+    }
+    let text = if directions.is_empty() {
+        req.input.clone()
+    } else {
+        format!("{}\n\n{}", directions.join(" "), req.input)
+    };
+
+    json!({
+        "contents": [{ "role": "user", "parts": [{ "text": text }] }],
+        "generationConfig": {
+            "responseModalities": ["AUDIO"],
+            "speechConfig": {
+                "voiceConfig": { "prebuiltVoiceConfig": { "voiceName": map_voice(req.voice.as_deref()) } }
+            }
+        }
+    })
+}
+
+/// 从 mimeType (如 "audio/L16;codec=pcm;rate=24000") 解析采样率
+pub fn parse_sample_rate(mime_type: &str) -> u32 {
+    mime_type
+        .split(';')
+        .filter_map(|p| p.trim().strip_prefix("rate="))
+        .find_map(|r| r.parse().ok())
+        .unwrap_or(DEFAULT_SAMPLE_RATE)
+}
+
+/// 16-bit 单声道 WAV 文件头
+/// data_len 为 None 时写入最大长度，用于流式输出 (播放器会读到流结束)
+pub fn wav_header(sample_rate: u32, data_len: Option<u32>) -> Vec<u8> {
+    let data_len = data_len.unwrap_or(u32::MAX - 36);
+    let mut h = Vec::with_capacity(44);
+    h.extend_from_slice(b"RIFF");
+    h.extend_from_slice(&(36u32.saturating_add(data_len)).to_le_bytes());
+    h.extend_from_slice(b"WAVE");
+    h.extend_from_slice(b"fmt ");
+    h.extend_from_slice(&16u32.to_le_bytes());
+    h.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    h.extend_from_slice(&1u16.to_le_bytes()); // mono
+    h.extend_from_slice(&sample_rate.to_le_bytes());
+    h.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    h.extend_from_slice(&2u16.to_le_bytes());
+    h.extend_from_slice(&16u16.to_le_bytes());
+    h.extend_from_slice(b"data");
+    h.extend_from_slice(&data_len.to_le_bytes());
+    h
+}
+
+/// 从 Gemini 响应块中提取音频 (mimeType, base64 data)
+pub fn extract_audio_parts(chunk: &Value) -> Vec<(String, String)> {
+    let inner = chunk.get("response").unwrap_or(chunk);
+    inner
+        .get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|p| p.get("inlineData"))
+                .filter_map(|d| {
+                    let mime = d.get("mimeType").and_then(|v| v.as_str()).unwrap_or("audio/L16");
+                    let data = d.get("data").and_then(|v| v.as_str())?;
+                    Some((mime.to_string(), data.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_voice_and_format_mapping() {
+        assert_eq!(map_voice(Some("alloy")), "Kore");
+        assert_eq!(map_voice(Some("onyx")), "Fenrir");
+        assert_eq!(map_voice(Some("puck")), "Puck");
+        assert_eq!(map_voice(None), "Kore");
+
+        assert_eq!(SpeechFormat::resolve(Some("wav")).unwrap(), SpeechFormat::Wav);
+        assert_eq!(SpeechFormat::resolve(Some("pcm")).unwrap(), SpeechFormat::Pcm);
+        // 未指定时默认 WAV；显式请求无编码器的格式时明确报错
+        assert_eq!(SpeechFormat::resolve(None).unwrap(), SpeechFormat::Wav);
+        assert!(SpeechFormat::resolve(Some("mp3")).unwrap_err().contains("'wav' or 'pcm'"));
+        assert!(SpeechFormat::resolve(Some("opus")).is_err());
+        assert!(SpeechFormat::resolve(Some("ogg")).is_err());
+    }
+
+    #[test]
+    fn test_tts_request_and_audio_helpers() {
+        let req: SpeechRequest = serde_json::from_value(json!({
+            "model": "tts-1", "input": "Hello", "voice": "nova", "speed": 1.5
+        })).unwrap();
+        let body = build_tts_request(&req);
+        assert_eq!(body["generationConfig"]["responseModalities"][0], "AUDIO");
+        assert_eq!(
+            body["generationConfig"]["speechConfig"]["voiceConfig"]["prebuiltVoiceConfig"]["voiceName"],
+            "Aoede"
+        );
+        assert!(body["contents"][0]["parts"][0]["text"].as_str().unwrap().ends_with("\n\nHello"));
+
+        assert_eq!(parse_sample_rate("audio/L16;codec=pcm;rate=16000"), 16000);
+        assert_eq!(parse_sample_rate("audio/L16"), DEFAULT_SAMPLE_RATE);
+
+        let header = wav_header(24000, Some(100));
+        assert_eq!(header.len(), 44);
+        assert_eq!(u32::from_le_bytes(header[4..8].try_into().unwrap()), 136);
+        assert_eq!(u32::from_le_bytes(header[28..32].try_into().unwrap()), 48000);
+
+        let chunk = json!({ "response": { "candidates": [{ "content": { "parts": [
+            { "inlineData": { "mimeType": "audio/L16;codec=pcm;rate=24000", "data": "AAA=" } }
+        ] } }] } });
+        assert_eq!(extract_audio_parts(&chunk), vec![("audio/L16;codec=pcm;rate=24000".to_string(), "AAA=".to_string())]);
+    }
+}
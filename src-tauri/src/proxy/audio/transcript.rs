@@ -0,0 +1,310 @@
+// 转录结果处理: Gemini 分段输出解析、分段拼接与 Whisper 兼容格式输出
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// 转录分段 (时间单位: 秒)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// 单个音频片段的转录结果
+#[derive(Debug, Clone, Default)]
+pub struct ChunkTranscript {
+    pub language: Option<String>,
+    pub segments: Vec<Segment>,
+}
+
+/// Whisper response_format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    Json,
+    Text,
+    Srt,
+    Vtt,
+    VerboseJson,
+}
+
+impl TranscriptFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            "srt" => Ok(Self::Srt),
+            "vtt" => Ok(Self::Vtt),
+            "verbose_json" => Ok(Self::VerboseJson),
+            other => Err(format!("不支持的 response_format: {}", other)),
+        }
+    }
+}
+
+/// 请求 Gemini 以此 Schema 返回分段结果
+pub fn segments_response_schema() -> Value {
+    json!({
+        "type": "OBJECT",
+        "properties": {
+            "language": { "type": "STRING" },
+            "segments": {
+                "type": "ARRAY",
+                "items": {
+                    "type": "OBJECT",
+                    "properties": {
+                        "start": { "type": "NUMBER" },
+                        "end": { "type": "NUMBER" },
+                        "text": { "type": "STRING" }
+                    },
+                    "required": ["start", "end", "text"]
+                }
+            }
+        },
+        "required": ["segments"]
+    })
+}
+
+/// 解析 Gemini 返回的分段 JSON
+/// 解析失败时将整段文本视为单个分段 (覆盖整个片段时长)
+pub fn parse_chunk_transcript(raw: &str, chunk_duration: Option<f64>) -> ChunkTranscript {
+    #[derive(Deserialize)]
+    struct Raw {
+        #[serde(default)]
+        language: Option<String>,
+        #[serde(default)]
+        segments: Vec<Segment>,
+    }
+
+    let trimmed = raw
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    match serde_json::from_str::<Raw>(trimmed) {
+        Ok(parsed) => {
+            let limit = chunk_duration.unwrap_or(f64::MAX);
+            let mut segments: Vec<Segment> = parsed
+                .segments
+                .into_iter()
+                .filter(|s| !s.text.trim().is_empty())
+                .map(|s| {
+                    let start = s.start.clamp(0.0, limit);
+                    Segment {
+                        start,
+                        end: s.end.clamp(start, limit),
+                        text: s.text.trim().to_string(),
+                    }
+                })
+                .collect();
+            segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+            ChunkTranscript {
+                language: parsed.language.filter(|l| !l.is_empty()),
+                segments,
+            }
+        }
+        Err(e) => {
+            tracing::warn!("[Audio] Failed to parse segment JSON, using plain text: {}", e);
+            let text = raw.trim();
+            ChunkTranscript {
+                language: None,
+                segments: if text.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![Segment { start: 0.0, end: chunk_duration.unwrap_or(0.0), text: text.to_string() }]
+                },
+            }
+        }
+    }
+}
+
+/// 拼接各片段结果，按片段起始时间修正时间戳
+pub fn stitch_segments(chunks: Vec<(f64, ChunkTranscript)>) -> (Option<String>, Vec<Segment>) {
+    let mut language = None;
+    let mut segments = Vec::new();
+    for (offset, chunk) in chunks {
+        if language.is_none() {
+            language = chunk.language;
+        }
+        segments.extend(chunk.segments.into_iter().map(|s| Segment {
+            start: s.start + offset,
+            end: s.end + offset,
+            text: s.text,
+        }));
+    }
+    (language, segments)
+}
+
+/// 拼接各分段文本: 默认以空格分隔，边界任一侧为中日文字符 (汉字/假名/全角标点) 时直接相连
+pub fn full_text(segments: &[Segment]) -> String {
+    let mut text = String::new();
+    for segment in segments.iter().map(|s| s.text.as_str()).filter(|t| !t.is_empty()) {
+        let joins_directly = text.chars().next_back().is_some_and(is_cjk) || segment.chars().next().is_some_and(is_cjk);
+        if !text.is_empty() && !joins_directly {
+            text.push(' ');
+        }
+        text.push_str(segment);
+    }
+    text
+}
+
+/// 书写时词间不加空格的字符 (韩文使用空格分词，不在此列)
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x303F // CJK 标点
+        | 0x3040..=0x30FF // 平假名、片假名
+        | 0x3400..=0x4DBF // 扩展 A
+        | 0x4E00..=0x9FFF // 基本汉字
+        | 0xF900..=0xFAFF // 兼容汉字
+        | 0xFF00..=0xFFEF // 全角字符
+        | 0x20000..=0x2FA1F // 扩展 B 及以后
+    )
+}
+
+fn format_timestamp(secs: f64, decimal_sep: char) -> String {
+    let total_ms = (secs.max(0.0) * 1000.0).round() as u64;
+    let (h, m, s, ms) = (
+        total_ms / 3_600_000,
+        (total_ms / 60_000) % 60,
+        (total_ms / 1000) % 60,
+        total_ms % 1000,
+    );
+    format!("{:02}:{:02}:{:02}{}{:03}", h, m, s, decimal_sep, ms)
+}
+
+pub fn to_srt(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                format_timestamp(s.start, ','),
+                format_timestamp(s.end, ','),
+                s.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn to_vtt(segments: &[Segment]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for s in segments {
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_timestamp(s.start, '.'),
+            format_timestamp(s.end, '.'),
+            s.text
+        ));
+    }
+    out
+}
+
+/// 构建 verbose_json 响应
+/// 上游不提供词级时间戳，words 按字符长度在分段内线性分配 (近似值)
+pub fn to_verbose_json(
+    task: &str,
+    language: Option<&str>,
+    duration: f64,
+    segments: &[Segment],
+    include_words: bool,
+) -> Value {
+    let segments_json: Vec<Value> = segments
+        .iter()
+        .enumerate()
+        .map(|(id, s)| {
+            json!({
+                "id": id,
+                "seek": 0,
+                "start": s.start,
+                "end": s.end,
+                "text": s.text,
+                "tokens": [],
+                "temperature": 0.0,
+                "avg_logprob": 0.0,
+                "compression_ratio": 0.0,
+                "no_speech_prob": 0.0
+            })
+        })
+        .collect();
+
+    let mut result = json!({
+        "task": task,
+        "language": language.unwrap_or("unknown"),
+        "duration": duration,
+        "text": full_text(segments),
+        "segments": segments_json,
+    });
+
+    if include_words {
+        let mut words = Vec::new();
+        for s in segments {
+            let tokens: Vec<&str> = s.text.split_whitespace().collect();
+            let total_chars: usize = tokens.iter().map(|w| w.chars().count()).sum();
+            if total_chars == 0 {
+                continue;
+            }
+            let span = s.end - s.start;
+            let mut cursor = s.start;
+            for w in tokens {
+                let len = span * w.chars().count() as f64 / total_chars as f64;
+                words.push(json!({ "word": w, "start": cursor, "end": cursor + len }));
+                cursor += len;
+            }
+        }
+        result["words"] = json!(words);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(start: f64, end: f64, text: &str) -> Segment {
+        Segment { start, end, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_parse_and_stitch() {
+        let first = parse_chunk_transcript(
+            r#"{"language":"en","segments":[{"start":1.5,"end":3.0,"text":" world "},{"start":0,"end":1.5,"text":"hello"}]}"#,
+            Some(600.0),
+        );
+        assert_eq!(first.segments[0].text, "hello");
+        let second = parse_chunk_transcript(r#"{"segments":[{"start":2,"end":999,"text":"again"}]}"#, Some(10.0));
+        assert_eq!(second.segments[0].end, 10.0);
+
+        let (language, segments) = stitch_segments(vec![(0.0, first), (600.0, second)]);
+        assert_eq!(language.as_deref(), Some("en"));
+        assert_eq!(segments[2], seg(602.0, 610.0, "again"));
+        assert_eq!(full_text(&segments), "hello world again");
+        // 中日文边界不插入空格
+        let cjk = vec![seg(0.0, 1.0, "今天天气很好，"), seg(1.0, 2.0, "我们去公园"), seg(2.0, 3.0, "OK"), seg(3.0, 4.0, "ですね")];
+        assert_eq!(full_text(&cjk), "今天天气很好，我们去公园OKですね");
+        assert_eq!(full_text(&[seg(0.0, 1.0, "안녕하세요"), seg(1.0, 2.0, "여러분")]), "안녕하세요 여러분");
+
+        let fallback = parse_chunk_transcript("not json", Some(5.0));
+        assert_eq!(fallback.segments, vec![seg(0.0, 5.0, "not json")]);
+    }
+
+    #[test]
+    fn test_subtitle_formats() {
+        let segments = vec![seg(0.0, 1.25, "Hi"), seg(3661.5, 3662.0, "Bye")];
+        assert_eq!(
+            to_srt(&segments),
+            "1\n00:00:00,000 --> 00:00:01,250\nHi\n\n2\n01:01:01,500 --> 01:01:02,000\nBye\n"
+        );
+        assert!(to_vtt(&segments).starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:01.250\nHi\n"));
+
+        let verbose = to_verbose_json("transcribe", Some("en"), 3662.0, &segments, true);
+        assert_eq!(verbose["segments"][1]["start"], 3661.5);
+        assert_eq!(verbose["words"][1]["word"], "Bye");
+        assert_eq!(verbose["text"], "Hi Bye");
+        assert!(TranscriptFormat::parse("xml").is_err());
+    }
+}
@@ -0,0 +1,198 @@
+// 后台任务识别 (标题生成、摘要、提示建议等)
+// 规则来自 ProxyConfig.background_tasks，Claude / OpenAI / Gemini 三种协议共用
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+
+use crate::proxy::config::BackgroundTaskConfig;
+
+/// 只检查消息开头部分，避免长消息中的偶然匹配
+const PREVIEW_CHARS: usize = 500;
+
+/// 识别结果
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct BackgroundTaskMatch {
+    /// 命中的类别名
+    pub category: String,
+    /// 降级目标模型
+    pub target_model: String,
+    /// 命中的关键词或正则 (用于审计误判)
+    pub matched: String,
+}
+
+impl BackgroundTaskMatch {
+    /// 请求日志中记录的降级说明: "类别: 原模型 -> 目标模型"
+    pub fn describe(&self, from_model: &str) -> String {
+        format!("{}: {} -> {}", self.category, from_model, self.target_model)
+    }
+}
+
+/// 根据最后一条用户消息识别后台任务
+pub fn detect_background_task(last_user_message: &str, config: &BackgroundTaskConfig) -> Option<BackgroundTaskMatch> {
+    if !config.enabled || last_user_message.len() > config.max_message_length {
+        return None;
+    }
+    let preview: String = last_user_message.chars().take(PREVIEW_CHARS).collect();
+
+    config.categories.iter().filter(|c| c.enabled).find_map(|category| {
+        let matched = category
+            .keywords
+            .iter()
+            .find(|kw| !kw.is_empty() && preview.contains(kw.as_str()))
+            .or_else(|| category.patterns.iter().find(|p| regex_search(p, &preview)))?;
+        Some(BackgroundTaskMatch {
+            category: category.name.clone(),
+            target_model: category.target_model.clone(),
+            matched: matched.clone(),
+        })
+    })
+}
+
+fn regex_search(pattern: &str, text: &str) -> bool {
+    static CACHE: Lazy<Mutex<HashMap<String, Option<regex::Regex>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+    let Ok(mut cache) = CACHE.lock() else {
+        return false;
+    };
+    cache
+        .entry(pattern.to_string())
+        .or_insert_with(|| match regex::Regex::new(pattern) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                tracing::warn!("[Background-Task] 无效的后台任务正则 {}: {}", pattern, e);
+                None
+            }
+        })
+        .as_ref()
+        .is_some_and(|re| re.is_match(text))
+}
+
+/// 过滤不参与识别的消息 (空消息、Warmup 与 system-reminder 注入)
+fn detectable(content: String) -> Option<String> {
+    if content.trim().is_empty() || content.starts_with("Warmup") || content.contains("<system-reminder>") {
+        None
+    } else {
+        Some(content)
+    }
+}
+
+/// Claude: 最后一条可识别的用户文本
+pub fn last_user_text_claude(request: &crate::proxy::mappers::claude::ClaudeRequest) -> Option<String> {
+    use crate::proxy::mappers::claude::{ContentBlock, MessageContent};
+
+    request.messages.iter().rev().filter(|m| m.role == "user").find_map(|m| {
+        let content = match &m.content {
+            MessageContent::String(s) => s.to_string(),
+            MessageContent::Array(arr) => arr
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        detectable(content)
+    })
+}
+
+/// OpenAI: 最后一条可识别的用户文本
+pub fn last_user_text_openai(messages: &[crate::proxy::mappers::openai::OpenAIMessage]) -> Option<String> {
+    use crate::proxy::mappers::openai::{OpenAIContent, OpenAIContentBlock};
+
+    messages.iter().rev().filter(|m| m.role == "user").find_map(|m| {
+        let content = match m.content.as_ref()? {
+            OpenAIContent::String(s) => s.to_string(),
+            OpenAIContent::Array(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    OpenAIContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        detectable(content)
+    })
+}
+
+/// Gemini: contents 中最后一条可识别的用户文本
+pub fn last_user_text_gemini(body: &Value) -> Option<String> {
+    body.get("contents")?
+        .as_array()?
+        .iter()
+        .rev()
+        .filter(|c| c.get("role").and_then(|r| r.as_str()).unwrap_or("user") == "user")
+        .find_map(|c| {
+            let content = c
+                .get("parts")?
+                .as_array()?
+                .iter()
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join(" ");
+            detectable(content)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config::BackgroundTaskCategory;
+
+    #[test]
+    fn test_detect_background_task() {
+        let mut config = BackgroundTaskConfig::default();
+
+        let title = detect_background_task("Please write a 5-10 word title for this chat", &config).unwrap();
+        assert_eq!(title.category, "title_generation");
+        assert_eq!(title.target_model, "gemini-2.5-flash-lite");
+        assert_eq!(title.describe("claude-opus-4-5-thinking"), "title_generation: claude-opus-4-5-thinking -> gemini-2.5-flash-lite");
+
+        // 简短摘要优先于上下文压缩
+        assert_eq!(detect_background_task("Summarize the conversation in under 50 characters", &config).unwrap().category, "simple_summary");
+        assert_eq!(detect_background_task("Summarize the conversation", &config).unwrap().target_model, "gemini-2.5-flash");
+
+        // 过长消息与普通请求不降级
+        assert!(detect_background_task(&format!("Generate a title for {}", "x".repeat(900)), &config).is_none());
+        assert!(detect_background_task("Refactor this function", &config).is_none());
+
+        // 自定义正则类别
+        config.categories.insert(0, BackgroundTaskCategory {
+            name: "commit_message".to_string(),
+            enabled: true,
+            keywords: Vec::new(),
+            patterns: vec![r"(?i)^write a commit message".to_string()],
+            target_model: "gemini-3-flash".to_string(),
+        });
+        let commit = detect_background_task("Write a commit message for the diff", &config).unwrap();
+        assert_eq!((commit.category.as_str(), commit.matched.as_str()), ("commit_message", r"(?i)^write a commit message"));
+
+        // 禁用类别与总开关
+        config.categories.iter_mut().for_each(|c| c.enabled = c.name != "title_generation");
+        assert!(detect_background_task("Generate a title for this", &config).is_none());
+        config.enabled = false;
+        assert!(detect_background_task("Write a commit message for the diff", &config).is_none());
+    }
+
+    #[test]
+    fn test_last_user_text_extraction() {
+        let body = serde_json::json!({
+            "contents": [
+                { "role": "user", "parts": [{ "text": "Generate a title for" }, { "text": "this chat" }] },
+                { "role": "model", "parts": [{ "text": "ok" }] },
+                { "role": "user", "parts": [{ "text": "<system-reminder>ignored</system-reminder>" }] }
+            ]
+        });
+        assert_eq!(last_user_text_gemini(&body).as_deref(), Some("Generate a title for this chat"));
+
+        let messages: Vec<crate::proxy::mappers::openai::OpenAIMessage> = serde_json::from_value(serde_json::json!([
+            { "role": "user", "content": [{ "type": "text", "text": "Concise summary" }] },
+            { "role": "assistant", "content": "done" }
+        ]))
+        .unwrap();
+        assert_eq!(last_user_text_openai(&messages).as_deref(), Some("Concise summary"));
+    }
+}
@@ -1,3 +1,6 @@
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde_json::Value;
 
 /// 递归清理 JSON Schema 以符合 Gemini 接口要求
@@ -281,6 +284,224 @@ fn extract_type_from_union(union_array: &Vec<Value>) -> Option<String> {
     None
 }
 
+/// 按原始 JSON Schema 校验实例 (用于 Structured Outputs strict 模式)
+///
+/// 仅覆盖 OpenAI strict 模式常用子集: type, properties, required, additionalProperties,
+/// items, enum, const, anyOf/oneOf/allOf, $ref ($defs/definitions) 以及常见数值/长度约束。
+/// 返回第一个不匹配项的 JSON Pointer 路径与原因。
+pub fn validate_json_schema(schema: &Value, instance: &Value) -> Result<(), String> {
+    validate_node(schema, schema, instance, "$", 0)
+}
+
+/// $ref 最大解析深度 (防止循环引用)
+const MAX_VALIDATE_DEPTH: usize = 64;
+
+fn validate_node(root: &Value, schema: &Value, instance: &Value, path: &str, depth: usize) -> Result<(), String> {
+    if depth > MAX_VALIDATE_DEPTH {
+        return Err(format!("{}: schema nesting too deep", path));
+    }
+
+    let map = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{}: no value is allowed here", path)),
+        Value::Object(map) => map,
+        _ => return Ok(()),
+    };
+
+    // 1. $ref (仅支持文档内引用 #/$defs/X 与 #/definitions/X)
+    if let Some(ref_path) = map.get("$ref").and_then(|v| v.as_str()) {
+        let target = ref_path
+            .strip_prefix('#')
+            .and_then(|p| root.pointer(p))
+            .ok_or_else(|| format!("{}: unresolvable $ref '{}'", path, ref_path))?;
+        validate_node(root, target, instance, path, depth + 1)?;
+    }
+
+    // 2. type
+    if let Some(type_val) = map.get("type") {
+        let allowed: Vec<&str> = match type_val {
+            Value::String(s) => vec![s.as_str()],
+            Value::Array(arr) => arr.iter().filter_map(|v| v.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        let nullable = map.get("nullable").and_then(|v| v.as_bool()).unwrap_or(false);
+        let type_matches = allowed.is_empty()
+            || (nullable && instance.is_null())
+            || allowed.iter().any(|t| instance_matches_type(instance, t));
+        if !type_matches {
+            return Err(format!("{}: expected type {}, got {}", path, allowed.join("|"), json_type_name(instance)));
+        }
+    }
+
+    // 3. enum / const
+    if let Some(Value::Array(options)) = map.get("enum") {
+        if !options.contains(instance) {
+            return Err(format!("{}: value {} is not one of the allowed enum values", path, instance));
+        }
+    }
+    if let Some(expected) = map.get("const") {
+        if expected != instance {
+            return Err(format!("{}: value {} does not equal const {}", path, instance, expected));
+        }
+    }
+
+    // 4. 组合关键字
+    if let Some(Value::Array(all_of)) = map.get("allOf") {
+        for sub in all_of {
+            validate_node(root, sub, instance, path, depth + 1)?;
+        }
+    }
+    if let Some(Value::Array(any_of)) = map.get("anyOf") {
+        if !any_of.iter().any(|sub| validate_node(root, sub, instance, path, depth + 1).is_ok()) {
+            return Err(format!("{}: value does not match any schema in anyOf", path));
+        }
+    }
+    if let Some(Value::Array(one_of)) = map.get("oneOf") {
+        let matched = one_of
+            .iter()
+            .filter(|sub| validate_node(root, sub, instance, path, depth + 1).is_ok())
+            .count();
+        if matched != 1 {
+            return Err(format!("{}: value matches {} schemas in oneOf (expected exactly 1)", path, matched));
+        }
+    }
+
+    // 5. 按实例类型校验
+    match instance {
+        Value::Object(obj) => {
+            if let Some(Value::Array(required)) = map.get("required") {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !obj.contains_key(key) {
+                        return Err(format!("{}: missing required property '{}'", path, key));
+                    }
+                }
+            }
+
+            let properties = map.get("properties").and_then(|p| p.as_object());
+            for (key, value) in obj {
+                let child_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(prop_schema) => validate_node(root, prop_schema, value, &child_path, depth + 1)?,
+                    None => match map.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(format!("{}: additional property '{}' is not allowed", path, key));
+                        }
+                        Some(extra_schema @ Value::Object(_)) => {
+                            validate_node(root, extra_schema, value, &child_path, depth + 1)?
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = map.get("minItems").and_then(|v| v.as_u64()) {
+                if (items.len() as u64) < min {
+                    return Err(format!("{}: expected at least {} items, got {}", path, min, items.len()));
+                }
+            }
+            if let Some(max) = map.get("maxItems").and_then(|v| v.as_u64()) {
+                if (items.len() as u64) > max {
+                    return Err(format!("{}: expected at most {} items, got {}", path, max, items.len()));
+                }
+            }
+            if let Some(item_schema) = map.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_node(root, item_schema, item, &format!("{}[{}]", path, i), depth + 1)?;
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = map.get("minLength").and_then(|v| v.as_u64()) {
+                if len < min {
+                    return Err(format!("{}: string shorter than minLength {}", path, min));
+                }
+            }
+            if let Some(max) = map.get("maxLength").and_then(|v| v.as_u64()) {
+                if len > max {
+                    return Err(format!("{}: string longer than maxLength {}", path, max));
+                }
+            }
+            if let Some(pattern) = map.get("pattern").and_then(|v| v.as_str()) {
+                if compiled_pattern(pattern).is_some_and(|re| !re.is_match(s)) {
+                    return Err(format!("{}: string does not match pattern '{}'", path, pattern));
+                }
+            }
+        }
+        Value::Number(n) => {
+            if let Some(x) = n.as_f64() {
+                if let Some(min) = map.get("minimum").and_then(|v| v.as_f64()) {
+                    if x < min {
+                        return Err(format!("{}: {} is less than minimum {}", path, x, min));
+                    }
+                }
+                if let Some(max) = map.get("maximum").and_then(|v| v.as_f64()) {
+                    if x > max {
+                        return Err(format!("{}: {} is greater than maximum {}", path, x, max));
+                    }
+                }
+                if let Some(min) = map.get("exclusiveMinimum").and_then(|v| v.as_f64()) {
+                    if x <= min {
+                        return Err(format!("{}: {} must be greater than {}", path, x, min));
+                    }
+                }
+                if let Some(max) = map.get("exclusiveMaximum").and_then(|v| v.as_f64()) {
+                    if x >= max {
+                        return Err(format!("{}: {} must be less than {}", path, x, max));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// 已编译的 pattern 正则 (无效 pattern 缓存为 None，忽略该约束)
+static PATTERN_CACHE: Lazy<DashMap<String, Option<Regex>>> = Lazy::new(DashMap::new);
+const PATTERN_CACHE_LIMIT: usize = 256;
+
+fn compiled_pattern(pattern: &str) -> Option<Regex> {
+    if let Some(re) = PATTERN_CACHE.get(pattern) {
+        return re.clone();
+    }
+    if PATTERN_CACHE.len() >= PATTERN_CACHE_LIMIT {
+        PATTERN_CACHE.clear();
+    }
+    let re = Regex::new(pattern).ok();
+    PATTERN_CACHE.insert(pattern.to_string(), re.clone());
+    re
+}
+
+fn instance_matches_type(instance: &Value, type_name: &str) -> bool {
+    match type_name.to_lowercase().as_str() {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        "number" => instance.is_number(),
+        "integer" => instance
+            .as_f64()
+            .map(|f| f.fract() == 0.0)
+            .unwrap_or(false),
+        _ => true,
+    }
+}
+
+fn json_type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -489,4 +710,34 @@ mod tests {
         assert_eq!(schema["properties"]["name"]["type"], "string");
         assert!(schema["properties"]["name"].get("anyOf").is_none());
     }
+
+    #[test]
+    fn test_validate_json_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "age": { "type": "integer", "minimum": 0 },
+                "tags": { "type": "array", "items": { "$ref": "#/$defs/tag" } },
+                "nickname": { "type": ["string", "null"] }
+            },
+            "required": ["name", "age", "tags", "nickname"],
+            "additionalProperties": false,
+            "$defs": {
+                "tag": { "type": "string", "enum": ["a", "b"] }
+            }
+        });
+
+        let ok = json!({ "name": "Ann", "age": 3, "tags": ["a"], "nickname": null });
+        assert!(validate_json_schema(&schema, &ok).is_ok());
+
+        let missing = json!({ "name": "Ann", "age": 3, "tags": [], "nickname": null, "extra": 1 });
+        assert!(validate_json_schema(&schema, &missing).unwrap_err().contains("extra"));
+
+        let bad_enum = json!({ "name": "Ann", "age": 3, "tags": ["c"], "nickname": null });
+        assert!(validate_json_schema(&schema, &bad_enum).unwrap_err().contains("$.tags[0]"));
+
+        let bad_type = json!({ "name": "Ann", "age": 3.5, "tags": [], "nickname": "x" });
+        assert!(validate_json_schema(&schema, &bad_type).is_err());
+    }
 }
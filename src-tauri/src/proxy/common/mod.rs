@@ -3,5 +3,8 @@
 // pub mod error;
 // pub mod rate_limiter;
 pub mod model_mapping;
+pub mod background_task;
+pub mod param_policy;
 pub mod utils;
 pub mod json_schema;
+pub mod thinking;
@@ -1,6 +1,11 @@
 // 模型名称映射
 use std::collections::HashMap;
+use std::sync::Mutex;
+use axum::http::HeaderMap;
 use once_cell::sync::Lazy;
+use serde_json::Value;
+
+use crate::proxy::config::{ModelFallbackConfig, ModelRoutingRule, RouteConditions, RouteParamOverrides, RoutePatternType};
 
 static CLAUDE_TO_GEMINI: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
     let mut m = HashMap::new();
@@ -51,6 +56,22 @@ static CLAUDE_TO_GEMINI: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|
     m.insert("gemini-3-flash", "gemini-3-flash");
     m.insert("gemini-3-pro-image", "gemini-3-pro-image");
 
+    // 音频转录映射表 (Whisper 兼容)
+    m.insert("whisper-1", "gemini-2.5-flash");
+    m.insert("gpt-4o-transcribe", "gemini-2.5-flash");
+    m.insert("gpt-4o-mini-transcribe", "gemini-2.5-flash");
+
+    // 语音合成映射表
+    m.insert("tts-1", "gemini-2.5-flash-preview-tts");
+    m.insert("tts-1-hd", "gemini-2.5-pro-preview-tts");
+    m.insert("gpt-4o-mini-tts", "gemini-2.5-flash-preview-tts");
+
+    // Embeddings 映射表
+    m.insert("text-embedding-3-small", "gemini-embedding-001");
+    m.insert("text-embedding-3-large", "gemini-embedding-001");
+    m.insert("text-embedding-ada-002", "gemini-embedding-001");
+    m.insert("text-embedding-004", "text-embedding-004");
+
 
     m
 });
@@ -70,6 +91,38 @@ pub fn map_claude_model_to_gemini(input: &str) -> String {
     "claude-sonnet-4-5".to_string()
 }
 
+/// 默认 Embedding 模型 (映射结果不是 Embedding 模型时使用)
+pub const DEFAULT_EMBEDDING_MODEL: &str = "gemini-embedding-001";
+
+/// 判断映射后的模型是否为 Embedding 模型
+pub fn is_embedding_model(mapped_model: &str) -> bool {
+    mapped_model.contains("embedding")
+}
+
+/// 默认语音合成模型
+pub const DEFAULT_TTS_MODEL: &str = "gemini-2.5-flash-preview-tts";
+
+/// 判断映射后的模型是否支持语音合成
+pub fn is_tts_model(mapped_model: &str) -> bool {
+    mapped_model.starts_with("gemini-") && mapped_model.contains("tts")
+}
+
+/// 获取映射后模型的最大输出 token 数
+/// 客户端 max_tokens 超出时按此上限截断
+pub fn get_model_output_limit(mapped_model: &str) -> u32 {
+    if mapped_model.starts_with("gemini-3-pro-image") {
+        32768
+    } else if mapped_model.starts_with("claude-") {
+        64000
+    } else if mapped_model.starts_with("gemini-2.5-") || mapped_model.starts_with("gemini-3-") {
+        65536
+    } else if mapped_model.starts_with("gemini-") {
+        8192
+    } else {
+        64000
+    }
+}
+
 /// 获取所有内置支持的模型列表关键字
 pub fn get_supported_models() -> Vec<String> {
     CLAUDE_TO_GEMINI.keys().map(|s| s.to_string()).collect()
@@ -77,7 +130,7 @@ pub fn get_supported_models() -> Vec<String> {
 
 /// 动态获取所有可用模型列表 (包含内置与用户自定义)
 pub async fn get_all_dynamic_models(
-    custom_mapping: &tokio::sync::RwLock<std::collections::HashMap<String, String>>,
+    routing_rules: &tokio::sync::RwLock<Vec<ModelRoutingRule>>,
 ) -> Vec<String> {
     use std::collections::HashSet;
     let mut model_ids = HashSet::new();
@@ -87,11 +140,13 @@ pub async fn get_all_dynamic_models(
         model_ids.insert(m);
     }
 
-    // 2. 获取所有自定义映射模型 (Custom)
+    // 2. 获取路由规则中的精确模型名 (通配符/正则规则无法枚举)
     {
-        let mapping = custom_mapping.read().await;
-        for key in mapping.keys() {
-            model_ids.insert(key.clone());
+        let rules = routing_rules.read().await;
+        for rule in rules.iter().filter(|r| r.enabled && r.pattern_type == RoutePatternType::Glob) {
+            if !rule.pattern.is_empty() && !rule.pattern.contains(['*', '?']) {
+                model_ids.insert(rule.pattern.clone());
+            }
         }
     }
 
@@ -126,54 +181,323 @@ pub async fn get_all_dynamic_models(
 }
 
 /// 通配符匹配辅助函数
-/// 支持简单的 * 通配符匹配
+/// 支持任意数量的 `*` (任意字符串) 与 `?` (单个字符)
 /// 
 /// # 示例
 /// - `gpt-4*` 匹配 `gpt-4`, `gpt-4-turbo`, `gpt-4-0613` 等
-/// - `claude-3-5-sonnet-*` 匹配所有 3.5 sonnet 版本
+/// - `claude-*-sonnet-*` 匹配所有 sonnet 版本
 /// - `*-thinking` 匹配所有以 `-thinking` 结尾的模型
-fn wildcard_match(pattern: &str, text: &str) -> bool {
-    if let Some(star_pos) = pattern.find('*') {
-        let prefix = &pattern[..star_pos];
-        let suffix = &pattern[star_pos + 1..];
-        text.starts_with(prefix) && text.ends_with(suffix)
-    } else {
-        pattern == text
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    // 最近一个 `*` 的位置及其当前吸收到的文本位置，失配时回溯
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star_pi, star_ti)) = star {
+            pi = star_pi + 1;
+            ti = star_ti + 1;
+            star = Some((star_pi, star_ti + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+/// 按模型名查找 `模式 -> 值` 表: 精确匹配 > 通配符 (字面字符多者优先，相同时按模式字典序)
+/// HashMap 遍历顺序不固定，多个通配符同时命中时必须按确定的顺序选取
+pub(crate) fn lookup_wildcard<'a, V>(table: &'a HashMap<String, V>, model: &str) -> Option<&'a V> {
+    if let Some(value) = table.get(model) {
+        return Some(value);
+    }
+    let literal = |p: &str| p.chars().filter(|c| !matches!(c, '*' | '?')).count();
+    table
+        .iter()
+        .filter(|(pattern, _)| pattern.contains(['*', '?']) && wildcard_match(pattern, model))
+        .max_by(|(a, _), (b, _)| literal(a).cmp(&literal(b)).then_with(|| b.cmp(a)))
+        .map(|(_, value)| value)
+}
+
+/// 正则匹配 (需匹配完整模型名)，编译结果按模式缓存，无效正则记录一次警告后视为不匹配
+fn regex_match(pattern: &str, text: &str) -> bool {
+    static CACHE: Lazy<Mutex<HashMap<String, Option<regex::Regex>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+    let Ok(mut cache) = CACHE.lock() else {
+        return false;
+    };
+    cache
+        .entry(pattern.to_string())
+        .or_insert_with(|| match regex::Regex::new(&format!("^(?:{})$", pattern)) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                tracing::warn!("[Router] 无效的路由正则 {}: {}", pattern, e);
+                None
+            }
+        })
+        .as_ref()
+        .is_some_and(|re| re.is_match(text))
+}
+
+/// 路由条件匹配所需的请求上下文
+#[derive(Debug, Clone, Default)]
+pub struct RouteContext {
+    /// 入口协议: openai / claude / gemini
+    pub protocol: &'static str,
+    pub client_key: Option<String>,
+    pub has_tools: bool,
+    pub has_images: bool,
+    /// 粗略估算的提示词 token 数
+    pub prompt_tokens: u64,
+    pub headers: HeaderMap,
+}
+
+impl RouteContext {
+    /// 仅包含协议信息的上下文 (音频/Embedding 等无需条件路由的入口)
+    pub fn new(protocol: &'static str) -> Self {
+        Self {
+            protocol,
+            ..Default::default()
+        }
+    }
+
+    /// 从原始请求体与请求头构建 (OpenAI / Claude / Gemini 请求体通用)
+    pub fn from_request(protocol: &'static str, body: &Value, headers: &HeaderMap) -> Self {
+        Self {
+            protocol,
+            client_key: client_key_from_headers(headers),
+            has_tools: body
+                .get("tools")
+                .and_then(|t| t.as_array())
+                .is_some_and(|t| !t.is_empty()),
+            has_images: contains_image(body),
+            prompt_tokens: estimate_prompt_tokens(body),
+            headers: headers.clone(),
+        }
+    }
+}
+
+/// 提取客户端 API Key (与认证中间件一致，额外支持 Gemini 的 x-goog-api-key)
+pub fn client_key_from_headers(headers: &HeaderMap) -> Option<String> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    header("authorization")
+        .map(|s| s.strip_prefix("Bearer ").unwrap_or(s))
+        .or_else(|| header("x-api-key"))
+        .or_else(|| header("x-goog-api-key"))
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// 递归检测请求体中的图像输入 (OpenAI image_url / Claude image / Responses input_image / Gemini inlineData)
+fn contains_image(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => {
+            let typed_image = map
+                .get("type")
+                .and_then(|t| t.as_str())
+                .is_some_and(|t| matches!(t, "image_url" | "image" | "input_image"));
+            let inline_image = ["inlineData", "inline_data", "fileData", "file_data"].iter().any(|key| {
+                map.get(*key)
+                    .and_then(|d| d.get("mimeType").or_else(|| d.get("mime_type")))
+                    .and_then(|m| m.as_str())
+                    .is_some_and(|m| m.starts_with("image/"))
+            });
+            typed_image || inline_image || map.values().any(contains_image)
+        }
+        Value::Array(items) => items.iter().any(contains_image),
+        _ => false,
+    }
+}
+
+/// 粗略估算提示词 token 数 (约 4 字符 / token)，跳过 base64 媒体数据
+pub fn estimate_prompt_tokens(body: &Value) -> u64 {
+    fn text_len(value: &Value) -> usize {
+        match value {
+            Value::String(s) if s.starts_with("data:") => 0,
+            Value::String(s) => s.len(),
+            Value::Array(items) => items.iter().map(text_len).sum(),
+            Value::Object(map) => map
+                .iter()
+                .filter(|(k, _)| !matches!(k.as_str(), "data" | "model"))
+                .map(|(_, v)| text_len(v))
+                .sum(),
+            _ => 0,
+        }
+    }
+    text_len(body).div_ceil(4) as u64
+}
+
+fn conditions_match(conditions: &RouteConditions, ctx: &RouteContext) -> bool {
+    let client_key_ok = conditions.client_keys.is_empty()
+        || ctx.client_key.as_ref().is_some_and(|k| conditions.client_keys.contains(k));
+    let protocol_ok = conditions.protocols.is_empty()
+        || conditions.protocols.iter().any(|p| p.eq_ignore_ascii_case(ctx.protocol));
+    let headers_ok = conditions.headers.iter().all(|(name, pattern)| {
+        ctx.headers
+            .get(name.to_lowercase().as_str())
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| wildcard_match(pattern, v))
+    });
+
+    client_key_ok
+        && protocol_ok
+        && headers_ok
+        && conditions.has_tools.is_none_or(|v| v == ctx.has_tools)
+        && conditions.has_images.is_none_or(|v| v == ctx.has_images)
+        && conditions.min_prompt_tokens.is_none_or(|min| ctx.prompt_tokens >= min)
+        && conditions.max_prompt_tokens.is_none_or(|max| ctx.prompt_tokens <= max)
+}
+
+fn rule_matches(rule: &ModelRoutingRule, model: &str, ctx: &RouteContext) -> bool {
+    if !rule.enabled || rule.pattern.is_empty() || rule.target.is_empty() {
+        return false;
+    }
+    let name_matches = match rule.pattern_type {
+        RoutePatternType::Glob => wildcard_match(&rule.pattern, model),
+        RoutePatternType::Regex => regex_match(&rule.pattern, model),
+    };
+    name_matches && conditions_match(&rule.conditions, ctx)
+}
+
+/// 路由结果
+#[derive(Debug, Clone)]
+pub struct RouteDecision {
+    /// 目标模型
+    pub model: String,
+    /// 命中的规则 (None 表示使用系统默认映射)
+    pub rule: Option<ModelRoutingRule>,
+}
+
+impl RouteDecision {
+    /// 应用命中规则的参数覆盖
+    pub fn apply_overrides(&self, gemini_body: &mut Value) {
+        if let Some(rule) = &self.rule {
+            apply_route_overrides(gemini_body, &rule.overrides);
+        }
     }
 }
 
 /// 核心模型路由解析引擎
-/// 优先级：精确匹配 > 通配符匹配 > 系统默认映射
+/// 优先级：路由规则 (按列表顺序，首个命中生效) > 系统默认映射
 /// 
 /// # 参数
 /// - `original_model`: 原始模型名称
-/// - `custom_mapping`: 用户自定义映射表
-/// 
-/// # 返回
-/// 映射后的目标模型名称
-pub fn resolve_model_route(
-    original_model: &str,
-    custom_mapping: &std::collections::HashMap<String, String>,
-) -> String {
-    // 1. 精确匹配 (最高优先级)
-    if let Some(target) = custom_mapping.get(original_model) {
-        crate::modules::logger::log_info(&format!("[Router] 精确映射: {} -> {}", original_model, target));
-        return target.clone();
+/// - `rules`: 用户配置的有序路由规则
+/// - `ctx`: 请求上下文 (用于条件匹配)
+pub fn resolve_route(original_model: &str, rules: &[ModelRoutingRule], ctx: &RouteContext) -> RouteDecision {
+    if let Some(rule) = find_route_rule(original_model, rules, ctx) {
+        crate::modules::logger::log_info(&format!(
+            "[Router] 规则映射: {} -> {} (规则: {})",
+            original_model,
+            rule.target,
+            rule.label()
+        ));
+        return RouteDecision {
+            model: rule.target.clone(),
+            rule: Some(rule.clone()),
+        };
     }
-    
-    // 2. 通配符匹配
-    for (pattern, target) in custom_mapping.iter() {
-        if pattern.contains('*') && wildcard_match(pattern, original_model) {
-            crate::modules::logger::log_info(&format!("[Router] 通配符映射: {} -> {} (规则: {})", original_model, target, pattern));
-            return target.clone();
-        }
-    }
-    
-    // 3. 系统默认映射
+
     let result = map_claude_model_to_gemini(original_model);
     if result != original_model {
         crate::modules::logger::log_info(&format!("[Router] 系统默认映射: {} -> {}", original_model, result));
     }
+    RouteDecision { model: result, rule: None }
+}
+
+/// 查找首个命中的路由规则 (不记录日志)
+pub fn find_route_rule<'a>(
+    original_model: &str,
+    rules: &'a [ModelRoutingRule],
+    ctx: &RouteContext,
+) -> Option<&'a ModelRoutingRule> {
+    rules.iter().find(|r| rule_matches(r, original_model, ctx))
+}
+
+/// 仅解析目标模型名
+pub fn resolve_model_route(original_model: &str, rules: &[ModelRoutingRule], ctx: &RouteContext) -> String {
+    resolve_route(original_model, rules, ctx).model
+}
+
+/// 将命中规则的参数覆盖写入 Gemini 请求体 (v1internal 包装: request.generationConfig)
+pub fn apply_route_overrides(body: &mut Value, overrides: &RouteParamOverrides) {
+    if *overrides == RouteParamOverrides::default() {
+        return;
+    }
+    let Some(request) = body.get_mut("request").and_then(|r| r.as_object_mut()) else {
+        return;
+    };
+    let gen_config = request
+        .entry("generationConfig")
+        .or_insert_with(|| serde_json::json!({}));
+    let Some(gen_config) = gen_config.as_object_mut() else {
+        return;
+    };
+
+    if let Some(temperature) = overrides.temperature {
+        gen_config.insert("temperature".to_string(), temperature.into());
+    }
+    if let Some(top_p) = overrides.top_p {
+        gen_config.insert("topP".to_string(), top_p.into());
+    }
+    if let Some(max_tokens) = overrides.max_tokens {
+        gen_config.insert("maxOutputTokens".to_string(), max_tokens.into());
+    }
+
+    // 仅调整已启用思考的请求，且预算须小于 maxOutputTokens
+    let max_output = gen_config.get("maxOutputTokens").and_then(|v| v.as_u64());
+    if let Some(thinking) = gen_config.get_mut("thinkingConfig").and_then(|t| t.as_object_mut()) {
+        if let Some(budget) = overrides.thinking_budget {
+            thinking.insert("thinkingBudget".to_string(), budget.into());
+            if budget == 0 {
+                thinking.insert("includeThoughts".to_string(), false.into());
+            }
+        }
+        let budget = thinking.get("thinkingBudget").and_then(|v| v.as_u64());
+        if let (Some(budget), Some(max_output)) = (budget, max_output) {
+            if budget >= max_output {
+                thinking.insert("thinkingBudget".to_string(), max_output.saturating_sub(1).into());
+            }
+        }
+    }
+}
+
+/// 旧版 custom_mapping (精确/单通配符映射表) 迁移为有序路由规则
+/// 精确匹配排在前面；通配符按字面字符数从多到少排序 (更具体的规则优先)，保证迁移结果确定
+pub fn migrate_custom_mapping<'a>(mapping: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<ModelRoutingRule> {
+    let mut entries: Vec<(&str, &str)> = mapping.into_iter().collect();
+    entries.sort_by(|(a, _), (b, _)| {
+        let specificity = |p: &str| (!p.contains('*'), p.chars().filter(|c| *c != '*').count());
+        specificity(b).cmp(&specificity(a)).then_with(|| a.cmp(b))
+    });
+    entries
+        .into_iter()
+        .map(|(pattern, target)| ModelRoutingRule::simple(pattern, target))
+        .collect()
+}
+
+/// 查找模型的回退链 (按 lookup_wildcard: 精确匹配优先，其次按通配符字面字符数从多到少匹配)
+/// 结果去除主模型与重复项；未启用或未命中时返回空
+pub fn resolve_fallback_chain(mapped_model: &str, config: &ModelFallbackConfig) -> Vec<String> {
+    if !config.enabled {
+        return Vec::new();
+    }
+    let chain = lookup_wildcard(&config.chains, mapped_model);
+
+    let mut result: Vec<String> = Vec::new();
+    for model in chain.into_iter().flatten() {
+        let model = model.trim();
+        if !model.is_empty() && model != mapped_model && !result.iter().any(|m| m == model) {
+            result.push(model.to_string());
+        }
+    }
     result
 }
 
@@ -201,4 +525,118 @@ mod tests {
             "claude-sonnet-4-5"
         );
     }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("gpt-4*", "gpt-4-turbo"));
+        assert!(wildcard_match("claude-*-sonnet-*", "claude-3-5-sonnet-20241022"));
+        assert!(wildcard_match("gpt-4?", "gpt-4o"));
+        assert!(!wildcard_match("gpt-4?", "gpt-4"));
+        assert!(wildcard_match("*", "anything"));
+        assert!(!wildcard_match("claude-*-opus", "claude-3-sonnet"));
+    }
+
+    #[test]
+    fn test_routing_rules_order_and_conditions() {
+        let mut tools_rule = ModelRoutingRule::simple("gpt-4*", "gemini-3-pro-high");
+        tools_rule.conditions.has_tools = Some(true);
+        tools_rule.overrides.temperature = Some(0.2);
+        let mut regex_rule = ModelRoutingRule::simple(r"gpt-4o(-\d{4}-\d{2}-\d{2})?", "gemini-3-flash");
+        regex_rule.pattern_type = RoutePatternType::Regex;
+        let mut header_rule = ModelRoutingRule::simple("*", "gemini-2.5-flash");
+        header_rule.conditions.headers.insert("X-Route".to_string(), "cheap*".to_string());
+        let rules = vec![tools_rule, regex_rule, header_rule, ModelRoutingRule::simple("gpt-4*", "gemini-2.5-pro")];
+
+        let plain = RouteContext::new("openai");
+        let decision = resolve_route("gpt-4o-2024-08-06", &rules, &plain);
+        assert_eq!(decision.model, "gemini-3-flash");
+        assert_eq!(resolve_model_route("gpt-4-turbo", &rules, &plain), "gemini-2.5-pro");
+
+        let body = serde_json::json!({
+            "messages": [{ "role": "user", "content": [
+                { "type": "text", "text": "hello world" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,AAAA" } }
+            ]}],
+            "tools": [{ "type": "function", "function": { "name": "f" } }]
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert("x-route", "cheap-tier".parse().unwrap());
+        headers.insert("authorization", "Bearer sk-test".parse().unwrap());
+        let ctx = RouteContext::from_request("openai", &body, &headers);
+        assert!(ctx.has_tools && ctx.has_images);
+        assert_eq!(ctx.client_key.as_deref(), Some("sk-test"));
+
+        // 首个命中的规则生效
+        let decision = resolve_route("gpt-4o", &rules, &ctx);
+        assert_eq!(decision.model, "gemini-3-pro-high");
+        assert_eq!(decision.rule.unwrap().overrides.temperature, Some(0.2));
+        assert_eq!(resolve_model_route("claude-opus-4", &rules, &ctx), "gemini-2.5-flash");
+        // 未命中规则时回退系统默认映射
+        assert_eq!(resolve_model_route("claude-opus-4", &rules, &plain), "claude-opus-4-5-thinking");
+    }
+
+    #[test]
+    fn test_apply_route_overrides() {
+        let mut body = serde_json::json!({
+            "request": { "generationConfig": {
+                "maxOutputTokens": 64000,
+                "thinkingConfig": { "includeThoughts": true, "thinkingBudget": 16000 }
+            }}
+        });
+        let overrides = RouteParamOverrides {
+            top_p: Some(0.9),
+            max_tokens: Some(8000),
+            ..Default::default()
+        };
+        apply_route_overrides(&mut body, &overrides);
+        let gen_config = &body["request"]["generationConfig"];
+        assert_eq!(gen_config["topP"], 0.9);
+        assert_eq!(gen_config["maxOutputTokens"], 8000);
+        assert_eq!(gen_config["thinkingConfig"]["thinkingBudget"], 7999);
+    }
+
+    #[test]
+    fn test_migrate_custom_mapping() {
+        let rules = migrate_custom_mapping([
+            ("gpt-4*", "a"),
+            ("gpt-4o*", "b"),
+            ("gpt-4", "c"),
+            ("*", "d"),
+        ]);
+        let patterns: Vec<&str> = rules.iter().map(|r| r.pattern.as_str()).collect();
+        assert_eq!(patterns, vec!["gpt-4", "gpt-4o*", "gpt-4*", "*"]);
+        assert_eq!(resolve_model_route("gpt-4o-mini", &rules, &RouteContext::new("openai")), "b");
+    }
+
+    #[test]
+    fn test_model_output_limit() {
+        assert_eq!(get_model_output_limit("claude-sonnet-4-5-thinking"), 64000);
+        assert_eq!(get_model_output_limit("gemini-2.5-flash"), 65536);
+        assert_eq!(get_model_output_limit("gemini-3-pro-high"), 65536);
+        assert_eq!(get_model_output_limit("gemini-3-pro-image-4k"), 32768);
+        assert_eq!(get_model_output_limit("gemini-2.0-flash-exp"), 8192);
+    }
+
+    #[test]
+    fn test_resolve_fallback_chain() {
+        let mut config = ModelFallbackConfig::default();
+        config.chains.insert(
+            "claude-opus-4-5-thinking".to_string(),
+            vec!["claude-sonnet-4-5-thinking".to_string(), "gemini-3-pro-high".to_string()],
+        );
+        config.chains.insert("claude-*".to_string(), vec!["gemini-3-flash".to_string()]);
+        config.chains.insert("claude-sonnet-*".to_string(), vec!["claude-sonnet-4-5-thinking".to_string(), "gemini-3-pro-high".to_string()]);
+
+        assert_eq!(
+            resolve_fallback_chain("claude-opus-4-5-thinking", &config),
+            vec!["claude-sonnet-4-5-thinking", "gemini-3-pro-high"]
+        );
+        // 更具体的通配符优先，且去掉主模型自身
+        assert_eq!(resolve_fallback_chain("claude-sonnet-4-5-thinking", &config), vec!["gemini-3-pro-high"]);
+        assert_eq!(resolve_fallback_chain("claude-haiku-4-5", &config), vec!["gemini-3-flash"]);
+        assert!(resolve_fallback_chain("gemini-3-pro-high", &config).is_empty());
+
+        config.enabled = false;
+        assert!(resolve_fallback_chain("claude-opus-4-5-thinking", &config).is_empty());
+    }
 }
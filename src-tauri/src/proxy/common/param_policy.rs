@@ -0,0 +1,407 @@
+// 生成参数策略
+// 在协议转换前按 (映射后模型, 客户端 Key) 应用默认值、范围、强制值与系统指令；
+// 安全设置在转换后写入 Gemini 请求体 (各协议均无对应字段)；
+// 转发给其他服务商的原始请求体按入口协议的字段名应用 (apply_raw)，不含安全设置
+
+use serde_json::{json, Map, Value};
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::config::{ParamRule, ParameterPolicy};
+use crate::proxy::mappers::common_utils::{build_safety_settings, SafetyThreshold};
+
+/// 从策略中取出某个参数的规则
+type RuleGetter = fn(&ParameterPolicy) -> Option<ParamRule>;
+
+/// 命中当前请求的策略 (按配置顺序)
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedPolicy {
+    policies: Vec<ParameterPolicy>,
+}
+
+impl ResolvedPolicy {
+    pub fn resolve(mapped_model: &str, client_key: Option<&str>, policies: &[ParameterPolicy]) -> Self {
+        let policies = policies
+            .iter()
+            .filter(|p| p.enabled)
+            .filter(|p| p.models.is_empty() || p.models.iter().any(|m| wildcard_match(m, mapped_model)))
+            .filter(|p| p.client_keys.is_empty() || client_key.is_some_and(|k| p.client_keys.iter().any(|c| c == k)))
+            .cloned()
+            .collect::<Vec<_>>();
+        let resolved = Self { policies };
+        if !resolved.is_empty() {
+            tracing::debug!("[Param-Policy] {} matched policies: {:?}", mapped_model, resolved.names());
+        }
+        resolved
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// 命中的策略名
+    pub fn names(&self) -> Vec<&str> {
+        self.policies.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    /// 依次应用各策略的参数规则
+    fn apply_param(&self, value: Option<f64>, rule: impl Fn(&ParameterPolicy) -> Option<ParamRule>) -> Option<f64> {
+        self.policies
+            .iter()
+            .filter_map(rule)
+            .fold(value, |value, rule| apply_rule(value, &rule))
+    }
+
+    fn system_prepends(&self) -> impl Iterator<Item = &str> {
+        // 后应用的策略排在最前
+        self.policies.iter().rev().filter_map(|p| p.system_prepend.as_deref()).filter(|s| !s.is_empty())
+    }
+
+    fn system_appends(&self) -> impl Iterator<Item = &str> {
+        self.policies.iter().filter_map(|p| p.system_append.as_deref()).filter(|s| !s.is_empty())
+    }
+
+    /// 最后一条设置了有效安全阈值的策略生效
+    pub fn safety_threshold(&self) -> Option<SafetyThreshold> {
+        self.policies
+            .iter()
+            .rev()
+            .find_map(|p| p.safety_threshold.as_deref().and_then(SafetyThreshold::parse))
+    }
+
+    /// 无命中策略时返回 None，避免克隆请求
+    pub fn applied_openai(
+        &self,
+        request: &crate::proxy::mappers::openai::OpenAIRequest,
+    ) -> Option<crate::proxy::mappers::openai::OpenAIRequest> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut request = request.clone();
+        self.apply_openai(&mut request);
+        Some(request)
+    }
+
+    pub fn apply_openai(&self, request: &mut crate::proxy::mappers::openai::OpenAIRequest) {
+        use crate::proxy::mappers::openai::{OpenAIContent, OpenAIMessage};
+
+        request.temperature = self.apply_param(request.temperature.map(f64::from), |p| p.temperature).map(|v| v as f32);
+        request.top_p = self.apply_param(request.top_p.map(f64::from), |p| p.top_p).map(|v| v as f32);
+        request.max_tokens = self.apply_param(request.max_tokens.map(f64::from), |p| p.max_tokens).map(to_u32);
+
+        let system_message = |text: &str| OpenAIMessage {
+            role: "system".to_string(),
+            content: Some(OpenAIContent::String(text.to_string())),
+            reasoning_content: None,
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+        };
+        for text in self.system_prepends() {
+            request.messages.insert(0, system_message(text));
+        }
+        for text in self.system_appends() {
+            let pos = request.messages.iter().rposition(|m| m.role == "system").map_or(0, |i| i + 1);
+            request.messages.insert(pos, system_message(text));
+        }
+    }
+
+    pub fn apply_claude(&self, request: &mut crate::proxy::mappers::claude::ClaudeRequest) {
+        use crate::proxy::mappers::claude::{SystemBlock, SystemPrompt};
+
+        request.temperature = self.apply_param(request.temperature.map(f64::from), |p| p.temperature).map(|v| v as f32);
+        request.top_p = self.apply_param(request.top_p.map(f64::from), |p| p.top_p).map(|v| v as f32);
+        request.top_k = self.apply_param(request.top_k.map(f64::from), |p| p.top_k).map(to_u32);
+        request.max_tokens = self.apply_param(request.max_tokens.map(f64::from), |p| p.max_tokens).map(to_u32);
+
+        let block = |text: &str| SystemBlock { block_type: "text".to_string(), text: text.to_string() };
+        for text in self.system_prepends() {
+            request.system = Some(match request.system.take() {
+                None => SystemPrompt::String(text.to_string()),
+                Some(SystemPrompt::String(s)) => SystemPrompt::String(format!("{}\n\n{}", text, s)),
+                Some(SystemPrompt::Array(mut blocks)) => {
+                    blocks.insert(0, block(text));
+                    SystemPrompt::Array(blocks)
+                }
+            });
+        }
+        for text in self.system_appends() {
+            request.system = Some(match request.system.take() {
+                None => SystemPrompt::String(text.to_string()),
+                Some(SystemPrompt::String(s)) => SystemPrompt::String(format!("{}\n\n{}", s, text)),
+                Some(SystemPrompt::Array(mut blocks)) => {
+                    blocks.push(block(text));
+                    SystemPrompt::Array(blocks)
+                }
+            });
+        }
+    }
+
+    /// Gemini 原生请求体 (generationConfig / systemInstruction)
+    pub fn apply_gemini(&self, body: &mut Value) {
+        let Some(obj) = body.as_object_mut() else {
+            return;
+        };
+
+        let gen_key = if obj.contains_key("generation_config") { "generation_config" } else { "generationConfig" };
+        if let Some(gen_config) = obj.entry(gen_key).or_insert_with(|| json!({})).as_object_mut() {
+            self.apply_json_params(
+                gen_config,
+                &[
+                    ("temperature", |p| p.temperature, false),
+                    ("topP", |p| p.top_p, false),
+                    ("topK", |p| p.top_k, true),
+                    ("maxOutputTokens", |p| p.max_tokens, true),
+                ],
+            );
+            if gen_config.is_empty() {
+                obj.remove(gen_key);
+            }
+        }
+
+        let sys_key = if obj.contains_key("system_instruction") { "system_instruction" } else { "systemInstruction" };
+        let prepends: Vec<Value> = self.system_prepends().map(|t| json!({ "text": t })).collect();
+        let appends: Vec<Value> = self.system_appends().map(|t| json!({ "text": t })).collect();
+        if prepends.is_empty() && appends.is_empty() {
+            return;
+        }
+        let instruction = obj.entry(sys_key).or_insert_with(|| json!({ "parts": [] }));
+        if let Some(parts) = instruction
+            .as_object_mut()
+            .map(|i| i.entry("parts").or_insert_with(|| json!([])))
+            .and_then(|p| p.as_array_mut())
+        {
+            parts.splice(0..0, prepends);
+            parts.extend(appends);
+        }
+    }
+
+    /// 转发给其他服务商的原始请求体 (claude / openai / gemini 入口协议)，未涉及的字段保持不变
+    pub fn apply_raw(&self, protocol: &str, body: &mut Value) {
+        if self.is_empty() {
+            return;
+        }
+        if protocol == "gemini" {
+            self.apply_gemini(body);
+            return;
+        }
+        let Some(obj) = body.as_object_mut() else {
+            return;
+        };
+        match protocol {
+            "claude" => {
+                self.apply_json_params(
+                    obj,
+                    &[
+                        ("temperature", |p| p.temperature, false),
+                        ("top_p", |p| p.top_p, false),
+                        ("top_k", |p| p.top_k, true),
+                        ("max_tokens", |p| p.max_tokens, true),
+                    ],
+                );
+                for text in self.system_prepends() {
+                    let system = match obj.remove("system") {
+                        Some(Value::String(s)) => json!(format!("{}\n\n{}", text, s)),
+                        Some(Value::Array(mut blocks)) => {
+                            blocks.insert(0, json!({ "type": "text", "text": text }));
+                            Value::Array(blocks)
+                        }
+                        _ => json!(text),
+                    };
+                    obj.insert("system".to_string(), system);
+                }
+                for text in self.system_appends() {
+                    let system = match obj.remove("system") {
+                        Some(Value::String(s)) => json!(format!("{}\n\n{}", s, text)),
+                        Some(Value::Array(mut blocks)) => {
+                            blocks.push(json!({ "type": "text", "text": text }));
+                            Value::Array(blocks)
+                        }
+                        _ => json!(text),
+                    };
+                    obj.insert("system".to_string(), system);
+                }
+            }
+            "openai" => {
+                let max_key = if obj.contains_key("max_completion_tokens") { "max_completion_tokens" } else { "max_tokens" };
+                self.apply_json_params(
+                    obj,
+                    &[
+                        ("temperature", |p| p.temperature, false),
+                        ("top_p", |p| p.top_p, false),
+                        (max_key, |p| p.max_tokens, true),
+                    ],
+                );
+                if let Some(messages) = obj.get_mut("messages").and_then(|m| m.as_array_mut()) {
+                    for text in self.system_prepends() {
+                        messages.insert(0, json!({ "role": "system", "content": text }));
+                    }
+                    for text in self.system_appends() {
+                        let pos = messages.iter().rposition(|m| m["role"] == "system").map_or(0, |i| i + 1);
+                        messages.insert(pos, json!({ "role": "system", "content": text }));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 按 (字段名, 规则, 是否整数) 更新 JSON 对象中的数值参数
+    fn apply_json_params(&self, obj: &mut Map<String, Value>, params: &[(&str, RuleGetter, bool)]) {
+        for &(key, rule, integer) in params {
+            let current = obj.get(key).and_then(|v| v.as_f64());
+            match self.apply_param(current, rule) {
+                Some(v) if integer => obj.insert(key.to_string(), json!(to_u32(v))),
+                Some(v) => obj.insert(key.to_string(), json!(v)),
+                None => None,
+            };
+        }
+    }
+
+    /// 转换后的 v1internal 请求体 (request.safetySettings)
+    pub fn apply_safety(&self, body: &mut Value) {
+        let Some(threshold) = self.safety_threshold() else {
+            return;
+        };
+        if let Some(request) = body.get_mut("request").and_then(|r| r.as_object_mut()) {
+            request.insert("safetySettings".to_string(), build_safety_settings(threshold));
+        }
+    }
+}
+
+/// force > 当前值 > default，再按 min/max 截断
+fn apply_rule(value: Option<f64>, rule: &ParamRule) -> Option<f64> {
+    let value = rule.force.or(value).or(rule.default)?;
+    let value = rule.min.map_or(value, |min| value.max(min));
+    Some(rule.max.map_or(value, |max| value.min(max)))
+}
+
+fn to_u32(value: f64) -> u32 {
+    value.round().clamp(0.0, u32::MAX as f64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(json: Value) -> ParameterPolicy {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_and_apply_openai() {
+        let policies = vec![
+            policy(json!({
+                "name": "claude-defaults",
+                "models": ["claude-*"],
+                "temperature": { "default": 0.7, "max": 1.0 },
+                "max_tokens": { "max": 8192 },
+                "system_prepend": "Be concise.",
+                "safety_threshold": "medium"
+            })),
+            policy(json!({ "name": "team-a", "client_keys": ["sk-team-a"], "top_p": { "force": 0.9 }, "system_append": "Team A rules." })),
+            policy(json!({ "name": "disabled", "enabled": false, "temperature": { "force": 0.0 } })),
+        ];
+
+        assert!(ResolvedPolicy::resolve("gemini-3-flash", None, &policies).is_empty());
+        let resolved = ResolvedPolicy::resolve("claude-sonnet-4-5", Some("sk-team-a"), &policies);
+        assert_eq!(resolved.safety_threshold(), Some(SafetyThreshold::BlockMediumAndAbove));
+
+        let mut req: crate::proxy::mappers::openai::OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "max_tokens": 20000,
+            "messages": [
+                { "role": "system", "content": "You are helpful." },
+                { "role": "user", "content": "hi" }
+            ]
+        }))
+        .unwrap();
+        resolved.apply_openai(&mut req);
+        assert_eq!(req.temperature, Some(0.7));
+        assert_eq!(req.top_p, Some(0.9));
+        assert_eq!(req.max_tokens, Some(8192));
+        let roles_and_text: Vec<(String, String)> = req
+            .messages
+            .iter()
+            .map(|m| (m.role.clone(), serde_json::to_value(&m.content).unwrap().as_str().unwrap_or_default().to_string()))
+            .collect();
+        assert_eq!(roles_and_text[0], ("system".to_string(), "Be concise.".to_string()));
+        assert_eq!(roles_and_text[2], ("system".to_string(), "Team A rules.".to_string()));
+        assert_eq!(roles_and_text[3].0, "user");
+
+        // 客户端值在范围内时保留
+        let mut req: crate::proxy::mappers::openai::OpenAIRequest =
+            serde_json::from_value(json!({ "model": "m", "temperature": 0.2, "messages": [] })).unwrap();
+        ResolvedPolicy::resolve("claude-sonnet-4-5", None, &policies).apply_openai(&mut req);
+        assert_eq!(req.temperature, Some(0.2));
+        assert_eq!(req.top_p, None);
+    }
+
+    #[test]
+    fn test_apply_claude_gemini_and_safety() {
+        let resolved = ResolvedPolicy::resolve(
+            "gemini-3-pro-high",
+            None,
+            &[policy(json!({
+                "top_k": { "force": 40 },
+                "temperature": { "min": 0.5 },
+                "system_prepend": "Policy first.",
+                "system_append": "Policy last.",
+                "safety_threshold": "HIGH"
+            }))],
+        );
+
+        let mut claude: crate::proxy::mappers::claude::ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "temperature": 0.1,
+            "system": "Original.",
+            "messages": [{ "role": "user", "content": "hi" }]
+        }))
+        .unwrap();
+        resolved.apply_claude(&mut claude);
+        assert_eq!(claude.temperature, Some(0.5));
+        assert_eq!(claude.top_k, Some(40));
+        assert!(matches!(&claude.system, Some(crate::proxy::mappers::claude::SystemPrompt::String(s)) if s == "Policy first.\n\nOriginal.\n\nPolicy last."));
+
+        let mut gemini = json!({
+            "contents": [],
+            "systemInstruction": { "parts": [{ "text": "Original." }] },
+            "generationConfig": { "temperature": 0.2 }
+        });
+        resolved.apply_gemini(&mut gemini);
+        assert_eq!(gemini["generationConfig"], json!({ "temperature": 0.5, "topK": 40 }));
+        assert_eq!(
+            gemini["systemInstruction"]["parts"],
+            json!([{ "text": "Policy first." }, { "text": "Original." }, { "text": "Policy last." }])
+        );
+
+        let mut raw_claude = json!({
+            "model": "glm-4.6",
+            "temperature": 0.1,
+            "system": [{ "type": "text", "text": "Original.", "cache_control": { "type": "ephemeral" } }],
+            "metadata": { "user_id": "u1" }
+        });
+        resolved.apply_raw("claude", &mut raw_claude);
+        assert_eq!(raw_claude["temperature"], json!(0.5));
+        assert_eq!(raw_claude["top_k"], json!(40));
+        assert_eq!(raw_claude["system"][0]["text"], "Policy first.");
+        assert_eq!(raw_claude["system"][1]["cache_control"]["type"], "ephemeral");
+        assert_eq!(raw_claude["system"][2]["text"], "Policy last.");
+        assert_eq!(raw_claude["metadata"]["user_id"], "u1");
+
+        let mut raw_openai = json!({
+            "model": "deepseek-chat",
+            "temperature": 0.2,
+            "max_completion_tokens": 100,
+            "messages": [{ "role": "system", "content": "Original." }, { "role": "user", "content": "hi" }]
+        });
+        resolved.apply_raw("openai", &mut raw_openai);
+        assert_eq!(raw_openai.get("top_k"), None);
+        assert_eq!(raw_openai["temperature"], json!(0.5));
+        let contents: Vec<&str> = raw_openai["messages"].as_array().unwrap().iter().map(|m| m["content"].as_str().unwrap()).collect();
+        assert_eq!(contents, ["Policy first.", "Original.", "Policy last.", "hi"]);
+
+        let mut wrapped = json!({ "request": { "safetySettings": [] } });
+        resolved.apply_safety(&mut wrapped);
+        assert_eq!(wrapped["request"]["safetySettings"][0]["threshold"], "BLOCK_ONLY_HIGH");
+    }
+}
@@ -0,0 +1,116 @@
+// 推理预算 (Thinking Budget) 解析
+// 将 OpenAI reasoning_effort / 显式预算映射为各模型可接受的 thinkingBudget
+
+use crate::proxy::common::model_mapping::lookup_wildcard;
+use crate::proxy::config::ThinkingBudgetConfig;
+
+/// 模型支持的 thinkingBudget 范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThinkingRange {
+    pub min: u32,
+    pub max: u32,
+    /// 是否允许通过 0 关闭思考
+    pub can_disable: bool,
+}
+
+/// 获取映射后模型的 thinkingBudget 范围，不支持思考的模型返回 None
+pub fn thinking_range(mapped_model: &str) -> Option<ThinkingRange> {
+    let range = |min, max, can_disable| Some(ThinkingRange { min, max, can_disable });
+
+    if mapped_model.starts_with("gemini-3-pro-image") {
+        None
+    } else if mapped_model.starts_with("gemini-2.5-flash-lite") {
+        range(512, 24576, true)
+    } else if mapped_model.starts_with("gemini-2.5-flash") {
+        range(1, 24576, true)
+    } else if mapped_model.starts_with("gemini-2.5-pro") || mapped_model.starts_with("gemini-3") {
+        range(128, 32768, false)
+    } else if mapped_model.starts_with("claude-") && mapped_model.contains("thinking") {
+        range(1024, 32000, true)
+    } else {
+        None
+    }
+}
+
+/// reasoning_effort 档位对应的预算 (与 Gemini OpenAI 兼容层一致)
+pub fn effort_to_budget(effort: &str) -> Option<u32> {
+    match effort.to_lowercase().as_str() {
+        "none" | "minimal" => Some(0),
+        "low" => Some(1024),
+        "medium" => Some(8192),
+        "high" => Some(24576),
+        _ => None,
+    }
+}
+
+/// 解析最终 thinkingBudget
+/// 优先级：显式预算 > reasoning_effort > 配置的模型默认值
+/// - None: 不注入 thinkingConfig (沿用上游默认行为)
+/// - Some(0): 关闭思考
+pub fn resolve_thinking_budget(
+    mapped_model: &str,
+    reasoning_effort: Option<&str>,
+    explicit_budget: Option<u32>,
+    config: &ThinkingBudgetConfig,
+) -> Option<u32> {
+    let range = thinking_range(mapped_model)?;
+
+    let requested = explicit_budget
+        .or_else(|| reasoning_effort.and_then(effort_to_budget))
+        .or_else(|| model_default_budget(mapped_model, config))?;
+
+    if requested == 0 {
+        return Some(if range.can_disable { 0 } else { range.min });
+    }
+    Some(requested.clamp(range.min, range.max))
+}
+
+/// 查找配置中的模型默认预算 (精确匹配优先，其次字面字符最多的通配符)
+fn model_default_budget(mapped_model: &str, config: &ThinkingBudgetConfig) -> Option<u32> {
+    lookup_wildcard(&config.model_defaults, mapped_model).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_thinking_budget() {
+        let config = ThinkingBudgetConfig::default();
+
+        // 配置默认值: Gemini 3 Pro 16000，其他模型不注入
+        assert_eq!(resolve_thinking_budget("gemini-3-pro-high", None, None, &config), Some(16000));
+        assert_eq!(resolve_thinking_budget("gemini-2.5-flash", None, None, &config), None);
+
+        // effort 映射并按模型上限截断
+        assert_eq!(resolve_thinking_budget("gemini-2.5-flash", Some("high"), None, &config), Some(24576));
+        assert_eq!(resolve_thinking_budget("gemini-2.5-flash", Some("low"), None, &config), Some(1024));
+
+        // 关闭思考: 支持关闭的模型返回 0，不支持的返回最小预算
+        assert_eq!(resolve_thinking_budget("gemini-2.5-flash", Some("minimal"), None, &config), Some(0));
+        assert_eq!(resolve_thinking_budget("gemini-3-pro-high", Some("minimal"), None, &config), Some(128));
+
+        // 显式预算优先于 effort
+        assert_eq!(resolve_thinking_budget("claude-sonnet-4-5-thinking", Some("low"), Some(50000), &config), Some(32000));
+
+        // 不支持思考的模型
+        assert_eq!(resolve_thinking_budget("claude-sonnet-4-5", Some("high"), None, &config), None);
+        assert_eq!(resolve_thinking_budget("gemini-3-pro-image", Some("high"), None, &config), None);
+    }
+
+    #[test]
+    fn test_model_default_wildcard_precedence() {
+        let config = ThinkingBudgetConfig {
+            model_defaults: [("gemini-*", 1000), ("gemini-3-pro-*", 2000), ("gemini-3-*", 3000), ("gemini-3-pro-low", 500)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        };
+        // 精确匹配 > 字面字符最多的通配符
+        assert_eq!(model_default_budget("gemini-3-pro-low", &config), Some(500));
+        assert_eq!(model_default_budget("gemini-3-pro-high", &config), Some(2000));
+        assert_eq!(model_default_budget("gemini-3-flash", &config), Some(3000));
+        assert_eq!(model_default_budget("gemini-2.5-pro", &config), Some(1000));
+        assert_eq!(model_default_budget("claude-sonnet-4-5", &config), None);
+    }
+}
@@ -88,10 +88,27 @@ pub struct ZaiConfig {
     pub enabled: bool,
     #[serde(default = "default_zai_base_url")]
     pub base_url: String,
+    /// OpenAI-compatible PaaS endpoint, used for OpenAI and Gemini protocol clients.
+    #[serde(default = "default_zai_openai_base_url")]
+    pub openai_base_url: String,
     #[serde(default)]
     pub api_key: String,
+    /// Additional API keys. Requests rotate across `api_key` and these keys,
+    /// skipping keys that are locked out after a 429.
+    #[serde(default)]
+    pub api_keys: Vec<String>,
     #[serde(default)]
     pub dispatch_mode: ZaiDispatchMode,
+    /// Pooled mode: traffic weight, where each Google account counts as 1.
+    #[serde(default = "default_zai_pool_weight")]
+    pub pool_weight: u32,
+    /// Pooled mode: fixed share of pooled traffic in percent (0-100). Overrides `pool_weight` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_percent: Option<u32>,
+    /// Retry on the Google pool when z.ai fails (every key rate limited, 5xx or connection error).
+    /// Opt-in, and never applies in `exclusive` mode.
+    #[serde(default)]
+    pub fallback_to_google: bool,
     /// Optional per-model mapping overrides for Anthropic/Claude model ids.
     /// Key: incoming `model` string, Value: upstream z.ai model id (e.g. `glm-4.7`).
     #[serde(default)]
@@ -107,8 +124,13 @@ impl Default for ZaiConfig {
         Self {
             enabled: false,
             base_url: default_zai_base_url(),
+            openai_base_url: default_zai_openai_base_url(),
             api_key: String::new(),
+            api_keys: Vec::new(),
             dispatch_mode: ZaiDispatchMode::Off,
+            pool_weight: default_zai_pool_weight(),
+            pool_percent: None,
+            fallback_to_google: false,
             model_mapping: HashMap::new(),
             models: ZaiModelDefaults::default(),
             mcp: ZaiMcpConfig::default(),
@@ -116,6 +138,20 @@ impl Default for ZaiConfig {
     }
 }
 
+impl ZaiConfig {
+    /// `api_key` followed by `api_keys`, without blanks or duplicates.
+    pub fn all_api_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::new();
+        for key in std::iter::once(&self.api_key).chain(self.api_keys.iter()) {
+            let key = key.trim();
+            if !key.is_empty() && !keys.iter().any(|k| k == key) {
+                keys.push(key.to_string());
+            }
+        }
+        keys
+    }
+}
+
 /// 实验性功能配置 (Feature Flags)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExperimentalConfig {
@@ -144,6 +180,628 @@ impl Default for ExperimentalConfig {
 
 fn default_true() -> bool { true }
 
+/// 推理预算 (Thinking Budget) 配置
+/// 客户端未指定 reasoning_effort / thinking_budget 时按模型使用默认预算
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThinkingBudgetConfig {
+    /// 模型 (映射后, 支持 * / ? 通配符，精确匹配优先，其次字面字符最多的模式) -> 默认 thinkingBudget
+    /// 0 表示关闭 (不支持关闭的模型取最小预算)；未命中的模型不注入 thinkingConfig
+    #[serde(default = "default_thinking_model_defaults")]
+    pub model_defaults: HashMap<String, u32>,
+}
+
+impl Default for ThinkingBudgetConfig {
+    fn default() -> Self {
+        Self {
+            model_defaults: default_thinking_model_defaults(),
+        }
+    }
+}
+
+fn default_thinking_model_defaults() -> HashMap<String, u32> {
+    // 保持原有行为: Gemini 3 Pro 默认 16000
+    HashMap::from([("gemini-3-pro*".to_string(), 16000)])
+}
+
+/// 生成图像存储配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageStorageConfig {
+    /// 图像保留时长 (小时)，0 表示永久保留
+    #[serde(default = "default_image_retention_hours")]
+    pub retention_hours: u64,
+    /// 签名链接有效期 (秒)
+    #[serde(default = "default_image_url_ttl_secs")]
+    pub url_ttl_secs: u64,
+    /// 对外访问地址 (如 https://proxy.example.com)，未设置时按请求 Host 生成
+    #[serde(default)]
+    pub public_base_url: Option<String>,
+}
+
+impl Default for ImageStorageConfig {
+    fn default() -> Self {
+        Self {
+            retention_hours: default_image_retention_hours(),
+            url_ttl_secs: default_image_url_ttl_secs(),
+            public_base_url: None,
+        }
+    }
+}
+
+fn default_image_retention_hours() -> u64 {
+    168 // 7 天
+}
+
+fn default_image_url_ttl_secs() -> u64 {
+    3600
+}
+
+/// 文生图配置 (/v1/images/generations 与 /v1/images/jobs)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageGenerationConfig {
+    /// 单个账号最多承担的图像数量，超过后分摊到多个账号
+    #[serde(default = "default_images_per_account")]
+    pub images_per_account: usize,
+    /// 同时运行的异步任务上限，超出的任务保持 queued 状态
+    #[serde(default = "default_max_concurrent_image_jobs")]
+    pub max_concurrent_jobs: usize,
+}
+
+impl Default for ImageGenerationConfig {
+    fn default() -> Self {
+        Self {
+            images_per_account: default_images_per_account(),
+            max_concurrent_jobs: default_max_concurrent_image_jobs(),
+        }
+    }
+}
+
+fn default_images_per_account() -> usize {
+    2
+}
+
+fn default_max_concurrent_image_jobs() -> usize {
+    4
+}
+
+/// 输入图像预处理配置 (发送上游前解码校验、缩放、重新编码)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImagePreprocessConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 最长边上限 (像素)，适用于 detail=high/auto 及 Claude 图像
+    #[serde(default = "default_image_max_dimension")]
+    pub max_dimension: u32,
+    /// OpenAI detail=low 时的最长边上限
+    #[serde(default = "default_image_low_detail_dimension")]
+    pub low_detail_dimension: u32,
+    /// 重新编码 JPEG 的质量 (1-100)
+    #[serde(default = "default_image_jpeg_quality")]
+    pub jpeg_quality: u8,
+    /// 未缩放的图像超过该大小 (KB) 时也尝试重新编码
+    #[serde(default = "default_image_reencode_threshold_kb")]
+    pub reencode_threshold_kb: u32,
+}
+
+impl Default for ImagePreprocessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_dimension: default_image_max_dimension(),
+            low_detail_dimension: default_image_low_detail_dimension(),
+            jpeg_quality: default_image_jpeg_quality(),
+            reencode_threshold_kb: default_image_reencode_threshold_kb(),
+        }
+    }
+}
+
+fn default_image_max_dimension() -> u32 {
+    2048
+}
+
+fn default_image_low_detail_dimension() -> u32 {
+    512
+}
+
+fn default_image_jpeg_quality() -> u8 {
+    85
+}
+
+fn default_image_reencode_threshold_kb() -> u32 {
+    1024
+}
+
+/// 远程媒体下载配置 (http(s) 图像/PDF/音频由服务端下载后内联)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaFetchConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 单次下载超时 (秒)
+    #[serde(default = "default_media_timeout_secs")]
+    pub timeout_secs: u64,
+    /// 单个文件大小上限 (MB)
+    #[serde(default = "default_media_max_mb")]
+    pub max_mb: u64,
+    /// 允许的协议
+    #[serde(default = "default_media_allowed_schemes")]
+    pub allowed_schemes: Vec<String>,
+    /// 允许的主机 (支持 *.example.com)，为空表示不限制
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    /// 禁止访问本机与内网地址
+    #[serde(default = "default_true")]
+    pub block_private_networks: bool,
+    /// 最大重定向次数
+    #[serde(default = "default_media_max_redirects")]
+    pub max_redirects: usize,
+    /// 缓存有效期 (秒)，过期后按 ETag 重新验证；0 表示不缓存
+    #[serde(default = "default_media_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+    /// 缓存总大小上限 (MB)
+    #[serde(default = "default_media_cache_max_mb")]
+    pub cache_max_mb: u64,
+    /// 本地文件访问策略 (file:// 与本地路径)
+    #[serde(default)]
+    pub local_files: LocalFileAccessConfig,
+}
+
+/// 本地文件访问策略
+/// 默认禁用；启用后只允许读取 allowed_roots 下的文件 (解析符号链接后校验，拒绝 `..`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalFileAccessConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 允许访问的根目录 (绝对路径)，为空时拒绝所有本地文件
+    #[serde(default)]
+    pub allowed_roots: Vec<String>,
+    /// 单个文件大小上限 (MB)
+    #[serde(default = "default_media_max_mb")]
+    pub max_mb: u64,
+}
+
+impl Default for LocalFileAccessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_roots: Vec::new(),
+            max_mb: default_media_max_mb(),
+        }
+    }
+}
+
+impl Default for MediaFetchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_secs: default_media_timeout_secs(),
+            max_mb: default_media_max_mb(),
+            allowed_schemes: default_media_allowed_schemes(),
+            allowed_hosts: Vec::new(),
+            block_private_networks: true,
+            max_redirects: default_media_max_redirects(),
+            cache_ttl_secs: default_media_cache_ttl_secs(),
+            cache_max_mb: default_media_cache_max_mb(),
+            local_files: LocalFileAccessConfig::default(),
+        }
+    }
+}
+
+fn default_media_timeout_secs() -> u64 {
+    30
+}
+
+fn default_media_max_mb() -> u64 {
+    20
+}
+
+fn default_media_allowed_schemes() -> Vec<String> {
+    vec!["https".to_string(), "http".to_string()]
+}
+
+fn default_media_max_redirects() -> usize {
+    5
+}
+
+fn default_media_cache_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_media_cache_max_mb() -> u64 {
+    128
+}
+
+/// 模型回退链配置
+/// 主模型没有可用账号 (额度耗尽/限流) 时，依次改用链中的模型重试
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelFallbackConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 模型 (映射后, 支持 * 通配符) -> 按顺序尝试的回退模型
+    /// 例: "claude-opus-4-5-thinking" -> ["claude-sonnet-4-5-thinking", "gemini-3-pro-high"]
+    #[serde(default)]
+    pub chains: HashMap<String, Vec<String>>,
+}
+
+impl Default for ModelFallbackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            chains: HashMap::new(),
+        }
+    }
+}
+
+/// 后台任务识别与降级配置
+/// 客户端的标题生成、摘要、建议等后台请求按规则降级到轻量模型，节省主力模型额度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundTaskConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 最后一条用户消息超过该长度 (字节) 时视为真实请求，不做识别
+    #[serde(default = "default_background_max_message_length")]
+    pub max_message_length: usize,
+    /// 按顺序匹配，先命中的类别生效
+    #[serde(default = "default_background_categories")]
+    pub categories: Vec<BackgroundTaskCategory>,
+}
+
+impl Default for BackgroundTaskConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_message_length: default_background_max_message_length(),
+            categories: default_background_categories(),
+        }
+    }
+}
+
+/// 后台任务类别: 关键词 (子串) 或正则任一命中即识别为该类别
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackgroundTaskCategory {
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// 正则表达式 (在消息中搜索，不要求完整匹配)
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// 降级目标模型
+    pub target_model: String,
+}
+
+impl BackgroundTaskCategory {
+    fn with_keywords(name: &str, keywords: &[&str], target_model: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            enabled: true,
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+            patterns: Vec::new(),
+            target_model: target_model.to_string(),
+        }
+    }
+}
+
+fn default_background_max_message_length() -> usize {
+    800
+}
+
+fn default_background_categories() -> Vec<BackgroundTaskCategory> {
+    const LITE: &str = "gemini-2.5-flash-lite"; // 简单/轻量任务
+    const STANDARD: &str = "gemini-2.5-flash"; // 复杂后台任务
+    vec![
+        BackgroundTaskCategory::with_keywords(
+            "system_message",
+            &["Warmup", "<system-reminder>", "This is a system message"],
+            LITE,
+        ),
+        BackgroundTaskCategory::with_keywords(
+            "title_generation",
+            &[
+                "write a 5-10 word title",
+                "Please write a 5-10 word title",
+                "Respond with the title",
+                "Generate a title for",
+                "Create a brief title",
+                "title for the conversation",
+                "conversation title",
+                "生成标题",
+                "为对话起个标题",
+            ],
+            LITE,
+        ),
+        BackgroundTaskCategory::with_keywords("simple_summary", &["in under 50 characters"], LITE),
+        BackgroundTaskCategory::with_keywords(
+            "context_compression",
+            &[
+                "Summarize this coding conversation",
+                "Summarize the conversation",
+                "Concise summary",
+                "compress the context",
+                "Provide a concise summary",
+                "condense the previous messages",
+                "shorten the conversation history",
+                "extract key points from",
+            ],
+            STANDARD,
+        ),
+        BackgroundTaskCategory::with_keywords(
+            "prompt_suggestion",
+            &[
+                "prompt suggestion generator",
+                "suggest next prompts",
+                "what should I ask next",
+                "generate follow-up questions",
+                "recommend next steps",
+                "possible next actions",
+            ],
+            LITE,
+        ),
+        BackgroundTaskCategory::with_keywords(
+            "environment_probe",
+            &["check current directory", "list available tools", "verify environment", "test connection"],
+            LITE,
+        ),
+    ]
+}
+
+/// 请求/响应脚本钩子 (Rhai)
+/// 脚本通过可修改的 `data` 变量读写 JSON，通过只读的 `ctx` 获取上下文
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HookConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 单个脚本的执行超时 (毫秒)，超时的脚本不生效
+    /// 钩子在请求任务上同步执行；upstream_event 钩子对每个 SSE 事件执行，超时上限固定为 5ms
+    #[serde(default = "default_hook_timeout_ms")]
+    pub timeout_ms: u64,
+    /// 按顺序执行
+    #[serde(default)]
+    pub hooks: Vec<ScriptHook>,
+}
+
+impl Default for HookConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_ms: default_hook_timeout_ms(),
+            hooks: Vec::new(),
+        }
+    }
+}
+
+fn default_hook_timeout_ms() -> u64 {
+    50
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ScriptHook {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 挂载点 (可多个)
+    #[serde(default)]
+    pub points: Vec<HookPoint>,
+    /// 内联脚本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    /// 脚本文件路径 (未设置 script 时读取)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// 脚本钩子挂载点
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HookPoint {
+    /// 协议解析后 (客户端原始请求 JSON，转换前)
+    RequestParsed,
+    /// 发送上游前 (转换后的 v1internal 请求体)
+    BeforeUpstream,
+    /// 上游 SSE 的每个 data 事件 (v1internal 响应块)，同步执行，脚本应保持轻量
+    UpstreamEvent,
+    /// 返回客户端前 (非流式响应 JSON)
+    BeforeResponse,
+}
+
+/// 额外的上游服务商 (Google v1internal 账号池之外的 Anthropic / OpenAI 兼容端点)
+/// 路由规则通过 `provider` 字段按 id 选择服务商
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct UpstreamProviderConfig {
+    /// 唯一标识 (路由规则中引用)，`google` 保留给内置账号池
+    pub id: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 上游协议
+    pub kind: UpstreamProviderKind,
+    /// 基础地址，请求路径追加在其后 (如 `https://api.deepseek.com`)
+    pub base_url: String,
+    /// API Key 列表 (轮询使用)，本地服务可为空
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// 模型映射: 客户端模型 (支持 * / ? 通配符) -> 上游模型
+    #[serde(default)]
+    pub model_mapping: HashMap<String, String>,
+    /// 映射表未命中时使用的上游模型，为空则原样透传
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+    /// 参与自动调度的模型 (支持通配符)，为空表示所有模型；路由规则显式选择时不受限制
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// 自动调度方式 (路由规则显式选择不受此影响)
+    #[serde(default)]
+    pub dispatch_mode: ProviderDispatchMode,
+    /// pooled 模式下的权重 (每个 Google 账号权重为 1)
+    #[serde(default = "default_provider_weight")]
+    pub weight: u32,
+}
+
+fn default_provider_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamProviderKind {
+    /// Anthropic Messages 兼容 (`/v1/messages`)
+    #[default]
+    Anthropic,
+    /// OpenAI Chat Completions 兼容 (`/v1/chat/completions`)
+    Openai,
+}
+
+/// 服务商的自动调度方式 (与 ZaiDispatchMode 语义一致)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderDispatchMode {
+    /// 仅在路由规则选择时使用
+    #[default]
+    RuleOnly,
+    /// 接管所有兼容协议的请求
+    Exclusive,
+    /// 按权重与 Google 账号池共同轮询
+    Pooled,
+    /// Google 账号池为空时使用
+    Fallback,
+}
+
+/// 生成参数策略 (按模型/客户端 Key 统一设置默认值、范围、强制值、系统指令与安全设置)
+/// 多条策略同时命中时按列表顺序依次应用
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ParameterPolicy {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 模型名 (映射后, 支持 * / ? 通配符)，为空表示所有模型
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// 客户端 API Key，为空表示所有客户端
+    #[serde(default)]
+    pub client_keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<ParamRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<ParamRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<ParamRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<ParamRule>,
+    /// 插入到系统指令开头
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prepend: Option<String>,
+    /// 追加到系统指令末尾
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_append: Option<String>,
+    /// 安全阈值: OFF / LOW / MEDIUM / HIGH / NONE (同 GEMINI_SAFETY_THRESHOLD)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety_threshold: Option<String>,
+}
+
+/// 单个生成参数的规则
+/// 取值顺序: force > 客户端值 > default，之后按 min/max 截断
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+pub struct ParamRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force: Option<f64>,
+}
+
+/// 路由规则的模型名匹配方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutePatternType {
+    /// 通配符: `*` 匹配任意字符串，`?` 匹配单个字符
+    #[default]
+    Glob,
+    /// 正则表达式 (需匹配完整模型名)
+    Regex,
+}
+
+/// 路由规则的附加条件，未设置的条件不参与匹配，已设置的需全部满足
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct RouteConditions {
+    /// 客户端使用的 API Key
+    pub client_keys: Vec<String>,
+    /// 入口协议: openai / claude / gemini
+    pub protocols: Vec<String>,
+    /// 请求是否携带工具定义
+    pub has_tools: Option<bool>,
+    /// 请求是否包含图像输入
+    pub has_images: Option<bool>,
+    /// 估算的提示词 token 数范围
+    pub min_prompt_tokens: Option<u64>,
+    pub max_prompt_tokens: Option<u64>,
+    /// 请求头 (名称不区分大小写，值支持通配符)
+    pub headers: std::collections::BTreeMap<String, String>,
+}
+
+/// 命中路由规则后覆盖的生成参数
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct RouteParamOverrides {
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u32>,
+    pub thinking_budget: Option<u32>,
+}
+
+/// 模型路由规则 (按列表顺序匹配，首个命中的规则生效)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ModelRoutingRule {
+    /// 规则名称 (用于日志)
+    pub name: Option<String>,
+    pub enabled: bool,
+    /// 原始模型名匹配模式
+    pub pattern: String,
+    pub pattern_type: RoutePatternType,
+    /// 目标模型
+    pub target: String,
+    /// 上游服务商 id (`google` 或 providers 中的 id)，为空时按服务商调度方式自动选择
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    pub conditions: RouteConditions,
+    pub overrides: RouteParamOverrides,
+}
+
+impl Default for ModelRoutingRule {
+    fn default() -> Self {
+        Self {
+            name: None,
+            enabled: true,
+            pattern: String::new(),
+            pattern_type: RoutePatternType::Glob,
+            target: String::new(),
+            provider: None,
+            conditions: RouteConditions::default(),
+            overrides: RouteParamOverrides::default(),
+        }
+    }
+}
+
+impl ModelRoutingRule {
+    /// 无条件的通配符规则 (旧版 custom_mapping 迁移)
+    pub fn simple(pattern: &str, target: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            target: target.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// 规则在日志中的显示名称
+    pub fn label(&self) -> &str {
+        self.name.as_deref().filter(|n| !n.is_empty()).unwrap_or(&self.pattern)
+    }
+}
+
 /// 反代服务配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyConfig {
@@ -174,9 +832,9 @@ pub struct ProxyConfig {
     /// 是否自动启动
     pub auto_start: bool,
 
-    /// 自定义精确模型映射表 (key: 原始模型名, value: 目标模型名)
+    /// 模型路由规则 (有序，取代旧版 custom_mapping 映射表)
     #[serde(default)]
-    pub custom_mapping: std::collections::HashMap<String, String>,
+    pub routing_rules: Vec<ModelRoutingRule>,
 
     /// API 请求超时时间(秒)
     #[serde(default = "default_request_timeout")]
@@ -201,6 +859,79 @@ pub struct ProxyConfig {
     /// 实验性功能配置
     #[serde(default)]
     pub experimental: ExperimentalConfig,
+
+    /// 推理预算配置 (OpenAI reasoning_effort 映射)
+    #[serde(default)]
+    pub thinking: ThinkingBudgetConfig,
+
+    /// 生成图像存储配置 (response_format=url)
+    #[serde(default)]
+    pub image_storage: ImageStorageConfig,
+
+    /// 文生图配置
+    #[serde(default)]
+    pub image_generation: ImageGenerationConfig,
+
+    /// 输入图像预处理配置
+    #[serde(default)]
+    pub image_preprocess: ImagePreprocessConfig,
+
+    /// 远程媒体下载配置
+    #[serde(default)]
+    pub media_fetch: MediaFetchConfig,
+
+    /// 模型回退链配置
+    #[serde(default)]
+    pub model_fallback: ModelFallbackConfig,
+
+    /// 后台任务识别与降级配置
+    #[serde(default)]
+    pub background_tasks: BackgroundTaskConfig,
+
+    /// 生成参数策略 (按顺序应用)
+    #[serde(default)]
+    pub parameter_policies: Vec<ParameterPolicy>,
+
+    /// 请求/响应脚本钩子
+    #[serde(default)]
+    pub hooks: HookConfig,
+
+    /// 额外的上游服务商 (Anthropic / OpenAI 兼容端点)
+    #[serde(default)]
+    pub providers: Vec<UpstreamProviderConfig>,
+
+    /// 管理界面独立监听配置
+    /// 启用后 Admin UI / `/api/admin/*` 只在该监听上提供，主端口仅提供 `/v1`、`/v1beta`、`/mcp`
+    #[serde(default)]
+    pub admin_listener: AdminListenerConfig,
+}
+
+/// 管理界面 (Admin UI/API) 独立监听配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminListenerConfig {
+    /// 是否启用独立监听
+    #[serde(default)]
+    pub enabled: bool,
+    /// 监听地址 (默认仅本机)
+    #[serde(default = "default_admin_host")]
+    pub host: String,
+    /// 监听端口
+    #[serde(default = "default_admin_port")]
+    pub port: u16,
+    /// Unix socket 路径 (仅 Unix 平台，设置后忽略 host/port)
+    #[serde(default)]
+    pub unix_socket: Option<String>,
+}
+
+impl Default for AdminListenerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: default_admin_host(),
+            port: default_admin_port(),
+            unix_socket: None,
+        }
+    }
 }
 
 /// 上游代理配置
@@ -221,13 +952,24 @@ impl Default for ProxyConfig {
             port: 8045,
             api_key: format!("sk-{}", uuid::Uuid::new_v4().simple()),
             auto_start: false,
-            custom_mapping: std::collections::HashMap::new(),
+            routing_rules: Vec::new(),
             request_timeout: default_request_timeout(),
             enable_logging: false, // 默认关闭，节省性能
             upstream_proxy: UpstreamProxyConfig::default(),
             zai: ZaiConfig::default(),
             scheduling: crate::proxy::sticky_config::StickySessionConfig::default(),
             experimental: ExperimentalConfig::default(),
+            thinking: ThinkingBudgetConfig::default(),
+            image_storage: ImageStorageConfig::default(),
+            image_generation: ImageGenerationConfig::default(),
+            image_preprocess: ImagePreprocessConfig::default(),
+            media_fetch: MediaFetchConfig::default(),
+            model_fallback: ModelFallbackConfig::default(),
+            background_tasks: BackgroundTaskConfig::default(),
+            parameter_policies: Vec::new(),
+            hooks: HookConfig::default(),
+            providers: Vec::new(),
+            admin_listener: AdminListenerConfig::default(),
         }
     }
 }
@@ -236,10 +978,26 @@ fn default_request_timeout() -> u64 {
     120  // 默认 120 秒,原来 60 秒太短
 }
 
+fn default_admin_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_admin_port() -> u16 {
+    8046
+}
+
 fn default_zai_base_url() -> String {
     "https://api.z.ai/api/anthropic".to_string()
 }
 
+fn default_zai_pool_weight() -> u32 {
+    1
+}
+
+fn default_zai_openai_base_url() -> String {
+    "https://api.z.ai/api/paas/v4".to_string()
+}
+
 fn default_zai_opus_model() -> String {
     "glm-4.7".to_string()
 }
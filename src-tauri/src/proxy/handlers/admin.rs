@@ -245,16 +245,65 @@ pub async fn handle_save_config(
     }
     
     // Hot Reload
-    // state.custom_mapping ...
     {
-        let mut m = state.custom_mapping.write().await;
-        *m = config.proxy.custom_mapping.clone();
+        let mut m = state.routing_rules.write().await;
+        *m = config.proxy.routing_rules.clone();
     }
     
     {
         let mut p = state.upstream_proxy.write().await;
         *p = config.proxy.upstream_proxy.clone();
     }
+
+    {
+        let mut t = state.thinking.write().await;
+        *t = config.proxy.thinking.clone();
+    }
+
+    {
+        let mut s = state.image_storage.write().await;
+        *s = config.proxy.image_storage.clone();
+    }
+
+    {
+        let mut g = state.image_generation.write().await;
+        *g = config.proxy.image_generation.clone();
+    }
+
+    {
+        let mut p = state.image_preprocess.write().await;
+        *p = config.proxy.image_preprocess.clone();
+    }
+
+    {
+        let mut m = state.media_fetch.write().await;
+        *m = config.proxy.media_fetch.clone();
+    }
+
+    {
+        let mut f = state.model_fallback.write().await;
+        *f = config.proxy.model_fallback.clone();
+    }
+
+    {
+        let mut b = state.background_tasks.write().await;
+        *b = config.proxy.background_tasks.clone();
+    }
+
+    {
+        let mut p = state.parameter_policies.write().await;
+        *p = config.proxy.parameter_policies.clone();
+    }
+
+    {
+        let mut h = state.hooks.write().await;
+        *h = config.proxy.hooks.clone();
+    }
+
+    {
+        let mut p = state.providers.write().await;
+        *p = config.proxy.providers.clone();
+    }
     
     {
          // Assuming AppState has security_state? 
@@ -328,3 +377,36 @@ pub async fn handle_refresh_account_quota(
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "status": "error", "message": e }))).into_response(),
     }
 }
+
+// --- Images ---
+
+/// 生成图像图库 (按时间倒序，附带签名链接)
+pub async fn handle_list_images(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> impl IntoResponse {
+    let store = crate::proxy::image_store::ImageStore::global();
+    let base_url = crate::proxy::handlers::files::resolve_base_url(&state, &headers).await;
+    let ttl = state.image_storage.read().await.url_ttl_secs;
+
+    let images: Vec<serde_json::Value> = store
+        .list()
+        .into_iter()
+        .map(|meta| {
+            let url = store.signed_url(&base_url, &meta.id, ttl);
+            let mut item = json!(meta);
+            item["url"] = json!(url);
+            item
+        })
+        .collect();
+
+    Json(json!({ "status": "success", "data": images })).into_response()
+}
+
+pub async fn handle_delete_image(Path(image_id): Path<String>) -> impl IntoResponse {
+    if crate::proxy::image_store::ImageStore::global().delete(&image_id) {
+        Json(json!({ "status": "success" })).into_response()
+    } else {
+        (StatusCode::NOT_FOUND, Json(json!({ "status": "error", "message": "Image not found" }))).into_response()
+    }
+}
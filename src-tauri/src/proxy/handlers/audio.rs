@@ -1,7 +1,7 @@
 use axum::{
     extract::{Multipart, State},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 use serde_json::{json, Value};
@@ -9,21 +9,72 @@ use tracing::{debug, info};
 use uuid::Uuid;
 
 use crate::proxy::{
-    audio::AudioProcessor,
+    audio::{
+        chunker::{is_splittable, split_audio, unsplittable_error, AudioChunk},
+        transcript::{self, ChunkTranscript, TranscriptFormat},
+        AudioProcessor, MAX_AUDIO_SIZE,
+    },
     server::AppState,
 };
 
+const MAX_RETRY_ATTEMPTS: usize = 3;
+/// 长音频按此时长分段 (秒)，保证单段转录输出不超过模型输出上限
+const MAX_CHUNK_SECS: f64 = 600.0;
+
+/// 转录任务类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AudioTask {
+    Transcribe,
+    Translate,
+}
+
+impl AudioTask {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AudioTask::Transcribe => "transcribe",
+            AudioTask::Translate => "translate",
+        }
+    }
+}
+
+/// 解析后的 multipart 表单
+struct AudioForm {
+    audio_data: Vec<u8>,
+    file_name: String,
+    model: String,
+    prompt: Option<String>,
+    response_format: TranscriptFormat,
+    language: Option<String>,
+    temperature: Option<f32>,
+    word_timestamps: bool,
+}
+
 /// 处理音频转录请求 (OpenAI Whisper API 兼容)
 pub async fn handle_audio_transcription(
     State(state): State<AppState>,
-    mut multipart: Multipart,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    handle_audio_task(state, multipart, AudioTask::Transcribe).await
+}
+
+/// 处理音频翻译请求 (翻译为英文，OpenAI Whisper API 兼容)
+pub async fn handle_audio_translation(
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    handle_audio_task(state, multipart, AudioTask::Translate).await
+}
+
+async fn parse_audio_form(mut multipart: Multipart) -> Result<AudioForm, (StatusCode, String)> {
     let mut audio_data: Option<Vec<u8>> = None;
     let mut filename: Option<String> = None;
     let mut model = "gemini-2.0-flash-exp".to_string();
-    let mut prompt = "Generate a transcript of the speech.".to_string();
+    let mut prompt = None;
+    let mut response_format = TranscriptFormat::Json;
+    let mut language = None;
+    let mut temperature = None;
+    let mut word_timestamps = false;
 
-    // 1. 解析 multipart/form-data
     while let Some(field) = multipart.next_field().await.map_err(|e| {
         (StatusCode::BAD_REQUEST, format!("解析表单失败: {}", e))
     })? {
@@ -40,13 +91,28 @@ pub async fn handle_audio_transcription(
                 model = field.text().await.unwrap_or(model);
             }
             "prompt" => {
-                prompt = field.text().await.unwrap_or(prompt);
+                prompt = field.text().await.ok().filter(|s| !s.trim().is_empty());
+            }
+            "response_format" => {
+                let value = field.text().await.unwrap_or_default();
+                response_format = TranscriptFormat::parse(value.trim())
+                    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            }
+            "language" => {
+                language = field.text().await.ok().filter(|s| !s.trim().is_empty());
+            }
+            "temperature" => {
+                temperature = field.text().await.ok().and_then(|s| s.trim().parse::<f32>().ok());
+            }
+            // 兼容 timestamp_granularities[] 与 timestamp_granularities 两种写法
+            "timestamp_granularities[]" | "timestamp_granularities" => {
+                word_timestamps |= field.text().await.unwrap_or_default().trim() == "word";
             }
             _ => {}
         }
     }
 
-    let audio_bytes = audio_data.ok_or((
+    let audio_data = audio_data.ok_or((
         StatusCode::BAD_REQUEST,
         "缺少音频文件".to_string(),
     ))?;
@@ -56,103 +122,393 @@ pub async fn handle_audio_transcription(
         "无法获取文件名".to_string(),
     ))?;
 
-    info!(
-        "收到音频转录请求: 文件={}, 大小={} bytes, 模型={}",
+    Ok(AudioForm {
+        audio_data,
         file_name,
-        audio_bytes.len(),
-        model
+        model,
+        prompt,
+        response_format,
+        language,
+        temperature,
+        word_timestamps,
+    })
+}
+
+async fn handle_audio_task(
+    state: AppState,
+    multipart: Multipart,
+    task: AudioTask,
+) -> Result<Response, (StatusCode, String)> {
+    let form = parse_audio_form(multipart).await?;
+
+    // 1. 模型路由 (whisper-1 等别名)，非 Gemini 模型回退到默认模型
+    let mut mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &form.model,
+        &state.routing_rules.read().await,
+        &crate::proxy::common::model_mapping::RouteContext::new("openai"),
+    );
+    if !mapped_model.starts_with("gemini-") {
+        debug!("音频模型 {} 映射为非 Gemini 模型 {}，回退到 gemini-2.5-flash", form.model, mapped_model);
+        mapped_model = "gemini-2.5-flash".to_string();
+    }
+
+    info!(
+        "收到音频{}请求: 文件={}, 大小={} bytes, 模型={} -> {}, 格式={:?}",
+        if task == AudioTask::Translate { "翻译" } else { "转录" },
+        form.file_name,
+        form.audio_data.len(),
+        form.model,
+        mapped_model,
+        form.response_format
     );
 
     // 2. 检测 MIME 类型
-    let mime_type = AudioProcessor::detect_mime_type(&file_name)
+    let mime_type = AudioProcessor::detect_mime_type(&form.file_name)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    // 3. 长音频自动分段 (WAV/MP3 按帧切分；m4a/ogg/webm 等格式不转码，超过大小限制时返回 413)
+    if form.audio_data.len() > MAX_AUDIO_SIZE && !is_splittable(&mime_type) {
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, unsplittable_error(&mime_type, MAX_AUDIO_SIZE)));
+    }
+    let chunks = split_audio(&form.audio_data, &mime_type, MAX_CHUNK_SECS, MAX_AUDIO_SIZE)
         .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if chunks.len() > 1 {
+        info!("音频已切分为 {} 段", chunks.len());
+    }
+
+    // 4. 逐段转录，前一段末尾文本作为下一段上下文以保持连贯
+    let mut results = Vec::with_capacity(chunks.len());
+    let mut previous_tail: Option<String> = None;
+    for (index, chunk) in chunks.iter().enumerate() {
+        let chunk_prompt = build_prompt(task, &form, previous_tail.as_deref());
+        let transcript = transcribe_chunk(&state, &mapped_model, &mime_type, chunk, &chunk_prompt, form.temperature)
+            .await
+            .map_err(|(status, e)| (status, format!("第 {}/{} 段转录失败: {}", index + 1, chunks.len(), e)))?;
+        previous_tail = transcript.segments.last().map(|s| s.text.clone());
+        results.push((chunk.start, transcript));
+    }
+
+    let duration = chunks.last().and_then(|c| c.duration.map(|d| c.start + d));
+    let (detected_language, segments) = transcript::stitch_segments(results);
+    let duration = duration.unwrap_or_else(|| segments.last().map(|s| s.end).unwrap_or(0.0));
+    let language = match task {
+        AudioTask::Translate => Some("english".to_string()),
+        AudioTask::Transcribe => form.language.clone().or(detected_language),
+    };
+
+    info!("音频{}完成: {} 个分段, 时长 {:.1}s", task.as_str(), segments.len(), duration);
+
+    // 5. 按 response_format 返回
+    let text_response = |body: String, content_type: &'static str| {
+        ([(header::CONTENT_TYPE, content_type)], body).into_response()
+    };
+    Ok(match form.response_format {
+        TranscriptFormat::Json => Json(json!({ "text": transcript::full_text(&segments) })).into_response(),
+        TranscriptFormat::Text => text_response(transcript::full_text(&segments), "text/plain; charset=utf-8"),
+        TranscriptFormat::Srt => text_response(transcript::to_srt(&segments), "text/plain; charset=utf-8"),
+        TranscriptFormat::Vtt => text_response(transcript::to_vtt(&segments), "text/vtt; charset=utf-8"),
+        TranscriptFormat::VerboseJson => Json(transcript::to_verbose_json(
+            task.as_str(),
+            language.as_deref(),
+            duration,
+            &segments,
+            form.word_timestamps,
+        ))
+        .into_response(),
+    })
+}
 
-    // 3. 验证文件大小
-    if AudioProcessor::exceeds_size_limit(audio_bytes.len()) {
-        let size_mb = audio_bytes.len() as f64 / (1024.0 * 1024.0);
-        return Err((
-            StatusCode::PAYLOAD_TOO_LARGE,
-            format!(
-                "音频文件过大 ({:.1} MB)。最大支持 15 MB (约 16 分钟 MP3)。建议: 1) 压缩音频质量 2) 分段上传",
-                size_mb
-            ),
-        ));
+fn build_prompt(task: AudioTask, form: &AudioForm, previous_tail: Option<&str>) -> String {
+    let mut prompt = match task {
+        AudioTask::Transcribe => String::from(
+            "Generate a verbatim transcript of the speech in this audio. \
+             Split it into segments of at most a few sentences.",
+        ),
+        AudioTask::Translate => String::from(
+            "Translate the speech in this audio into English. \
+             Split the translation into segments of at most a few sentences.",
+        ),
+    };
+    prompt.push_str(
+        " For each segment give start and end times in seconds relative to the beginning of this audio. \
+         Also report the spoken language as an ISO-639-1 code.",
+    );
+    if task == AudioTask::Transcribe {
+        if let Some(lang) = &form.language {
+            prompt.push_str(&format!(" The audio is in language '{}'; transcribe it in that language.", lang));
+        }
+    }
+    if let Some(hint) = &form.prompt {
+        prompt.push_str(&format!("\n\nContext and vocabulary hints: {}", hint));
     }
+    if let Some(tail) = previous_tail {
+        prompt.push_str(&format!("\n\nThis audio continues a previous part that ended with: \"{}\"", tail));
+    }
+    prompt
+}
 
-    // 4. 使用 Inline Data 方式
-    debug!("使用 Inline Data 方式处理");
-    let base64_audio = AudioProcessor::encode_to_base64(&audio_bytes);
+/// 转录单个片段 (429/5xx/401/403 时轮换账号重试)
+async fn transcribe_chunk(
+    state: &AppState,
+    model: &str,
+    mime_type: &str,
+    chunk: &AudioChunk,
+    prompt: &str,
+    temperature: Option<f32>,
+) -> Result<ChunkTranscript, (StatusCode, String)> {
+    let mut generation_config = json!({
+        "responseMimeType": "application/json",
+        "responseSchema": transcript::segments_response_schema(),
+    });
+    if let Some(t) = temperature {
+        generation_config["temperature"] = json!(t);
+    }
 
-    // 5. 构建 Gemini 请求
     let gemini_request = json!({
         "contents": [{
+            "role": "user",
             "parts": [
                 {"text": prompt},
                 {
                     "inlineData": {
                         "mimeType": mime_type,
-                        "data": base64_audio
+                        "data": AudioProcessor::encode_to_base64(&chunk.data)
                     }
                 }
             ]
-        }]
+        }],
+        "generationConfig": generation_config
     });
 
-    // 6. 获取 Token 和上游客户端
-    let token_manager = state.token_manager;
-    let (access_token, project_id, email) = token_manager
-        .get_token("text", false, None)
-        .await
-        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
-
-    info!("使用账号: {}", email);
-
-    // 7. 包装请求为 v1internal 格式
-    let wrapped_body = json!({
-        "project": project_id,
-        "requestId": format!("audio-{}", Uuid::new_v4()),
-        "request": gemini_request,
-        "model": model,
-        "userAgent": "antigravity",
-        "requestType": "text"
-    });
+    let token_manager = state.token_manager.clone();
+    let upstream = state.upstream.clone();
+    let max_attempts = MAX_RETRY_ATTEMPTS.min(token_manager.len()).max(1);
+    let mut last_error = String::new();
+
+    for attempt in 0..max_attempts {
+        let (access_token, project_id, email) = token_manager
+            .get_token_for_model("text", attempt > 0, None, Some(model))
+            .await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+
+        info!("使用账号: {}", email);
+
+        // 包装请求为 v1internal 格式
+        let wrapped_body = json!({
+            "project": project_id,
+            "requestId": format!("audio-{}", Uuid::new_v4()),
+            "request": gemini_request,
+            "model": model,
+            "userAgent": "antigravity",
+            "requestType": "text"
+        });
 
-    // 8. 发送请求到 Gemini
+        let response = match upstream
+            .call_v1_internal("generateContent", &access_token, wrapped_body, None)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                last_error = format!("上游请求失败: {}", e);
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if !status.is_success() {
+            let status_code = status.as_u16();
+            let retry_after = response.headers().get("Retry-After").and_then(|h| h.to_str().ok()).map(|s| s.to_string());
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            last_error = format!("Gemini API 错误: {}", error_text);
+
+            if matches!(status_code, 429 | 529 | 503 | 500 | 403 | 401) {
+                token_manager.mark_rate_limited(&email, status_code, retry_after.as_deref(), &error_text, Some(model));
+                tracing::warn!("Audio Upstream {} on account {} attempt {}/{}, rotating account", status_code, email, attempt + 1, max_attempts);
+                continue;
+            }
+            return Err((StatusCode::BAD_GATEWAY, last_error));
+        }
+
+        let result: Value = response
+            .json()
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, format!("解析响应失败: {}", e)))?;
+
+        // 提取文本响应（解包 v1internal 响应）
+        let inner_response = result.get("response").unwrap_or(&result);
+        let raw_text: String = inner_response
+            .get("candidates")
+            .and_then(|c| c.get(0))
+            .and_then(|c| c.get("content"))
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array())
+            .map(|parts| {
+                parts
+                    .iter()
+                    .filter(|p| !p.get("thought").and_then(|v| v.as_bool()).unwrap_or(false))
+                    .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        return Ok(transcript::parse_chunk_transcript(&raw_text, chunk.duration));
+    }
+
+    Err((StatusCode::BAD_GATEWAY, last_error))
+}
+
+/// 处理语音合成请求 (OpenAI /v1/audio/speech 兼容)
+/// 使用 streamGenerateContent 获取 PCM 音频，边接收边封装输出
+/// response_format 支持 wav (默认) / pcm，显式请求 mp3 等需编码器的格式返回 400
+pub async fn handle_audio_speech(
+    State(state): State<AppState>,
+    Json(body): Json<Value>,
+) -> Result<Response, (StatusCode, String)> {
+    use crate::proxy::audio::speech::{build_tts_request, SpeechFormat, SpeechRequest};
+    use crate::proxy::common::model_mapping::{is_tts_model, DEFAULT_TTS_MODEL};
+
+    let req: SpeechRequest = serde_json::from_value(body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+    if req.input.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "'input' must not be empty".to_string()));
+    }
+    if let Some(speed) = req.speed {
+        if !(0.25..=4.0).contains(&speed) {
+            return Err((StatusCode::BAD_REQUEST, "'speed' must be between 0.25 and 4.0".to_string()));
+        }
+    }
+    let format = SpeechFormat::resolve(req.response_format.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mut mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &req.model,
+        &state.routing_rules.read().await,
+        &crate::proxy::common::model_mapping::RouteContext::new("openai"),
+    );
+    if !is_tts_model(&mapped_model) {
+        debug!("TTS 模型 {} 映射为非 TTS 模型 {}，回退到 {}", req.model, mapped_model, DEFAULT_TTS_MODEL);
+        mapped_model = DEFAULT_TTS_MODEL.to_string();
+    }
+
+    info!(
+        "收到语音合成请求: 模型={} -> {}, 音色={:?}, 格式={:?}, 长度={} 字符",
+        req.model,
+        mapped_model,
+        req.voice,
+        format,
+        req.input.chars().count()
+    );
+
+    let gemini_request = build_tts_request(&req);
+    let token_manager = state.token_manager.clone();
     let upstream = state.upstream.clone();
-    let response = upstream
-        .call_v1_internal("generateContent", &access_token, wrapped_body, None)
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("上游请求失败: {}", e)))?;
-
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("Gemini API 错误: {}", error_text),
-        ));
+    let max_attempts = MAX_RETRY_ATTEMPTS.min(token_manager.len()).max(1);
+    let mut last_error = String::new();
+
+    for attempt in 0..max_attempts {
+        let (access_token, project_id, email) = token_manager
+            .get_token_for_model("tts", attempt > 0, None, Some(&mapped_model))
+            .await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+
+        info!("使用账号: {}", email);
+
+        let wrapped_body = json!({
+            "project": project_id,
+            "requestId": format!("tts-{}", Uuid::new_v4()),
+            "request": gemini_request,
+            "model": mapped_model,
+            "userAgent": "antigravity",
+            "requestType": "text"
+        });
+
+        let response = match upstream
+            .call_v1_internal("streamGenerateContent", &access_token, wrapped_body, Some("alt=sse"))
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                last_error = format!("上游请求失败: {}", e);
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if !status.is_success() {
+            let status_code = status.as_u16();
+            let retry_after = response.headers().get("Retry-After").and_then(|h| h.to_str().ok()).map(|s| s.to_string());
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            last_error = format!("Gemini API 错误: {}", error_text);
+
+            if matches!(status_code, 429 | 529 | 503 | 500 | 403 | 401) {
+                token_manager.mark_rate_limited(&email, status_code, retry_after.as_deref(), &error_text, Some(&mapped_model));
+                tracing::warn!("TTS Upstream {} on account {} attempt {}/{}, rotating account", status_code, email, attempt + 1, max_attempts);
+                continue;
+            }
+            return Err((status, last_error));
+        }
+
+        let audio_stream = create_speech_stream(Box::pin(response.bytes_stream()), format);
+        return Ok(Response::builder()
+            .header(header::CONTENT_TYPE, format.content_type())
+            .header("X-Account-Email", &email)
+            .header("X-Mapped-Model", &mapped_model)
+            .body(axum::body::Body::from_stream(audio_stream))
+            .unwrap()
+            .into_response());
     }
 
-    let result: Value = response
-        .json()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("解析响应失败: {}", e)))?;
-
-    // 9. 提取文本响应（解包 v1internal 响应）
-    let inner_response = result.get("response").unwrap_or(&result);
-    let text = inner_response
-        .get("candidates")
-        .and_then(|c| c.get(0))
-        .and_then(|c| c.get("content"))
-        .and_then(|c| c.get("parts"))
-        .and_then(|p| p.get(0))
-        .and_then(|p| p.get("text"))
-        .and_then(|t| t.as_str())
-        .unwrap_or("");
-
-    info!("音频转录完成，返回 {} 字符", text.len());
-
-    // 10. 返回标准格式响应
-    Ok(Json(json!({
-        "text": text
-    })))
+    Err((StatusCode::BAD_GATEWAY, last_error))
+}
+
+/// Gemini SSE -> 音频字节流 (WAV 在首个音频块到达时写入流式文件头)
+fn create_speech_stream(
+    mut gemini_stream: std::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send>>,
+    format: crate::proxy::audio::speech::SpeechFormat,
+) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, String>> + Send>> {
+    use crate::proxy::audio::speech::{extract_audio_parts, parse_sample_rate, wav_header, SpeechFormat};
+    use base64::Engine as _;
+    use bytes::{Bytes, BytesMut};
+    use futures::StreamExt;
+
+    Box::pin(async_stream::stream! {
+        let mut buffer = BytesMut::new();
+        let mut header_sent = false;
+
+        while let Some(item) = gemini_stream.next().await {
+            let bytes = match item {
+                Ok(b) => b,
+                Err(e) => {
+                    tracing::error!("[TTS] Upstream stream error: {}", e);
+                    yield Err(format!("Stream error: {}", e));
+                    return;
+                }
+            };
+            buffer.extend_from_slice(&bytes);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line_raw = buffer.split_to(pos + 1);
+                let Ok(line) = std::str::from_utf8(&line_raw) else { continue };
+                let Some(data) = line.trim().strip_prefix("data: ") else { continue };
+                let Ok(chunk) = serde_json::from_str::<Value>(data) else { continue };
+
+                for (mime_type, b64) in extract_audio_parts(&chunk) {
+                    let Ok(pcm) = base64::engine::general_purpose::STANDARD.decode(b64) else {
+                        tracing::warn!("[TTS] Invalid base64 audio chunk");
+                        continue;
+                    };
+                    if format == SpeechFormat::Wav && !header_sent {
+                        yield Ok::<Bytes, String>(Bytes::from(wav_header(parse_sample_rate(&mime_type), None)));
+                    }
+                    header_sent = true;
+                    yield Ok::<Bytes, String>(Bytes::from(pcm));
+                }
+            }
+        }
+
+        if !header_sent {
+            tracing::warn!("[TTS] Upstream returned no audio data");
+            yield Err("Upstream returned no audio data".to_string());
+        }
+    })
 }
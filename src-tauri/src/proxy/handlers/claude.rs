@@ -13,21 +13,21 @@ use tokio::time::{sleep, Duration};
 use tracing::{debug, error, info};
 
 use crate::proxy::mappers::claude::{
-    transform_claude_request_in, transform_response, create_claude_sse_stream, ClaudeRequest,
+    transform_claude_request_in, transform_response, create_claude_sse_stream,
     close_tool_loop_for_thinking,
 };
+use crate::proxy::common::model_mapping::{resolve_route, RouteContext};
+use crate::proxy::common::background_task::{detect_background_task, last_user_text_claude};
+use crate::proxy::common::param_policy::ResolvedPolicy;
+use crate::proxy::config::HookPoint;
+use crate::proxy::handlers::common::{acquire_token, run_request_hooks, with_route_headers, RouteNotes, TokenLease};
+use crate::proxy::providers::{self, ProviderOutcome};
 use crate::proxy::server::AppState;
 use axum::http::HeaderMap;
-use std::sync::atomic::Ordering;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 const MIN_SIGNATURE_LENGTH: usize = 10;  // 最小有效签名长度
 
-// ===== Model Constants for Background Tasks =====
-// These can be adjusted for performance/cost optimization
-const BACKGROUND_MODEL_LITE: &str = "gemini-2.5-flash-lite";  // For simple/lightweight tasks
-const BACKGROUND_MODEL_STANDARD: &str = "gemini-2.5-flash";   // For complex background tasks
-
 // ===== Jitter Configuration (REMOVED) =====
 // Jitter was causing connection instability, reverted to fixed delays
 // const JITTER_FACTOR: f64 = 0.2;
@@ -67,7 +67,7 @@ fn sanitize_thinking_block(block: ContentBlock) -> ContentBlock {
 }
 
 /// 过滤消息中的无效 thinking 块
-fn filter_invalid_thinking_blocks(messages: &mut Vec<Message>) {
+pub(crate) fn filter_invalid_thinking_blocks(messages: &mut [Message]) {
     let mut total_filtered = 0;
     
     for msg in messages.iter_mut() {
@@ -132,6 +132,29 @@ fn filter_invalid_thinking_blocks(messages: &mut Vec<Message>) {
     }
 }
 
+/// 按请求类型清理转换前的请求
+/// - 后台任务: 移除工具定义与 Thinking 配置 (降级模型不支持)，并清理历史中的 Thinking Block，防止 Invalid Argument
+/// - 真实请求: 移除尾部无签名的 thinking 块
+pub(crate) fn sanitize_for_target(request: &mut crate::proxy::mappers::claude::ClaudeRequest, background: bool) {
+    if background {
+        request.tools = None;
+        request.thinking = None;
+        for msg in request.messages.iter_mut() {
+            if let MessageContent::Array(blocks) = &mut msg.content {
+                blocks.retain(|b| !matches!(b, ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. }));
+            }
+        }
+        return;
+    }
+    for msg in request.messages.iter_mut() {
+        if msg.role == "assistant" || msg.role == "model" {
+            if let MessageContent::Array(blocks) = &mut msg.content {
+                remove_trailing_unsigned_thinking(blocks);
+            }
+        }
+    }
+}
+
 /// 移除尾部的无签名 thinking 块
 fn remove_trailing_unsigned_thinking(blocks: &mut Vec<ContentBlock>) {
     if blocks.is_empty() {
@@ -307,9 +330,10 @@ fn should_rotate_account(status_code: u16) -> bool {
 pub async fn handle_messages(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(body): Json<Value>,
+    Json(mut body): Json<Value>,
 ) -> Response {
     tracing::debug!("handle_messages called. Body JSON len: {}", body.to_string().len());
+    let hooks = run_request_hooks(&state, "claude", None, &mut body, &headers).await;
     
     // 生成随机 Trace ID 用户追踪
     let trace_id: String = rand::Rng::sample_iter(rand::thread_rng(), &rand::distributions::Alphanumeric)
@@ -317,29 +341,8 @@ pub async fn handle_messages(
         .map(char::from)
         .collect::<String>().to_lowercase();
         
-    // Decide whether this request should be handled by z.ai (Anthropic passthrough) or the existing Google flow.
-    let zai = state.zai.read().await.clone();
-    let zai_enabled = zai.enabled && !matches!(zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
-    let google_accounts = state.token_manager.len();
-
-    let use_zai = if !zai_enabled {
-        false
-    } else {
-        match zai.dispatch_mode {
-            crate::proxy::ZaiDispatchMode::Off => false,
-            crate::proxy::ZaiDispatchMode::Exclusive => true,
-            crate::proxy::ZaiDispatchMode::Fallback => google_accounts == 0,
-            crate::proxy::ZaiDispatchMode::Pooled => {
-                // Treat z.ai as exactly one extra slot in the pool.
-                // No strict guarantees: it may get 0 requests if selection never hits.
-                let total = google_accounts.saturating_add(1).max(1);
-                let slot = state.provider_rr.fetch_add(1, Ordering::Relaxed) % total;
-                slot == 0
-            }
-        }
-    };
-
-    // [CRITICAL REFACTOR] 优先解析并过滤 Thinking 块，确保 z.ai 也是用修复后的 Body
+    // [CRITICAL REFACTOR] 优先解析并过滤 Thinking 块，确保其他服务商也使用修复后的 Body
+    let route_ctx = RouteContext::from_request("claude", &body, &headers);
     let mut request: crate::proxy::mappers::claude::models::ClaudeRequest = match serde_json::from_value(body) {
         Ok(r) => r,
         Err(e) => {
@@ -365,29 +368,54 @@ pub async fn handle_messages(
         close_tool_loop_for_thinking(&mut request.messages);
     }
 
-    if use_zai {
+    // 选择上游服务商 (路由规则指定 / z.ai 等兼容端点 / Google 账号池)
+    let dispatch = providers::dispatch(&state, "claude", &request.model, &route_ctx).await;
+    if !dispatch.is_google() {
         // 重新序列化修复后的请求体
         let new_body = match serde_json::to_value(&request) {
             Ok(v) => v,
             Err(e) => {
-                tracing::error!("Failed to serialize fixed request for z.ai: {}", e);
+                tracing::error!("Failed to serialize fixed request for provider {}: {}", dispatch.provider_id(), e);
                 return StatusCode::INTERNAL_SERVER_ERROR.into_response();
             }
         };
 
-        return crate::proxy::providers::zai_anthropic::forward_anthropic_json(
-            &state,
-            axum::http::Method::POST,
-            "/v1/messages",
-            &headers,
-            new_body,
-        )
-        .await;
+        if let ProviderOutcome::Responded(response) =
+            dispatch.forward_with_policy(&state, "/v1/messages", &headers, new_body, &hooks).await
+        {
+            return response;
+        }
     }
     
     // Google Flow 继续使用 request 对象
     // (后续代码不需要再次 filter_invalid_thinking_blocks)
 
+    // 远程媒体下载 (url 类型的 image/document source) 与图像预处理 (解码校验、缩放并修正 MIME 类型)
+    let media_fetch = state.media_fetch.read().await.clone();
+    let image_preprocess = state.image_preprocess.read().await.clone();
+    let media_result = match crate::proxy::media_fetcher::MediaFetcher::global()
+        .inline_claude_media(&mut request.messages, &media_fetch)
+        .await
+    {
+        Ok(()) => {
+            use crate::proxy::mappers::image_preprocess::{preprocess_claude_images, preprocess_images_blocking};
+            preprocess_images_blocking(&mut request.messages, &image_preprocess, preprocess_claude_images).await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = media_result {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "type": "error",
+                "error": {
+                    "type": "invalid_request_error",
+                    "message": format!("Invalid media input: {}", e)
+                }
+            }))
+        ).into_response();
+    }
+
     // 获取最新一条“有意义”的消息内容（用于日志记录和后台任务检测）
     // 策略：反向遍历，首先筛选出所有角色为 "user" 的消息，然后从中找到第一条非 "Warmup" 且非空的文本消息
     // 获取最新一条“有意义”的消息内容（用于日志记录和后台任务检测）
@@ -485,7 +513,7 @@ pub async fn handle_messages(
     
     // 3. 准备闭包
     let mut request_for_body = request.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
@@ -495,26 +523,43 @@ pub async fn handle_messages(
     
     for attempt in 0..max_attempts {
         // 2. 模型路由解析
-        let mut mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &request_for_body.model,
-            &*state.custom_mapping.read().await,
-        );
+        let route = resolve_route(&request_for_body.model, &state.routing_rules.read().await, &route_ctx);
         
         // 将 Claude 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = request_for_body.tools.as_ref().map(|list| {
             list.iter().map(|t| serde_json::to_value(t).unwrap_or(json!({}))).collect()
         });
 
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(&request_for_body.model, &mapped_model, &tools_val);
-
         // 0. 尝试提取 session_id 用于粘性调度 (Phase 2/3)
         // 使用 SessionManager 生成稳定的会话指纹
         let session_id_str = crate::proxy::session_manager::SessionManager::extract_session_id(&request_for_body);
         let session_id = Some(session_id_str.as_str());
 
+        // ===== 【优化】后台任务智能检测与降级 =====
+        // 规则来自 background_tasks 配置 (关键词/正则 -> 目标模型)
+        let background_task = {
+            let background_config = state.background_tasks.read().await;
+            last_user_text_claude(&request_for_body).and_then(|text| detect_background_task(&text, &background_config))
+        };
+        let (target_model, target_tools) = match &background_task {
+            Some(task) => (task.target_model.clone(), None),
+            None => (route.model.clone(), tools_val),
+        };
+
         let force_rotate_token = attempt > 0;
-        let (access_token, project_id, email) = match token_manager.get_token(&config.request_type, force_rotate_token, session_id).await {
-            Ok(t) => t,
+        // 无可用账号时按回退链切换模型
+        let TokenLease { access_token, project_id, email, mapped_model, config, fallback_from } = match acquire_token(
+            &state,
+            &request_for_body.model,
+            &target_model,
+            &target_tools,
+            force_rotate_token,
+            session_id,
+            &headers,
+        )
+        .await
+        {
+            Ok(lease) => lease,
             Err(e) => {
                 let safe_message = if e.contains("invalid_grant") {
                     "OAuth refresh failed (invalid_grant): refresh_token likely revoked/expired; reauthorize account(s) to restore service.".to_string()
@@ -537,44 +582,23 @@ pub async fn handle_messages(
         info!("✓ Using account: {} (type: {})", email, config.request_type);
         
         
-        // ===== 【优化】后台任务智能检测与降级 =====
-        // 使用新的检测系统，支持 5 大类关键词和多 Flash 模型策略
-        let background_task_type = detect_background_task_type(&request_for_body);
-        
         // 传递映射后的模型名
         let mut request_with_mapped = request_for_body.clone();
+        let notes = RouteNotes {
+            fallback_from,
+            downgrade: background_task.as_ref().map(|task| task.describe(&route.model)),
+        };
 
-        if let Some(task_type) = background_task_type {
-            // 检测到后台任务,强制降级到 Flash 模型
-            let downgrade_model = select_background_model(task_type);
-            
+        if let Some(task) = &background_task {
+            // 检测到后台任务,已按配置降级到目标模型
             info!(
-                "[{}][AUTO] 检测到后台任务 (类型: {:?}),强制降级: {} -> {}",
+                "[{}][AUTO] 检测到后台任务 (类别: {}, 命中: {:?}),强制降级: {} -> {}",
                 trace_id,
-                task_type,
-                mapped_model,
-                downgrade_model
+                task.category,
+                task.matched,
+                route.model,
+                mapped_model
             );
-            
-            // 覆盖用户自定义映射
-            mapped_model = downgrade_model.to_string();
-            
-            // 后台任务净化：
-            // 1. 移除工具定义（后台任务不需要工具）
-            request_with_mapped.tools = None;
-            
-            // 2. 移除 Thinking 配置（Flash 模型不支持）
-            request_with_mapped.thinking = None;
-            
-            // 3. 清理历史消息中的 Thinking Block，防止 Invalid Argument
-            for msg in request_with_mapped.messages.iter_mut() {
-                if let crate::proxy::mappers::claude::models::MessageContent::Array(blocks) = &mut msg.content {
-                    blocks.retain(|b| !matches!(b, 
-                        crate::proxy::mappers::claude::models::ContentBlock::Thinking { .. } |
-                        crate::proxy::mappers::claude::models::ContentBlock::RedactedThinking { .. }
-                    ));
-                }
-            }
         } else {
             // 真实用户请求,保持原映射
             debug!(
@@ -582,26 +606,25 @@ pub async fn handle_messages(
                 trace_id,
                 mapped_model
             );
-            
-            // 对真实请求应用额外的清理:移除尾部无签名的 thinking 块
-            // 对真实请求应用额外的清理:移除尾部无签名的 thinking 块
-            for msg in request_with_mapped.messages.iter_mut() {
-                if msg.role == "assistant" || msg.role == "model" {
-                    if let crate::proxy::mappers::claude::models::MessageContent::Array(blocks) = &mut msg.content {
-                        remove_trailing_unsigned_thinking(blocks);
-                    }
-                }
-            }
         }
+        // 后台任务净化 / 真实请求清理
+        sanitize_for_target(&mut request_with_mapped, background_task.is_some());
+
+        // 参数策略: 默认值/范围/强制值与系统指令 (转换前应用)
+        let policy = ResolvedPolicy::resolve(&mapped_model, route_ctx.client_key.as_deref(), &state.parameter_policies.read().await);
+        policy.apply_claude(&mut request_with_mapped);
+        let attempt_hooks = hooks.with_route(&email, &mapped_model);
 
-        
         request_with_mapped.model = mapped_model;
 
         // 生成 Trace ID (简单用时间戳后缀)
         // let _trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
 
         let gemini_body = match transform_claude_request_in(&request_with_mapped, &project_id) {
-            Ok(b) => {
+            Ok(mut b) => {
+                route.apply_overrides(&mut b);
+                policy.apply_safety(&mut b);
+                attempt_hooks.run(HookPoint::BeforeUpstream, &mut b);
                 debug!("[{}] Transformed Gemini Body: {}", trace_id, serde_json::to_string_pretty(&b).unwrap_or_default());
                 b
             },
@@ -651,13 +674,17 @@ pub async fn handle_messages(
         // 成功
         if status.is_success() {
             // [智能限流] 请求成功，重置该账号的连续失败计数
-            token_manager.mark_account_success(&email);
+            token_manager.mark_account_success(&email, Some(&request_with_mapped.model));
             
             // 处理流式响应
             if actual_stream {
-                let stream = response.bytes_stream();
-                let gemini_stream = Box::pin(stream);
-                let claude_stream = create_claude_sse_stream(gemini_stream, trace_id.clone(), email.clone());
+                let gemini_stream = attempt_hooks.wrap_upstream_stream(Box::pin(response.bytes_stream()));
+                let claude_stream = create_claude_sse_stream(
+                    gemini_stream,
+                    trace_id.clone(),
+                    email.clone(),
+                    request_with_mapped.stop_sequences.clone().unwrap_or_default(),
+                );
 
                 // 转换为 Bytes stream
                 let sse_stream = claude_stream.map(|result| -> Result<Bytes, std::io::Error> {
@@ -670,7 +697,7 @@ pub async fn handle_messages(
                 // 判断客户端期望的格式
                 if client_wants_stream {
                     // 客户端本就要 Stream，直接返回 SSE
-                    return Response::builder()
+                    let response = Response::builder()
                         .status(StatusCode::OK)
                         .header(header::CONTENT_TYPE, "text/event-stream")
                         .header(header::CACHE_CONTROL, "no-cache")
@@ -679,6 +706,7 @@ pub async fn handle_messages(
                         .header("X-Mapped-Model", &request_with_mapped.model)
                         .body(Body::from_stream(sse_stream))
                         .unwrap();
+                    return with_route_headers(response, &request_with_mapped.model, &notes);
                 } else {
                     // 客户端要非 Stream，需要收集完整响应并转换为 JSON
                     use crate::proxy::mappers::claude::collect_stream_to_json;
@@ -686,13 +714,14 @@ pub async fn handle_messages(
                     match collect_stream_to_json(sse_stream).await {
                         Ok(full_response) => {
                             info!("[{}] ✓ Stream collected and converted to JSON", trace_id);
-                            return Response::builder()
+                            let response = Response::builder()
                                 .status(StatusCode::OK)
                                 .header(header::CONTENT_TYPE, "application/json")
                                 .header("X-Account-Email", &email)
                                 .header("X-Mapped-Model", &request_with_mapped.model)
-                                .body(Body::from(serde_json::to_string(&full_response).unwrap()))
+                                .body(Body::from(attempt_hooks.before_response(&full_response).to_string()))
                                 .unwrap();
+                            return with_route_headers(response, &request_with_mapped.model, &notes);
                         }
                         Err(e) => {
                             return (StatusCode::INTERNAL_SERVER_ERROR, format!("Stream collection error: {}", e)).into_response();
@@ -726,7 +755,10 @@ pub async fn handle_messages(
                 };
                 
                 // 转换
-                let claude_response = match transform_response(&gemini_response) {
+                let claude_response = match transform_response(
+                    &gemini_response,
+                    request_with_mapped.stop_sequences.clone().unwrap_or_default(),
+                ) {
                     Ok(r) => r,
                     Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Transform error: {}", e)).into_response(),
                 };
@@ -747,7 +779,8 @@ pub async fn handle_messages(
                     cache_info
                 );
 
-                return (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", request_with_mapped.model.as_str())], Json(claude_response)).into_response();
+                let response = (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", request_with_mapped.model.as_str())], Json(attempt_hooks.before_response(&claude_response))).into_response();
+                return with_route_headers(response, &request_with_mapped.model, &notes);
             }
         }
         
@@ -857,7 +890,7 @@ pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoRespo
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
 
     let model_ids = get_all_dynamic_models(
-        &state.custom_mapping,
+        &state.routing_rules,
     ).await;
 
     let data: Vec<_> = model_ids.into_iter().map(|id| {
@@ -881,18 +914,25 @@ pub async fn handle_count_tokens(
     headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
-    let zai_enabled = zai.enabled && !matches!(zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
-
-    if zai_enabled {
-        return crate::proxy::providers::zai_anthropic::forward_anthropic_json(
-            &state,
-            axum::http::Method::POST,
-            "/v1/messages/count_tokens",
-            &headers,
-            body,
-        )
-        .await;
+    // Google 账号池没有计数接口: 优先使用本次调度的服务商，否则使用首个参与自动调度的 Anthropic 兼容服务商
+    let model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+    let route_ctx = RouteContext::from_request("claude", &body, &headers);
+    let mut dispatch = providers::dispatch(&state, "claude", &model, &route_ctx).await;
+    if dispatch.is_google() {
+        if let Some(provider) = providers::registry(&state).await.into_iter().find(|p| {
+            p.id() != providers::GOOGLE_PROVIDER_ID
+                && p.supports("claude")
+                && p.dispatch_mode() != crate::proxy::config::ProviderDispatchMode::RuleOnly
+        }) {
+            dispatch.provider = provider;
+        }
+    }
+    if !dispatch.is_google() {
+        if let ProviderOutcome::Responded(response) =
+            dispatch.forward(&state, "/v1/messages/count_tokens", &headers, body).await
+        {
+            return response;
+        }
     }
 
     Json(json!({
@@ -914,151 +954,3 @@ mod tests {
     }
 }
 */
-
-// ===== 后台任务检测辅助函数 =====
-
-/// 后台任务类型
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum BackgroundTaskType {
-    TitleGeneration,      // 标题生成
-    SimpleSummary,        // 简单摘要
-    ContextCompression,   // 上下文压缩
-    PromptSuggestion,     // 提示建议
-    SystemMessage,        // 系统消息
-    EnvironmentProbe,     // 环境探测
-}
-
-/// 标题生成关键词
-const TITLE_KEYWORDS: &[&str] = &[
-    "write a 5-10 word title",
-    "Please write a 5-10 word title",
-    "Respond with the title",
-    "Generate a title for",
-    "Create a brief title",
-    "title for the conversation",
-    "conversation title",
-    "生成标题",
-    "为对话起个标题",
-];
-
-/// 摘要生成关键词
-const SUMMARY_KEYWORDS: &[&str] = &[
-    "Summarize this coding conversation",
-    "Summarize the conversation",
-    "Concise summary",
-    "in under 50 characters",
-    "compress the context",
-    "Provide a concise summary",
-    "condense the previous messages",
-    "shorten the conversation history",
-    "extract key points from",
-];
-
-/// 建议生成关键词
-const SUGGESTION_KEYWORDS: &[&str] = &[
-    "prompt suggestion generator",
-    "suggest next prompts",
-    "what should I ask next",
-    "generate follow-up questions",
-    "recommend next steps",
-    "possible next actions",
-];
-
-/// 系统消息关键词
-const SYSTEM_KEYWORDS: &[&str] = &[
-    "Warmup",
-    "<system-reminder>",
-    // Removed: "Caveat: The messages below were generated" - this is a normal Claude Desktop system prompt
-    "This is a system message",
-];
-
-/// 环境探测关键词
-const PROBE_KEYWORDS: &[&str] = &[
-    "check current directory",
-    "list available tools",
-    "verify environment",
-    "test connection",
-];
-
-/// 检测后台任务并返回任务类型
-fn detect_background_task_type(request: &ClaudeRequest) -> Option<BackgroundTaskType> {
-    let last_user_msg = extract_last_user_message_for_detection(request)?;
-    let preview = last_user_msg.chars().take(500).collect::<String>();
-    
-    // 长度过滤：后台任务通常不超过 800 字符
-    if last_user_msg.len() > 800 {
-        return None;
-    }
-    
-    // 按优先级匹配
-    if matches_keywords(&preview, SYSTEM_KEYWORDS) {
-        return Some(BackgroundTaskType::SystemMessage);
-    }
-    
-    if matches_keywords(&preview, TITLE_KEYWORDS) {
-        return Some(BackgroundTaskType::TitleGeneration);
-    }
-    
-    if matches_keywords(&preview, SUMMARY_KEYWORDS) {
-        if preview.contains("in under 50 characters") {
-            return Some(BackgroundTaskType::SimpleSummary);
-        }
-        return Some(BackgroundTaskType::ContextCompression);
-    }
-    
-    if matches_keywords(&preview, SUGGESTION_KEYWORDS) {
-        return Some(BackgroundTaskType::PromptSuggestion);
-    }
-    
-    if matches_keywords(&preview, PROBE_KEYWORDS) {
-        return Some(BackgroundTaskType::EnvironmentProbe);
-    }
-    
-    None
-}
-
-/// 辅助函数：关键词匹配
-fn matches_keywords(text: &str, keywords: &[&str]) -> bool {
-    keywords.iter().any(|kw| text.contains(kw))
-}
-
-/// 辅助函数：提取最后一条用户消息（用于检测）
-fn extract_last_user_message_for_detection(request: &ClaudeRequest) -> Option<String> {
-    request.messages.iter().rev()
-        .filter(|m| m.role == "user")
-        .find_map(|m| {
-            let content = match &m.content {
-                crate::proxy::mappers::claude::models::MessageContent::String(s) => s.to_string(),
-                crate::proxy::mappers::claude::models::MessageContent::Array(arr) => {
-                    arr.iter()
-                        .filter_map(|block| match block {
-                            crate::proxy::mappers::claude::models::ContentBlock::Text { text } => Some(text.as_str()),
-                            _ => None,
-                        })
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                }
-            };
-            
-            if content.trim().is_empty() 
-                || content.starts_with("Warmup") 
-                || content.contains("<system-reminder>") 
-            {
-                None 
-            } else {
-                Some(content)
-            }
-        })
-}
-
-/// 根据后台任务类型选择合适的模型
-fn select_background_model(task_type: BackgroundTaskType) -> &'static str {
-    match task_type {
-        BackgroundTaskType::TitleGeneration => BACKGROUND_MODEL_LITE,     // 极简任务
-        BackgroundTaskType::SimpleSummary => BACKGROUND_MODEL_LITE,       // 简单摘要
-        BackgroundTaskType::SystemMessage => BACKGROUND_MODEL_LITE,       // 系统消息
-        BackgroundTaskType::PromptSuggestion => BACKGROUND_MODEL_LITE,    // 建议生成
-        BackgroundTaskType::EnvironmentProbe => BACKGROUND_MODEL_LITE,    // 环境探测
-        BackgroundTaskType::ContextCompression => BACKGROUND_MODEL_STANDARD, // 复杂压缩
-    }
-}
@@ -1,7 +1,123 @@
-use axum::{extract::State, extract::Json, http::StatusCode, response::IntoResponse};
+use axum::{extract::State, extract::Json, http::{HeaderMap, HeaderValue, StatusCode}, response::{IntoResponse, Response}};
 use serde_json::{json, Value};
+use crate::proxy::mappers::common_utils::{resolve_request_config, RequestConfig};
+use crate::proxy::config::HookPoint;
+use crate::proxy::hooks::{HookContext, HookRunner};
 use crate::proxy::server::AppState;
 
+/// 请求头: 设为 off/false/0 时本次请求不使用模型回退链
+pub const MODEL_FALLBACK_REQUEST_HEADER: &str = "x-model-fallback";
+
+/// 获取到的账号及实际使用的模型 (可能为回退模型)
+pub struct TokenLease {
+    pub access_token: String,
+    pub project_id: String,
+    pub email: String,
+    /// 实际使用的模型 (回退时为回退链中的模型)
+    pub mapped_model: String,
+    pub config: RequestConfig,
+    /// 发生回退时的原始映射模型
+    pub fallback_from: Option<String>,
+}
+
+/// 客户端是否通过请求头关闭了模型回退
+fn fallback_disabled_by_request(headers: &HeaderMap) -> bool {
+    headers
+        .get(MODEL_FALLBACK_REQUEST_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "off" | "false" | "0" | "disabled"))
+        .unwrap_or(false)
+}
+
+/// 获取 Token；主模型没有可用账号时按回退链依次尝试其他模型
+/// 全部失败时返回主模型的错误
+pub async fn acquire_token(
+    state: &AppState,
+    original_model: &str,
+    mapped_model: &str,
+    tools: &Option<Vec<Value>>,
+    force_rotate: bool,
+    session_id: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<TokenLease, String> {
+    let chain = if fallback_disabled_by_request(headers) {
+        Vec::new()
+    } else {
+        crate::proxy::common::model_mapping::resolve_fallback_chain(mapped_model, &*state.model_fallback.read().await)
+    };
+
+    let mut first_error: Option<String> = None;
+    for model in std::iter::once(mapped_model.to_string()).chain(chain) {
+        let config = resolve_request_config(original_model, &model, tools);
+        match state
+            .token_manager
+            .get_token_for_model(&config.request_type, force_rotate, session_id, Some(&model))
+            .await
+        {
+            Ok((access_token, project_id, email)) => {
+                let fallback_from = (model != mapped_model).then(|| mapped_model.to_string());
+                if let Some(primary) = &fallback_from {
+                    tracing::warn!("[Model-Fallback] No account available for {}, falling back to {}", primary, model);
+                }
+                return Ok(TokenLease { access_token, project_id, email, mapped_model: model, config, fallback_from });
+            }
+            Err(e) => {
+                if first_error.is_some() {
+                    tracing::debug!("[Model-Fallback] Fallback model {} unavailable: {}", model, e);
+                }
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    Err(first_error.unwrap_or_else(|| "Token pool is empty".to_string()))
+}
+
+/// 构建本次请求的脚本钩子，并对客户端请求执行 RequestParsed 钩子
+/// model 为空时取请求体中的 model 字段 (Gemini 的模型在路径中)
+pub async fn run_request_hooks(
+    state: &AppState,
+    protocol: &str,
+    model: Option<&str>,
+    body: &mut Value,
+    headers: &HeaderMap,
+) -> HookRunner {
+    let model = model.or_else(|| body.get("model").and_then(|m| m.as_str())).unwrap_or_default();
+    let client_key = crate::proxy::common::model_mapping::client_key_from_headers(headers);
+    let ctx = HookContext {
+        protocol: protocol.to_string(),
+        model: model.to_string(),
+        ..Default::default()
+    }
+    .with_client_key(client_key.as_deref());
+    let hooks = HookRunner::new(&*state.hooks.read().await, ctx);
+    hooks.run(HookPoint::RequestParsed, body);
+    hooks
+}
+
+/// 本次请求的路由调整 (模型回退/后台任务降级)，通过响应头告知客户端并写入请求日志
+#[derive(Debug, Clone, Default)]
+pub struct RouteNotes {
+    /// 发生模型回退时的原始映射模型
+    pub fallback_from: Option<String>,
+    /// 后台任务降级说明 ("类别: 原模型 -> 目标模型")
+    pub downgrade: Option<String>,
+}
+
+/// 添加 X-Model-Fallback ("原模型 -> 回退模型") 与 X-Background-Downgrade 响应头
+pub fn with_route_headers(mut response: Response, mapped_model: &str, notes: &RouteNotes) -> Response {
+    if let Some(primary) = &notes.fallback_from {
+        if let Ok(value) = HeaderValue::from_str(&format!("{} -> {}", primary, mapped_model)) {
+            response.headers_mut().insert("X-Model-Fallback", value);
+        }
+    }
+    if let Some(downgrade) = &notes.downgrade {
+        if let Ok(value) = HeaderValue::from_str(downgrade) {
+            response.headers_mut().insert("X-Background-Downgrade", value);
+        }
+    }
+    response
+}
+
 /// Detects model capabilities and configuration
 /// POST /v1/models/detect
 pub async fn handle_detect_model(
@@ -17,7 +133,8 @@ pub async fn handle_detect_model(
     // 1. Resolve mapping
     let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
         model_name,
-        &*state.custom_mapping.read().await,
+        &state.routing_rules.read().await,
+        &crate::proxy::common::model_mapping::RouteContext::new("openai"),
     );
 
     // 2. Resolve capabilities
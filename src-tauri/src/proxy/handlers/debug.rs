@@ -0,0 +1,235 @@
+// 调试接口: 路由解释 (dry-run)
+// 按真实处理流程解析一次请求，返回各阶段的决策与转换后的 v1internal 请求体，不调用上游
+// 远程媒体下载与图像预处理需要网络/解码，不在此执行
+
+use axum::{extract::State, http::HeaderMap, http::StatusCode, response::IntoResponse, Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::proxy::common::background_task::{
+    detect_background_task, last_user_text_claude, last_user_text_gemini, last_user_text_openai,
+};
+use crate::proxy::common::model_mapping::{resolve_fallback_chain, resolve_route, RouteContext};
+use crate::proxy::common::param_policy::ResolvedPolicy;
+use crate::proxy::config::HookPoint;
+use crate::proxy::handlers::common::run_request_hooks;
+use crate::proxy::mappers::common_utils::resolve_request_config;
+use crate::proxy::providers;
+use crate::proxy::server::AppState;
+use crate::proxy::session_manager::SessionManager;
+
+/// 未选中账号或账号缺少 project_id 时使用的占位
+const PLACEHOLDER_PROJECT_ID: &str = "<project-id>";
+
+#[derive(Deserialize)]
+pub struct ExplainRequest {
+    /// 入口协议: claude / openai (Chat Completions) / gemini
+    pub protocol: String,
+    /// 原始请求体
+    pub request: Value,
+    /// Gemini 的模型在 URL 路径中，需单独传入
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 模拟的客户端请求头 (客户端 Key、路由条件中的 header 等)
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// 解析请求后得到的协议相关部分
+struct ParsedRequest {
+    original_model: String,
+    tools: Option<Vec<Value>>,
+    session_id: String,
+    background_text: Option<String>,
+}
+
+fn bad_request(message: String) -> axum::response::Response {
+    (StatusCode::BAD_REQUEST, Json(json!({ "status": "error", "message": message }))).into_response()
+}
+
+/// POST /api/admin/debug/explain
+pub async fn handle_explain(State(state): State<AppState>, Json(payload): Json<ExplainRequest>) -> impl IntoResponse {
+    let protocol: &'static str = match payload.protocol.to_ascii_lowercase().as_str() {
+        "claude" | "anthropic" => "claude",
+        "openai" => "openai",
+        "gemini" => "gemini",
+        other => return bad_request(format!("Unsupported protocol: {}", other)),
+    };
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &payload.headers {
+        match (name.parse::<axum::http::HeaderName>(), value.parse::<axum::http::HeaderValue>()) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => return bad_request(format!("Invalid header: {}", name)),
+        }
+    }
+
+    // 1. 协议解析后钩子 + 路由上下文
+    let mut body = payload.request;
+    let hooks = run_request_hooks(&state, protocol, payload.model.as_deref(), &mut body, &headers).await;
+    let route_ctx = RouteContext::from_request(protocol, &body, &headers);
+
+    let mut claude_req = None;
+    let mut openai_req = None;
+    let parsed = match protocol {
+        "claude" => {
+            let mut request: crate::proxy::mappers::claude::ClaudeRequest = match serde_json::from_value(body.clone()) {
+                Ok(r) => r,
+                Err(e) => return bad_request(format!("Invalid request body: {}", e)),
+            };
+            crate::proxy::handlers::claude::filter_invalid_thinking_blocks(&mut request.messages);
+            if state.experimental.read().await.enable_tool_loop_recovery {
+                crate::proxy::mappers::claude::close_tool_loop_for_thinking(&mut request.messages);
+            }
+            let parsed = ParsedRequest {
+                original_model: request.model.clone(),
+                tools: request
+                    .tools
+                    .as_ref()
+                    .map(|list| list.iter().map(|t| serde_json::to_value(t).unwrap_or(json!({}))).collect()),
+                session_id: SessionManager::extract_session_id(&request),
+                background_text: last_user_text_claude(&request),
+            };
+            claude_req = Some(request);
+            parsed
+        }
+        "openai" => {
+            let request: crate::proxy::mappers::openai::OpenAIRequest = match serde_json::from_value(body.clone()) {
+                Ok(r) => r,
+                Err(e) => return bad_request(format!("Invalid request: {}", e)),
+            };
+            let parsed = ParsedRequest {
+                original_model: request.model.clone(),
+                tools: request.tools.as_ref().map(|list| list.to_vec()),
+                session_id: SessionManager::extract_openai_session_id(&request),
+                background_text: last_user_text_openai(&request.messages),
+            };
+            openai_req = Some(request);
+            parsed
+        }
+        _ => {
+            let Some(model) = payload.model.clone() else {
+                return bad_request("Gemini requests require `model`".to_string());
+            };
+            ParsedRequest {
+                tools: body.get("tools").and_then(|t| t.as_array()).map(|arr| {
+                    arr.iter()
+                        .flat_map(|entry| match entry.get("functionDeclarations").and_then(|v| v.as_array()) {
+                            Some(decls) => decls.clone(),
+                            None => vec![entry.clone()],
+                        })
+                        .collect()
+                }),
+                session_id: SessionManager::extract_gemini_session_id(&body, &model),
+                background_text: last_user_text_gemini(&body),
+                original_model: model,
+            }
+        }
+    };
+
+    // 2. 服务商选择 (非 Google 账号池时请求体直接透传)
+    let dispatch = providers::preview_dispatch(&state, protocol, &parsed.original_model, &route_ctx).await;
+    let provider = json!({
+        "id": dispatch.provider_id(),
+        "reason": dispatch.reason,
+        "upstream_model": dispatch.upstream_model(),
+    });
+    if !dispatch.is_google() {
+        if let Some(model) = body.get_mut("model") {
+            *model = Value::String(dispatch.upstream_model());
+        }
+        return Json(json!({
+            "status": "success",
+            "data": {
+                "protocol": protocol,
+                "original_model": parsed.original_model,
+                "provider": provider,
+                "upstream_body": body,
+            }
+        }))
+        .into_response();
+    }
+
+    // 3. 模型路由 + 后台任务识别
+    let route = resolve_route(&parsed.original_model, &state.routing_rules.read().await, &route_ctx);
+    let background_task = {
+        let background_config = state.background_tasks.read().await;
+        parsed.background_text.as_deref().and_then(|text| detect_background_task(text, &background_config))
+    };
+    let (target_model, tools) = match &background_task {
+        // Claude 后台任务会移除工具定义
+        Some(task) if protocol == "claude" => (task.target_model.clone(), None),
+        Some(task) => (task.target_model.clone(), parsed.tools.clone()),
+        None => (route.model.clone(), parsed.tools.clone()),
+    };
+
+    // 4. 请求配置、回退链与账号选择
+    let request_config = resolve_request_config(&parsed.original_model, &target_model, &tools);
+    let fallback_chain = resolve_fallback_chain(&target_model, &*state.model_fallback.read().await);
+    let account = state
+        .token_manager
+        .preview_token(&request_config.request_type, false, Some(&parsed.session_id), Some(&target_model))
+        .await;
+    let project_id = account.project_id.clone().unwrap_or_else(|| PLACEHOLDER_PROJECT_ID.to_string());
+
+    // 5. 参数策略 + 协议转换 + 路由覆盖 + 发送上游前钩子
+    let policy = ResolvedPolicy::resolve(&target_model, route_ctx.client_key.as_deref(), &state.parameter_policies.read().await);
+    let upstream_body = match (claude_req, openai_req) {
+        (Some(mut request), _) => {
+            crate::proxy::handlers::claude::sanitize_for_target(&mut request, background_task.is_some());
+            policy.apply_claude(&mut request);
+            request.model = target_model.clone();
+            crate::proxy::mappers::claude::transform_claude_request_in(&request, &project_id)
+        }
+        (_, Some(mut request)) => {
+            policy.apply_openai(&mut request);
+            Ok(crate::proxy::mappers::openai::transform_openai_request(
+                &request,
+                &project_id,
+                &target_model,
+                &*state.thinking.read().await,
+            ))
+        }
+        _ => {
+            policy.apply_gemini(&mut body);
+            Ok(crate::proxy::mappers::gemini::wrap_request(&body, &project_id, &target_model))
+        }
+    };
+    let upstream_body = match upstream_body {
+        Ok(mut b) => {
+            route.apply_overrides(&mut b);
+            policy.apply_safety(&mut b);
+            hooks
+                .with_route(account.account.as_deref().unwrap_or_default(), &target_model)
+                .run(HookPoint::BeforeUpstream, &mut b);
+            b
+        }
+        Err(e) => json!({ "error": format!("Transform error: {}", e) }),
+    };
+
+    Json(json!({
+        "status": "success",
+        "data": {
+            "protocol": protocol,
+            "original_model": parsed.original_model,
+            "provider": provider,
+            "route": {
+                "model": route.model,
+                "rule": route.rule.as_ref().map(|r| r.label()),
+                "rule_config": route.rule,
+            },
+            "background_task": background_task,
+            "target_model": target_model,
+            "request_config": request_config,
+            "session_id": parsed.session_id,
+            "fallback_chain": fallback_chain,
+            "parameter_policies": policy.names(),
+            "account": account,
+            "upstream_body": upstream_body,
+        }
+    }))
+    .into_response()
+}
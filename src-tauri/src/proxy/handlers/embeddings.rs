@@ -0,0 +1,202 @@
+// Embeddings Handler
+// OpenAI /v1/embeddings 与 Gemini 原生 embedContent / batchEmbedContents
+use axum::{extract::Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde_json::{json, Value};
+use tracing::{debug, error, info};
+
+use crate::proxy::common::model_mapping::{
+    is_embedding_model, resolve_model_route, RouteContext, DEFAULT_EMBEDDING_MODEL,
+};
+use crate::proxy::mappers::gemini::unwrap_response;
+use crate::proxy::mappers::openai::embeddings::{
+    build_batch_embed_request, build_openai_embedding_response, extract_embeddings,
+    parse_embedding_input, EmbeddingRequest, MAX_BATCH_SIZE,
+};
+use crate::proxy::server::AppState;
+
+const MAX_RETRY_ATTEMPTS: usize = 3;
+/// Embedding 请求使用独立的配额组
+const EMBEDDING_QUOTA_GROUP: &str = "embedding";
+
+/// 处理 OpenAI Embeddings API (/v1/embeddings)
+pub async fn handle_embeddings(
+    State(state): State<AppState>,
+    Json(body): Json<Value>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let req: EmbeddingRequest = serde_json::from_value(body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+    let texts = parse_embedding_input(&req.input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mapped_model = resolve_embedding_model(&state, &req.model, "openai").await;
+    info!(
+        "[Embeddings] Received request: model={} -> {}, inputs={}",
+        req.model,
+        mapped_model,
+        texts.len()
+    );
+
+    // 超过单次批量上限时分批请求，每批独立选取账号
+    let mut embeddings = Vec::with_capacity(texts.len());
+    let mut last_email = String::new();
+    for chunk in texts.chunks(MAX_BATCH_SIZE) {
+        let inner = build_batch_embed_request(chunk, &mapped_model, req.dimensions);
+        let (resp, email) = call_embed_upstream(&state, "batchEmbedContents", &mapped_model, inner).await?;
+        let chunk_embeddings = extract_embeddings(&resp).map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+        if chunk_embeddings.len() != chunk.len() {
+            return Err((
+                StatusCode::BAD_GATEWAY,
+                format!(
+                    "Upstream returned {} embeddings for {} inputs",
+                    chunk_embeddings.len(),
+                    chunk.len()
+                ),
+            ));
+        }
+        embeddings.extend(chunk_embeddings);
+        last_email = email;
+    }
+
+    let response = build_openai_embedding_response(&req.model, &texts, embeddings, req.wants_base64());
+    Ok((
+        StatusCode::OK,
+        [("X-Account-Email", last_email), ("X-Mapped-Model", mapped_model)],
+        Json(response),
+    ))
+}
+
+/// 处理 Gemini 原生 embedContent / batchEmbedContents (由 gemini::handle_generate 分发)
+pub async fn handle_native_embed(
+    state: AppState,
+    model_name: &str,
+    method: &str,
+    mut body: Value,
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    let mapped_model = resolve_embedding_model(&state, model_name, "gemini").await;
+    info!("[Embeddings] Received native {} request: model={} -> {}", method, model_name, mapped_model);
+
+    // 批量请求中的每条 model 字段同样需要替换为映射后的模型
+    if method == "batchEmbedContents" {
+        let requests = body
+            .get_mut("requests")
+            .and_then(|v| v.as_array_mut())
+            .ok_or((StatusCode::BAD_REQUEST, "Missing 'requests' array".to_string()))?;
+        for req in requests.iter_mut() {
+            req["model"] = json!(format!("models/{}", mapped_model));
+        }
+    }
+
+    let (resp, email) = call_embed_upstream(&state, method, &mapped_model, body).await?;
+    Ok((
+        StatusCode::OK,
+        [("X-Account-Email", email), ("X-Mapped-Model", mapped_model)],
+        Json(resp),
+    )
+        .into_response())
+}
+
+/// 模型路由解析，映射结果不是 Embedding 模型时回退到默认模型
+async fn resolve_embedding_model(state: &AppState, model: &str, protocol: &'static str) -> String {
+    let mapped = resolve_model_route(model, &state.routing_rules.read().await, &RouteContext::new(protocol));
+    if is_embedding_model(&mapped) {
+        mapped
+    } else {
+        debug!(
+            "[Embeddings] {} resolved to non-embedding model {}, using {}",
+            model, mapped, DEFAULT_EMBEDDING_MODEL
+        );
+        DEFAULT_EMBEDDING_MODEL.to_string()
+    }
+}
+
+/// 调用上游 embed 接口 (带账号轮换重试)，返回解包后的响应与所用账号
+async fn call_embed_upstream(
+    state: &AppState,
+    method: &str,
+    mapped_model: &str,
+    inner_request: Value,
+) -> Result<(Value, String), (StatusCode, String)> {
+    let upstream = state.upstream.clone();
+    let token_manager = state.token_manager.clone();
+    let max_attempts = MAX_RETRY_ATTEMPTS.min(token_manager.len()).max(1);
+
+    let mut last_error = String::new();
+
+    for attempt in 0..max_attempts {
+        let (access_token, project_id, email) = match token_manager
+            .get_token_for_model(EMBEDDING_QUOTA_GROUP, attempt > 0, None, Some(mapped_model))
+            .await
+        {
+            Ok(t) => t,
+            Err(e) => {
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Token error: {}", e),
+                ));
+            }
+        };
+
+        info!("✓ Using account: {} (type: {})", email, EMBEDDING_QUOTA_GROUP);
+
+        let wrapped = json!({
+            "project": project_id,
+            "requestId": format!("embed-{}", uuid::Uuid::new_v4()),
+            "model": mapped_model,
+            "request": inner_request,
+        });
+
+        let response = match upstream.call_v1_internal(method, &access_token, wrapped, None).await {
+            Ok(r) => r,
+            Err(e) => {
+                last_error = e.clone();
+                debug!(
+                    "Embedding request failed on attempt {}/{}: {}",
+                    attempt + 1,
+                    max_attempts,
+                    e
+                );
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            let gemini_resp: Value = response
+                .json()
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+            return Ok((unwrap_response(&gemini_resp), email));
+        }
+
+        let status_code = status.as_u16();
+        let retry_after = response.headers().get("Retry-After").and_then(|h| h.to_str().ok()).map(|s| s.to_string());
+        let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status_code));
+        last_error = format!("HTTP {}: {}", status_code, error_text);
+
+        // 限流与过载: 记录限流信息后轮换账号
+        if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 {
+            token_manager.mark_rate_limited(&email, status_code, retry_after.as_deref(), &error_text, Some(mapped_model));
+
+            if status_code == 429 && error_text.contains("QUOTA_EXHAUSTED") {
+                error!("Embedding Quota exhausted (429) on account {} attempt {}/{}, stopping to protect pool.", email, attempt + 1, max_attempts);
+                return Err((status, error_text));
+            }
+
+            tracing::warn!("Embedding Upstream {} on account {} attempt {}/{}, rotating account", status_code, email, attempt + 1, max_attempts);
+            continue;
+        }
+
+        // 403 (权限/地区限制) 和 401 (认证失效) 与限流无关，不写入限流状态，仅轮换账号
+        if status_code == 403 || status_code == 401 {
+            tracing::warn!("Embedding Upstream {} on account {} attempt {}/{}, rotating account", status_code, email, attempt + 1, max_attempts);
+            continue;
+        }
+
+        error!("Embedding Upstream non-retryable error {}: {}", status_code, error_text);
+        return Err((status, error_text));
+    }
+
+    Err((
+        StatusCode::TOO_MANY_REQUESTS,
+        format!("All accounts exhausted. Last error: {}", last_error),
+    ))
+}
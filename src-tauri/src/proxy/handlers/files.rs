@@ -0,0 +1,88 @@
+// 文件访问处理器 - 生成图像的签名链接 (/files/images/:id)
+// 链接由签名与过期时间鉴权，不需要 API Key，便于直接嵌入客户端 <img>
+
+use axum::{
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::proxy::image_store::ImageStore;
+use crate::proxy::server::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct SignedFileQuery {
+    pub exp: i64,
+    pub sig: String,
+}
+
+/// GET /files/images/:id?exp=..&sig=..
+pub async fn handle_get_image(
+    Path(id): Path<String>,
+    Query(query): Query<SignedFileQuery>,
+) -> Response {
+    let store = ImageStore::global();
+    if !store.verify(&id, query.exp, &query.sig) {
+        return (StatusCode::FORBIDDEN, "Invalid or expired image link").into_response();
+    }
+
+    match store.load(&id) {
+        Some((data, mime_type)) => (
+            [
+                (header::CONTENT_TYPE, mime_type),
+                // 内容寻址，但缓存时长不能超过链接剩余有效期
+                (header::CACHE_CONTROL, cache_control(query.exp, chrono::Utc::now().timestamp())),
+            ],
+            data,
+        )
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, "Image not found").into_response(),
+    }
+}
+
+/// 按签名链接的剩余有效期生成 Cache-Control
+fn cache_control(expires: i64, now: i64) -> String {
+    format!("private, max-age={}, immutable", expires.saturating_sub(now).max(0))
+}
+
+/// 生成签名链接的对外地址
+/// 优先使用配置的 public_base_url，否则按请求 Host (兼容反向代理的 X-Forwarded-*) 推断
+pub async fn resolve_base_url(state: &AppState, headers: &HeaderMap) -> String {
+    if let Some(base) = state
+        .image_storage
+        .read()
+        .await
+        .public_base_url
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+    {
+        return base.trim_end_matches('/').to_string();
+    }
+
+    let header_str = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let host = header_str("x-forwarded-host")
+        .or_else(|| header_str("host"))
+        .unwrap_or_else(|| "127.0.0.1".to_string());
+    let proto = header_str("x-forwarded-proto").unwrap_or_else(|| "http".to_string());
+    format!("{}://{}", proto, host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_control_follows_link_expiry() {
+        assert_eq!(cache_control(1_000 + 90, 1_000), "private, max-age=90, immutable");
+        assert_eq!(cache_control(1_000, 1_000), "private, max-age=0, immutable");
+    }
+}
@@ -1,9 +1,15 @@
 // Gemini Handler
-use axum::{extract::State, extract::{Json, Path}, http::StatusCode, response::IntoResponse};
+use axum::{extract::State, extract::{Json, Path}, http::{HeaderMap, StatusCode}, response::IntoResponse};
 use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
+use crate::proxy::common::background_task::{detect_background_task, last_user_text_gemini};
+use crate::proxy::common::model_mapping::{resolve_route, RouteContext};
+use crate::proxy::common::param_policy::ResolvedPolicy;
+use crate::proxy::config::HookPoint;
+use crate::proxy::handlers::common::{acquire_token, run_request_hooks, with_route_headers, RouteNotes, TokenLease};
 use crate::proxy::mappers::gemini::{wrap_request, unwrap_response};
+use crate::proxy::providers::{self, ProviderOutcome};
 use crate::proxy::server::AppState;
 use crate::proxy::session_manager::SessionManager;
  
@@ -14,7 +20,8 @@ const MAX_RETRY_ATTEMPTS: usize = 3;
 pub async fn handle_generate(
     State(state): State<AppState>,
     Path(model_action): Path<String>,
-    Json(body): Json<Value>
+    headers: HeaderMap,
+    Json(mut body): Json<Value>
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // 解析 model:method
     let (model_name, method) = if let Some((m, action)) = model_action.rsplit_once(':') {
@@ -25,26 +32,44 @@ pub async fn handle_generate(
 
     crate::modules::logger::log_info(&format!("Received Gemini request: {}/{}", model_name, method));
 
+    // Embedding 方法走独立的处理流程 (独立配额组)
+    if method == "embedContent" || method == "batchEmbedContents" {
+        return crate::proxy::handlers::embeddings::handle_native_embed(state, &model_name, &method, body).await;
+    }
+
     // 1. 验证方法
     if method != "generateContent" && method != "streamGenerateContent" {
         return Err((StatusCode::BAD_REQUEST, format!("Unsupported method: {}", method)));
     }
     let is_stream = method == "streamGenerateContent";
+    let hooks = run_request_hooks(&state, "gemini", Some(&model_name), &mut body, &headers).await;
+    let route_ctx = RouteContext::from_request("gemini", &body, &headers);
+
+    // 选择上游服务商: 其他服务商 (如 z.ai) 自行完成协议转换，Google 账号池继续走 v1internal 流程
+    let dispatch = providers::dispatch(&state, "gemini", &model_name, &route_ctx).await;
+    let path = format!("/v1beta/models/{}:{}", model_name, method);
+    let body = match dispatch.forward_with_policy(&state, &path, &headers, body, &hooks).await {
+        ProviderOutcome::Responded(response) => return Ok(response),
+        ProviderOutcome::Continue(body) => body,
+    };
 
     // 2. 获取 UpstreamClient 和 TokenManager
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
     
+    // 后台任务识别: 命中 background_tasks 规则时降级到目标模型
+    let background_task = {
+        let background_config = state.background_tasks.read().await;
+        last_user_text_gemini(&body).and_then(|text| detect_background_task(&text, &background_config))
+    };
+
     let mut last_error = String::new();
 
     for attempt in 0..max_attempts {
         // 3. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &model_name,
-            &*state.custom_mapping.read().await,
-        );
+        let route = resolve_route(&model_name, &state.routing_rules.read().await, &route_ctx);
         // 提取 tools 列表以进行联网探测 (Gemini 风格可能是嵌套的)
         let tools_val: Option<Vec<Value>> = body.get("tools").and_then(|t| t.as_array()).map(|arr| {
             let mut flattened = Vec::new();
@@ -58,24 +83,52 @@ pub async fn handle_generate(
             flattened
         });
 
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(&model_name, &mapped_model, &tools_val);
-
-        // 4. 获取 Token (使用准确的 request_type)
+        // 4. 获取 Token (使用准确的 request_type，无可用账号时按回退链切换模型)
         // 提取 SessionId (粘性指纹)
         let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
 
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
-        let (access_token, project_id, email) = match token_manager.get_token(&config.request_type, attempt > 0, Some(&session_id)).await {
-            Ok(t) => t,
+        let target_model = background_task.as_ref().map_or_else(|| route.model.clone(), |task| task.target_model.clone());
+        let TokenLease { access_token, project_id, email, mapped_model, config, fallback_from } = match acquire_token(
+            &state,
+            &model_name,
+            &target_model,
+            &tools_val,
+            attempt > 0,
+            Some(&session_id),
+            &headers,
+        )
+        .await
+        {
+            Ok(lease) => lease,
             Err(e) => {
                 return Err((StatusCode::SERVICE_UNAVAILABLE, format!("Token error: {}", e)));
             }
         };
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
+        let notes = RouteNotes {
+            fallback_from,
+            downgrade: background_task.as_ref().map(|task| task.describe(&route.model)),
+        };
+        if let Some(task) = &background_task {
+            info!("[Gemini][AUTO] 检测到后台任务 (类别: {}, 命中: {:?}),强制降级: {} -> {}", task.category, task.matched, route.model, mapped_model);
+        }
+
+        // 参数策略: 默认值/范围/强制值与系统指令 (包装前应用)
+        let policy = ResolvedPolicy::resolve(&mapped_model, route_ctx.client_key.as_deref(), &state.parameter_policies.read().await);
+        let policy_body = (!policy.is_empty()).then(|| {
+            let mut b = body.clone();
+            policy.apply_gemini(&mut b);
+            b
+        });
 
         // 5. 包装请求 (project injection)
-        let wrapped_body = wrap_request(&body, &project_id, &mapped_model);
+        let mut wrapped_body = wrap_request(policy_body.as_ref().unwrap_or(&body), &project_id, &mapped_model);
+        route.apply_overrides(&mut wrapped_body);
+        policy.apply_safety(&mut wrapped_body);
+        let attempt_hooks = hooks.with_route(&email, &mapped_model);
+        attempt_hooks.run(HookPoint::BeforeUpstream, &mut wrapped_body);
 
         // 5. 上游调用
         let query_string = if is_stream { Some("alt=sse") } else { None };
@@ -101,7 +154,7 @@ pub async fn handle_generate(
                 use bytes::{Bytes, BytesMut};
                 use futures::StreamExt;
                 
-                let mut response_stream = response.bytes_stream();
+                let mut response_stream = attempt_hooks.wrap_upstream_stream(Box::pin(response.bytes_stream()));
                 let mut buffer = BytesMut::new();
 
                 let stream = async_stream::stream! {
@@ -158,15 +211,15 @@ pub async fn handle_generate(
                 };
                 
                 let body = Body::from_stream(stream);
-                return Ok(Response::builder()
+                let response = Response::builder()
                     .header("Content-Type", "text/event-stream")
                     .header("Cache-Control", "no-cache")
                     .header("Connection", "keep-alive")
                     .header("X-Account-Email", &email)
                     .header("X-Mapped-Model", &mapped_model)
                     .body(body)
-                    .unwrap()
-                    .into_response());
+                    .unwrap();
+                return Ok(with_route_headers(response, &mapped_model, &notes));
             }
 
             let gemini_resp: Value = response
@@ -175,7 +228,8 @@ pub async fn handle_generate(
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
 
             let unwrapped = unwrap_response(&gemini_resp);
-            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(unwrapped)).into_response());
+            let response = (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(attempt_hooks.before_response(&unwrapped))).into_response();
+            return Ok(with_route_headers(response, &mapped_model, &notes));
         }
 
         // 处理错误并重试
@@ -187,7 +241,7 @@ pub async fn handle_generate(
         // 只有 429 (限流), 529 (过载), 503, 403 (权限) 和 401 (认证失效) 触发账号轮换
         if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 || status_code == 403 || status_code == 401 {
             // 记录限流信息 (全局同步)
-            token_manager.mark_rate_limited(&email, status_code, retry_after.as_deref(), &error_text);
+            token_manager.mark_rate_limited(&email, status_code, retry_after.as_deref(), &error_text, Some(&mapped_model));
 
             // 只有明确包含 "QUOTA_EXHAUSTED" 才停止，避免误判上游的频率限制提示 (如 "check quota")
             if status_code == 429 && error_text.contains("QUOTA_EXHAUSTED") {
@@ -212,7 +266,7 @@ pub async fn handle_list_models(State(state): State<AppState>) -> Result<impl In
 
     // 获取所有动态模型列表（与 /v1/models 一致）
     let model_ids = get_all_dynamic_models(
-        &state.custom_mapping,
+        &state.routing_rules,
     ).await;
 
     // 转换为 Gemini API 格式
@@ -10,6 +10,7 @@ use serde_json::{json, Value};
 use tokio::time::Duration;
 use tokio_stream::wrappers::IntervalStream;
 
+use crate::proxy::providers::zai::pick_api_key;
 use crate::proxy::server::AppState;
 
 fn build_client(
@@ -50,9 +51,9 @@ async fn forward_mcp(
     body: Body,
 ) -> Response {
     let zai = state.zai.read().await.clone();
-    if !zai.enabled || zai.api_key.trim().is_empty() {
+    let Some(api_key) = pick_api_key(&zai).filter(|_| zai.enabled) else {
         return (StatusCode::BAD_REQUEST, "z.ai is not configured").into_response();
-    }
+    };
 
     if !zai.mcp.enabled {
         return StatusCode::NOT_FOUND.into_response();
@@ -76,7 +77,7 @@ async fn forward_mcp(
     };
 
     let mut headers = copy_passthrough_headers(&incoming_headers);
-    if let Ok(v) = HeaderValue::from_str(&format!("Bearer {}", zai.api_key)) {
+    if let Ok(v) = HeaderValue::from_str(&format!("Bearer {}", api_key)) {
         headers.insert(header::AUTHORIZATION, v);
     }
 
@@ -334,11 +335,13 @@ async fn handle_vision_post(state: AppState, headers: HeaderMap, body: Body) ->
 
             let zai = state.zai.read().await.clone();
             let upstream_proxy = state.upstream_proxy.read().await.clone();
+            let local_files = state.media_fetch.read().await.local_files.clone();
             let timeout = state.request_timeout;
 
             match crate::proxy::zai_vision_tools::call_tool(
                 &zai,
                 upstream_proxy,
+                &local_files,
                 timeout,
                 tool_name,
                 &arguments,
@@ -380,7 +383,7 @@ pub async fn handle_zai_mcp_server(
     body: Body,
 ) -> Response {
     let zai = state.zai.read().await.clone();
-    if !zai.enabled || zai.api_key.trim().is_empty() {
+    if !zai.enabled || zai.all_api_keys().is_empty() {
         return (StatusCode::BAD_REQUEST, "z.ai is not configured").into_response();
     }
     if !zai.mcp.enabled || !zai.mcp.vision_enabled {
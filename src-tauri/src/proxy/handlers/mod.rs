@@ -7,8 +7,11 @@ pub mod gemini;
 pub mod mcp;
 pub mod common;
 pub mod audio;  // 音频转录处理器 (PR #311)
+pub mod embeddings;  // Embeddings (OpenAI + Gemini 原生)
+pub mod files;       // 生成图像签名链接
 pub mod admin;
 pub mod proxy_control;  // 管理接口 (Web Control)
+pub mod debug;          // 调试接口 (路由解释)
 pub mod web_auth;       // Web 认证 (登录/登出)
 pub mod web_oauth;      // Web OAuth (手动 Code 流程)
 
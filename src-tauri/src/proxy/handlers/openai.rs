@@ -5,6 +5,14 @@ use bytes::Bytes;
 use serde_json::{json, Value};
 use tracing::{debug, error, info}; // Import Engine trait for encode method
 
+use crate::proxy::common::background_task::{detect_background_task, last_user_text_openai};
+use crate::proxy::common::model_mapping::{resolve_route, RouteContext};
+use crate::proxy::common::param_policy::ResolvedPolicy;
+use crate::proxy::config::HookPoint;
+use crate::proxy::handlers::common::{acquire_token, run_request_hooks, with_route_headers, RouteNotes, TokenLease};
+use crate::proxy::mappers::image_preprocess::{preprocess_images_blocking, preprocess_openai_images};
+use crate::proxy::media_fetcher::MediaFetcher;
+use crate::proxy::providers::{self, ProviderOutcome};
 use crate::proxy::mappers::openai::{
     transform_openai_request, transform_openai_response, OpenAIRequest,
 };
@@ -16,8 +24,20 @@ use crate::proxy::session_manager::SessionManager;
 
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
-    Json(body): Json<Value>,
+    headers: axum::http::HeaderMap,
+    Json(mut body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let hooks = run_request_hooks(&state, "openai", None, &mut body, &headers).await;
+    let route_ctx = RouteContext::from_request("openai", &body, &headers);
+
+    // 选择上游服务商: OpenAI 兼容端点直接透传，Google 账号池继续走转换流程
+    let model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+    let dispatch = providers::dispatch(&state, "openai", &model, &route_ctx).await;
+    let body = match dispatch.forward_with_policy(&state, "/v1/chat/completions", &headers, body, &hooks).await {
+        ProviderOutcome::Responded(response) => return Ok(response),
+        ProviderOutcome::Continue(body) => body,
+    };
+
     let mut openai_req: OpenAIRequest = serde_json::from_value(body)
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
 
@@ -40,41 +60,71 @@ pub async fn handle_chat_completions(
 
     debug!("Received OpenAI request for model: {}", openai_req.model);
 
+    // 远程媒体下载: http(s) image_url / audio_url 转为 data URL (上游无法访问任意 URL)
+    MediaFetcher::global()
+        .inline_openai_media(&mut openai_req.messages, &*state.media_fetch.read().await)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid media input: {}", e)))?;
+
+    // 音频输入校验: 格式与大小限制
+    crate::proxy::audio::AudioProcessor::validate_audio_inputs(&openai_req.messages)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid audio input: {}", e)))?;
+
+    // 图像输入预处理: 解码校验、按 detail 缩放并修正 MIME 类型
+    let image_preprocess = state.image_preprocess.read().await.clone();
+    preprocess_images_blocking(&mut openai_req.messages, &image_preprocess, preprocess_openai_images)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid image input: {}", e)))?;
+
+    // [NEW] Structured Outputs strict 模式: 按原始 Schema 校验响应 (不匹配时重试)
+    let strict_schema = openai_req
+        .response_format
+        .as_ref()
+        .and_then(|f| f.strict_schema())
+        .cloned();
+
     // 1. 获取 UpstreamClient (Clone handle)
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
-    let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
+    let min_attempts = if strict_schema.is_some() { 2 } else { 1 };
+    let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(min_attempts);
+
+    // 后台任务识别: 命中 background_tasks 规则时降级到目标模型
+    let background_task = {
+        let background_config = state.background_tasks.read().await;
+        last_user_text_openai(&openai_req.messages).and_then(|text| detect_background_task(&text, &background_config))
+    };
 
     let mut last_error = String::new();
 
     for attempt in 0..max_attempts {
         // 2. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &openai_req.model,
-            &*state.custom_mapping.read().await,
-        );
+        let route = resolve_route(&openai_req.model, &state.routing_rules.read().await, &route_ctx);
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req
             .tools
             .as_ref()
             .map(|list| list.iter().cloned().collect());
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(
-            &openai_req.model,
-            &mapped_model,
-            &tools_val,
-        );
 
         // 3. 提取 SessionId (粘性指纹)
         let session_id = SessionManager::extract_openai_session_id(&openai_req);
 
-        // 4. 获取 Token (使用准确的 request_type)
+        // 4. 获取 Token (使用准确的 request_type，无可用账号时按回退链切换模型)
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
-        let (access_token, project_id, email) = match token_manager
-            .get_token(&config.request_type, attempt > 0, Some(&session_id))
-            .await
+        let target_model = background_task.as_ref().map_or_else(|| route.model.clone(), |task| task.target_model.clone());
+        let TokenLease { access_token, project_id, email, mapped_model, config, fallback_from } = match acquire_token(
+            &state,
+            &openai_req.model,
+            &target_model,
+            &tools_val,
+            attempt > 0,
+            Some(&session_id),
+            &headers,
+        )
+        .await
         {
-            Ok(t) => t,
+            Ok(lease) => lease,
             Err(e) => {
                 return Err((
                     StatusCode::SERVICE_UNAVAILABLE,
@@ -84,9 +134,28 @@ pub async fn handle_chat_completions(
         };
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
+        let notes = RouteNotes {
+            fallback_from,
+            downgrade: background_task.as_ref().map(|task| task.describe(&route.model)),
+        };
+        if let Some(task) = &background_task {
+            info!("[OpenAI][AUTO] 检测到后台任务 (类别: {}, 命中: {:?}),强制降级: {} -> {}", task.category, task.matched, route.model, mapped_model);
+        }
 
         // 4. 转换请求
-        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
+        // 参数策略: 默认值/范围/强制值与系统指令 (转换前应用)
+        let policy = ResolvedPolicy::resolve(&mapped_model, route_ctx.client_key.as_deref(), &state.parameter_policies.read().await);
+        let policy_req = policy.applied_openai(&openai_req);
+        let mut gemini_body = transform_openai_request(
+            policy_req.as_ref().unwrap_or(&openai_req),
+            &project_id,
+            &mapped_model,
+            &*state.thinking.read().await,
+        );
+        route.apply_overrides(&mut gemini_body);
+        policy.apply_safety(&mut gemini_body);
+        let attempt_hooks = hooks.with_route(&email, &mapped_model);
+        attempt_hooks.run(HookPoint::BeforeUpstream, &mut gemini_body);
 
         // [New] 打印转换后的报文 (Gemini Body) 供调试
         if let Ok(body_json) = serde_json::to_string_pretty(&gemini_body) {
@@ -135,23 +204,26 @@ pub async fn handle_chat_completions(
                 use axum::body::Body;
                 use axum::response::Response;
 
-                let gemini_stream = response.bytes_stream();
-                let openai_stream =
-                    create_openai_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                let gemini_stream = attempt_hooks.wrap_upstream_stream(Box::pin(response.bytes_stream()));
+                let mut openai_stream =
+                    create_openai_sse_stream(gemini_stream, openai_req.model.clone());
+                if let Some(schema) = strict_schema.clone() {
+                    openai_stream = crate::proxy::mappers::openai::structured::create_schema_validated_stream(openai_stream, schema);
+                }
                 
                 // 判断客户端期望的格式
                 if client_wants_stream {
                     // 客户端本就要 Stream，直接返回 SSE
                     let body = Body::from_stream(openai_stream);
-                    return Ok(Response::builder()
+                    let response = Response::builder()
                         .header("Content-Type", "text/event-stream")
                         .header("Cache-Control", "no-cache")
                         .header("Connection", "keep-alive")
                         .header("X-Account-Email", &email)
                         .header("X-Mapped-Model", &mapped_model)
                         .body(body)
-                        .unwrap()
-                        .into_response());
+                        .unwrap();
+                    return Ok(with_route_headers(response, &mapped_model, &notes));
                 } else {
                     // 客户端要非 Stream，需要收集完整响应并转换为 JSON
                     use crate::proxy::mappers::openai::collect_openai_stream_to_json;
@@ -167,8 +239,27 @@ pub async fn handle_chat_completions(
                     
                     match collect_openai_stream_to_json(sse_stream).await {
                         Ok(full_response) => {
+                            if let Some(schema) = &strict_schema {
+                                use crate::proxy::mappers::openai::structured::{structured_output_error, validate_structured_response};
+                                if let Err(e) = validate_structured_response(schema, &full_response) {
+                                    last_error = format!("Structured output validation failed: {}", e);
+                                    if attempt + 1 < max_attempts {
+                                        tracing::warn!(
+                                            "[OpenAI] Response does not match json_schema on attempt {}/{}, retrying: {}",
+                                            attempt + 1,
+                                            max_attempts,
+                                            e
+                                        );
+                                        continue;
+                                    }
+                                    error!("[OpenAI] Response does not match json_schema after {} attempts: {}", max_attempts, e);
+                                    let response = (StatusCode::BAD_GATEWAY, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(structured_output_error(&e))).into_response();
+                                    return Ok(with_route_headers(response, &mapped_model, &notes));
+                                }
+                            }
                             info!("[OpenAI] ✓ Stream collected and converted to JSON");
-                            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(full_response)).into_response());
+                            let response = (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(attempt_hooks.before_response(&full_response))).into_response();
+                            return Ok(with_route_headers(response, &mapped_model, &notes));
                         }
                         Err(e) => {
                             return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Stream collection error: {}", e)));
@@ -183,7 +274,8 @@ pub async fn handle_chat_completions(
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
 
             let openai_response = transform_openai_response(&gemini_resp);
-            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(openai_response)).into_response());
+            let response = (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(attempt_hooks.before_response(&openai_response))).into_response();
+            return Ok(with_route_headers(response, &mapped_model, &notes));
         }
 
         // 处理特定错误并重试
@@ -202,7 +294,7 @@ pub async fn handle_chat_completions(
         // 429/529/503 智能处理
         if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 {
             // 记录限流信息 (全局同步)
-            token_manager.mark_rate_limited(&email, status_code, retry_after.as_deref(), &error_text);
+            token_manager.mark_rate_limited(&email, status_code, retry_after.as_deref(), &error_text, Some(&mapped_model));
 
             // 1. 优先尝试解析 RetryInfo (由 Google Cloud 直接下发)
             if let Some(delay_ms) = crate::proxy::upstream::retry::parse_retry_delay(&error_text) {
@@ -270,12 +362,17 @@ pub async fn handle_chat_completions(
 
 /// 处理 Legacy Completions API (/v1/completions)
 /// 将 Prompt 转换为 Chat Message 格式，复用 handle_chat_completions
+/// 仅由 Google 账号池提供；模型被规则或 exclusive 模式指定给其他服务商时返回 400
 pub async fn handle_completions(
     State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
     Json(mut body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let hooks = run_request_hooks(&state, "openai", None, &mut body, &headers).await;
+    let model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+    providers::require_google(&state, "/v1/completions", &model, &RouteContext::from_request("openai", &body, &headers)).await?;
     info!(
-        "Received /v1/completions or /v1/responses payload: {:?}",
+        "Received /v1/completions payload: {:?}",
         body
     );
 
@@ -528,6 +625,7 @@ pub async fn handle_completions(
     // Actually, due to SSE handling differences (Codex uses different event format), we replicate the loop here or abstract it.
     // For now, let's replicate the core loop but with Codex specific SSE mapping.
 
+    let route_ctx = RouteContext::from_request("openai", &body, &headers);
     let mut openai_req: OpenAIRequest = serde_json::from_value(body.clone())
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
 
@@ -547,33 +645,40 @@ pub async fn handle_completions(
             });
     }
 
+    MediaFetcher::global()
+        .inline_openai_media(&mut openai_req.messages, &*state.media_fetch.read().await)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid media input: {}", e)))?;
+    let image_preprocess = state.image_preprocess.read().await.clone();
+    preprocess_images_blocking(&mut openai_req.messages, &image_preprocess, preprocess_openai_images)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid image input: {}", e)))?;
+
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
-    let pool_size = token_manager.len();
+    let pool_size = state.token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
 
+    // 后台任务识别: 命中 background_tasks 规则时降级到目标模型
+    let background_task = {
+        let background_config = state.background_tasks.read().await;
+        last_user_text_openai(&openai_req.messages).and_then(|text| detect_background_task(&text, &background_config))
+    };
+
     let mut last_error = String::new();
 
     for _attempt in 0..max_attempts {
         // 1. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &openai_req.model,
-            &*state.custom_mapping.read().await,
-        );
+        let route = resolve_route(&openai_req.model, &state.routing_rules.read().await, &route_ctx);
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req
             .tools
             .as_ref()
             .map(|list| list.iter().cloned().collect());
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(
-            &openai_req.model,
-            &mapped_model,
-            &tools_val,
-        );
 
-        let (access_token, project_id, email) =
-            match token_manager.get_token(&config.request_type, false, None).await {
-                Ok(t) => t,
+        let target_model = background_task.as_ref().map_or_else(|| route.model.clone(), |task| task.target_model.clone());
+        let TokenLease { access_token, project_id, email, mapped_model, config, fallback_from } =
+            match acquire_token(&state, &openai_req.model, &target_model, &tools_val, false, None, &headers).await {
+                Ok(lease) => lease,
                 Err(e) => {
                     return Err((
                         StatusCode::SERVICE_UNAVAILABLE,
@@ -583,8 +688,27 @@ pub async fn handle_completions(
             };
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
+        let notes = RouteNotes {
+            fallback_from,
+            downgrade: background_task.as_ref().map(|task| task.describe(&route.model)),
+        };
+        if let Some(task) = &background_task {
+            info!("[OpenAI][AUTO] 检测到后台任务 (类别: {}, 命中: {:?}),强制降级: {} -> {}", task.category, task.matched, route.model, mapped_model);
+        }
 
-        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
+        // 参数策略: 默认值/范围/强制值与系统指令 (转换前应用)
+        let policy = ResolvedPolicy::resolve(&mapped_model, route_ctx.client_key.as_deref(), &state.parameter_policies.read().await);
+        let policy_req = policy.applied_openai(&openai_req);
+        let mut gemini_body = transform_openai_request(
+            policy_req.as_ref().unwrap_or(&openai_req),
+            &project_id,
+            &mapped_model,
+            &*state.thinking.read().await,
+        );
+        route.apply_overrides(&mut gemini_body);
+        policy.apply_safety(&mut gemini_body);
+        let attempt_hooks = hooks.with_route(&email, &mapped_model);
+        attempt_hooks.run(HookPoint::BeforeUpstream, &mut gemini_body);
 
         // [New] 打印转换后的报文 (Gemini Body) 供调试 (Codex 路径)
         if let Ok(body_json) = serde_json::to_string_pretty(&gemini_body) {
@@ -616,28 +740,28 @@ pub async fn handle_completions(
                 use axum::body::Body;
                 use axum::response::Response;
 
-                let gemini_stream = response.bytes_stream();
+                let gemini_stream = attempt_hooks.wrap_upstream_stream(Box::pin(response.bytes_stream()));
                 let body = if is_codex_style {
                     use crate::proxy::mappers::openai::streaming::create_codex_sse_stream;
                     let s =
-                        create_codex_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                        create_codex_sse_stream(gemini_stream, openai_req.model.clone());
                     Body::from_stream(s)
                 } else {
                     use crate::proxy::mappers::openai::streaming::create_legacy_sse_stream;
                     let s =
-                        create_legacy_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                        create_legacy_sse_stream(gemini_stream, openai_req.model.clone());
                     Body::from_stream(s)
                 };
 
-                return Ok(Response::builder()
+                let response = Response::builder()
                     .header("Content-Type", "text/event-stream")
                     .header("Cache-Control", "no-cache")
                     .header("Connection", "keep-alive")
                     .header("X-Account-Email", &email)
                     .header("X-Mapped-Model", &mapped_model)
                     .body(body)
-                    .unwrap()
-                    .into_response());
+                    .unwrap();
+                return Ok(with_route_headers(response, &mapped_model, &notes));
             }
 
             let gemini_resp: Value = response
@@ -668,7 +792,8 @@ pub async fn handle_completions(
                 "choices": choices
             });
 
-            return Ok(axum::Json(legacy_resp).into_response());
+            let response = (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(attempt_hooks.before_response(&legacy_resp))).into_response();
+            return Ok(with_route_headers(response, &mapped_model, &notes));
         }
 
         // Handle errors and retry
@@ -688,11 +813,304 @@ pub async fn handle_completions(
     ))
 }
 
+/// 处理 OpenAI Responses API (/v1/responses)
+/// input items 转换为 Chat 消息后复用 Gemini 转换；previous_response_id 通过本地响应存储续接
+/// 仅由 Google 账号池提供；模型被规则或 exclusive 模式指定给其他服务商时返回 400
+pub async fn handle_responses(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(mut body): Json<Value>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    use crate::proxy::mappers::openai::responses::{
+        build_chat_request, collect_responses_stream, create_responses_sse_stream,
+        parse_input_items, ResponsesRequest, ResponsesStreamState,
+    };
+    use crate::proxy::response_store::{ResponseStore, StoredResponse};
+
+    let hooks = run_request_hooks(&state, "openai", None, &mut body, &headers).await;
+    let route_ctx = RouteContext::from_request("openai", &body, &headers);
+    let model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+    providers::require_google(&state, "/v1/responses", &model, &route_ctx).await?;
+    let resp_req: ResponsesRequest = serde_json::from_value(body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+
+    // 1. 组装完整对话 items: 历史 (previous_response_id) + 本轮输入
+    let mut items = match &resp_req.previous_response_id {
+        Some(prev_id) => ResponseStore::global()
+            .get(prev_id, route_ctx.client_key.as_deref())
+            .map(|stored| stored.items)
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("Previous response with id '{}' not found.", prev_id),
+                )
+            })?,
+        None => Vec::new(),
+    };
+    items.extend(parse_input_items(&resp_req.input).map_err(|e| (StatusCode::BAD_REQUEST, e))?);
+
+    let mut openai_req = build_chat_request(&resp_req, &items);
+    if openai_req.messages.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Invalid request: input is empty".to_string()));
+    }
+    // 上游始终使用流式接口
+    openai_req.stream = true;
+    MediaFetcher::global()
+        .inline_openai_media(&mut openai_req.messages, &*state.media_fetch.read().await)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid media input: {}", e)))?;
+    let image_preprocess = state.image_preprocess.read().await.clone();
+    preprocess_images_blocking(&mut openai_req.messages, &image_preprocess, preprocess_openai_images)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid image input: {}", e)))?;
+
+    debug!(
+        "[Responses] Received request for model: {} ({} items, previous: {:?})",
+        resp_req.model,
+        items.len(),
+        resp_req.previous_response_id
+    );
+
+    let upstream = state.upstream.clone();
+    let token_manager = state.token_manager.clone();
+    let max_attempts = MAX_RETRY_ATTEMPTS.min(token_manager.len()).max(1);
+    let should_store = resp_req.store.unwrap_or(true);
+
+    // 后台任务识别: 命中 background_tasks 规则时降级到目标模型
+    let background_task = {
+        let background_config = state.background_tasks.read().await;
+        last_user_text_openai(&openai_req.messages).and_then(|text| detect_background_task(&text, &background_config))
+    };
+
+    let mut last_error = String::new();
+
+    for attempt in 0..max_attempts {
+        let route = resolve_route(&openai_req.model, &state.routing_rules.read().await, &route_ctx);
+        let session_id = SessionManager::extract_openai_session_id(&openai_req);
+
+        let target_model = background_task.as_ref().map_or_else(|| route.model.clone(), |task| task.target_model.clone());
+        let TokenLease { access_token, project_id, email, mapped_model, config, fallback_from } = match acquire_token(
+            &state,
+            &openai_req.model,
+            &target_model,
+            &openai_req.tools,
+            attempt > 0,
+            Some(&session_id),
+            &headers,
+        )
+        .await
+        {
+            Ok(lease) => lease,
+            Err(e) => {
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Token error: {}", e),
+                ));
+            }
+        };
+
+        info!("✓ Using account: {} (type: {})", email, config.request_type);
+        let notes = RouteNotes {
+            fallback_from,
+            downgrade: background_task.as_ref().map(|task| task.describe(&route.model)),
+        };
+        if let Some(task) = &background_task {
+            info!("[OpenAI][AUTO] 检测到后台任务 (类别: {}, 命中: {:?}),强制降级: {} -> {}", task.category, task.matched, route.model, mapped_model);
+        }
+
+        // 参数策略: 默认值/范围/强制值与系统指令 (转换前应用)
+        let policy = ResolvedPolicy::resolve(&mapped_model, route_ctx.client_key.as_deref(), &state.parameter_policies.read().await);
+        let policy_req = policy.applied_openai(&openai_req);
+        let mut gemini_body = transform_openai_request(
+            policy_req.as_ref().unwrap_or(&openai_req),
+            &project_id,
+            &mapped_model,
+            &*state.thinking.read().await,
+        );
+        route.apply_overrides(&mut gemini_body);
+        policy.apply_safety(&mut gemini_body);
+        let attempt_hooks = hooks.with_route(&email, &mapped_model);
+        attempt_hooks.run(HookPoint::BeforeUpstream, &mut gemini_body);
+
+        let response = match upstream
+            .call_v1_internal("streamGenerateContent", &access_token, gemini_body, Some("alt=sse"))
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                last_error = e.clone();
+                debug!(
+                    "Responses request failed on attempt {}/{}: {}",
+                    attempt + 1,
+                    max_attempts,
+                    e
+                );
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            let gemini_stream = attempt_hooks.wrap_upstream_stream(Box::pin(response.bytes_stream()));
+            let stream_state = ResponsesStreamState::new(&resp_req);
+
+            if resp_req.stream {
+                use axum::body::Body;
+                use axum::response::Response;
+
+                let history = items.clone();
+                let client_key = route_ctx.client_key.clone();
+                let sse_stream = create_responses_sse_stream(gemini_stream, stream_state, move |state| {
+                    if should_store {
+                        let mut all_items = history;
+                        all_items.extend(state.output_items().iter().cloned());
+                        ResponseStore::global().insert(
+                            state.response_id(),
+                            StoredResponse { response: state.response(), items: all_items, client_key },
+                        );
+                    }
+                });
+
+                let response = Response::builder()
+                    .header("Content-Type", "text/event-stream")
+                    .header("Cache-Control", "no-cache")
+                    .header("Connection", "keep-alive")
+                    .header("X-Account-Email", &email)
+                    .header("X-Mapped-Model", &mapped_model)
+                    .body(Body::from_stream(sse_stream))
+                    .unwrap();
+                return Ok(with_route_headers(response, &mapped_model, &notes));
+            }
+
+            let final_state = collect_responses_stream(gemini_stream, stream_state)
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Stream collection error: {}", e)))?;
+            let full_response = final_state.response();
+
+            if should_store {
+                let mut all_items = items;
+                all_items.extend(final_state.output_items().iter().cloned());
+                ResponseStore::global().insert(
+                    final_state.response_id(),
+                    StoredResponse {
+                        response: full_response.clone(),
+                        items: all_items,
+                        client_key: route_ctx.client_key.clone(),
+                    },
+                );
+            }
+
+            info!("[Responses] ✓ Stream collected into response {}", final_state.response_id());
+            let response = (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(attempt_hooks.before_response(&full_response))).into_response();
+            return Ok(with_route_headers(response, &mapped_model, &notes));
+        }
+
+        let status_code = status.as_u16();
+        let retry_after = response.headers().get("Retry-After").and_then(|h| h.to_str().ok()).map(|s| s.to_string());
+        let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status_code));
+        last_error = format!("HTTP {}: {}", status_code, error_text);
+
+        tracing::error!("[Responses-Upstream] Error Response {}: {}", status_code, error_text);
+
+        if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 {
+            token_manager.mark_rate_limited(&email, status_code, retry_after.as_deref(), &error_text, Some(&mapped_model));
+
+            if let Some(delay_ms) = crate::proxy::upstream::retry::parse_retry_delay(&error_text) {
+                let actual_delay = delay_ms.saturating_add(200).min(10_000);
+                tracing::warn!(
+                    "Responses Upstream {} on {} attempt {}/{}, waiting {}ms then retrying",
+                    status_code,
+                    email,
+                    attempt + 1,
+                    max_attempts,
+                    actual_delay
+                );
+                tokio::time::sleep(tokio::time::Duration::from_millis(actual_delay)).await;
+                continue;
+            }
+
+            if error_text.contains("QUOTA_EXHAUSTED") {
+                error!(
+                    "Responses Quota exhausted (429) on account {} attempt {}/{}, stopping to protect pool.",
+                    email,
+                    attempt + 1,
+                    max_attempts
+                );
+                return Err((status, error_text));
+            }
+
+            tracing::warn!(
+                "Responses Upstream {} on {} attempt {}/{}, rotating account",
+                status_code,
+                email,
+                attempt + 1,
+                max_attempts
+            );
+            continue;
+        }
+
+        if status_code == 403 || status_code == 401 {
+            tracing::warn!(
+                "Responses Upstream {} on account {} attempt {}/{}, rotating account",
+                status_code,
+                email,
+                attempt + 1,
+                max_attempts
+            );
+            continue;
+        }
+
+        error!(
+            "Responses Upstream non-retryable error {} on account {}: {}",
+            status_code, email, error_text
+        );
+        return Err((status, error_text));
+    }
+
+    Err((
+        StatusCode::TOO_MANY_REQUESTS,
+        format!("All accounts exhausted. Last error: {}", last_error),
+    ))
+}
+
+/// 获取已保存的 Response (GET /v1/responses/:id)
+/// 只能读取同一客户端 Key 创建的响应，其他 Key 一律返回 404
+pub async fn handle_get_response(
+    axum::extract::Path(response_id): axum::extract::Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let client_key = crate::proxy::common::model_mapping::client_key_from_headers(&headers);
+    crate::proxy::response_store::ResponseStore::global()
+        .get(&response_id, client_key.as_deref())
+        .map(|stored| Json(stored.response))
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Response with id '{}' not found.", response_id),
+            )
+        })
+}
+
+/// 删除已保存的 Response (DELETE /v1/responses/:id)
+pub async fn handle_delete_response(
+    axum::extract::Path(response_id): axum::extract::Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let client_key = crate::proxy::common::model_mapping::client_key_from_headers(&headers);
+    if !crate::proxy::response_store::ResponseStore::global().remove(&response_id, client_key.as_deref()) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Response with id '{}' not found.", response_id),
+        ));
+    }
+    Ok(Json(json!({ "id": response_id, "object": "response", "deleted": true })))
+}
+
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
 
     let model_ids = get_all_dynamic_models(
-        &state.custom_mapping,
+        &state.routing_rules,
     ).await;
 
     let data: Vec<_> = model_ids.into_iter().map(|id| {
@@ -712,146 +1130,336 @@ pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoRespo
 
 /// OpenAI Images API: POST /v1/images/generations
 /// 处理图像生成请求，转换为 Gemini API 格式
-pub async fn handle_images_generations(
-    State(state): State<AppState>,
-    Json(body): Json<Value>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // 1. 解析请求参数
-    let prompt = body.get("prompt").and_then(|v| v.as_str()).ok_or((
-        StatusCode::BAD_REQUEST,
-        "Missing 'prompt' field".to_string(),
-    ))?;
-
-    let model = body
-        .get("model")
-        .and_then(|v| v.as_str())
-        .unwrap_or("gemini-3-pro-image");
-
-    let n = body.get("n").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
-
-    let size = body
-        .get("size")
-        .and_then(|v| v.as_str())
-        .unwrap_or("1024x1024");
-
-    let response_format = body
-        .get("response_format")
-        .and_then(|v| v.as_str())
-        .unwrap_or("b64_json");
-
-    let quality = body
-        .get("quality")
-        .and_then(|v| v.as_str())
-        .unwrap_or("standard");
-    let style = body
-        .get("style")
-        .and_then(|v| v.as_str())
-        .unwrap_or("vivid");
+/// 默认文生图模型 (映射结果不是图像模型时使用)
+const IMAGE_GEN_MODEL: &str = "gemini-3-pro-image";
+/// 单次请求的图像数量上限 (与 OpenAI 一致)
+const MAX_IMAGES_PER_REQUEST: usize = 10;
+
+/// 校验图像数量 (1..=10)，超出时返回 400
+fn validate_image_count(n: usize) -> Result<usize, (StatusCode, String)> {
+    if n > MAX_IMAGES_PER_REQUEST {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("'n' must be at most {}, got {}", MAX_IMAGES_PER_REQUEST, n),
+        ));
+    }
+    Ok(n.max(1))
+}
 
-    info!(
-        "[Images] Received request: model={}, prompt={:.50}..., n={}, size={}, quality={}, style={}",
+/// 模型路由解析，映射结果不是图像模型时回退到默认文生图模型
+async fn resolve_image_model(state: &AppState, model: &str) -> String {
+    let mapped = crate::proxy::common::model_mapping::resolve_model_route(
         model,
-        prompt,
-        n,
-        size,
-        quality,
-        style
+        &state.routing_rules.read().await,
+        &crate::proxy::common::model_mapping::RouteContext::new("openai"),
     );
+    if mapped.contains("image") {
+        mapped
+    } else {
+        tracing::debug!("[Images] {} resolved to non-image model {}, using {}", model, mapped, IMAGE_GEN_MODEL);
+        IMAGE_GEN_MODEL.to_string()
+    }
+}
 
-    // 2. 解析尺寸为宽高比
-    let aspect_ratio = match size {
-        "1792x768" | "2560x1080" => "21:9", // Ultra-wide
-        "1792x1024" | "1920x1080" => "16:9",
-        "1024x1792" | "1080x1920" => "9:16",
-        "1024x768" | "1280x960" => "4:3",
-        "768x1024" | "960x1280" => "3:4",
-        _ => "1:1", // 默认 1024x1024
-    };
+/// 图像生成参数 (同步与异步接口共用)
+#[derive(Debug, Clone)]
+struct ImageGenParams {
+    model: String,
+    /// 路由映射后的上游模型
+    mapped_model: String,
+    prompt: String,
+    n: usize,
+    response_format: String,
+    aspect_ratio: &'static str,
+    final_prompt: String,
+}
+
+impl ImageGenParams {
+    fn from_body(body: &Value) -> Result<Self, (StatusCode, String)> {
+        // 1. 解析请求参数
+        let prompt = body.get("prompt").and_then(|v| v.as_str()).ok_or((
+            StatusCode::BAD_REQUEST,
+            "Missing 'prompt' field".to_string(),
+        ))?;
 
-    // Prompt Enhancement
-    let mut final_prompt = prompt.to_string();
-    if quality == "hd" {
-        final_prompt.push_str(", (high quality, highly detailed, 4k resolution, hdr)");
+        let model = body
+            .get("model")
+            .and_then(|v| v.as_str())
+            .unwrap_or(IMAGE_GEN_MODEL);
+
+        let n = validate_image_count(body.get("n").and_then(|v| v.as_u64()).unwrap_or(1) as usize)?;
+
+        let size = body
+            .get("size")
+            .and_then(|v| v.as_str())
+            .unwrap_or("1024x1024");
+
+        let response_format = body
+            .get("response_format")
+            .and_then(|v| v.as_str())
+            .unwrap_or("b64_json");
+
+        let quality = body
+            .get("quality")
+            .and_then(|v| v.as_str())
+            .unwrap_or("standard");
+        let style = body
+            .get("style")
+            .and_then(|v| v.as_str())
+            .unwrap_or("vivid");
+
+        info!(
+            "[Images] Received request: model={}, prompt={:.50}..., n={}, size={}, quality={}, style={}",
+            model,
+            prompt,
+            n,
+            size,
+            quality,
+            style
+        );
+
+        // 2. 解析尺寸为宽高比
+        let aspect_ratio = match size {
+            "1792x768" | "2560x1080" => "21:9", // Ultra-wide
+            "1792x1024" | "1920x1080" => "16:9",
+            "1024x1792" | "1080x1920" => "9:16",
+            "1024x768" | "1280x960" => "4:3",
+            "768x1024" | "960x1280" => "3:4",
+            _ => "1:1", // 默认 1024x1024
+        };
+
+        // Prompt Enhancement
+        let mut final_prompt = prompt.to_string();
+        if quality == "hd" {
+            final_prompt.push_str(", (high quality, highly detailed, 4k resolution, hdr)");
+        }
+        match style {
+            "vivid" => final_prompt.push_str(", (vivid colors, dramatic lighting, rich details)"),
+            "natural" => final_prompt.push_str(", (natural lighting, realistic, photorealistic)"),
+            _ => {}
+        }
+
+        Ok(Self {
+            model: model.to_string(),
+            mapped_model: model.to_string(),
+            prompt: prompt.to_string(),
+            n,
+            response_format: response_format.to_string(),
+            aspect_ratio,
+            final_prompt,
+        })
     }
-    match style {
-        "vivid" => final_prompt.push_str(", (vivid colors, dramatic lighting, rich details)"),
-        "natural" => final_prompt.push_str(", (natural lighting, realistic, photorealistic)"),
-        _ => {}
+}
+
+/// 生成图像的输出方式
+/// 所有图像写入 ImageStore (管理界面图库)；response_format=url 时返回签名链接，否则返回 base64
+/// 异步任务的 base64 结果只记录图库 ID (见 image_jobs::STORED_IMAGE_ID)，查询任务时再读取
+struct ImageOutput {
+    response_format: String,
+    /// 异步任务: 不在结果中保存 base64 数据
+    deferred: bool,
+    base_url: String,
+    storage: crate::proxy::config::ImageStorageConfig,
+    prompt: String,
+    model: String,
+}
+
+impl ImageOutput {
+    async fn new(
+        state: &AppState,
+        headers: &axum::http::HeaderMap,
+        response_format: &str,
+        prompt: &str,
+        model: &str,
+    ) -> Self {
+        Self {
+            response_format: response_format.to_string(),
+            deferred: false,
+            base_url: crate::proxy::handlers::files::resolve_base_url(state, headers).await,
+            storage: state.image_storage.read().await.clone(),
+            prompt: prompt.to_string(),
+            model: model.to_string(),
+        }
     }
 
-    // 3. 获取 Token
-    let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    fn deferred(self) -> Self {
+        Self { deferred: true, ..self }
+    }
 
-    let (access_token, project_id, email) = match token_manager.get_token("image_gen", false, None).await
+    /// 保存图像并构建响应条目
+    fn item(&self, mime_type: &str, data: &str, account: &str) -> Value {
+        let store = crate::proxy::image_store::ImageStore::global();
+        let stored = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| store.save(&bytes, mime_type, &self.prompt, &self.model, account))
+            .map_err(|e| tracing::warn!("[Images] Failed to store generated image: {}", e))
+            .ok();
+
+        if self.response_format != "url" {
+            return match stored {
+                Some(id) if self.deferred => json!({ crate::proxy::image_jobs::STORED_IMAGE_ID: id }),
+                // 存储失败时只能保留 base64 数据
+                _ => json!({ "b64_json": data }),
+            };
+        }
+        match stored {
+            Some(id) => json!({ "url": store.signed_url(&self.base_url, &id, self.storage.url_ttl_secs) }),
+            // 存储失败时回退为 data URL
+            None => json!({ "url": format!("data:{};base64,{}", mime_type, data) }),
+        }
+    }
+
+    /// 后台清理超过保留期的图像
+    fn cleanup_expired(&self) {
+        let retention_hours = self.storage.retention_hours;
+        tokio::task::spawn_blocking(move || {
+            let removed = crate::proxy::image_store::ImageStore::global().cleanup(retention_hours);
+            if removed > 0 {
+                tracing::info!("[Images] Removed {} expired image(s)", removed);
+            }
+        });
+    }
+}
+
+/// 为 n 张图像选取账号：超过 images_per_account 时轮换获取多个不同账号
+async fn acquire_image_accounts(
+    token_manager: &crate::proxy::TokenManager,
+    n: usize,
+    images_per_account: usize,
+    model: &str,
+) -> Result<Vec<(String, String, String)>, (StatusCode, String)> {
+    let wanted = n.div_ceil(images_per_account.max(1)).min(token_manager.len()).max(1);
+    let mut accounts: Vec<(String, String, String)> = Vec::with_capacity(wanted);
+
+    // 轮换可能返回已选中的账号 (池中可用账号不足)，最多尝试 2 * wanted 次
+    for attempt in 0..wanted * 2 {
+        if accounts.len() >= wanted {
+            break;
+        }
+        match token_manager.get_token_for_model("image_gen", attempt > 0, None, Some(model)).await {
+            Ok(t) => {
+                if !accounts.iter().any(|(_, _, email)| email == &t.2) {
+                    accounts.push(t);
+                }
+            }
+            Err(e) if accounts.is_empty() => {
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Token error: {}", e),
+                ))
+            }
+            Err(_) => break,
+        }
+    }
+    Ok(accounts)
+}
+
+/// 单张图像请求
+async fn generate_single_image(
+    upstream: &crate::proxy::upstream::client::UpstreamClient,
+    access_token: &str,
+    project_id: &str,
+    model: &str,
+    final_prompt: &str,
+    aspect_ratio: &str,
+) -> Result<Value, (u16, String)> {
+    let gemini_body = json!({
+        "project": project_id,
+        "requestId": format!("img-{}", uuid::Uuid::new_v4()),
+        "model": model,
+        "userAgent": "antigravity",
+        "requestType": "image_gen",
+        "request": {
+            "contents": [{
+                "role": "user",
+                "parts": [{"text": final_prompt}]
+            }],
+            "generationConfig": {
+                "candidateCount": 1, // 强制单张
+                "imageConfig": {
+                    "aspectRatio": aspect_ratio
+                }
+            },
+            "safetySettings": [
+                { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
+                { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
+                { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
+                { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
+                { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" },
+            ]
+        }
+    });
+
+    match upstream
+        .call_v1_internal("generateContent", access_token, gemini_body, None)
+        .await
     {
-        Ok(t) => t,
-        Err(e) => {
-            return Err((
-                StatusCode::SERVICE_UNAVAILABLE,
-                format!("Token error: {}", e),
-            ))
+        Ok(response) => {
+            let status = response.status();
+            if !status.is_success() {
+                let err_text = response.text().await.unwrap_or_default();
+                return Err((status.as_u16(), format!("Upstream error {}: {}", status, err_text)));
+            }
+            match response.json::<Value>().await {
+                Ok(json) => Ok(json),
+                Err(e) => Err((0, format!("Parse error: {}", e))),
+            }
         }
-    };
+        Err(e) => Err((0, format!("Network error: {}", e))),
+    }
+}
 
-    info!("✓ Using account: {} for image generation", email);
+/// 生成图像并构建 OpenAI 格式响应
+/// n 张图像按账号轮流分配并发执行；失败的图像在下一个账号上重试一次
+async fn generate_images(
+    state: AppState,
+    params: ImageGenParams,
+    output: ImageOutput,
+) -> Result<Value, (StatusCode, String)> {
+    // 3. 获取 Token
+    let upstream = state.upstream.clone();
+    let token_manager = state.token_manager.clone();
+    let images_per_account = state.image_generation.read().await.images_per_account;
+    let accounts = std::sync::Arc::new(
+        acquire_image_accounts(&token_manager, params.n, images_per_account, &params.mapped_model).await?,
+    );
+
+    info!(
+        "✓ Using {} account(s) for {} image(s): {}",
+        accounts.len(),
+        params.n,
+        accounts.iter().map(|(_, _, email)| email.as_str()).collect::<Vec<_>>().join(", ")
+    );
 
     // 4. 并发发送请求 (解决 candidateCount > 1 不支持的问题)
     let mut tasks = Vec::new();
 
-    for _ in 0..n {
+    for idx in 0..params.n {
         let upstream = upstream.clone();
-        let access_token = access_token.clone();
-        let project_id = project_id.clone();
-        let final_prompt = final_prompt.clone();
-        let aspect_ratio = aspect_ratio.to_string();
-        let _response_format = response_format.to_string();
+        let token_manager = token_manager.clone();
+        let accounts = accounts.clone();
+        let final_prompt = params.final_prompt.clone();
+        let model = params.mapped_model.clone();
+        let aspect_ratio = params.aspect_ratio;
 
         tasks.push(tokio::spawn(async move {
-            let gemini_body = json!({
-                "project": project_id,
-                "requestId": format!("img-{}", uuid::Uuid::new_v4()),
-                "model": "gemini-3-pro-image",
-                "userAgent": "antigravity",
-                "requestType": "image_gen",
-                "request": {
-                    "contents": [{
-                        "role": "user",
-                        "parts": [{"text": final_prompt}]
-                    }],
-                    "generationConfig": {
-                        "candidateCount": 1, // 强制单张
-                        "imageConfig": {
-                            "aspectRatio": aspect_ratio
+            let max_tries = accounts.len().min(2);
+            let mut last_err = String::new();
+            for attempt in 0..max_tries {
+                let (access_token, project_id, email) = &accounts[(idx + attempt) % accounts.len()];
+                match generate_single_image(&upstream, access_token, project_id, &model, &final_prompt, aspect_ratio).await {
+                    Ok(json) => return Ok((json, email.clone())),
+                    Err((status_code, e)) => {
+                        if matches!(status_code, 429 | 503 | 529) {
+                            token_manager.mark_rate_limited(email, status_code, None, &e, Some(&model));
                         }
-                    },
-                    "safetySettings": [
-                        { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
-                        { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
-                        { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
-                        { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
-                        { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" },
-                    ]
-                }
-            });
-
-            match upstream
-                .call_v1_internal("generateContent", &access_token, gemini_body, None)
-                .await
-            {
-                Ok(response) => {
-                    let status = response.status();
-                    if !status.is_success() {
-                        let err_text = response.text().await.unwrap_or_default();
-                        return Err(format!("Upstream error {}: {}", status, err_text));
-                    }
-                    match response.json::<Value>().await {
-                        Ok(json) => Ok(json),
-                        Err(e) => Err(format!("Parse error: {}", e)),
+                        tracing::warn!("[Images] Task {} failed on {} (attempt {}/{}): {}", idx, email, attempt + 1, max_tries, e);
+                        last_err = e;
                     }
                 }
-                Err(e) => Err(format!("Network error: {}", e)),
             }
+            Err(last_err)
         }));
     }
 
@@ -862,7 +1470,7 @@ pub async fn handle_images_generations(
     for (idx, task) in tasks.into_iter().enumerate() {
         match task.await {
             Ok(result) => match result {
-                Ok(gemini_resp) => {
+                Ok((gemini_resp, email)) => {
                     let raw = gemini_resp.get("response").unwrap_or(&gemini_resp);
                     if let Some(parts) = raw
                         .get("candidates")
@@ -875,19 +1483,11 @@ pub async fn handle_images_generations(
                             if let Some(img) = part.get("inlineData") {
                                 let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
                                 if !data.is_empty() {
-                                    if response_format == "url" {
-                                        let mime_type = img
-                                            .get("mimeType")
-                                            .and_then(|v| v.as_str())
-                                            .unwrap_or("image/png");
-                                        images.push(json!({
-                                            "url": format!("data:{};base64,{}", mime_type, data)
-                                        }));
-                                    } else {
-                                        images.push(json!({
-                                            "b64_json": data
-                                        }));
-                                    }
+                                    let mime_type = img
+                                        .get("mimeType")
+                                        .and_then(|v| v.as_str())
+                                        .unwrap_or("image/png");
+                                    images.push(output.item(mime_type, data, &email));
                                     tracing::debug!("[Images] Task {} succeeded", idx);
                                 }
                             }
@@ -913,7 +1513,7 @@ pub async fn handle_images_generations(
         } else {
             "No images generated".to_string()
         };
-        tracing::error!("[Images] All {} requests failed. Errors: {}", n, error_msg);
+        tracing::error!("[Images] All {} requests failed. Errors: {}", params.n, error_msg);
         return Err((StatusCode::BAD_GATEWAY, error_msg));
     }
 
@@ -922,7 +1522,7 @@ pub async fn handle_images_generations(
         tracing::warn!(
             "[Images] Partial success: {} out of {} requests succeeded. Errors: {}",
             images.len(),
-            n,
+            params.n,
             errors.join("; ")
         );
     }
@@ -930,20 +1530,85 @@ pub async fn handle_images_generations(
     tracing::info!(
         "[Images] Successfully generated {} out of {} requested image(s)",
         images.len(),
-        n
+        params.n
     );
 
+    output.cleanup_expired();
+
     // 6. 构建 OpenAI 格式响应
-    let openai_response = json!({
+    Ok(json!({
         "created": chrono::Utc::now().timestamp(),
         "data": images
-    });
+    }))
+}
 
+pub async fn handle_images_generations(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(body): Json<Value>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut params = ImageGenParams::from_body(&body)?;
+    params.mapped_model = resolve_image_model(&state, &params.model).await;
+    let output = ImageOutput::new(&state, &headers, &params.response_format, &params.prompt, &params.mapped_model).await;
+    let openai_response = generate_images(state, params, output).await?;
     Ok(Json(openai_response))
 }
 
+/// 创建异步图像生成任务 (POST /v1/images/jobs)
+/// 立即返回任务 ID，客户端通过 GET /v1/images/jobs/:id 轮询结果，避免 4K 等慢请求占用连接超时
+pub async fn handle_create_image_job(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(body): Json<Value>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    use crate::proxy::image_jobs::ImageJobStore;
+
+    let mut params = ImageGenParams::from_body(&body)?;
+    params.mapped_model = resolve_image_model(&state, &params.model).await;
+    // 签名链接地址在创建任务时确定 (后台任务没有请求头)
+    let output = ImageOutput::new(&state, &headers, &params.response_format, &params.prompt, &params.mapped_model)
+        .await
+        .deferred();
+    let job = ImageJobStore::global().create(&params.model, params.n);
+    let job_id = job.id.clone();
+
+    info!("[Images] Created async job {} (n={})", job_id, params.n);
+
+    tokio::spawn(async move {
+        let store = ImageJobStore::global();
+        // 限制同时运行的后台任务数量，未拿到槽位的任务保持 queued
+        let max_jobs = state.image_generation.read().await.max_concurrent_jobs;
+        let _slot = store.acquire_slot(max_jobs).await;
+        store.mark_running(&job_id);
+        let result = generate_images(state, params, output).await.map_err(|(_, e)| e);
+        if let Err(e) = &result {
+            tracing::error!("[Images] Async job {} failed: {}", job_id, e);
+        }
+        store.complete(&job_id, result);
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// 查询异步图像生成任务 (GET /v1/images/jobs/:id)
+pub async fn handle_get_image_job(
+    axum::extract::Path(job_id): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut job = crate::proxy::image_jobs::ImageJobStore::global().get(&job_id).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Image job with id '{}' not found.", job_id),
+        )
+    })?;
+    if let Some(result) = job.result.as_mut() {
+        crate::proxy::image_jobs::inline_stored_images(result, crate::proxy::image_store::ImageStore::global());
+    }
+    Ok(Json(job))
+}
+
 pub async fn handle_images_edits(
     State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
     mut multipart: axum::extract::Multipart,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     tracing::info!("[Images] Received edit request");
@@ -954,7 +1619,7 @@ pub async fn handle_images_edits(
     let mut n = 1;
     let mut size = "1024x1024".to_string();
     let mut response_format = "b64_json".to_string(); // Default to b64_json for better compatibility with tools handling edits
-    let mut model = "gemini-3-pro-image".to_string();
+    let mut model = IMAGE_GEN_MODEL.to_string();
 
     while let Some(field) = multipart
         .next_field()
@@ -982,7 +1647,7 @@ pub async fn handle_images_edits(
                 .map_err(|e| (StatusCode::BAD_REQUEST, format!("Prompt read error: {}", e)))?;
         } else if name == "n" {
             if let Ok(val) = field.text().await {
-                n = val.parse().unwrap_or(1);
+                n = validate_image_count(val.parse().unwrap_or(1))?;
             }
         } else if name == "size" {
             if let Ok(val) = field.text().await {
@@ -1029,11 +1694,14 @@ pub async fn handle_images_edits(
     // But if users see raw text, it means client defaulted to 'url' or we defaulted to 'url'.
     // Let's keep the log to confirm.
 
+    let model = resolve_image_model(&state, &model).await;
+    let output = ImageOutput::new(&state, &headers, &response_format, &prompt, &model).await;
+
     // 1. 获取 Upstream
     let upstream = state.upstream.clone();
     let token_manager = state.token_manager;
     // Fix: Proper get_token call with correct signature and unwrap (using image_gen quota)
-    let (access_token, project_id, _email) = match token_manager.get_token("image_gen", false, None).await
+    let (access_token, project_id, email) = match token_manager.get_token("image_gen", false, None).await
     {
         Ok(t) => t,
         Err(e) => {
@@ -1145,19 +1813,11 @@ pub async fn handle_images_edits(
                             if let Some(img) = part.get("inlineData") {
                                 let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
                                 if !data.is_empty() {
-                                    if response_format == "url" {
-                                        let mime_type = img
-                                            .get("mimeType")
-                                            .and_then(|v| v.as_str())
-                                            .unwrap_or("image/png");
-                                        images.push(json!({
-                                            "url": format!("data:{};base64,{}", mime_type, data)
-                                        }));
-                                    } else {
-                                        images.push(json!({
-                                            "b64_json": data
-                                        }));
-                                    }
+                                    let mime_type = img
+                                        .get("mimeType")
+                                        .and_then(|v| v.as_str())
+                                        .unwrap_or("image/png");
+                                    images.push(output.item(mime_type, data, &email));
                                     tracing::debug!("[Images] Task {} succeeded", idx);
                                 }
                             }
@@ -1206,6 +1866,8 @@ pub async fn handle_images_edits(
         n
     );
 
+    output.cleanup_expired();
+
     let openai_response = json!({
         "created": chrono::Utc::now().timestamp(),
         "data": images
@@ -108,7 +108,7 @@ pub async fn handle_update_model_mapping(
     
     // Save to disk
     if let Ok(mut app_config) = crate::modules::load_app_config() {
-        app_config.proxy.custom_mapping = payload.config.custom_mapping;
+        app_config.proxy.routing_rules = payload.config.routing_rules;
         let _ = crate::modules::save_app_config(&app_config);
     }
     
@@ -181,9 +181,9 @@ pub async fn handle_fetch_zai_models(
     if zai.base_url.trim().is_empty() {
         return (StatusCode::BAD_REQUEST, Json(json!({ "status": "error", "message": "z.ai base_url is empty" }))).into_response();
     }
-    if zai.api_key.trim().is_empty() {
+    let Some(api_key) = zai.all_api_keys().into_iter().next() else {
         return (StatusCode::BAD_REQUEST, Json(json!({ "status": "error", "message": "z.ai api_key is not set" }))).into_response();
-    }
+    };
 
     let url = join_base_url(&zai.base_url, "/v1/models");
 
@@ -203,8 +203,8 @@ pub async fn handle_fetch_zai_models(
 
     let resp_result = client
         .get(&url)
-        .header("Authorization", format!("Bearer {}", zai.api_key))
-        .header("x-api-key", zai.api_key)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("x-api-key", api_key)
         .header("anthropic-version", "2023-06-01")
         .header("accept", "application/json")
         .send()
@@ -0,0 +1,379 @@
+// 请求/响应脚本钩子 (Rhai)
+// 挂载点: 协议解析后、发送上游前、上游 SSE 每个事件、返回客户端前
+// 每个钩子独立执行: 编译失败、运行出错或超时只跳过该钩子，不影响请求与其他钩子
+// 钩子在请求所在的异步任务上同步执行；UpstreamEvent 钩子对每个 SSE 事件执行一次，
+// 其超时单独收紧到 MAX_EVENT_HOOK_TIMEOUT，避免长流被脚本拖慢
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use once_cell::sync::Lazy;
+use rhai::{Dynamic, Engine, Scope, AST};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::proxy::config::{HookConfig, HookPoint, ScriptHook};
+
+/// UpstreamEvent 钩子的单次超时上限 (每个 SSE 事件都会执行)
+const MAX_EVENT_HOOK_TIMEOUT: Duration = Duration::from_millis(5);
+/// 已编译脚本缓存的条目上限
+const MAX_AST_CACHE_ENTRIES: usize = 128;
+
+thread_local! {
+    /// 当前线程上正在执行的脚本的截止时间
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+static ENGINE: Lazy<Engine> = Lazy::new(|| {
+    let mut engine = Engine::new();
+    engine.set_max_call_levels(64);
+    engine.set_max_expr_depths(128, 64);
+    // 限制脚本可构造的数据规模，防止拼接/扩容耗尽内存 (请求体可能携带 base64 图像，字符串上限留足余量)
+    engine.set_max_string_size(32 * 1024 * 1024);
+    engine.set_max_array_size(65_536);
+    engine.set_max_map_size(65_536);
+    engine.on_print(|s| tracing::info!("[Hook] {}", s));
+    engine.on_debug(|s, _, pos| tracing::debug!("[Hook] {} @ {:?}", s, pos));
+    // 每 256 次操作检查一次超时，返回值会终止脚本
+    engine.on_progress(|ops| {
+        if ops % 256 != 0 {
+            return None;
+        }
+        DEADLINE.with(|d| match d.get() {
+            Some(deadline) if Instant::now() > deadline => Some(Dynamic::from("timeout")),
+            _ => None,
+        })
+    });
+    engine
+});
+
+/// 已编译脚本缓存: 内联脚本按源码哈希、脚本文件按路径存放 (文件修改时间作为指纹，变化时替换)，编译错误同样缓存
+type AstCache = HashMap<String, (String, Result<Arc<AST>, String>)>;
+static AST_CACHE: Lazy<Mutex<AstCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 脚本中只读的 `ctx`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HookContext {
+    /// 入口协议: openai / claude / gemini
+    pub protocol: String,
+    /// 掩码后的客户端 Key (仅保留首尾字符)，用于日志与展示
+    pub client_key: Option<String>,
+    /// 客户端 Key 的 SHA-256 (十六进制)，用于在脚本中区分客户端
+    pub client_key_hash: Option<String>,
+    /// 客户端请求的模型
+    pub model: String,
+    /// 本次尝试使用的账号 (获取 Token 后才有)
+    pub account: Option<String>,
+    pub mapped_model: Option<String>,
+}
+
+impl HookContext {
+    /// 写入客户端 Key 的掩码与哈希，脚本中不暴露原始 Key
+    pub fn with_client_key(mut self, key: Option<&str>) -> Self {
+        self.client_key = key.map(mask_key);
+        self.client_key_hash = key.map(|k| format!("{:x}", Sha256::digest(k.as_bytes())));
+        self
+    }
+}
+
+fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 10 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}...{}", head, tail)
+}
+
+/// 单个请求的钩子执行器 (配置快照 + 上下文)
+#[derive(Debug, Clone)]
+pub struct HookRunner {
+    hooks: Arc<Vec<ScriptHook>>,
+    timeout: Duration,
+    ctx: HookContext,
+}
+
+impl HookRunner {
+    pub fn new(config: &HookConfig, ctx: HookContext) -> Self {
+        let hooks = if config.enabled {
+            config.hooks.iter().filter(|h| h.enabled && !h.points.is_empty()).cloned().collect()
+        } else {
+            Vec::new()
+        };
+        Self {
+            hooks: Arc::new(hooks),
+            timeout: Duration::from_millis(config.timeout_ms.max(1)),
+            ctx,
+        }
+    }
+
+    /// 获取 Token 后补充账号与映射模型
+    pub fn with_route(&self, account: &str, mapped_model: &str) -> Self {
+        let mut runner = self.clone();
+        runner.ctx.account = Some(account.to_string());
+        runner.ctx.mapped_model = Some(mapped_model.to_string());
+        runner
+    }
+
+    pub fn is_active(&self, point: HookPoint) -> bool {
+        self.hooks.iter().any(|h| h.points.contains(&point))
+    }
+
+    /// 依次执行挂载在该点的钩子，出错的钩子不修改 data
+    pub fn run(&self, point: HookPoint, data: &mut Value) {
+        for hook in self.hooks.iter().filter(|h| h.points.contains(&point)) {
+            match self.run_hook(hook, point, data) {
+                Ok(value) => *data = value,
+                Err(e) => tracing::warn!("[Hook] {} ({:?}) skipped: {}", hook.name, point, e),
+            }
+        }
+    }
+
+    fn run_hook(&self, hook: &ScriptHook, point: HookPoint, data: &Value) -> Result<Value, String> {
+        let ast = compile(hook)?;
+
+        let mut ctx = rhai::serde::to_dynamic(&self.ctx).map_err(|e| e.to_string())?;
+        if let Some(map) = ctx.write_lock::<rhai::Map>().as_deref_mut() {
+            let point = serde_json::to_value(point).ok().and_then(|p| p.as_str().map(str::to_string)).unwrap_or_default();
+            map.insert("point".into(), point.into());
+        }
+        let mut scope = Scope::new();
+        scope.push_constant("ctx", ctx);
+        scope.push("data", rhai::serde::to_dynamic(data).map_err(|e| e.to_string())?);
+
+        let timeout = if point == HookPoint::UpstreamEvent {
+            self.timeout.min(MAX_EVENT_HOOK_TIMEOUT)
+        } else {
+            self.timeout
+        };
+        DEADLINE.with(|d| d.set(Some(Instant::now() + timeout)));
+        let result = ENGINE.run_ast_with_scope(&mut scope, &ast);
+        DEADLINE.with(|d| d.set(None));
+        result.map_err(|e| match *e {
+            rhai::EvalAltResult::ErrorTerminated(..) => format!("timed out after {:?}", timeout),
+            e => e.to_string(),
+        })?;
+
+        let data = scope.get_value::<Dynamic>("data").ok_or("data was removed")?;
+        rhai::serde::from_dynamic::<Value>(&data).map_err(|e| e.to_string())
+    }
+
+    /// 对返回客户端的 JSON 执行 BeforeResponse 钩子
+    pub fn before_response<T: Serialize>(&self, response: &T) -> Value {
+        let mut value = serde_json::to_value(response).unwrap_or_default();
+        self.run(HookPoint::BeforeResponse, &mut value);
+        value
+    }
+
+    /// 在上游 SSE 流的每个 data 事件上执行 UpstreamEvent 钩子
+    pub fn wrap_upstream_stream<E: Send + 'static>(
+        &self,
+        stream: Pin<Box<dyn Stream<Item = Result<Bytes, E>> + Send>>,
+    ) -> Pin<Box<dyn Stream<Item = Result<Bytes, E>> + Send>> {
+        if !self.is_active(HookPoint::UpstreamEvent) {
+            return stream;
+        }
+        let runner = self.clone();
+        Box::pin(async_stream::stream! {
+            let mut stream = stream;
+            let mut buffer = BytesMut::new();
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        buffer.extend_from_slice(&bytes);
+                        let mut out = BytesMut::new();
+                        while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                            let line = buffer.split_to(pos + 1);
+                            out.extend_from_slice(&runner.process_sse_line(&line));
+                        }
+                        if !out.is_empty() {
+                            yield Ok(out.freeze());
+                        }
+                    }
+                    Err(e) => yield Err(e),
+                }
+            }
+            if !buffer.is_empty() {
+                yield Ok(runner.process_sse_line(&buffer));
+            }
+        })
+    }
+
+    fn process_sse_line(&self, line: &[u8]) -> Bytes {
+        let passthrough = || Bytes::copy_from_slice(line);
+        let Ok(text) = std::str::from_utf8(line) else {
+            return passthrough();
+        };
+        let content = text.trim_end_matches(['\r', '\n']);
+        let Some(payload) = content.strip_prefix("data:").map(str::trim) else {
+            return passthrough();
+        };
+        let Ok(mut value) = serde_json::from_str::<Value>(payload) else {
+            return passthrough();
+        };
+        self.run(HookPoint::UpstreamEvent, &mut value);
+        Bytes::from(format!("data: {}{}", value, &text[content.len()..]))
+    }
+}
+
+fn compile(hook: &ScriptHook) -> Result<Arc<AST>, String> {
+    let (key, fingerprint, source) = match (&hook.script, &hook.file) {
+        // 名称可以为空或重复，内联脚本只按内容区分
+        (Some(script), _) => (format!("inline:{:x}", Sha256::digest(script.as_bytes())), String::new(), None),
+        (None, Some(path)) => {
+            let modified = std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .map_err(|e| format!("cannot read {}: {}", path, e))?;
+            (format!("file:{}", path), format!("{:?}", modified), Some(path))
+        }
+        (None, None) => return Err("neither script nor file is set".to_string()),
+    };
+
+    let mut cache = AST_CACHE.lock().map_err(|_| "script cache poisoned".to_string())?;
+    if let Some((cached_fingerprint, cached)) = cache.get(&key) {
+        if *cached_fingerprint == fingerprint {
+            return cached.clone();
+        }
+    }
+    let compiled = match source {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e)),
+        None => Ok(hook.script.clone().unwrap_or_default()),
+    }
+    .and_then(|src| ENGINE.compile(&src).map(Arc::new).map_err(|e| format!("compile error: {}", e)));
+    // 脚本修改或删除后旧条目不会再命中，超出上限时整体清空
+    if cache.len() >= MAX_AST_CACHE_ENTRIES && !cache.contains_key(&key) {
+        cache.clear();
+    }
+    cache.insert(key, (fingerprint, compiled.clone()));
+    compiled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hook(name: &str, points: Vec<HookPoint>, script: &str) -> ScriptHook {
+        ScriptHook {
+            name: name.to_string(),
+            enabled: true,
+            points,
+            script: Some(script.to_string()),
+            file: None,
+        }
+    }
+
+    #[test]
+    fn test_hooks_transform_and_isolation() {
+        let config = HookConfig {
+            enabled: true,
+            timeout_ms: 20,
+            hooks: vec![
+                hook("broken", vec![HookPoint::RequestParsed], "let x = ;"),
+                hook("endless", vec![HookPoint::RequestParsed], "data.model = \"never\"; loop {}"),
+                hook("throws", vec![HookPoint::RequestParsed], "data.model = \"never\"; throw \"boom\";"),
+                hook(
+                    "rename-tool",
+                    vec![HookPoint::RequestParsed],
+                    r#"for i in 0..data.tools.len() { if data.tools[i].name == "Bash" { data.tools[i].name = "shell"; } }
+                       data.tag = ctx.protocol + ":" + ctx.client_key; data.key_hash = ctx.client_key_hash;"#,
+                ),
+                hook("upstream", vec![HookPoint::BeforeUpstream], "data.account = ctx.account; data.point = ctx.point;"),
+            ],
+        };
+        let ctx = HookContext {
+            protocol: "claude".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+            ..Default::default()
+        }
+        .with_client_key(Some("sk-team-a-0123456789"));
+        let runner = HookRunner::new(&config, ctx);
+
+        let mut body = json!({ "model": "claude-sonnet-4-5", "tools": [{ "name": "Bash" }, { "name": "Read" }] });
+        runner.run(HookPoint::RequestParsed, &mut body);
+        // 脚本只能看到掩码后的 Key 与其哈希
+        let key_hash = format!("{:x}", Sha256::digest(b"sk-team-a-0123456789"));
+        assert_eq!(
+            body,
+            json!({
+                "model": "claude-sonnet-4-5",
+                "tools": [{ "name": "shell" }, { "name": "Read" }],
+                "tag": "claude:sk-t...6789",
+                "key_hash": key_hash
+            })
+        );
+
+        let mut upstream = json!({});
+        runner.with_route("a@example.com", "gemini-3-flash").run(HookPoint::BeforeUpstream, &mut upstream);
+        assert_eq!(upstream, json!({ "account": "a@example.com", "point": "before_upstream" }));
+
+        // 总开关关闭时不执行
+        let disabled = HookRunner::new(&HookConfig { enabled: false, ..config }, HookContext::default());
+        assert!(!disabled.is_active(HookPoint::RequestParsed));
+    }
+
+    #[test]
+    fn test_upstream_sse_hook() {
+        let config = HookConfig {
+            enabled: true,
+            hooks: vec![hook("tag", vec![HookPoint::UpstreamEvent], "data.response.tagged = true;")],
+            ..Default::default()
+        };
+        let runner = HookRunner::new(&config, HookContext::default());
+
+        let chunks: Vec<Result<Bytes, std::io::Error>> = vec![
+            Ok(Bytes::from("data: {\"response\":{\"a\":1}}\r\n\ndata: {\"resp")),
+            Ok(Bytes::from("onse\":{\"b\":2}}\n\ndata: [DONE]")),
+        ];
+        let stream = runner.wrap_upstream_stream(Box::pin(futures::stream::iter(chunks)));
+        let out: Vec<u8> = futures::executor::block_on(stream.map(|c| c.unwrap()).collect::<Vec<_>>()).concat();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "data: {\"response\":{\"a\":1,\"tagged\":true}}\r\n\ndata: {\"response\":{\"b\":2,\"tagged\":true}}\n\ndata: [DONE]"
+        );
+    }
+
+    #[test]
+    fn test_script_limits_and_cache_replacement() {
+        let mut script = hook("grow", vec![HookPoint::RequestParsed], "let s = \"x\"; loop { s += s; } data.done = true;");
+        let config = HookConfig { enabled: true, timeout_ms: 1000, hooks: vec![script.clone()] };
+        let mut body = json!({});
+        // 超出字符串上限的脚本被终止，不修改 data
+        HookRunner::new(&config, HookContext::default()).run(HookPoint::RequestParsed, &mut body);
+        assert_eq!(body, json!({}));
+
+        // 同名钩子修改脚本后使用新版本
+        script.script = Some("data.version = 2;".to_string());
+        let config = HookConfig { hooks: vec![script], ..config };
+        HookRunner::new(&config, HookContext::default()).run(HookPoint::RequestParsed, &mut body);
+        assert_eq!(body, json!({ "version": 2 }));
+    }
+
+    #[test]
+    fn test_unnamed_inline_hooks_compiled_separately() {
+        let config = HookConfig {
+            enabled: true,
+            hooks: vec![
+                hook("", vec![HookPoint::BeforeResponse], "data.first = true;"),
+                hook("", vec![HookPoint::BeforeResponse], "data.second = true;"),
+            ],
+            ..Default::default()
+        };
+        let runner = HookRunner::new(&config, HookContext::default());
+        for _ in 0..2 {
+            assert_eq!(runner.before_response(&json!({})), json!({ "first": true, "second": true }));
+        }
+        // 各自缓存一份，不会互相替换
+        let cache = AST_CACHE.lock().unwrap();
+        for script in ["data.first = true;", "data.second = true;"] {
+            assert!(cache.contains_key(&format!("inline:{:x}", Sha256::digest(script.as_bytes()))));
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use base64::Engine as _;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// 完成的任务保留 1 小时供客户端轮询
+const JOB_TTL: Duration = Duration::from_secs(60 * 60);
+const MAX_JOBS: usize = 256;
+
+/// 任务结果中代替 b64_json 的图库 ID: 图像数据只保存在 ImageStore，查询任务时再读取，
+/// 避免 MAX_JOBS 个已完成任务的 base64 结果常驻内存
+pub const STORED_IMAGE_ID: &str = "stored_image_id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageJobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// 异步图像生成任务
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageJob {
+    pub id: String,
+    pub object: &'static str,
+    pub status: ImageJobStatus,
+    pub model: String,
+    pub n: usize,
+    pub created_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<i64>,
+    /// 成功时为 OpenAI images 响应 ({created, data})，base64 图像以图库 ID 代替 (查询时展开)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    updated: SystemTime,
+}
+
+impl ImageJob {
+    fn is_expired(&self) -> bool {
+        matches!(self.status, ImageJobStatus::Succeeded | ImageJobStatus::Failed)
+            && self.updated.elapsed().unwrap_or(Duration::ZERO) > JOB_TTL
+    }
+}
+
+/// 将结果中的图库 ID 展开为 b64_json (图像已被删除或过期时该条目返回 error)
+pub fn inline_stored_images(result: &mut Value, store: &crate::proxy::image_store::ImageStore) {
+    let Some(items) = result.get_mut("data").and_then(|d| d.as_array_mut()) else {
+        return;
+    };
+    for item in items.iter_mut().filter_map(|i| i.as_object_mut()) {
+        let Some(id) = item.remove(STORED_IMAGE_ID) else {
+            continue;
+        };
+        match id.as_str().and_then(|id| store.load(id)) {
+            Some((data, _)) => item.insert("b64_json".to_string(), json!(base64::engine::general_purpose::STANDARD.encode(data))),
+            None => item.insert("error".to_string(), json!("image is no longer available")),
+        };
+    }
+}
+
+/// 图像生成任务存储 (内存，重启后丢失)
+pub struct ImageJobStore {
+    jobs: Mutex<HashMap<String, ImageJob>>,
+    /// 并发槽位 (上限, 信号量)；上限变更时替换信号量，已持有的旧许可自然释放
+    slots: Mutex<(usize, Arc<Semaphore>)>,
+}
+
+impl ImageJobStore {
+    fn new() -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            slots: Mutex::new((0, Arc::new(Semaphore::new(0)))),
+        }
+    }
+
+    /// Global singleton instance
+    pub fn global() -> &'static ImageJobStore {
+        static INSTANCE: OnceLock<ImageJobStore> = OnceLock::new();
+        INSTANCE.get_or_init(ImageJobStore::new)
+    }
+
+    /// 创建排队中的任务
+    pub fn create(&self, model: &str, n: usize) -> ImageJob {
+        let job = ImageJob {
+            id: format!("imgjob_{}", uuid::Uuid::new_v4().simple()),
+            object: "image.generation.job",
+            status: ImageJobStatus::Queued,
+            model: model.to_string(),
+            n,
+            created_at: chrono::Utc::now().timestamp(),
+            completed_at: None,
+            result: None,
+            error: None,
+            updated: SystemTime::now(),
+        };
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.retain(|_, j| !j.is_expired());
+            // 超出容量时淘汰最早完成的任务 (运行中的任务不淘汰)
+            while jobs.len() >= MAX_JOBS {
+                let oldest = jobs
+                    .values()
+                    .filter(|j| matches!(j.status, ImageJobStatus::Succeeded | ImageJobStatus::Failed))
+                    .min_by_key(|j| j.updated)
+                    .map(|j| j.id.clone());
+                match oldest {
+                    Some(id) => jobs.remove(&id),
+                    None => break,
+                };
+            }
+            jobs.insert(job.id.clone(), job.clone());
+        }
+        job
+    }
+
+    /// 等待运行槽位，限制同时执行的后台任务数量 (limit 至少为 1)
+    pub async fn acquire_slot(&self, limit: usize) -> OwnedSemaphorePermit {
+        let limit = limit.max(1);
+        let semaphore = {
+            let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+            if slots.0 != limit {
+                *slots = (limit, Arc::new(Semaphore::new(limit)));
+            }
+            slots.1.clone()
+        };
+        semaphore.acquire_owned().await.expect("image job semaphore is never closed")
+    }
+
+    pub fn get(&self, id: &str) -> Option<ImageJob> {
+        let jobs = self.jobs.lock().ok()?;
+        jobs.get(id).filter(|j| !j.is_expired()).cloned()
+    }
+
+    pub fn mark_running(&self, id: &str) {
+        self.update(id, |job| job.status = ImageJobStatus::Running);
+    }
+
+    pub fn complete(&self, id: &str, result: Result<Value, String>) {
+        self.update(id, |job| {
+            job.completed_at = Some(chrono::Utc::now().timestamp());
+            match result {
+                Ok(value) => {
+                    job.status = ImageJobStatus::Succeeded;
+                    job.result = Some(value);
+                }
+                Err(e) => {
+                    job.status = ImageJobStatus::Failed;
+                    job.error = Some(e);
+                }
+            }
+        });
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut ImageJob)) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            if let Some(job) = jobs.get_mut(id) {
+                f(job);
+                job.updated = SystemTime::now();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_job_lifecycle() {
+        let store = ImageJobStore::new();
+        let job = store.create("gemini-3-pro-image", 4);
+        assert_eq!(store.get(&job.id).unwrap().status, ImageJobStatus::Queued);
+
+        store.mark_running(&job.id);
+        assert_eq!(store.get(&job.id).unwrap().status, ImageJobStatus::Running);
+
+        store.complete(&job.id, Ok(json!({ "data": [] })));
+        let done = store.get(&job.id).unwrap();
+        assert_eq!(done.status, ImageJobStatus::Succeeded);
+        assert!(done.completed_at.is_some());
+
+        let serialized = serde_json::to_value(&done).unwrap();
+        assert_eq!(serialized["status"], "succeeded");
+        assert!(serialized.get("error").is_none());
+
+        let failed = store.create("gemini-3-pro-image", 1);
+        store.complete(&failed.id, Err("boom".to_string()));
+        assert_eq!(store.get(&failed.id).unwrap().error.as_deref(), Some("boom"));
+        assert!(store.get("imgjob_missing").is_none());
+    }
+
+    #[test]
+    fn test_inline_stored_images() {
+        let dir = std::env::temp_dir().join(format!("image_jobs_test_{}", uuid::Uuid::new_v4().simple()));
+        let store = crate::proxy::image_store::ImageStore::new(dir.clone()).unwrap();
+        let id = store.save(b"fake-png", "image/png", "a cat", "gemini-3-pro-image", "a@example.com").unwrap();
+
+        let mut result = json!({
+            "created": 1,
+            "data": [{ STORED_IMAGE_ID: id }, { STORED_IMAGE_ID: "0".repeat(64) }, { "url": "https://example.com/a.png" }]
+        });
+        inline_stored_images(&mut result, &store);
+        assert_eq!(
+            result["data"],
+            json!([
+                { "b64_json": "ZmFrZS1wbmc=" },
+                { "error": "image is no longer available" },
+                { "url": "https://example.com/a.png" }
+            ])
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_slot_limits_concurrency() {
+        let store = ImageJobStore::new();
+        let first = store.acquire_slot(1).await;
+        let blocked = tokio::time::timeout(Duration::from_millis(50), store.acquire_slot(1)).await;
+        assert!(blocked.is_err());
+
+        drop(first);
+        let second = tokio::time::timeout(Duration::from_millis(50), store.acquire_slot(1)).await;
+        assert!(second.is_ok());
+    }
+}
@@ -0,0 +1,240 @@
+// 生成图像存储
+// 图像按内容 SHA-256 寻址保存在数据目录 images/ 下 ({id}.{ext} + {id}.json 元数据)，
+// 通过带过期时间与签名的 /files/images/{id} 链接对外提供，避免在响应与监控日志中传输 base64。
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+const SIGNING_KEY_FILE: &str = ".signing_key";
+
+/// 图像元数据 (管理界面图库展示)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredImageMeta {
+    pub id: String,
+    pub mime_type: String,
+    pub size: usize,
+    pub prompt: String,
+    pub model: String,
+    pub account: String,
+    pub created_at: i64,
+}
+
+pub struct ImageStore {
+    root: PathBuf,
+    signing_key: String,
+}
+
+impl ImageStore {
+    /// 在指定目录创建存储 (自动生成并持久化签名密钥)
+    pub fn new(root: PathBuf) -> Result<Self, String> {
+        std::fs::create_dir_all(&root).map_err(|e| format!("创建图像目录失败: {}", e))?;
+        let key_path = root.join(SIGNING_KEY_FILE);
+        let signing_key = match std::fs::read_to_string(&key_path) {
+            Ok(key) if !key.trim().is_empty() => key.trim().to_string(),
+            _ => {
+                let key = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+                std::fs::write(&key_path, &key).map_err(|e| format!("写入签名密钥失败: {}", e))?;
+                key
+            }
+        };
+        Ok(Self { root, signing_key })
+    }
+
+    /// Global singleton instance (数据目录/images，不可用时回退到临时目录)
+    pub fn global() -> &'static ImageStore {
+        static INSTANCE: OnceLock<ImageStore> = OnceLock::new();
+        INSTANCE.get_or_init(|| {
+            let root = crate::modules::account::get_data_dir()
+                .map(|d| d.join("images"))
+                .unwrap_or_else(|_| std::env::temp_dir().join("antigravity_images"));
+            ImageStore::new(root.clone()).unwrap_or_else(|e| {
+                tracing::error!("[ImageStore] {}: {:?}, falling back to temp dir", e, root);
+                ImageStore::new(std::env::temp_dir().join("antigravity_images"))
+                    .expect("Failed to create fallback image store")
+            })
+        })
+    }
+
+    fn extension(mime_type: &str) -> &'static str {
+        match mime_type {
+            "image/jpeg" => "jpg",
+            "image/webp" => "webp",
+            "image/gif" => "gif",
+            _ => "png",
+        }
+    }
+
+    fn is_valid_id(id: &str) -> bool {
+        id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+
+    fn meta_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.json", id))
+    }
+
+    /// 保存图像，返回内容 ID (相同内容只保存一次)
+    pub fn save(&self, data: &[u8], mime_type: &str, prompt: &str, model: &str, account: &str) -> Result<String, String> {
+        let id = format!("{:x}", Sha256::digest(data));
+        let meta_path = self.meta_path(&id);
+        if meta_path.exists() {
+            return Ok(id);
+        }
+
+        let file_path = self.root.join(format!("{}.{}", id, Self::extension(mime_type)));
+        std::fs::write(&file_path, data).map_err(|e| format!("写入图像失败: {}", e))?;
+        let meta = StoredImageMeta {
+            id: id.clone(),
+            mime_type: mime_type.to_string(),
+            size: data.len(),
+            prompt: prompt.to_string(),
+            model: model.to_string(),
+            account: account.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        let meta_json = serde_json::to_vec_pretty(&meta).map_err(|e| e.to_string())?;
+        std::fs::write(&meta_path, meta_json).map_err(|e| format!("写入图像元数据失败: {}", e))?;
+        Ok(id)
+    }
+
+    pub fn get_meta(&self, id: &str) -> Option<StoredImageMeta> {
+        if !Self::is_valid_id(id) {
+            return None;
+        }
+        let raw = std::fs::read(self.meta_path(id)).ok()?;
+        serde_json::from_slice(&raw).ok()
+    }
+
+    /// 读取图像 (数据, MIME 类型)
+    pub fn load(&self, id: &str) -> Option<(Vec<u8>, String)> {
+        let meta = self.get_meta(id)?;
+        let path = self.root.join(format!("{}.{}", id, Self::extension(&meta.mime_type)));
+        std::fs::read(path).ok().map(|data| (data, meta.mime_type))
+    }
+
+    /// 按时间倒序列出所有图像
+    pub fn list(&self) -> Vec<StoredImageMeta> {
+        let mut items: Vec<StoredImageMeta> = std::fs::read_dir(&self.root)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json"))
+                    .filter_map(|p| std::fs::read(p).ok())
+                    .filter_map(|raw| serde_json::from_slice(&raw).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        items.sort_by_key(|m| std::cmp::Reverse(m.created_at));
+        items
+    }
+
+    pub fn delete(&self, id: &str) -> bool {
+        let Some(meta) = self.get_meta(id) else {
+            return false;
+        };
+        let _ = std::fs::remove_file(self.root.join(format!("{}.{}", id, Self::extension(&meta.mime_type))));
+        std::fs::remove_file(self.meta_path(id)).is_ok()
+    }
+
+    /// 删除超过保留期的图像，返回删除数量
+    pub fn cleanup(&self, retention_hours: u64) -> usize {
+        if retention_hours == 0 {
+            return 0;
+        }
+        let cutoff = chrono::Utc::now().timestamp() - (retention_hours as i64) * 3600;
+        self.list()
+            .into_iter()
+            .filter(|m| m.created_at < cutoff)
+            .filter(|m| self.delete(&m.id))
+            .count()
+    }
+
+    /// HMAC-SHA256(signing_key, "{id}:{expires}")
+    fn mac(&self, id: &str, expires: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.signing_key.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}:{}", id, expires).as_bytes());
+        mac
+    }
+
+    fn signature(&self, id: &str, expires: i64) -> String {
+        self.mac(id, expires)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// 生成签名链接: {base_url}/files/images/{id}?exp=..&sig=..
+    pub fn signed_url(&self, base_url: &str, id: &str, ttl_secs: u64) -> String {
+        let expires = chrono::Utc::now().timestamp() + ttl_secs as i64;
+        format!(
+            "{}/files/images/{}?exp={}&sig={}",
+            base_url.trim_end_matches('/'),
+            id,
+            expires,
+            self.signature(id, expires)
+        )
+    }
+
+    /// 校验签名链接 (签名比较为常量时间)
+    pub fn verify(&self, id: &str, expires: i64, signature: &str) -> bool {
+        if expires < chrono::Utc::now().timestamp() {
+            return false;
+        }
+        decode_hex(signature).is_some_and(|sig| self.mac(id, expires).verify_slice(&sig).is_ok())
+    }
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_sign_and_cleanup() {
+        let dir = std::env::temp_dir().join(format!("image_store_test_{}", uuid::Uuid::new_v4().simple()));
+        let store = ImageStore::new(dir.clone()).unwrap();
+
+        let id = store.save(b"fake-png", "image/png", "a cat", "gemini-3-pro-image", "a@example.com").unwrap();
+        assert_eq!(id.len(), 64);
+        // 相同内容返回相同 ID
+        assert_eq!(store.save(b"fake-png", "image/png", "other", "m", "b").unwrap(), id);
+        assert_eq!(store.load(&id).unwrap(), (b"fake-png".to_vec(), "image/png".to_string()));
+        assert_eq!(store.list()[0].prompt, "a cat");
+        assert!(store.load("../../etc/passwd").is_none());
+
+        let url = store.signed_url("http://127.0.0.1:8045/", &id, 60);
+        let query = url.split_once('?').unwrap().1;
+        let exp: i64 = query.split('&').next().unwrap().trim_start_matches("exp=").parse().unwrap();
+        let sig = query.split("sig=").nth(1).unwrap();
+        assert!(url.starts_with("http://127.0.0.1:8045/files/images/"));
+        assert!(store.verify(&id, exp, sig));
+        assert_eq!(sig.len(), 64);
+        assert!(!store.verify(&id, exp + 1, sig));
+        assert!(!store.verify(&id, exp, &sig[..32]));
+        assert!(!store.verify(&id, exp, "zz"));
+        assert!(!store.verify(&id, chrono::Utc::now().timestamp() - 1, &store.signature(&id, chrono::Utc::now().timestamp() - 1)));
+
+        // 签名密钥持久化，重新打开后链接仍然有效
+        let reopened = ImageStore::new(dir.clone()).unwrap();
+        assert!(reopened.verify(&id, exp, sig));
+
+        assert_eq!(store.cleanup(1), 0);
+        assert!(store.delete(&id));
+        assert!(store.list().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
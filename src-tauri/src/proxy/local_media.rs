@@ -0,0 +1,140 @@
+// 本地文件媒体访问策略
+// 所有从服务端文件系统读取媒体 (file:// 与本地路径) 的入口都必须经过这里：
+// 默认禁用，启用后仅允许 allowed_roots 下的普通文件，拒绝 `..` 与指向根目录外的符号链接
+
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use crate::proxy::config::LocalFileAccessConfig;
+
+/// file:// URL 或本地路径 -> 文件系统路径
+pub fn path_from_source(source: &str) -> PathBuf {
+    let source = source.trim();
+    if source.starts_with("file://") {
+        #[cfg(target_os = "windows")]
+        {
+            PathBuf::from(source.trim_start_matches("file:///").replace('/', "\\"))
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            PathBuf::from(source.trim_start_matches("file://"))
+        }
+    } else {
+        PathBuf::from(source)
+    }
+}
+
+/// 文件不存在、不在允许目录中或不是普通文件时返回的统一错误，避免通过错误信息探测服务端文件系统
+fn not_accessible(raw: &Path) -> String {
+    format!("本地文件不可访问 (local file is not accessible): {}", raw.display())
+}
+
+/// 按策略解析路径，返回解析符号链接后的真实路径
+pub fn resolve_allowed_path(source: &str, policy: &LocalFileAccessConfig) -> Result<PathBuf, String> {
+    if !policy.enabled {
+        return Err("本地文件访问已禁用 (media_fetch.local_files.enabled = false)".to_string());
+    }
+
+    let raw = path_from_source(source);
+    if !raw.is_absolute() {
+        return Err(format!("本地文件必须使用绝对路径: {}", raw.display()));
+    }
+    if raw.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err(format!("本地文件路径不允许包含 '..': {}", raw.display()));
+    }
+
+    // canonicalize 会解析符号链接，指向根目录外的链接因此被拒绝
+    let canonical = raw.canonicalize().map_err(|e| {
+        tracing::debug!("[Local-Media] {} 无法解析: {}", raw.display(), e);
+        not_accessible(&raw)
+    })?;
+    let allowed = policy
+        .allowed_roots
+        .iter()
+        .map(|r| r.trim())
+        .filter(|r| !r.is_empty())
+        .filter_map(|r| Path::new(r).canonicalize().ok())
+        .any(|root| canonical.starts_with(&root));
+    if !allowed {
+        tracing::debug!("[Local-Media] {} 不在允许的目录中", canonical.display());
+        return Err(not_accessible(&raw));
+    }
+    Ok(canonical)
+}
+
+/// 按策略读取本地文件，返回 (数据, 真实路径)
+pub fn read_local_file(source: &str, policy: &LocalFileAccessConfig) -> Result<(Vec<u8>, PathBuf), String> {
+    let path = resolve_allowed_path(source, policy)?;
+    let raw = path_from_source(source);
+    let max_bytes = policy.max_mb.saturating_mul(1024 * 1024);
+
+    let meta = std::fs::metadata(&path).map_err(|_| not_accessible(&raw))?;
+    if !meta.is_file() {
+        return Err(not_accessible(&raw));
+    }
+    if meta.len() > max_bytes {
+        return Err(format!("本地文件超过 {} MB 限制: {}", policy.max_mb, raw.display()));
+    }
+
+    // 限制读取长度，防止检查后文件被追加
+    let mut data = Vec::with_capacity(meta.len() as usize);
+    std::fs::File::open(&path)
+        .and_then(|f| f.take(max_bytes + 1).read_to_end(&mut data))
+        .map_err(|e| format!("读取本地文件失败: {}", e))?;
+    if data.len() as u64 > max_bytes {
+        return Err(format!("本地文件超过 {} MB 限制: {}", policy.max_mb, raw.display()));
+    }
+    Ok((data, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_file_policy() {
+        let base = std::env::temp_dir().join(format!("local_media_test_{}", uuid::Uuid::new_v4().simple()));
+        let root = base.join("allowed");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(root.join("a.png"), b"png").unwrap();
+        std::fs::write(outside.join("secret.txt"), b"secret").unwrap();
+
+        let policy = LocalFileAccessConfig {
+            enabled: true,
+            allowed_roots: vec![root.to_string_lossy().to_string()],
+            max_mb: 1,
+        };
+        let inside = root.join("a.png");
+
+        // 默认禁用
+        assert!(read_local_file(&inside.to_string_lossy(), &LocalFileAccessConfig::default()).is_err());
+
+        let (data, _) = read_local_file(&inside.to_string_lossy(), &policy).unwrap();
+        assert_eq!(data, b"png");
+
+        // 根目录外、`..` 逃逸与相对路径均被拒绝
+        assert!(read_local_file(&outside.join("secret.txt").to_string_lossy(), &policy).is_err());
+        assert!(read_local_file(&root.join("../outside/secret.txt").to_string_lossy(), &policy).is_err());
+        assert!(read_local_file("a.png", &policy).is_err());
+
+        // 不存在、不在允许目录中与非普通文件返回相同的错误 (不泄露文件是否存在)
+        let error_for = |path: &Path| read_local_file(&path.to_string_lossy(), &policy).unwrap_err().replace(&*path.to_string_lossy(), "");
+        let outside_err = error_for(&outside.join("secret.txt"));
+        assert!(outside_err.contains("local file is not accessible"));
+        assert_eq!(error_for(&outside.join("missing.txt")), outside_err);
+        assert_eq!(error_for(&root.join("missing.txt")), outside_err);
+        assert_eq!(error_for(&root), outside_err);
+
+        #[cfg(unix)]
+        {
+            assert!(read_local_file(&format!("file://{}", inside.to_string_lossy()), &policy).is_ok());
+            // 指向根目录外的符号链接被拒绝
+            std::os::unix::fs::symlink(outside.join("secret.txt"), root.join("link.txt")).unwrap();
+            assert!(read_local_file(&root.join("link.txt").to_string_lossy(), &policy).is_err());
+        }
+
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
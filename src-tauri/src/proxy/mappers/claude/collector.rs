@@ -191,6 +191,9 @@ where
                     if let Some(stop_reason) = delta.get("stop_reason").and_then(|v| v.as_str()) {
                         response.stop_reason = stop_reason.to_string();
                     }
+                    if let Some(stop_sequence) = delta.get("stop_sequence").and_then(|v| v.as_str()) {
+                        response.stop_sequence = Some(stop_sequence.to_string());
+                    }
                 }
                 if let Some(usage) = event.data.get("usage") {
                     if let Ok(u) = serde_json::from_value::<Usage>(usage.clone()) {
@@ -25,13 +25,14 @@ pub fn create_claude_sse_stream(
     mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     trace_id: String,
     email: String,
+    stop_sequences: Vec<String>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     use async_stream::stream;
     use bytes::BytesMut;
     use futures::StreamExt;
 
     Box::pin(stream! {
-        let mut state = StreamingState::new();
+        let mut state = StreamingState::with_stop_sequences(stop_sequences);
         let mut buffer = BytesMut::new();
 
         while let Some(chunk_result) = gemini_stream.next().await {
@@ -52,7 +53,10 @@ pub fn create_claude_sse_stream(
                                 }
                             }
                         }
+                        // 已发送 message_stop (如命中 stop_sequence), 不再读取上游
+                        if state.message_stop_sent { break; }
                     }
+                    if state.message_stop_sent { break; }
                 }
                 Err(e) => {
                     yield Err(format!("Stream error: {}", e));
@@ -140,6 +144,15 @@ fn process_sse_line(line: &str, state: &mut StreamingState, trace_id: &str, emai
         }
     }
 
+    // 命中客户端 stop_sequence: 立即结束消息
+    if state.stop_sequence_matched() && !state.message_stop_sent {
+        let usage = raw_json
+            .get("usageMetadata")
+            .and_then(|u| serde_json::from_value::<UsageMetadata>(u.clone()).ok());
+        chunks.extend(state.emit_finish(Some("STOP"), usage.as_ref()));
+        return Some(chunks);
+    }
+
     // Process grounding metadata (googleSearch results) and append as citations
     // [DISABLED] Temporarily disabled to fix Cherry Studio compatibility
     // Cherry Studio doesn't recognize "web_search_tool_result" type, causing validation errors
@@ -374,4 +387,29 @@ mod tests {
         assert!(all_text.contains("content_block_start"));
         assert!(all_text.contains("Hello"));
     }
+    #[test]
+    fn test_process_sse_line_stop_sequence() {
+        let mut state = StreamingState::with_stop_sequences(vec!["STOP".to_string()]);
+
+        let first = r#"data: {"candidates":[{"content":{"parts":[{"text":"Hello ST"}]}}]}"#;
+        let chunks = process_sse_line(first, &mut state, "test_id", "test@example.com").unwrap();
+        let first_text: String = chunks
+            .iter()
+            .map(|b| String::from_utf8(b.to_vec()).unwrap_or_default())
+            .collect();
+        assert!(first_text.contains("Hello "));
+        assert!(!first_text.contains("ST\""));
+        assert!(!state.message_stop_sent);
+
+        let second = r#"data: {"candidates":[{"content":{"parts":[{"text":"OP world"}]}}]}"#;
+        let chunks = process_sse_line(second, &mut state, "test_id", "test@example.com").unwrap();
+        let second_text: String = chunks
+            .iter()
+            .map(|b| String::from_utf8(b.to_vec()).unwrap_or_default())
+            .collect();
+        assert!(!second_text.contains("world"));
+        assert!(second_text.contains(r#""stop_reason":"stop_sequence""#));
+        assert!(second_text.contains(r#""stop_sequence":"STOP""#));
+        assert!(state.message_stop_sent);
+    }
 }
@@ -12,10 +12,14 @@ pub struct ClaudeRequest {
     pub system: Option<SystemPrompt>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
     #[serde(default)]
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -95,6 +99,8 @@ pub enum ContentBlock {
     #[serde(rename = "document")]
     Document {
         source: DocumentSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         cache_control: Option<serde_json::Value>,
     },
@@ -140,17 +146,26 @@ pub enum ContentBlock {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageSource {
     #[serde(rename = "type")]
-    pub source_type: String,
+    pub source_type: String, // "base64" | "url"
+    #[serde(default)]
     pub media_type: String,
+    #[serde(default)]
     pub data: String,
+    /// source_type = "url" 时的远程地址 (由 MediaFetcher 下载后转为 base64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentSource {
     #[serde(rename = "type")]
-    pub source_type: String, // "base64"
-    pub media_type: String,  // e.g. "application/pdf"
-    pub data: String,        // base64 data
+    pub source_type: String, // "base64" | "url" | "text"
+    #[serde(default)]
+    pub media_type: String,  // e.g. "application/pdf", "text/plain"
+    #[serde(default)]
+    pub data: String,        // base64 data (text 类型为纯文本)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
 }
 
 /// Tool - supports both client tools (with input_schema) and server tools (like web_search)
@@ -203,6 +218,44 @@ impl Tool {
     }
 }
 
+/// Tool choice - controls how the model uses the provided tools
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// Model decides whether to call a tool
+    Auto {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    /// Model must call one of the tools
+    Any {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    /// Model must call the named tool
+    Tool {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    /// Model must not call any tool
+    None,
+}
+
+impl ToolChoice {
+    /// Whether the client asked for at most one tool call per turn
+    pub fn disables_parallel_tool_use(&self) -> bool {
+        match self {
+            ToolChoice::Auto { disable_parallel_tool_use }
+            | ToolChoice::Any { disable_parallel_tool_use }
+            | ToolChoice::Tool { disable_parallel_tool_use, .. } => {
+                disable_parallel_tool_use.unwrap_or(false)
+            }
+            ToolChoice::None => false,
+        }
+    }
+}
+
 /// Metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metadata {
@@ -3,66 +3,10 @@
 
 use super::models::*;
 use crate::proxy::mappers::signature_store::get_thought_signature;
+use crate::proxy::mappers::common_utils::{build_safety_settings, SafetyThreshold};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 
-// ===== Safety Settings Configuration =====
-
-/// Safety threshold levels for Gemini API
-/// Can be configured via GEMINI_SAFETY_THRESHOLD environment variable
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum SafetyThreshold {
-    /// Disable all safety filters (default for proxy compatibility)
-    Off,
-    /// Block low probability and above
-    BlockLowAndAbove,
-    /// Block medium probability and above
-    BlockMediumAndAbove,
-    /// Only block high probability content
-    BlockOnlyHigh,
-    /// Don't block anything (BLOCK_NONE)
-    BlockNone,
-}
-
-impl SafetyThreshold {
-    /// Get threshold from environment variable or default to Off
-    pub fn from_env() -> Self {
-        match std::env::var("GEMINI_SAFETY_THRESHOLD").as_deref() {
-            Ok("OFF") | Ok("off") => SafetyThreshold::Off,
-            Ok("LOW") | Ok("low") => SafetyThreshold::BlockLowAndAbove,
-            Ok("MEDIUM") | Ok("medium") => SafetyThreshold::BlockMediumAndAbove,
-            Ok("HIGH") | Ok("high") => SafetyThreshold::BlockOnlyHigh,
-            Ok("NONE") | Ok("none") => SafetyThreshold::BlockNone,
-            _ => SafetyThreshold::Off, // Default: maintain current behavior
-        }
-    }
-
-    /// Convert to Gemini API threshold string
-    pub fn to_gemini_threshold(&self) -> &'static str {
-        match self {
-            SafetyThreshold::Off => "OFF",
-            SafetyThreshold::BlockLowAndAbove => "BLOCK_LOW_AND_ABOVE",
-            SafetyThreshold::BlockMediumAndAbove => "BLOCK_MEDIUM_AND_ABOVE",
-            SafetyThreshold::BlockOnlyHigh => "BLOCK_ONLY_HIGH",
-            SafetyThreshold::BlockNone => "BLOCK_NONE",
-        }
-    }
-}
-
-/// Build safety settings based on configuration
-fn build_safety_settings() -> Value {
-    let threshold = SafetyThreshold::from_env();
-    let threshold_str = threshold.to_gemini_threshold();
-
-    json!([
-        { "category": "HARM_CATEGORY_HARASSMENT", "threshold": threshold_str },
-        { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": threshold_str },
-        { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": threshold_str },
-        { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": threshold_str },
-        { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": threshold_str },
-    ])
-}
-
 /// 清理消息中的 cache_control 字段
 /// 
 /// 这个函数会深度遍历所有消息内容块,移除 cache_control 字段。
@@ -266,7 +210,7 @@ pub fn transform_claude_request_in(
     let tools = build_tools(&claude_req.tools, has_web_search_tool)?;
 
     // 5. Safety Settings (configurable via GEMINI_SAFETY_THRESHOLD env var)
-    let safety_settings = build_safety_settings();
+    let safety_settings = build_safety_settings(SafetyThreshold::from_env());
 
     // Build inner request
     let mut inner_request = json!({
@@ -286,13 +230,32 @@ pub fn transform_claude_request_in(
     }
 
     if let Some(tools_val) = tools {
+        let has_function_declarations = tools_val
+            .as_array()
+            .map(|arr| arr.iter().any(|t| t.get("functionDeclarations").is_some()))
+            .unwrap_or(false);
         inner_request["tools"] = tools_val;
-        // 显式设置工具配置模式为 VALIDATED
-        inner_request["toolConfig"] = json!({
-            "functionCallingConfig": {
-                "mode": "VALIDATED"
+
+        if has_function_declarations {
+            // tool_choice -> functionCallingConfig (未指定或 auto 时沿用 VALIDATED)
+            inner_request["toolConfig"] = build_tool_config(&claude_req.tool_choice);
+
+            // disable_parallel_tool_use: Gemini 无对应开关，通过系统指令约束
+            let disable_parallel = claude_req
+                .tool_choice
+                .as_ref()
+                .map(|c| c.disables_parallel_tool_use())
+                .unwrap_or(false);
+            if disable_parallel {
+                if let Some(parts) = inner_request
+                    .get_mut("systemInstruction")
+                    .and_then(|s| s.get_mut("parts"))
+                    .and_then(|p| p.as_array_mut())
+                {
+                    parts.push(json!({"text": crate::proxy::mappers::common_utils::SINGLE_TOOL_CALL_INSTRUCTION}));
+                }
             }
-        });
+        }
     }
 
     // Inject googleSearch tool if needed (and not already done by build_tools)
@@ -305,6 +268,7 @@ pub fn transform_claude_request_in(
         if let Some(obj) = inner_request.as_object_mut() {
             // 1. Remove tools (image generation does not support tools)
             obj.remove("tools");
+            obj.remove("toolConfig");
 
             // 2. Remove systemInstruction (image generation does not support system prompts)
             obj.remove("systemInstruction");
@@ -623,14 +587,23 @@ fn build_contents(
                                 }));
                             }
                         }
-                        ContentBlock::Document { source, .. } => {
-                            if source.source_type == "base64" {
-                                parts.push(json!({
+                        ContentBlock::Document { source, title, .. } => {
+                            // url 类型已由 handler 下载为 base64 (MediaFetcher::inline_claude_media)
+                            match source.source_type.as_str() {
+                                "base64" => parts.push(json!({
                                     "inlineData": {
                                         "mimeType": source.media_type,
                                         "data": source.data
                                     }
-                                }));
+                                })),
+                                "text" => {
+                                    let text = match title {
+                                        Some(title) => format!("[Document: {}]\n{}", title, source.data),
+                                        None => source.data.clone(),
+                                    };
+                                    parts.push(json!({ "text": text }));
+                                }
+                                other => tracing::warn!("[Claude-Request] Skipping document source type: {}", other),
                             }
                         }
                         ContentBlock::ToolUse { id, name, input, signature, .. } => {
@@ -769,7 +742,7 @@ fn build_contents(
             } else {
                 // [Crucial Check] 即使有 thought 块，也必须保证它位于 parts 的首位 (Index 0)
                 // 且必须包含 thought: true 标记
-                let first_is_thought = parts.get(0).map_or(false, |p| {
+                let first_is_thought = parts.first().is_some_and(|p| {
                     (p.get("thought").is_some() || p.get("thoughtSignature").is_some())
                     && p.get("text").is_some() // 对于 v1internal，通常 text + thought: true 才是合规的思维块
                 });
@@ -928,6 +901,18 @@ fn build_tools(tools: &Option<Vec<Tool>>, has_web_search: bool) -> Result<Option
     Ok(None)
 }
 
+/// 构建 Tool Config (Claude tool_choice -> Gemini functionCallingConfig)
+fn build_tool_config(tool_choice: &Option<ToolChoice>) -> Value {
+    use crate::proxy::mappers::common_utils::build_tool_config as gemini_tool_config;
+
+    match tool_choice {
+        None | Some(ToolChoice::Auto { .. }) => gemini_tool_config("VALIDATED", &[]),
+        Some(ToolChoice::Any { .. }) => gemini_tool_config("ANY", &[]),
+        Some(ToolChoice::Tool { name, .. }) => gemini_tool_config("ANY", std::slice::from_ref(name)),
+        Some(ToolChoice::None) => gemini_tool_config("NONE", &[]),
+    }
+}
+
 /// 构建 Generation Config
 fn build_generation_config(
     claude_req: &ClaudeRequest,
@@ -991,21 +976,41 @@ fn build_generation_config(
         config["candidateCount"] = json!(1);
     }*/
 
-    // max_tokens 映射为 maxOutputTokens
-    config["maxOutputTokens"] = json!(64000);
+    // max_tokens 映射为 maxOutputTokens (按映射后模型的输出上限截断)
+    let output_limit = crate::proxy::common::model_mapping::get_model_output_limit(&claude_req.model);
+    let max_output_tokens = claude_req
+        .max_tokens
+        .map(|t| t.min(output_limit))
+        .unwrap_or(output_limit);
+    config["maxOutputTokens"] = json!(max_output_tokens);
 
     // [优化] 设置全局停止序列,防止流式输出冗余
-    config["stopSequences"] = json!([
-        "<|user|>",
-        "<|endoftext|>",
-        "<|end_of_turn|>",
-        "[DONE]",
-        "\n\nHuman:"
-    ]);
+    config["stopSequences"] = json!(upstream_stop_sequences(&claude_req.stop_sequences));
 
     config
 }
 
+/// 默认停止序列 (防止流式输出冗余)
+const DEFAULT_STOP_SEQUENCES: [&str; 5] = [
+    "<|user|>",
+    "<|endoftext|>",
+    "<|end_of_turn|>",
+    "[DONE]",
+    "\n\nHuman:",
+];
+
+/// 合并默认停止序列与客户端 stop_sequences
+/// Gemini 的 finishReason 无法区分命中了哪条序列 (且上限 5 条), 客户端 stop_sequences
+/// 由响应侧 StopSequenceMatcher 在本地匹配并回填 stop_sequence; 上游只保留客户端未声明的默认序列,
+/// 否则与默认序列重合的客户端序列会在上游被截断, 无法正确返回 stop_reason。
+fn upstream_stop_sequences(user_stop_sequences: &Option<Vec<String>>) -> Vec<&'static str> {
+    let user = user_stop_sequences.as_deref().unwrap_or(&[]);
+    DEFAULT_STOP_SEQUENCES
+        .into_iter()
+        .filter(|seq| !user.iter().any(|u| u == seq))
+        .collect()
+}
+
 /// Recursively remove 'thought' and 'thoughtSignature' fields
 /// Used when downgrading thinking (e.g. during 400 retry)
 pub fn clean_thinking_fields_recursive(val: &mut Value) {
@@ -1064,8 +1069,10 @@ mod tests {
             }],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
+            stop_sequences: None,
             temperature: None,
             top_p: None,
             top_k: None,
@@ -1082,6 +1089,113 @@ mod tests {
         assert!(body["requestId"].as_str().unwrap().starts_with("agent-"));
     }
 
+    #[test]
+    fn test_max_tokens_and_stop_sequences() {
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{ "role": "user", "content": "Hello" }],
+            "max_tokens": 200000,
+            "stop_sequences": ["\n\nHuman:", "END"]
+        })).unwrap();
+
+        let body = transform_claude_request_in(&req, "test-project").unwrap();
+        let gen_config = &body["request"]["generationConfig"];
+        // 超出模型输出上限时截断
+        assert_eq!(gen_config["maxOutputTokens"], 65536);
+        // 客户端声明的序列在本地匹配, 不再下发到上游
+        let stops: Vec<&str> = gen_config["stopSequences"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        assert!(!stops.contains(&"\n\nHuman:"));
+        assert!(!stops.contains(&"END"));
+        assert!(stops.contains(&"<|endoftext|>"));
+
+        let mut small = req.clone();
+        small.max_tokens = Some(1024);
+        let body = transform_claude_request_in(&small, "test-project").unwrap();
+        assert_eq!(body["request"]["generationConfig"]["maxOutputTokens"], 1024);
+    }
+
+    #[test]
+    fn test_client_stop_sequences_matched_locally() {
+        // 客户端序列即使超过 5 条也不会挤掉默认序列
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{ "role": "user", "content": "Count" }],
+            "stop_sequences": ["S1", "S2", "S3", "S4", "S5", "END"]
+        })).unwrap();
+        let body = transform_claude_request_in(&req, "test-project").unwrap();
+        let stops = body["request"]["generationConfig"]["stopSequences"].as_array().unwrap().clone();
+        assert_eq!(stops, DEFAULT_STOP_SEQUENCES.iter().map(|s| json!(s)).collect::<Vec<_>>());
+
+        // 上游未收到 END，原样输出后以 STOP 结束，由本地匹配截断并回填 stop_sequence
+        let gemini_resp: crate::proxy::mappers::claude::models::GeminiResponse = serde_json::from_value(json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "1 2 3 END 4 5" }] },
+                "finishReason": "STOP"
+            }]
+        })).unwrap();
+        let resp = crate::proxy::mappers::claude::transform_response(&gemini_resp, req.stop_sequences.clone().unwrap()).unwrap();
+        assert_eq!(resp.stop_reason, "stop_sequence");
+        assert_eq!(resp.stop_sequence.as_deref(), Some("END"));
+        match &resp.content[0] {
+            ContentBlock::Text { text } => assert_eq!(text, "1 2 3 "),
+            other => panic!("Expected Text block, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tool_choice_mapping() {
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{ "role": "user", "content": "weather?" }],
+            "tools": [{ "name": "get_weather", "input_schema": { "type": "object", "properties": {} } }],
+            "tool_choice": { "type": "tool", "name": "get_weather", "disable_parallel_tool_use": true }
+        })).unwrap();
+
+        let body = transform_claude_request_in(&req, "test-project").unwrap();
+        let fcc = &body["request"]["toolConfig"]["functionCallingConfig"];
+        assert_eq!(fcc["mode"], "ANY");
+        assert_eq!(fcc["allowedFunctionNames"][0], "get_weather");
+
+        let sys_parts = body["request"]["systemInstruction"]["parts"].as_array().unwrap();
+        assert!(sys_parts.iter().any(|p| p["text"] == crate::proxy::mappers::common_utils::SINGLE_TOOL_CALL_INSTRUCTION));
+
+        // 未指定 tool_choice 时保持 VALIDATED
+        let mut req = req;
+        req.tool_choice = None;
+        let body = transform_claude_request_in(&req, "test-project").unwrap();
+        assert_eq!(body["request"]["toolConfig"]["functionCallingConfig"]["mode"], "VALIDATED");
+
+        req.tool_choice = Some(ToolChoice::None);
+        let body = transform_claude_request_in(&req, "test-project").unwrap();
+        assert_eq!(body["request"]["toolConfig"]["functionCallingConfig"]["mode"], "NONE");
+    }
+
+    #[test]
+    fn test_document_sources() {
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "document", "source": { "type": "base64", "media_type": "application/pdf", "data": "JVBERi0xLjc=" } },
+                    { "type": "document", "title": "notes.txt", "source": { "type": "text", "media_type": "text/plain", "data": "hello" } },
+                    { "type": "text", "text": "Summarize" }
+                ]
+            }]
+        })).unwrap();
+
+        let body = transform_claude_request_in(&req, "test-project").unwrap();
+        let parts = &body["request"]["contents"][0]["parts"];
+        assert_eq!(parts[0]["inlineData"]["mimeType"], "application/pdf");
+        assert_eq!(parts[1]["text"], "[Document: notes.txt]\nhello");
+        assert_eq!(parts[2]["text"], "Summarize");
+    }
+
     #[test]
     fn test_clean_json_schema() {
         let mut schema = json!({
@@ -1161,8 +1275,10 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
+            stop_sequences: None,
             temperature: None,
             top_p: None,
             top_k: None,
@@ -1223,6 +1339,7 @@ mod tests {
                                 source_type: "base64".to_string(),
                                 media_type: "image/png".to_string(),
                                 data: "iVBORw0KGgo=".to_string(),
+                                url: None,
                             },
                             cache_control: Some(json!({"type": "ephemeral"})), // 这个也应该被清理
                         },
@@ -1231,8 +1348,10 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
+            stop_sequences: None,
             temperature: None,
             top_p: None,
             top_k: None,
@@ -1303,8 +1422,10 @@ mod tests {
                     // cache_control: None, // removed
                 }
             ]),
+            tool_choice: None,
             stream: false,
             max_tokens: None,
+            stop_sequences: None,
             temperature: None,
             top_p: None,
             top_k: None,
@@ -1355,8 +1476,10 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
+            stop_sequences: None,
             temperature: None,
             top_p: None,
             top_k: None,
@@ -1405,8 +1528,10 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
+            stop_sequences: None,
             temperature: None,
             top_p: None,
             top_k: None,
@@ -1448,8 +1573,10 @@ mod tests {
             ],
             system: None,
             tools: None,
+            tool_choice: None,
             stream: false,
             max_tokens: None,
+            stop_sequences: None,
             temperature: None,
             top_p: None,
             top_k: None,
@@ -2,7 +2,7 @@
 // 对应 NonStreamingProcessor
 
 use super::models::*;
-use super::utils::to_claude_usage;
+use super::utils::{to_claude_usage, StopSequenceMatcher};
 
 /// Known parameter remappings for Gemini → Claude compatibility
 /// [FIX] Gemini sometimes uses different parameter names than specified in tool schema
@@ -106,10 +106,16 @@ pub struct NonStreamingProcessor {
     thinking_signature: Option<String>,
     trailing_signature: Option<String>,
     has_tool_call: bool,
+    stop_matcher: StopSequenceMatcher,
 }
 
 impl NonStreamingProcessor {
     pub fn new() -> Self {
+        Self::with_stop_sequences(Vec::new())
+    }
+
+    /// 携带客户端 stop_sequences 创建处理器
+    pub fn with_stop_sequences(stop_sequences: Vec<String>) -> Self {
         Self {
             content_blocks: Vec::new(),
             text_builder: String::new(),
@@ -117,6 +123,7 @@ impl NonStreamingProcessor {
             thinking_signature: None,
             trailing_signature: None,
             has_tool_call: false,
+            stop_matcher: StopSequenceMatcher::new(stop_sequences),
         }
     }
 
@@ -163,6 +170,11 @@ impl NonStreamingProcessor {
 
     /// 处理单个 part
     fn process_part(&mut self, part: &GeminiPart) {
+        // 命中 stop_sequence 后丢弃后续所有内容
+        if self.stop_matcher.matched().is_some() {
+            return;
+        }
+
         let signature = part.thought_signature.clone();
 
         // 1. FunctionCall 处理
@@ -253,7 +265,8 @@ impl NonStreamingProcessor {
                     });
                 }
 
-                self.text_builder.push_str(text);
+                let text = self.stop_matcher.push(text);
+                self.text_builder.push_str(&text);
 
                 // 非空 text 带签名 - 立即刷新并输出空 thinking 块
                 if let Some(sig) = signature {
@@ -321,6 +334,9 @@ impl NonStreamingProcessor {
 
     /// 刷新 text builder
     fn flush_text(&mut self) {
+        let held = self.stop_matcher.flush();
+        self.text_builder.push_str(&held);
+
         if self.text_builder.is_empty() {
             return;
         }
@@ -357,8 +373,12 @@ impl NonStreamingProcessor {
             .and_then(|c| c.get(0))
             .and_then(|candidate| candidate.finish_reason.as_deref());
 
+        let stop_sequence = self.stop_matcher.matched().map(|s| s.to_string());
+
         let stop_reason = if self.has_tool_call {
             "tool_use"
+        } else if stop_sequence.is_some() {
+            "stop_sequence"
         } else if finish_reason == Some("MAX_TOKENS") {
             "max_tokens"
         } else {
@@ -386,15 +406,18 @@ impl NonStreamingProcessor {
             model: gemini_response.model_version.clone().unwrap_or_default(),
             content: self.content_blocks.clone(),
             stop_reason: stop_reason.to_string(),
-            stop_sequence: None,
+            stop_sequence,
             usage,
         }
     }
 }
 
 /// 转换 Gemini 响应为 Claude 响应 (公共接口)
-pub fn transform_response(gemini_response: &GeminiResponse) -> Result<ClaudeResponse, String> {
-    let mut processor = NonStreamingProcessor::new();
+pub fn transform_response(
+    gemini_response: &GeminiResponse,
+    stop_sequences: Vec<String>,
+) -> Result<ClaudeResponse, String> {
+    let mut processor = NonStreamingProcessor::with_stop_sequences(stop_sequences);
     Ok(processor.process(gemini_response))
 }
 
@@ -431,7 +454,7 @@ mod tests {
             response_id: Some("resp_123".to_string()),
         };
 
-        let result = transform_response(&gemini_resp);
+        let result = transform_response(&gemini_resp, Vec::new());
         assert!(result.is_ok());
 
         let claude_resp = result.unwrap();
@@ -447,6 +470,50 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_stop_reason_mapping() {
+        let text_part = |text: &str| GeminiPart {
+            text: Some(text.to_string()),
+            thought: None,
+            thought_signature: None,
+            function_call: None,
+            function_response: None,
+            inline_data: None,
+        };
+        let make_resp = |parts: Vec<GeminiPart>, finish: &str| GeminiResponse {
+            candidates: Some(vec![Candidate {
+                content: Some(GeminiContent {
+                    role: "model".to_string(),
+                    parts,
+                }),
+                finish_reason: Some(finish.to_string()),
+                index: Some(0),
+                grounding_metadata: None,
+            }]),
+            usage_metadata: None,
+            model_version: None,
+            response_id: None,
+        };
+
+        // MAX_TOKENS -> max_tokens
+        let resp = transform_response(&make_resp(vec![text_part("partial")], "MAX_TOKENS"), Vec::new()).unwrap();
+        assert_eq!(resp.stop_reason, "max_tokens");
+        assert_eq!(resp.stop_sequence, None);
+
+        // 命中客户端 stop_sequence (跨 part) -> 截断文本并返回命中的序列
+        let resp = transform_response(
+            &make_resp(vec![text_part("answer: 42 ##"), text_part("# ignored")], "STOP"),
+            vec!["###".to_string()],
+        )
+        .unwrap();
+        assert_eq!(resp.stop_reason, "stop_sequence");
+        assert_eq!(resp.stop_sequence.as_deref(), Some("###"));
+        match &resp.content[0] {
+            ContentBlock::Text { text } => assert_eq!(text, "answer: 42 "),
+            _ => panic!("Expected Text block"),
+        }
+    }
+
     #[test]
     fn test_thinking_with_signature() {
         let gemini_resp = GeminiResponse {
@@ -481,7 +548,7 @@ mod tests {
             response_id: Some("resp_456".to_string()),
         };
 
-        let result = transform_response(&gemini_resp);
+        let result = transform_response(&gemini_resp, Vec::new());
         assert!(result.is_ok());
 
         let claude_resp = result.unwrap();
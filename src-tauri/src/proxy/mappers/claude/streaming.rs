@@ -2,7 +2,7 @@
 // 对应 StreamingState + PartProcessor
 
 use super::models::*;
-use super::utils::to_claude_usage;
+use super::utils::{to_claude_usage, StopSequenceMatcher};
 // use crate::proxy::mappers::signature_store::store_thought_signature; // Deprecated
 use crate::proxy::SignatureCache;
 use bytes::Bytes;
@@ -156,10 +156,17 @@ pub struct StreamingState {
     last_valid_state: Option<BlockType>,
     // [NEW] Model tracking for signature cache
     pub model_name: Option<String>,
+    // 客户端 stop_sequences 本地匹配
+    stop_matcher: StopSequenceMatcher,
 }
 
 impl StreamingState {
     pub fn new() -> Self {
+        Self::with_stop_sequences(Vec::new())
+    }
+
+    /// 携带客户端 stop_sequences 创建状态机
+    pub fn with_stop_sequences(stop_sequences: Vec<String>) -> Self {
         Self {
             block_type: BlockType::None,
             block_index: 0,
@@ -174,6 +181,7 @@ impl StreamingState {
             parse_error_count: 0,
             last_valid_state: None,
             model_name: None,
+            stop_matcher: StopSequenceMatcher::new(stop_sequences),
         }
     }
 
@@ -313,7 +321,8 @@ impl StreamingState {
     ) -> Vec<Bytes> {
         let mut chunks = Vec::new();
 
-        // 关闭最后一个块
+        // 输出 stop_sequences 匹配时暂存的文本, 再关闭最后一个块
+        chunks.extend(self.flush_held_text());
         chunks.extend(self.end_block());
 
         // 处理 trailingSignature (PDF 776-778)
@@ -381,8 +390,11 @@ impl StreamingState {
         }
 
         // 确定 stop_reason
+        let stop_sequence = self.stop_matcher.matched().map(|s| s.to_string());
         let stop_reason = if self.used_tool {
             "tool_use"
+        } else if stop_sequence.is_some() {
+            "stop_sequence"
         } else if finish_reason == Some("MAX_TOKENS") {
             "max_tokens"
         } else {
@@ -403,7 +415,7 @@ impl StreamingState {
             "message_delta",
             json!({
                 "type": "message_delta",
-                "delta": { "stop_reason": stop_reason, "stop_sequence": null },
+                "delta": { "stop_reason": stop_reason, "stop_sequence": stop_sequence },
                 "usage": usage
             }),
         ));
@@ -418,6 +430,31 @@ impl StreamingState {
         chunks
     }
 
+    /// 是否已命中客户端 stop_sequence
+    pub fn stop_sequence_matched(&self) -> bool {
+        self.stop_matcher.matched().is_some()
+    }
+
+    /// 过滤普通文本: 截断命中的 stop_sequence, 暂存可能构成匹配的后缀
+    pub fn filter_text(&mut self, text: &str) -> String {
+        self.stop_matcher.push(text)
+    }
+
+    /// 输出暂存的文本 (在切换到其他块类型或结束前调用)
+    pub fn flush_held_text(&mut self) -> Vec<Bytes> {
+        let held = self.stop_matcher.flush();
+        if held.is_empty() {
+            return vec![];
+        }
+
+        let mut chunks = Vec::new();
+        if self.block_type != BlockType::Text {
+            chunks.extend(self.start_block(BlockType::Text, json!({ "type": "text", "text": "" })));
+        }
+        chunks.push(self.emit_delta("text_delta", json!({ "text": held })));
+        chunks
+    }
+
     /// 标记使用了工具
     pub fn mark_tool_used(&mut self) {
         self.used_tool = true;
@@ -528,8 +565,19 @@ impl<'a> PartProcessor<'a> {
     /// 处理单个 part
     pub fn process(&mut self, part: &GeminiPart) -> Vec<Bytes> {
         let mut chunks = Vec::new();
+
+        // 命中 stop_sequence 后丢弃后续所有内容
+        if self.state.stop_sequence_matched() {
+            return chunks;
+        }
+
         let signature = part.thought_signature.clone();
 
+        // 非普通文本的 part 会切换块, 先输出暂存文本以保持顺序
+        if part.function_call.is_some() || part.thought.unwrap_or(false) || part.inline_data.is_some() {
+            chunks.extend(self.state.flush_held_text());
+        }
+
         // 1. FunctionCall 处理
         if let Some(fc) = &part.function_call {
             // 先处理 trailingSignature (B4/C3 场景)
@@ -566,8 +614,9 @@ impl<'a> PartProcessor<'a> {
                 // Thinking
                 chunks.extend(self.process_thinking(text, signature));
             } else {
-                // 普通 Text
-                chunks.extend(self.process_text(text, signature));
+                // 普通 Text (先经过 stop_sequences 过滤)
+                let text = self.state.filter_text(text);
+                chunks.extend(self.process_text(&text, signature));
             }
         }
 
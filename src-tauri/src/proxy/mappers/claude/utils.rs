@@ -23,6 +23,74 @@ pub fn to_claude_usage(usage_metadata: &super::models::UsageMetadata) -> super::
     }
 }
 
+/// 客户端 stop_sequences 本地匹配器
+/// Gemini 的 finishReason 不区分是哪条停止序列触发的, 且 stopSequences 上限为 5 条,
+/// 因此客户端传入的 stop_sequences 在响应侧逐段匹配: 命中后截断文本并记录命中的序列。
+/// 跨 chunk 的潜在前缀会被暂存, 直到能确定其是否构成完整匹配。
+#[derive(Debug, Default)]
+pub struct StopSequenceMatcher {
+    sequences: Vec<String>,
+    pending: String,
+    matched: Option<String>,
+}
+
+impl StopSequenceMatcher {
+    pub fn new(sequences: Vec<String>) -> Self {
+        Self {
+            sequences: sequences.into_iter().filter(|s| !s.is_empty()).collect(),
+            pending: String::new(),
+            matched: None,
+        }
+    }
+
+    /// 已命中的停止序列
+    pub fn matched(&self) -> Option<&str> {
+        self.matched.as_deref()
+    }
+
+    /// 输入一段文本, 返回可以安全输出的部分
+    pub fn push(&mut self, text: &str) -> String {
+        if self.matched.is_some() {
+            return String::new();
+        }
+        if self.sequences.is_empty() {
+            return text.to_string();
+        }
+
+        let mut buffer = std::mem::take(&mut self.pending);
+        buffer.push_str(text);
+
+        // 1. 查找最早出现的完整匹配
+        let earliest = self
+            .sequences
+            .iter()
+            .filter_map(|seq| buffer.find(seq.as_str()).map(|pos| (pos, seq)))
+            .min_by_key(|(pos, _)| *pos);
+        if let Some((pos, seq)) = earliest {
+            self.matched = Some(seq.clone());
+            buffer.truncate(pos);
+            return buffer;
+        }
+
+        // 2. 暂存可能是某条序列前缀的最长后缀
+        let max_len = self.sequences.iter().map(|s| s.len()).max().unwrap_or(0);
+        let hold_from = buffer
+            .char_indices()
+            .map(|(i, _)| i)
+            .filter(|&i| buffer.len() - i < max_len)
+            .find(|&i| self.sequences.iter().any(|seq| seq.starts_with(&buffer[i..])));
+        if let Some(i) = hold_from {
+            self.pending = buffer.split_off(i);
+        }
+        buffer
+    }
+
+    /// 取出暂存的文本 (流结束或内容块切换时调用)
+    pub fn flush(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+}
+
 /// 提取 thoughtSignature
 // 已移除未使用的 extract_thought_signature 函数
 
@@ -48,4 +116,30 @@ mod tests {
         assert_eq!(claude_usage.input_tokens, 100);
         assert_eq!(claude_usage.output_tokens, 50);
     }
+
+    #[test]
+    fn test_stop_sequence_matcher() {
+        // 跨 chunk 匹配: 前缀被暂存, 命中后截断
+        let mut matcher = StopSequenceMatcher::new(vec!["END".to_string(), "###".to_string()]);
+        assert_eq!(matcher.push("Hello E"), "Hello ");
+        assert_eq!(matcher.push("ND tail"), "");
+        assert_eq!(matcher.matched(), Some("END"));
+        assert_eq!(matcher.push("more"), "");
+
+        // 多字节文本下的前缀暂存
+        let mut matcher = StopSequenceMatcher::new(vec!["END".to_string()]);
+        assert_eq!(matcher.push("你好 EN"), "你好 ");
+        assert_eq!(matcher.push("D?"), "");
+        assert_eq!(matcher.matched(), Some("END"));
+
+        // 暂存的前缀最终未构成匹配时由 flush 释放
+        let mut matcher = StopSequenceMatcher::new(vec!["END".to_string()]);
+        assert_eq!(matcher.push("send E"), "send ");
+        assert_eq!(matcher.flush(), "E");
+        assert_eq!(matcher.matched(), None);
+
+        // 无 stop_sequences 时透传
+        let mut matcher = StopSequenceMatcher::new(vec![]);
+        assert_eq!(matcher.push("END"), "END");
+    }
 }
@@ -4,7 +4,7 @@
 use serde_json::{json, Value};
 
 /// Request configuration after grounding resolution
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct RequestConfig {
     /// The request type: "agent", "web_search", or "image_gen"
     pub request_type: String,
@@ -149,6 +149,85 @@ pub fn inject_google_search_tool(body: &mut Value) {
     }
 }
 
+/// 禁用并行工具调用时注入的系统指令 (Gemini 没有对应的 toolConfig 开关)
+pub const SINGLE_TOOL_CALL_INSTRUCTION: &str =
+    "Call at most one function per response. Wait for its result before calling another function.";
+
+/// 构建 Gemini toolConfig
+/// - mode: AUTO / ANY / NONE / VALIDATED
+/// - allowed_function_names: 仅在 ANY / VALIDATED 模式下有意义，为空时不下发
+pub fn build_tool_config(mode: &str, allowed_function_names: &[String]) -> Value {
+    let mut function_calling_config = json!({ "mode": mode });
+    if !allowed_function_names.is_empty() && mode != "NONE" && mode != "AUTO" {
+        function_calling_config["allowedFunctionNames"] = json!(allowed_function_names);
+    }
+    json!({ "functionCallingConfig": function_calling_config })
+}
+
+// ===== Safety Settings Configuration =====
+
+/// Safety threshold levels for Gemini API
+/// Can be configured via GEMINI_SAFETY_THRESHOLD environment variable or parameter policies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SafetyThreshold {
+    /// Disable all safety filters (default for proxy compatibility)
+    Off,
+    /// Block low probability and above
+    BlockLowAndAbove,
+    /// Block medium probability and above
+    BlockMediumAndAbove,
+    /// Only block high probability content
+    BlockOnlyHigh,
+    /// Don't block anything (BLOCK_NONE)
+    BlockNone,
+}
+
+impl SafetyThreshold {
+    /// Parse OFF / LOW / MEDIUM / HIGH / NONE (case-insensitive)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "OFF" => Some(SafetyThreshold::Off),
+            "LOW" => Some(SafetyThreshold::BlockLowAndAbove),
+            "MEDIUM" => Some(SafetyThreshold::BlockMediumAndAbove),
+            "HIGH" => Some(SafetyThreshold::BlockOnlyHigh),
+            "NONE" => Some(SafetyThreshold::BlockNone),
+            _ => None,
+        }
+    }
+
+    /// Get threshold from environment variable or default to Off
+    pub fn from_env() -> Self {
+        std::env::var("GEMINI_SAFETY_THRESHOLD")
+            .ok()
+            .and_then(|v| Self::parse(&v))
+            .unwrap_or(SafetyThreshold::Off) // Default: maintain current behavior
+    }
+
+    /// Convert to Gemini API threshold string
+    pub fn to_gemini_threshold(&self) -> &'static str {
+        match self {
+            SafetyThreshold::Off => "OFF",
+            SafetyThreshold::BlockLowAndAbove => "BLOCK_LOW_AND_ABOVE",
+            SafetyThreshold::BlockMediumAndAbove => "BLOCK_MEDIUM_AND_ABOVE",
+            SafetyThreshold::BlockOnlyHigh => "BLOCK_ONLY_HIGH",
+            SafetyThreshold::BlockNone => "BLOCK_NONE",
+        }
+    }
+}
+
+/// Build safety settings for all harm categories
+pub fn build_safety_settings(threshold: SafetyThreshold) -> Value {
+    let threshold_str = threshold.to_gemini_threshold();
+
+    json!([
+        { "category": "HARM_CATEGORY_HARASSMENT", "threshold": threshold_str },
+        { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": threshold_str },
+        { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": threshold_str },
+        { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": threshold_str },
+        { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": threshold_str },
+    ])
+}
+
 /// 深度迭代清理客户端发送的 [undefined] 脏字符串，防止 Gemini 接口校验失败
 pub fn deep_clean_undefined(value: &mut Value) {
     match value {
@@ -307,6 +386,17 @@ mod tests {
         assert!(!config.inject_google_search);
     }
 
+    #[test]
+    fn test_build_tool_config() {
+        let forced = build_tool_config("ANY", &["get_weather".to_string()]);
+        assert_eq!(forced["functionCallingConfig"]["mode"], "ANY");
+        assert_eq!(forced["functionCallingConfig"]["allowedFunctionNames"][0], "get_weather");
+
+        // NONE 模式不下发 allowedFunctionNames
+        let none = build_tool_config("NONE", &["get_weather".to_string()]);
+        assert!(none["functionCallingConfig"].get("allowedFunctionNames").is_none());
+    }
+
     #[test]
     fn test_image_2k_and_ultrawide_config() {
         // Test 2K
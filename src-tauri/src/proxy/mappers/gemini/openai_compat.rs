@@ -0,0 +1,399 @@
+// Gemini generateContent <-> OpenAI Chat Completions 转换
+// 用于将 Gemini 协议客户端的请求发送到只提供 OpenAI 兼容接口的服务商
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde_json::{json, Map, Value};
+
+fn text_of_parts(parts: Option<&Value>) -> String {
+    parts
+        .and_then(|p| p.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter(|p| !p.get("thought").and_then(|t| t.as_bool()).unwrap_or(false))
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join("")
+        })
+        .unwrap_or_default()
+}
+
+/// Gemini Schema 的类型名为大写 (OBJECT/STRING)，OpenAI 使用 JSON Schema 小写
+fn lowercase_schema_types(schema: &mut Value) {
+    match schema {
+        Value::Object(map) => {
+            if let Some(Value::String(t)) = map.get_mut("type") {
+                *t = t.to_lowercase();
+            }
+            for v in map.values_mut() {
+                lowercase_schema_types(v);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(lowercase_schema_types),
+        _ => {}
+    }
+}
+
+/// 用户侧 parts -> OpenAI content (仅文本时为字符串)
+fn user_content(parts: &[Value]) -> Option<Value> {
+    let mut items = Vec::new();
+    for part in parts {
+        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+            items.push(json!({ "type": "text", "text": text }));
+        } else if let Some(inline) = part.get("inlineData").or_else(|| part.get("inline_data")) {
+            let mime = inline.get("mimeType").or_else(|| inline.get("mime_type")).and_then(|m| m.as_str()).unwrap_or("image/png");
+            let data = inline.get("data").and_then(|d| d.as_str()).unwrap_or_default();
+            items.push(json!({ "type": "image_url", "image_url": { "url": format!("data:{};base64,{}", mime, data) } }));
+        } else if let Some(uri) = part.get("fileData").and_then(|f| f.get("fileUri")).and_then(|u| u.as_str()) {
+            items.push(json!({ "type": "image_url", "image_url": { "url": uri } }));
+        }
+    }
+    if items.is_empty() {
+        return None;
+    }
+    if items.iter().all(|i| i["type"] == "text") {
+        let text: Vec<&str> = items.iter().filter_map(|i| i["text"].as_str()).collect();
+        return Some(Value::String(text.join("\n")));
+    }
+    Some(Value::Array(items))
+}
+
+/// Gemini 请求体 -> OpenAI Chat Completions 请求体
+pub fn gemini_to_openai_request(body: &Value, model: &str, stream: bool) -> Value {
+    let mut messages = Vec::new();
+
+    let system = body.get("systemInstruction").or_else(|| body.get("system_instruction"));
+    let system_text = text_of_parts(system.and_then(|s| s.get("parts")));
+    if !system_text.is_empty() {
+        messages.push(json!({ "role": "system", "content": system_text }));
+    }
+
+    // Gemini 的 functionResponse 只有函数名，按顺序对应到同名的未应答调用
+    let mut pending_calls: VecDeque<(String, String)> = VecDeque::new();
+    let mut call_seq = 0usize;
+
+    for content in body.get("contents").and_then(|c| c.as_array()).into_iter().flatten() {
+        let parts: &[Value] = content.get("parts").and_then(|p| p.as_array()).map(|p| p.as_slice()).unwrap_or_default();
+        if content.get("role").and_then(|r| r.as_str()) == Some("model") {
+            let tool_calls: Vec<Value> = parts
+                .iter()
+                .filter_map(|p| p.get("functionCall"))
+                .map(|call| {
+                    let name = call.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_string();
+                    let id = call
+                        .get("id")
+                        .and_then(|i| i.as_str())
+                        .map(str::to_string)
+                        .unwrap_or_else(|| {
+                            call_seq += 1;
+                            format!("call_{}", call_seq)
+                        });
+                    pending_calls.push_back((name.clone(), id.clone()));
+                    json!({
+                        "id": id,
+                        "type": "function",
+                        "function": { "name": name, "arguments": call.get("args").cloned().unwrap_or(json!({})).to_string() }
+                    })
+                })
+                .collect();
+            let text = text_of_parts(content.get("parts"));
+            let mut message = json!({ "role": "assistant", "content": if text.is_empty() { Value::Null } else { Value::String(text) } });
+            if !tool_calls.is_empty() {
+                message["tool_calls"] = Value::Array(tool_calls);
+            }
+            messages.push(message);
+            continue;
+        }
+
+        for response in parts.iter().filter_map(|p| p.get("functionResponse")) {
+            let name = response.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+            let id = match pending_calls.iter().position(|(n, _)| n == name) {
+                Some(pos) => pending_calls.remove(pos).map(|(_, id)| id).unwrap_or_default(),
+                None => response.get("id").and_then(|i| i.as_str()).unwrap_or(name).to_string(),
+            };
+            let output = match response.get("response") {
+                Some(Value::String(s)) => s.clone(),
+                Some(v) => v.to_string(),
+                None => String::new(),
+            };
+            messages.push(json!({ "role": "tool", "tool_call_id": id, "content": output }));
+        }
+        if let Some(content) = user_content(parts) {
+            messages.push(json!({ "role": "user", "content": content }));
+        }
+    }
+
+    let mut out = json!({ "model": model, "messages": messages, "stream": stream });
+    if stream {
+        out["stream_options"] = json!({ "include_usage": true });
+    }
+
+    let tools: Vec<Value> = body
+        .get("tools")
+        .and_then(|t| t.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|t| t.get("functionDeclarations").and_then(|d| d.as_array()))
+        .flatten()
+        .map(|decl| {
+            let mut parameters = decl
+                .get("parameters")
+                .or_else(|| decl.get("parametersJsonSchema"))
+                .cloned()
+                .unwrap_or_else(|| json!({ "type": "object", "properties": {} }));
+            lowercase_schema_types(&mut parameters);
+            let mut function = json!({ "name": decl.get("name").cloned().unwrap_or(Value::Null), "parameters": parameters });
+            if let Some(description) = decl.get("description") {
+                function["description"] = description.clone();
+            }
+            json!({ "type": "function", "function": function })
+        })
+        .collect();
+    if !tools.is_empty() {
+        out["tools"] = Value::Array(tools);
+        if let Some(config) = body.get("toolConfig").and_then(|c| c.get("functionCallingConfig")) {
+            let allowed = config.get("allowedFunctionNames").and_then(|a| a.as_array());
+            let choice = match config.get("mode").and_then(|m| m.as_str()).unwrap_or("AUTO") {
+                "ANY" => match allowed.filter(|a| a.len() == 1).and_then(|a| a[0].as_str()) {
+                    Some(name) => json!({ "type": "function", "function": { "name": name } }),
+                    None => json!("required"),
+                },
+                "NONE" => json!("none"),
+                _ => json!("auto"),
+            };
+            out["tool_choice"] = choice;
+        }
+    }
+
+    if let Some(config) = body.get("generationConfig").and_then(|c| c.as_object()) {
+        for (from, to) in [
+            ("temperature", "temperature"),
+            ("topP", "top_p"),
+            ("maxOutputTokens", "max_tokens"),
+            ("stopSequences", "stop"),
+            ("presencePenalty", "presence_penalty"),
+            ("frequencyPenalty", "frequency_penalty"),
+            ("seed", "seed"),
+        ] {
+            if let Some(v) = config.get(from) {
+                out[to] = v.clone();
+            }
+        }
+        if config.get("responseMimeType").and_then(|m| m.as_str()) == Some("application/json") {
+            out["response_format"] = json!({ "type": "json_object" });
+        }
+    }
+
+    out
+}
+
+fn finish_reason(reason: &str) -> &'static str {
+    match reason {
+        "length" => "MAX_TOKENS",
+        "content_filter" | "sensitive" => "SAFETY",
+        _ => "STOP",
+    }
+}
+
+fn usage_metadata(usage: &Value) -> Value {
+    let mut meta = json!({
+        "promptTokenCount": usage.get("prompt_tokens").cloned().unwrap_or(json!(0)),
+        "candidatesTokenCount": usage.get("completion_tokens").cloned().unwrap_or(json!(0)),
+        "totalTokenCount": usage.get("total_tokens").cloned().unwrap_or(json!(0)),
+    });
+    if let Some(reasoning) = usage.pointer("/completion_tokens_details/reasoning_tokens") {
+        meta["thoughtsTokenCount"] = reasoning.clone();
+    }
+    meta
+}
+
+fn function_call_part(name: &str, arguments: &str) -> Value {
+    let args = serde_json::from_str::<Value>(arguments).unwrap_or_else(|_| json!({}));
+    json!({ "functionCall": { "name": name, "args": args } })
+}
+
+/// OpenAI Chat Completions 响应 -> Gemini generateContent 响应
+pub fn openai_to_gemini_response(response: &Value, model: &str) -> Value {
+    let candidates: Vec<Value> = response
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, choice)| {
+            let message = choice.get("message").cloned().unwrap_or_default();
+            let mut parts = Vec::new();
+            if let Some(reasoning) = message.get("reasoning_content").and_then(|r| r.as_str()).filter(|r| !r.is_empty()) {
+                parts.push(json!({ "text": reasoning, "thought": true }));
+            }
+            if let Some(text) = message.get("content").and_then(|c| c.as_str()).filter(|c| !c.is_empty()) {
+                parts.push(json!({ "text": text }));
+            }
+            for call in message.get("tool_calls").and_then(|t| t.as_array()).into_iter().flatten() {
+                let name = call.pointer("/function/name").and_then(|n| n.as_str()).unwrap_or_default();
+                let arguments = call.pointer("/function/arguments").and_then(|a| a.as_str()).unwrap_or("{}");
+                parts.push(function_call_part(name, arguments));
+            }
+            json!({
+                "content": { "role": "model", "parts": parts },
+                "finishReason": finish_reason(choice.get("finish_reason").and_then(|r| r.as_str()).unwrap_or("stop")),
+                "index": index,
+            })
+        })
+        .collect();
+
+    let mut out = json!({
+        "candidates": candidates,
+        "modelVersion": response.get("model").and_then(|m| m.as_str()).unwrap_or(model),
+    });
+    if let Some(usage) = response.get("usage").filter(|u| u.is_object()) {
+        out["usageMetadata"] = usage_metadata(usage);
+    }
+    if let Some(id) = response.get("id") {
+        out["responseId"] = id.clone();
+    }
+    out
+}
+
+/// 流式响应转换: 工具调用参数跨块累积，在 finish_reason 到达时输出完整的 functionCall
+#[derive(Debug, Default)]
+pub struct OpenAIStreamToGemini {
+    model: String,
+    /// index -> (函数名, 已累积的参数)
+    tool_calls: BTreeMap<u64, (String, String)>,
+}
+
+impl OpenAIStreamToGemini {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// 转换一个 OpenAI 流式块，无需输出时返回 None
+    pub fn convert(&mut self, chunk: &Value) -> Option<Value> {
+        let mut parts = Vec::new();
+        let mut finish = None;
+
+        if let Some(choice) = chunk.get("choices").and_then(|c| c.get(0)) {
+            let delta = choice.get("delta").cloned().unwrap_or_default();
+            if let Some(reasoning) = delta.get("reasoning_content").and_then(|r| r.as_str()).filter(|r| !r.is_empty()) {
+                parts.push(json!({ "text": reasoning, "thought": true }));
+            }
+            if let Some(text) = delta.get("content").and_then(|c| c.as_str()).filter(|c| !c.is_empty()) {
+                parts.push(json!({ "text": text }));
+            }
+            for call in delta.get("tool_calls").and_then(|t| t.as_array()).into_iter().flatten() {
+                let index = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                let entry = self.tool_calls.entry(index).or_default();
+                if let Some(name) = call.pointer("/function/name").and_then(|n| n.as_str()) {
+                    entry.0.push_str(name);
+                }
+                if let Some(arguments) = call.pointer("/function/arguments").and_then(|a| a.as_str()) {
+                    entry.1.push_str(arguments);
+                }
+            }
+            if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+                for (name, arguments) in std::mem::take(&mut self.tool_calls).into_values() {
+                    parts.push(function_call_part(&name, if arguments.is_empty() { "{}" } else { &arguments }));
+                }
+                finish = Some(finish_reason(reason));
+            }
+        }
+
+        let usage = chunk.get("usage").filter(|u| u.is_object());
+        if parts.is_empty() && finish.is_none() && usage.is_none() {
+            return None;
+        }
+
+        let mut out = Map::new();
+        if !parts.is_empty() || finish.is_some() {
+            let mut candidate = json!({ "content": { "role": "model", "parts": parts }, "index": 0 });
+            if let Some(reason) = finish {
+                candidate["finishReason"] = json!(reason);
+            }
+            out.insert("candidates".to_string(), json!([candidate]));
+        }
+        if let Some(usage) = usage {
+            out.insert("usageMetadata".to_string(), usage_metadata(usage));
+        }
+        let model = chunk.get("model").and_then(|m| m.as_str()).unwrap_or(&self.model);
+        out.insert("modelVersion".to_string(), json!(model));
+        Some(Value::Object(out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gemini_request_to_openai() {
+        let body = json!({
+            "systemInstruction": { "parts": [{ "text": "Be brief." }] },
+            "contents": [
+                { "role": "user", "parts": [{ "text": "Weather?" }, { "inlineData": { "mimeType": "image/jpeg", "data": "AAAA" } }] },
+                { "role": "model", "parts": [{ "functionCall": { "name": "get_weather", "args": { "city": "Paris" } } }] },
+                { "role": "user", "parts": [{ "functionResponse": { "name": "get_weather", "response": { "temp": 21 } } }] }
+            ],
+            "tools": [{ "functionDeclarations": [{ "name": "get_weather", "parameters": { "type": "OBJECT", "properties": { "city": { "type": "STRING" } } } }] }],
+            "toolConfig": { "functionCallingConfig": { "mode": "ANY" } },
+            "generationConfig": { "temperature": 0.2, "maxOutputTokens": 256, "responseMimeType": "application/json" }
+        });
+
+        let out = gemini_to_openai_request(&body, "glm-4.7", true);
+        assert_eq!(out["model"], "glm-4.7");
+        assert_eq!(out["stream_options"]["include_usage"], true);
+        assert_eq!(out["messages"][0], json!({ "role": "system", "content": "Be brief." }));
+        assert_eq!(out["messages"][1]["content"][1]["image_url"]["url"], "data:image/jpeg;base64,AAAA");
+        assert_eq!(out["messages"][2]["tool_calls"][0]["id"], "call_1");
+        assert_eq!(out["messages"][2]["tool_calls"][0]["function"]["arguments"], "{\"city\":\"Paris\"}");
+        assert_eq!(out["messages"][3], json!({ "role": "tool", "tool_call_id": "call_1", "content": "{\"temp\":21}" }));
+        assert_eq!(out["tools"][0]["function"]["parameters"]["properties"]["city"]["type"], "string");
+        assert_eq!(out["tool_choice"], "required");
+        assert_eq!(out["max_tokens"], 256);
+        assert_eq!(out["response_format"]["type"], "json_object");
+    }
+
+    #[test]
+    fn test_openai_response_to_gemini() {
+        let response = json!({
+            "id": "chatcmpl-1",
+            "model": "glm-4.7",
+            "choices": [{
+                "message": { "role": "assistant", "reasoning_content": "hmm", "content": "Hi", "tool_calls": [
+                    { "id": "c1", "type": "function", "function": { "name": "ls", "arguments": "{\"path\":\".\"}" } }
+                ] },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": { "prompt_tokens": 3, "completion_tokens": 5, "total_tokens": 8 }
+        });
+        let out = openai_to_gemini_response(&response, "gemini-2.5-pro");
+        assert_eq!(
+            out["candidates"][0]["content"]["parts"],
+            json!([{ "text": "hmm", "thought": true }, { "text": "Hi" }, { "functionCall": { "name": "ls", "args": { "path": "." } } }])
+        );
+        assert_eq!(out["candidates"][0]["finishReason"], "STOP");
+        assert_eq!(out["usageMetadata"]["totalTokenCount"], 8);
+        assert_eq!(out["modelVersion"], "glm-4.7");
+
+        // 流式: 工具参数跨块累积
+        let mut converter = OpenAIStreamToGemini::new("glm-4.7");
+        let chunks = [
+            json!({ "choices": [{ "delta": { "content": "Hi" } }] }),
+            json!({ "choices": [{ "delta": { "tool_calls": [{ "index": 0, "function": { "name": "ls", "arguments": "{\"pa" } }] } }] }),
+            json!({ "choices": [{ "delta": { "tool_calls": [{ "index": 0, "function": { "arguments": "th\":\".\"}" } }] } }] }),
+            json!({ "choices": [{ "delta": {}, "finish_reason": "tool_calls" }] }),
+            json!({ "choices": [], "usage": { "prompt_tokens": 3, "completion_tokens": 5, "total_tokens": 8 } }),
+        ];
+        let events: Vec<Value> = chunks.iter().filter_map(|c| converter.convert(c)).collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["candidates"][0]["content"]["parts"][0]["text"], "Hi");
+        assert_eq!(events[1]["candidates"][0]["content"]["parts"][0]["functionCall"]["args"]["path"], ".");
+        assert_eq!(events[1]["candidates"][0]["finishReason"], "STOP");
+        assert_eq!(events[2]["usageMetadata"]["promptTokenCount"], 3);
+        assert!(events[2].get("candidates").is_none());
+    }
+}
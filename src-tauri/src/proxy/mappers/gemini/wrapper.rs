@@ -81,6 +81,7 @@ pub fn wrap_request(body: &Value, project_id: &str, mapped_model: &str) -> Value
              if let Some(gen_obj) = gen_config.as_object_mut() {
                  gen_obj.remove("thinkingConfig");
                  gen_obj.remove("responseMimeType"); 
+                 gen_obj.remove("responseSchema");
                  gen_obj.remove("responseModalities"); // Cherry Studio sends this, might conflict
                  gen_obj.insert("imageConfig".to_string(), image_config);
              }
@@ -160,6 +161,23 @@ mod tests {
         assert!(result["requestId"].as_str().unwrap().starts_with("agent-"));
     }
 
+    #[test]
+    fn test_response_schema_passthrough() {
+        // 原生 Gemini 请求自带的 responseSchema 应原样透传
+        let body = json!({
+            "contents": [{"role": "user", "parts": [{"text": "Hi"}]}],
+            "generationConfig": {
+                "responseMimeType": "application/json",
+                "responseSchema": { "type": "OBJECT", "properties": { "a": { "type": "STRING" } } }
+            }
+        });
+
+        let result = wrap_request(&body, "test-project", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+        assert_eq!(gen_config["responseSchema"], body["generationConfig"]["responseSchema"]);
+    }
+
     #[test]
     fn test_unwrap_response() {
         let wrapped = json!({
@@ -0,0 +1,306 @@
+// 输入图像预处理 (Claude / OpenAI 共用)
+// 发送上游前解码校验图像，按最长边缩放 (OpenAI detail: low/high)，重新编码并修正 MIME 类型
+
+use base64::Engine as _;
+use image::{DynamicImage, ImageFormat};
+use std::io::Cursor;
+
+use crate::proxy::config::ImagePreprocessConfig;
+
+/// 上游 (Gemini) 可直接接受的格式；其他可解码格式 (GIF/BMP/TIFF...) 转码为 PNG/JPEG
+const NATIVE_FORMATS: [ImageFormat; 3] = [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::WebP];
+
+/// 无法在本地解码、但上游支持的格式，原样透传
+const PASSTHROUGH_MIME_TYPES: [&str; 2] = ["image/heic", "image/heif"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessedImage {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// 根据文件扩展名推断图像 MIME 类型 (URL 需先去掉 query/fragment)
+pub fn mime_from_extension(path: &str) -> Option<&'static str> {
+    let path = path.split(['?', '#']).next().unwrap_or(path).to_lowercase();
+    let ext = path.rsplit_once('.')?.1;
+    match ext {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        "gif" => Some("image/gif"),
+        "heic" => Some("image/heic"),
+        "heif" => Some("image/heif"),
+        "bmp" => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+/// OpenAI detail 参数对应的最长边上限
+fn max_dimension_for_detail(detail: Option<&str>, config: &ImagePreprocessConfig) -> u32 {
+    match detail {
+        Some("low") => config.low_detail_dimension,
+        _ => config.max_dimension,
+    }
+}
+
+/// 解码校验并按需缩放、重新编码
+/// - 无法识别/解码的图像返回错误 (HEIC/HEIF 除外，原样透传)
+/// - 超过 max_dimension 时等比缩放；非原生格式或体积过大时重新编码
+/// - 结果 MIME 类型以实际内容为准，忽略客户端声明
+pub fn process_image(
+    data: &[u8],
+    declared_mime: Option<&str>,
+    max_dimension: u32,
+    config: &ImagePreprocessConfig,
+) -> Result<ProcessedImage, String> {
+    let format = match image::guess_format(data) {
+        Ok(f) => f,
+        Err(_) => {
+            if let Some(mime) = declared_mime.filter(|m| PASSTHROUGH_MIME_TYPES.contains(m)) {
+                return Ok(ProcessedImage { mime_type: mime.to_string(), data: data.to_vec() });
+            }
+            return Err(format!(
+                "无法识别的图像格式 (声明为 {})",
+                declared_mime.unwrap_or("unknown")
+            ));
+        }
+    };
+
+    let img = image::load_from_memory_with_format(data, format)
+        .map_err(|e| format!("图像解码失败: {}", e))?;
+
+    let max_dimension = max_dimension.max(1);
+    let needs_resize = img.width() > max_dimension || img.height() > max_dimension;
+    let is_native = NATIVE_FORMATS.contains(&format);
+    let is_large = data.len() > config.reencode_threshold_kb as usize * 1024;
+
+    if !needs_resize && is_native && !is_large {
+        return Ok(ProcessedImage {
+            mime_type: format.to_mime_type().to_string(),
+            data: data.to_vec(),
+        });
+    }
+
+    let img = if needs_resize {
+        tracing::debug!(
+            "[Image-Preprocess] Resizing {}x{} to fit {}px",
+            img.width(),
+            img.height(),
+            max_dimension
+        );
+        img.resize(max_dimension, max_dimension, image::imageops::FilterType::CatmullRom)
+    } else {
+        img
+    };
+
+    let encoded = encode_image(&img, config.jpeg_quality)?;
+
+    // 仅因体积触发的重新编码没有变小时保留原图
+    if !needs_resize && is_native && encoded.data.len() >= data.len() {
+        return Ok(ProcessedImage {
+            mime_type: format.to_mime_type().to_string(),
+            data: data.to_vec(),
+        });
+    }
+
+    tracing::debug!(
+        "[Image-Preprocess] Re-encoded {} ({} bytes) -> {} ({} bytes)",
+        format.to_mime_type(),
+        data.len(),
+        encoded.mime_type,
+        encoded.data.len()
+    );
+    Ok(encoded)
+}
+
+/// 含透明通道的图像编码为 PNG，其余编码为 JPEG
+fn encode_image(img: &DynamicImage, jpeg_quality: u8) -> Result<ProcessedImage, String> {
+    let mut buf = Cursor::new(Vec::new());
+    if img.color().has_alpha() {
+        img.write_to(&mut buf, ImageFormat::Png)
+            .map_err(|e| format!("PNG 编码失败: {}", e))?;
+        return Ok(ProcessedImage { mime_type: "image/png".to_string(), data: buf.into_inner() });
+    }
+
+    let rgb = img.to_rgb8();
+    let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buf, jpeg_quality.clamp(1, 100));
+    rgb.write_with_encoder(encoder)
+        .map_err(|e| format!("JPEG 编码失败: {}", e))?;
+    Ok(ProcessedImage { mime_type: "image/jpeg".to_string(), data: buf.into_inner() })
+}
+
+/// 处理 base64 图像数据，返回 (mime_type, base64)
+fn process_base64(
+    b64: &str,
+    declared_mime: Option<&str>,
+    max_dimension: u32,
+    config: &ImagePreprocessConfig,
+) -> Result<(String, String), String> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let bytes = engine
+        .decode(b64.trim())
+        .map_err(|e| format!("图像 base64 解码失败: {}", e))?;
+    let processed = process_image(&bytes, declared_mime, max_dimension, config)?;
+    // 未变化时复用原始 base64，避免重复编码
+    let data = if processed.data == bytes {
+        b64.trim().to_string()
+    } else {
+        engine.encode(&processed.data)
+    };
+    Ok((processed.mime_type, data))
+}
+
+/// OpenAI 消息中的 image_url (data URL) 预处理，结果写回为 data URL
+/// 远程与本地文件已由 MediaFetcher 转为 data URL，未转换的 URL 不在此处理
+pub fn preprocess_openai_images(
+    messages: &mut [crate::proxy::mappers::openai::OpenAIMessage],
+    config: &ImagePreprocessConfig,
+) -> Result<(), String> {
+    use crate::proxy::mappers::openai::{OpenAIContent, OpenAIContentBlock};
+
+    if !config.enabled {
+        return Ok(());
+    }
+
+    for msg in messages.iter_mut() {
+        let Some(OpenAIContent::Array(blocks)) = msg.content.as_mut() else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            let OpenAIContentBlock::ImageUrl { image_url } = block else {
+                continue;
+            };
+            let max_dimension = max_dimension_for_detail(image_url.detail.as_deref(), config);
+
+            if let Some(rest) = image_url.url.strip_prefix("data:") {
+                let (header, data) = rest.split_once(',').ok_or("image_url data URL 格式无效")?;
+                let declared = header.split(';').next().filter(|m| !m.is_empty());
+                let (mime_type, data) = process_base64(data, declared, max_dimension, config)?;
+                image_url.url = format!("data:{};base64,{}", mime_type, data);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Claude 消息中的 base64 图像预处理
+pub fn preprocess_claude_images(
+    messages: &mut [crate::proxy::mappers::claude::Message],
+    config: &ImagePreprocessConfig,
+) -> Result<(), String> {
+    use crate::proxy::mappers::claude::{ContentBlock, MessageContent};
+
+    if !config.enabled {
+        return Ok(());
+    }
+
+    for msg in messages.iter_mut() {
+        let MessageContent::Array(blocks) = &mut msg.content else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            if let ContentBlock::Image { source, .. } = block {
+                if source.source_type != "base64" {
+                    continue;
+                }
+                let (mime_type, data) =
+                    process_base64(&source.data, Some(&source.media_type), config.max_dimension, config)?;
+                source.media_type = mime_type;
+                source.data = data;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 在阻塞线程池中执行预处理 (解码、缩放、重新编码均为 CPU 密集操作，不能占用异步运行时的工作线程)
+/// 消息在处理期间移入阻塞任务，完成后写回
+pub async fn preprocess_images_blocking<T: Send + 'static>(
+    messages: &mut Vec<T>,
+    config: &ImagePreprocessConfig,
+    preprocess: fn(&mut [T], &ImagePreprocessConfig) -> Result<(), String>,
+) -> Result<(), String> {
+    if !config.enabled {
+        return Ok(());
+    }
+    let mut owned = std::mem::take(messages);
+    let config = config.clone();
+    let (owned, result) = tokio::task::spawn_blocking(move || {
+        let result = preprocess(&mut owned, &config);
+        (owned, result)
+    })
+    .await
+    .map_err(|e| format!("图像预处理任务失败: {}", e))?;
+    *messages = owned;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(img: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut buf = Cursor::new(Vec::new());
+        img.write_to(&mut buf, format).unwrap();
+        buf.into_inner()
+    }
+
+    #[test]
+    fn test_process_image_resize_and_mime() {
+        let config = ImagePreprocessConfig::default();
+
+        // 小图原样保留，但 MIME 以实际内容为准
+        let small = encode(&DynamicImage::new_rgb8(16, 16), ImageFormat::Png);
+        let out = process_image(&small, Some("image/jpeg"), 2048, &config).unwrap();
+        assert_eq!(out.mime_type, "image/png");
+        assert_eq!(out.data, small);
+
+        // 超过上限时等比缩放
+        let large = encode(&DynamicImage::new_rgb8(1200, 600), ImageFormat::Png);
+        let out = process_image(&large, None, 512, &config).unwrap();
+        assert_eq!(out.mime_type, "image/jpeg");
+        let resized = image::load_from_memory(&out.data).unwrap();
+        assert_eq!((resized.width(), resized.height()), (512, 256));
+
+        // 透明图保留 PNG
+        let alpha = encode(&DynamicImage::new_rgba8(1000, 1000), ImageFormat::Png);
+        assert_eq!(process_image(&alpha, None, 100, &config).unwrap().mime_type, "image/png");
+
+        // 非原生格式转码
+        let bmp = encode(&DynamicImage::new_rgb8(8, 8), ImageFormat::Bmp);
+        assert_eq!(process_image(&bmp, Some("image/bmp"), 2048, &config).unwrap().mime_type, "image/jpeg");
+
+        assert!(process_image(b"not an image", Some("image/png"), 2048, &config).is_err());
+        assert!(process_image(b"heic-bytes", Some("image/heic"), 2048, &config).is_ok());
+    }
+
+    #[test]
+    fn test_preprocess_openai_detail_low() {
+        let config = ImagePreprocessConfig::default();
+        let png = encode(&DynamicImage::new_rgb8(1024, 1024), ImageFormat::Png);
+        let url = format!(
+            "data:image/jpeg;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(&png)
+        );
+        let mut messages: Vec<crate::proxy::mappers::openai::OpenAIMessage> = serde_json::from_value(serde_json::json!([{
+            "role": "user",
+            "content": [{ "type": "image_url", "image_url": { "url": url, "detail": "low" } }]
+        }]))
+        .unwrap();
+
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(preprocess_images_blocking(&mut messages, &config, preprocess_openai_images))
+            .unwrap();
+        let value = serde_json::to_value(&messages).unwrap();
+        let out_url = value[0]["content"][0]["image_url"]["url"].as_str().unwrap();
+        let (header, data) = out_url.split_once(',').unwrap();
+        assert_eq!(header, "data:image/jpeg;base64");
+        let bytes = base64::engine::general_purpose::STANDARD.decode(data).unwrap();
+        assert_eq!(image::load_from_memory(&bytes).unwrap().width(), config.low_detail_dimension);
+
+        assert_eq!(mime_from_extension("https://x.com/a.PNG?size=2"), Some("image/png"));
+        assert_eq!(mime_from_extension("https://x.com/photo"), None);
+    }
+}
@@ -0,0 +1,171 @@
+// OpenAI Embeddings ↔ Gemini batchEmbedContents 转换
+
+use base64::Engine as _;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// 单次 batchEmbedContents 最多包含的条目数 (Gemini 限制)
+pub const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    /// 字符串或字符串数组 (token 数组暂不支持)
+    pub input: Value,
+    #[serde(default)]
+    pub dimensions: Option<u32>,
+    /// float (默认) / base64
+    #[serde(default)]
+    pub encoding_format: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+}
+
+impl EmbeddingRequest {
+    pub fn wants_base64(&self) -> bool {
+        self.encoding_format.as_deref() == Some("base64")
+    }
+}
+
+/// 解析 input: 字符串 -> 单条；字符串数组 -> 批量
+pub fn parse_embedding_input(input: &Value) -> Result<Vec<String>, String> {
+    let texts = match input {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(arr) if arr.iter().all(|v| v.is_string()) => arr
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect(),
+        Value::Array(_) => {
+            return Err("Token array input is not supported, please send strings".to_string())
+        }
+        _ => return Err("'input' must be a string or an array of strings".to_string()),
+    };
+
+    if texts.is_empty() {
+        return Err("'input' must not be empty".to_string());
+    }
+    if texts.iter().any(|t| t.is_empty()) {
+        return Err("'input' must not contain empty strings".to_string());
+    }
+    Ok(texts)
+}
+
+/// 构建 Gemini batchEmbedContents 请求体
+pub fn build_batch_embed_request(texts: &[String], mapped_model: &str, dimensions: Option<u32>) -> Value {
+    let requests: Vec<Value> = texts
+        .iter()
+        .map(|text| {
+            let mut req = json!({
+                "model": format!("models/{}", mapped_model),
+                "content": { "parts": [{ "text": text }] }
+            });
+            if let Some(dim) = dimensions {
+                req["outputDimensionality"] = json!(dim);
+            }
+            req
+        })
+        .collect();
+    json!({ "requests": requests })
+}
+
+/// 从 Gemini 响应中提取向量 (兼容 embedContent 与 batchEmbedContents)
+pub fn extract_embeddings(gemini_resp: &Value) -> Result<Vec<Vec<f32>>, String> {
+    let to_vec = |e: &Value| -> Option<Vec<f32>> {
+        e.get("values")?
+            .as_array()?
+            .iter()
+            .map(|v| v.as_f64().map(|f| f as f32))
+            .collect()
+    };
+
+    if let Some(list) = gemini_resp.get("embeddings").and_then(|v| v.as_array()) {
+        return list
+            .iter()
+            .map(|e| to_vec(e).ok_or_else(|| "Invalid embedding values in upstream response".to_string()))
+            .collect();
+    }
+    if let Some(single) = gemini_resp.get("embedding") {
+        return to_vec(single)
+            .map(|v| vec![v])
+            .ok_or_else(|| "Invalid embedding values in upstream response".to_string());
+    }
+    Err("Upstream response contains no embeddings".to_string())
+}
+
+/// 小端 f32 字节序列的 base64 (与 OpenAI encoding_format=base64 一致)
+pub fn encode_embedding_base64(values: &[f32]) -> String {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// 构建 OpenAI 格式的 embeddings 响应
+/// Gemini 不返回 token 统计，usage 按字符数粗略估算
+pub fn build_openai_embedding_response(
+    model: &str,
+    texts: &[String],
+    embeddings: Vec<Vec<f32>>,
+    base64: bool,
+) -> Value {
+    let data: Vec<Value> = embeddings
+        .iter()
+        .enumerate()
+        .map(|(index, values)| {
+            let embedding = if base64 {
+                json!(encode_embedding_base64(values))
+            } else {
+                json!(values)
+            };
+            json!({ "object": "embedding", "index": index, "embedding": embedding })
+        })
+        .collect();
+
+    let prompt_tokens: usize = texts.iter().map(|t| t.chars().count().div_ceil(4)).sum();
+
+    json!({
+        "object": "list",
+        "data": data,
+        "model": model,
+        "usage": { "prompt_tokens": prompt_tokens, "total_tokens": prompt_tokens }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_embedding_input() {
+        assert_eq!(parse_embedding_input(&json!("hello")).unwrap(), vec!["hello"]);
+        assert_eq!(parse_embedding_input(&json!(["a", "b"])).unwrap().len(), 2);
+        assert!(parse_embedding_input(&json!([1, 2, 3])).is_err());
+        assert!(parse_embedding_input(&json!([])).is_err());
+        assert!(parse_embedding_input(&json!(42)).is_err());
+    }
+
+    #[test]
+    fn test_batch_request_and_response() {
+        let texts = vec!["a".to_string(), "bb".to_string()];
+        let req = build_batch_embed_request(&texts, "gemini-embedding-001", Some(256));
+        assert_eq!(req["requests"][1]["model"], "models/gemini-embedding-001");
+        assert_eq!(req["requests"][1]["content"]["parts"][0]["text"], "bb");
+        assert_eq!(req["requests"][0]["outputDimensionality"], 256);
+
+        let upstream = json!({ "embeddings": [{ "values": [0.5, -1.0] }, { "values": [0.25, 2.0] }] });
+        let embeddings = extract_embeddings(&upstream).unwrap();
+        assert_eq!(embeddings[1], vec![0.25, 2.0]);
+
+        let resp = build_openai_embedding_response("text-embedding-3-small", &texts, embeddings.clone(), false);
+        assert_eq!(resp["data"][0]["embedding"][1], -1.0);
+        assert_eq!(resp["data"][1]["index"], 1);
+
+        let resp = build_openai_embedding_response("text-embedding-3-small", &texts, embeddings, true);
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(resp["data"][0]["embedding"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(decoded.len(), 8);
+        assert_eq!(f32::from_le_bytes(decoded[4..8].try_into().unwrap()), -1.0);
+
+        let single = json!({ "embedding": { "values": [1.0] } });
+        assert_eq!(extract_embeddings(&single).unwrap(), vec![vec![1.0]]);
+    }
+}
@@ -6,6 +6,9 @@ pub mod request;
 pub mod response;
 pub mod streaming;
 pub mod collector;
+pub mod structured;
+pub mod responses;
+pub mod embeddings;
 
 pub use models::*;
 pub use request::*;
@@ -27,6 +27,12 @@ pub struct OpenAIRequest {
     pub tool_choice: Option<Value>,
     #[serde(rename = "parallel_tool_calls")]
     pub parallel_tool_calls: Option<bool>,
+    /// 推理强度: minimal / low / medium / high
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+    /// [扩展] 显式推理预算 (tokens)，0 表示关闭，优先于 reasoning_effort
+    #[serde(default)]
+    pub thinking_budget: Option<u32>,
     // Codex proprietary fields
     pub instructions: Option<String>,
     pub input: Option<Value>,
@@ -35,6 +41,33 @@ pub struct OpenAIRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponseFormat {
     pub r#type: String,
+    /// Structured Outputs: {"type": "json_schema", "json_schema": {...}}
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<JsonSchemaFormat>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonSchemaFormat {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+impl ResponseFormat {
+    /// 返回需要严格校验的原始 Schema (仅 json_schema + strict: true)
+    pub fn strict_schema(&self) -> Option<&Value> {
+        let json_schema = self.json_schema.as_ref()?;
+        if self.r#type == "json_schema" && json_schema.strict.unwrap_or(false) {
+            json_schema.schema.as_ref()
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -59,6 +92,19 @@ pub enum OpenAIContentBlock {
     AudioUrl {
         audio_url: AudioUrlContent,
     },
+    #[serde(rename = "input_audio")]
+    InputAudio {
+        input_audio: InputAudioContent,
+    },
+    #[serde(rename = "file")]
+    File {
+        file: OpenAIFileContent,
+    },
+    /// 扩展: 视频输入 (data URL / http(s) / 本地文件)
+    #[serde(rename = "video_url")]
+    VideoUrl {
+        video_url: VideoUrlContent,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -73,6 +119,30 @@ pub struct AudioUrlContent {
     pub url: String,
 }
 
+/// OpenAI file: file_data 为 data URL 或纯 base64 (PDF / 文本)
+/// 本代理没有文件存储，file_id 会被拒绝
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OpenAIFileContent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VideoUrlContent {
+    pub url: String,
+}
+
+/// OpenAI input_audio: base64 音频数据 + 格式 (wav / mp3 等)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InputAudioContent {
+    pub data: String,
+    pub format: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenAIMessage {
     pub role: String,
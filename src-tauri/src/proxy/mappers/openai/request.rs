@@ -2,8 +2,14 @@
 use super::models::*;
 use serde_json::{json, Value};
 use super::streaming::get_thought_signature;
-
-pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mapped_model: &str) -> Value {
+use crate::proxy::mappers::image_preprocess::mime_from_extension;
+
+pub fn transform_openai_request(
+    request: &OpenAIRequest,
+    project_id: &str,
+    mapped_model: &str,
+    thinking_config: &crate::proxy::config::ThinkingBudgetConfig,
+) -> Value {
     // 将 OpenAI 工具转为 Value 数组以便探测
     let tools_val = request.tools.as_ref().map(|list| {
         list.iter().map(|v| v.clone()).collect::<Vec<_>>()
@@ -94,53 +100,43 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                                             }));
                                         }
                                     } else if image_url.url.starts_with("http") {
+                                        // 启用媒体下载时已由 MediaFetcher 转为 data URL，此处仅为禁用时的回退
+                                        // 按扩展名推断 MIME，无法推断时沿用 image/jpeg
+                                        let mime_type = mime_from_extension(&image_url.url).unwrap_or("image/jpeg");
                                         parts.push(json!({
-                                            "fileData": { "fileUri": &image_url.url, "mimeType": "image/jpeg" }
+                                            "fileData": { "fileUri": &image_url.url, "mimeType": mime_type }
                                         }));
                                     } else {
-                                        // [NEW] 处理本地文件路径 (file:// 或 Windows/Unix 路径)
-                                        let file_path = if image_url.url.starts_with("file://") {
-                                            // 移除 file:// 前缀
-                                            #[cfg(target_os = "windows")]
-                                            { image_url.url.trim_start_matches("file:///").replace('/', "\\") }
-                                            #[cfg(not(target_os = "windows"))]
-                                            { image_url.url.trim_start_matches("file://").to_string() }
-                                        } else {
-                                            image_url.url.clone()
-                                        };
-                                        
-                                        tracing::debug!("[OpenAI-Request] Reading local image: {}", file_path);
-                                        
-                                        // 读取文件并转换为 base64
-                                        if let Ok(file_bytes) = std::fs::read(&file_path) {
-                                            use base64::Engine as _;
-                                            let b64 = base64::engine::general_purpose::STANDARD.encode(&file_bytes);
-                                            
-                                            // 根据文件扩展名推断 MIME 类型
-                                            let mime_type = if file_path.to_lowercase().ends_with(".png") {
-                                                "image/png"
-                                            } else if file_path.to_lowercase().ends_with(".gif") {
-                                                "image/gif"
-                                            } else if file_path.to_lowercase().ends_with(".webp") {
-                                                "image/webp"
-                                            } else {
-                                                "image/jpeg"
-                                            };
-                                            
-                                            parts.push(json!({
-                                                "inlineData": { "mimeType": mime_type, "data": b64 }
-                                            }));
-                                            tracing::debug!("[OpenAI-Request] Successfully loaded image: {} ({} bytes)", file_path, file_bytes.len());
-                                        } else {
-                                            tracing::debug!("[OpenAI-Request] Failed to read local image: {}", file_path);
-                                        }
+                                        // 本地文件 (file:// 或本地路径) 只能经 handler 按本地文件访问策略内联
+                                        // (MediaFetcher::inline_openai_media)，mapper 不直接读取服务端文件系统
+                                        tracing::warn!("[OpenAI-Request] Skipping non-inlined local image: {}", image_url.url);
+                                    }
+                                }
+                                OpenAIContentBlock::AudioUrl { audio_url } => {
+                                    // 远程 URL 已由 handler 预先下载为 data URL (MediaFetcher::inline_openai_media)
+                                    match audio_url_to_part(&audio_url.url) {
+                                        Ok(part) => parts.push(part),
+                                        Err(e) => tracing::warn!("[OpenAI-Request] Skipping audio_url: {}", e),
+                                    }
+                                }
+                                OpenAIContentBlock::InputAudio { input_audio } => {
+                                    match input_audio_to_part(input_audio) {
+                                        Ok(part) => parts.push(part),
+                                        Err(e) => tracing::warn!("[OpenAI-Request] Skipping input_audio: {}", e),
                                     }
                                 }
-                                OpenAIContentBlock::AudioUrl { audio_url: _ } => {
-                                    // [PR #311 部分合并] 暂时跳过 audio_url 处理
-                                    // 完整实现需要下载音频文件并转换为 Gemini inlineData 格式
-                                    // 这会与 v3.3.16 的 thinkingConfig 逻辑冲突，留待后续版本实现
-                                    tracing::debug!("[OpenAI-Request] Skipping audio_url (not yet implemented in v3.3.16)");
+                                OpenAIContentBlock::File { file } => {
+                                    // file_data 已由 handler 校验并规范化为 data URL (MediaFetcher::inline_openai_media)
+                                    match file_to_part(file) {
+                                        Ok(part) => parts.push(part),
+                                        Err(e) => tracing::warn!("[OpenAI-Request] Skipping file: {}", e),
+                                    }
+                                }
+                                OpenAIContentBlock::VideoUrl { video_url } => {
+                                    match video_url_to_part(&video_url.url) {
+                                        Ok(part) => parts.push(part),
+                                        Err(e) => tracing::warn!("[OpenAI-Request] Skipping video_url: {}", e),
+                                    }
                                 }
                             }
                         }
@@ -218,10 +214,6 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     let contents = merged_contents;
 
     // 3. 构建请求体
-    // [FIX PR #368] 检测 Gemini 3 Pro thinking 模型，注入 thinkingBudget 配置
-    let is_gemini_3_thinking = mapped_model.contains("gemini-3") && 
-        (mapped_model.ends_with("-high") || mapped_model.ends_with("-low") || mapped_model.contains("-pro"));
-
     let mut gen_config = json!({
         "maxOutputTokens": request.max_tokens.unwrap_or(64000),
         "temperature": request.temperature.unwrap_or(1.0),
@@ -233,13 +225,18 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
         gen_config["candidateCount"] = json!(n);
     }
 
-    // [FIX PR #368] 为 Gemini 3 Pro 注入 thinkingConfig (使用 thinkingBudget 而非 thinkingLevel)
-    if is_gemini_3_thinking {
-        gen_config["thinkingConfig"] = json!({
-            "includeThoughts": true,
-            "thinkingBudget": 16000
-        });
-        tracing::debug!("[OpenAI-Request] Injected thinkingConfig for Gemini 3 Pro: thinkingBudget=16000");
+    // [FIX PR #368] 注入 thinkingConfig (使用 thinkingBudget 而非 thinkingLevel)
+    // 预算来源: thinking_budget > reasoning_effort > 配置的模型默认值
+    if let Some(budget) = crate::proxy::common::thinking::resolve_thinking_budget(
+        mapped_model,
+        request.reasoning_effort.as_deref(),
+        request.thinking_budget,
+        thinking_config,
+    ) {
+        if let Some(thinking) = build_thinking_config(mapped_model, budget, &gen_config) {
+            tracing::debug!("[OpenAI-Request] Injected thinkingConfig for {}: {}", mapped_model, thinking);
+            gen_config["thinkingConfig"] = thinking;
+        }
     }
 
 
@@ -251,19 +248,25 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     if let Some(fmt) = &request.response_format {
         if fmt.r#type == "json_object" {
             gen_config["responseMimeType"] = json!("application/json");
+        } else if fmt.r#type == "json_schema" {
+            gen_config["responseMimeType"] = json!("application/json");
+            // [NEW] Structured Outputs: json_schema -> responseSchema (复用工具 Schema 清洗逻辑)
+            if let Some(schema) = fmt.json_schema.as_ref().and_then(|s| s.schema.as_ref()) {
+                let mut response_schema = schema.clone();
+                crate::proxy::common::json_schema::clean_json_schema(&mut response_schema);
+                enforce_uppercase_types(&mut response_schema);
+                gen_config["responseSchema"] = response_schema;
+            }
         }
     }
 
     let mut inner_request = json!({
         "contents": contents,
         "generationConfig": gen_config,
-        "safetySettings": [
-            { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" },
-        ]
+        // 与 Claude 路径一致: 默认 OFF，可通过 GEMINI_SAFETY_THRESHOLD 或参数策略调整
+        "safetySettings": crate::proxy::mappers::common_utils::build_safety_settings(
+            crate::proxy::mappers::common_utils::SafetyThreshold::from_env()
+        ),
     });
 
     // 深度清理 [undefined] 字符串 (Cherry Studio 等客户端常见注入)
@@ -327,6 +330,13 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
         
         if !function_declarations.is_empty() {
             inner_request["tools"] = json!([{ "functionDeclarations": function_declarations }]);
+
+            // [NEW] tool_choice -> toolConfig.functionCallingConfig
+            if let Some((mode, allowed_names)) = resolve_tool_choice(&request.tool_choice) {
+                tracing::debug!("[OpenAI-Request] tool_choice mapped to mode={}, allowed={:?}", mode, allowed_names);
+                inner_request["toolConfig"] =
+                    crate::proxy::mappers::common_utils::build_tool_config(mode, &allowed_names);
+            }
         }
     }
     
@@ -352,6 +362,11 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
         parts.push(json!({"text": inst}));
     }
 
+    // 3. parallel_tool_calls=false: Gemini 无对应开关，通过指令约束单次只调用一个工具
+    if request.parallel_tool_calls == Some(false) && inner_request.get("tools").is_some() {
+        parts.push(json!({"text": crate::proxy::mappers::common_utils::SINGLE_TOOL_CALL_INSTRUCTION}));
+    }
+
     inner_request["systemInstruction"] = json!({ 
         "role": "user",
         "parts": parts 
@@ -364,11 +379,13 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     if let Some(image_config) = config.image_config {
          if let Some(obj) = inner_request.as_object_mut() {
              obj.remove("tools");
+             obj.remove("toolConfig");
              obj.remove("systemInstruction");
              let gen_config = obj.entry("generationConfig").or_insert_with(|| json!({}));
              if let Some(gen_obj) = gen_config.as_object_mut() {
                  gen_obj.remove("thinkingConfig");
                  gen_obj.remove("responseMimeType"); 
+                 gen_obj.remove("responseSchema");
                  gen_obj.remove("responseModalities");
                  gen_obj.insert("imageConfig".to_string(), image_config);
              }
@@ -385,6 +402,109 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     })
 }
 
+/// 解析 OpenAI tool_choice，返回 Gemini functionCallingConfig 的 (mode, allowedFunctionNames)
+/// - "none" -> NONE
+/// - "auto" -> AUTO
+/// - "required" -> ANY
+/// - {"type":"function","function":{"name":...}} -> ANY + [name]
+fn resolve_tool_choice(tool_choice: &Option<Value>) -> Option<(&'static str, Vec<String>)> {
+    let choice = tool_choice.as_ref()?;
+
+    if let Some(s) = choice.as_str() {
+        return match s {
+            "none" => Some(("NONE", Vec::new())),
+            "auto" => Some(("AUTO", Vec::new())),
+            "required" | "any" => Some(("ANY", Vec::new())),
+            _ => None,
+        };
+    }
+
+    // 兼容 Chat Completions ({"function": {"name"}}) 与 Responses API ({"name"}) 两种写法
+    let name = choice
+        .get("function")
+        .and_then(|f| f.get("name"))
+        .or_else(|| choice.get("name"))
+        .and_then(|v| v.as_str())?;
+    let final_name = if name == "local_shell_call" { "shell" } else { name };
+    Some(("ANY", vec![final_name.to_string()]))
+}
+
+/// 根据目标模型构建 thinkingConfig
+/// - Gemini: 预算 0 显式关闭 (includeThoughts=false)
+/// - Claude: 关闭时不注入；预算须小于 maxOutputTokens 且不低于 1024，否则不开启思考
+fn build_thinking_config(mapped_model: &str, budget: u32, gen_config: &Value) -> Option<Value> {
+    if !mapped_model.starts_with("claude-") {
+        return Some(json!({
+            "includeThoughts": budget > 0,
+            "thinkingBudget": budget
+        }));
+    }
+
+    let max_output = gen_config["maxOutputTokens"].as_u64().unwrap_or(64000) as u32;
+    let budget = budget.min(max_output.saturating_sub(1));
+    if budget < 1024 {
+        return None;
+    }
+    Some(json!({
+        "includeThoughts": true,
+        "thinkingBudget": budget
+    }))
+}
+
+/// audio_url (data URL) -> Gemini inlineData
+fn audio_url_to_part(url: &str) -> Result<Value, String> {
+    let rest = url.strip_prefix("data:").ok_or_else(|| format!("unsupported audio URL: {}", url))?;
+    let (meta, data) = rest.split_once(',').ok_or("malformed data URL")?;
+    let mime_type = meta.split(';').next().filter(|m| !m.is_empty()).unwrap_or("audio/mp3");
+    Ok(json!({ "inlineData": { "mimeType": mime_type, "data": data } }))
+}
+
+/// input_audio (base64 + format) -> Gemini inlineData
+fn input_audio_to_part(input_audio: &InputAudioContent) -> Result<Value, String> {
+    let mime_type = crate::proxy::audio::AudioProcessor::mime_from_format(&input_audio.format)?;
+    Ok(json!({ "inlineData": { "mimeType": mime_type, "data": input_audio.data } }))
+}
+
+/// file (data URL) -> Gemini part
+/// 文本文件直接展开为 text part (附文件名)，PDF 等二进制文件转为 inlineData
+fn file_to_part(file: &OpenAIFileContent) -> Result<Value, String> {
+    use base64::Engine as _;
+
+    let url = file.file_data.as_deref().ok_or("missing file_data")?;
+    let rest = url.strip_prefix("data:").ok_or("file_data is not a data URL")?;
+    let (meta, data) = rest.split_once(',').ok_or("malformed data URL")?;
+    let mime_type = meta.split(';').next().filter(|m| !m.is_empty()).unwrap_or("application/pdf");
+
+    if mime_type.starts_with("text/") {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| format!("invalid base64: {}", e))?;
+        let text = String::from_utf8_lossy(&bytes);
+        let text = match &file.filename {
+            Some(name) => format!("[File: {}]\n{}", name, text),
+            None => text.into_owned(),
+        };
+        return Ok(json!({ "text": text }));
+    }
+    Ok(json!({ "inlineData": { "mimeType": mime_type, "data": data } }))
+}
+
+/// video_url -> Gemini part (data URL 内联; http(s) 仅在禁用媒体下载时回退为 fileData)
+fn video_url_to_part(url: &str) -> Result<Value, String> {
+    if let Some(rest) = url.strip_prefix("data:") {
+        let (meta, data) = rest.split_once(',').ok_or("malformed data URL")?;
+        let mime_type = meta.split(';').next().filter(|m| !m.is_empty()).unwrap_or("video/mp4");
+        return Ok(json!({ "inlineData": { "mimeType": mime_type, "data": data } }));
+    }
+    if url.starts_with("http://") || url.starts_with("https://") {
+        let mime_type = crate::proxy::media_fetcher::media_mime_from_extension(url)
+            .filter(|m| m.starts_with("video/"))
+            .unwrap_or("video/mp4");
+        return Ok(json!({ "fileData": { "fileUri": url, "mimeType": mime_type } }));
+    }
+    Err(format!("non-inlined local video: {}", url))
+}
+
 fn enforce_uppercase_types(value: &mut Value) {
     if let Value::Object(map) = value {
         if let Some(type_val) = map.get_mut("type") {
@@ -441,15 +561,150 @@ mod tests {
             tools: None,
             tool_choice: None,
             parallel_tool_calls: None,
+            reasoning_effort: None,
+            thinking_budget: None,
             instructions: None,
             input: None,
             prompt: None,
         };
 
-        let result = transform_openai_request(&req, "test-v", "gemini-1.5-flash");
+        let result = transform_openai_request(&req, "test-v", "gemini-1.5-flash", &Default::default());
         let parts = &result["request"]["contents"][0]["parts"];
         assert_eq!(parts.as_array().unwrap().len(), 2);
         assert_eq!(parts[0]["text"].as_str().unwrap(), "What is in this image?");
         assert_eq!(parts[1]["inlineData"]["mimeType"].as_str().unwrap(), "image/png");
     }
+
+    #[test]
+    fn test_audio_content_blocks() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "Transcribe both" },
+                    { "type": "input_audio", "input_audio": { "data": "UklGRg==", "format": "wav" } },
+                    { "type": "audio_url", "audio_url": { "url": "data:audio/ogg;base64,T2dnUw==" } }
+                ]
+            }]
+        })).unwrap();
+
+        let result = transform_openai_request(&req, "test-v", "gemini-2.5-flash", &Default::default());
+        let parts = &result["request"]["contents"][0]["parts"];
+        assert_eq!(parts.as_array().unwrap().len(), 3);
+        assert_eq!(parts[1]["inlineData"]["mimeType"], "audio/wav");
+        assert_eq!(parts[1]["inlineData"]["data"], "UklGRg==");
+        assert_eq!(parts[2]["inlineData"]["mimeType"], "audio/ogg");
+        assert_eq!(parts[2]["inlineData"]["data"], "T2dnUw==");
+    }
+
+    #[test]
+    fn test_file_and_video_content_blocks() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "file", "file": { "file_data": "data:application/pdf;base64,JVBERi0xLjc=", "filename": "a.pdf" } },
+                    { "type": "file", "file": { "file_data": "data:text/plain;base64,aGVsbG8=", "filename": "notes.txt" } },
+                    { "type": "video_url", "video_url": { "url": "data:video/mp4;base64,AAAAGGZ0eXBpc29t" } }
+                ]
+            }]
+        })).unwrap();
+
+        let result = transform_openai_request(&req, "test-v", "gemini-2.5-flash", &Default::default());
+        let parts = &result["request"]["contents"][0]["parts"];
+        assert_eq!(parts.as_array().unwrap().len(), 3);
+        assert_eq!(parts[0]["inlineData"]["mimeType"], "application/pdf");
+        assert_eq!(parts[1]["text"], "[File: notes.txt]\nhello");
+        assert_eq!(parts[2]["inlineData"]["mimeType"], "video/mp4");
+    }
+
+    #[test]
+    fn test_tool_choice_mapping() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{ "role": "user", "content": "weather?" }],
+            "tools": [{
+                "type": "function",
+                "function": { "name": "get_weather", "parameters": { "type": "object", "properties": {} } }
+            }],
+            "tool_choice": { "type": "function", "function": { "name": "get_weather" } },
+            "parallel_tool_calls": false
+        })).unwrap();
+
+        let result = transform_openai_request(&req, "test-v", "gemini-2.5-flash", &Default::default());
+        let fcc = &result["request"]["toolConfig"]["functionCallingConfig"];
+        assert_eq!(fcc["mode"], "ANY");
+        assert_eq!(fcc["allowedFunctionNames"][0], "get_weather");
+
+        let sys_parts = result["request"]["systemInstruction"]["parts"].as_array().unwrap();
+        assert!(sys_parts.iter().any(|p| p["text"] == crate::proxy::mappers::common_utils::SINGLE_TOOL_CALL_INSTRUCTION));
+
+        assert_eq!(resolve_tool_choice(&Some(json!("none"))), Some(("NONE", vec![])));
+        assert_eq!(resolve_tool_choice(&Some(json!("required"))), Some(("ANY", vec![])));
+        assert_eq!(resolve_tool_choice(&None), None);
+    }
+
+    #[test]
+    fn test_reasoning_effort_mapping() {
+        let make_req = |extra: Value| -> OpenAIRequest {
+            let mut body = json!({
+                "model": "gpt-4o",
+                "messages": [{ "role": "user", "content": "Hi" }]
+            });
+            for (k, v) in extra.as_object().unwrap() {
+                body[k] = v.clone();
+            }
+            serde_json::from_value(body).unwrap()
+        };
+        let config = crate::proxy::config::ThinkingBudgetConfig::default();
+
+        // Gemini: effort -> 预算, minimal 关闭思考
+        let result = transform_openai_request(&make_req(json!({ "reasoning_effort": "medium" })), "test-v", "gemini-2.5-flash", &config);
+        assert_eq!(result["request"]["generationConfig"]["thinkingConfig"]["thinkingBudget"], 8192);
+        let result = transform_openai_request(&make_req(json!({ "reasoning_effort": "minimal" })), "test-v", "gemini-2.5-flash", &config);
+        assert_eq!(result["request"]["generationConfig"]["thinkingConfig"]["thinkingBudget"], 0);
+        assert_eq!(result["request"]["generationConfig"]["thinkingConfig"]["includeThoughts"], false);
+
+        // 未指定时沿用配置默认值
+        let result = transform_openai_request(&make_req(json!({})), "test-v", "gemini-3-pro-high", &config);
+        assert_eq!(result["request"]["generationConfig"]["thinkingConfig"]["thinkingBudget"], 16000);
+
+        // Claude: 预算须小于 max_tokens，关闭时不注入
+        let result = transform_openai_request(&make_req(json!({ "thinking_budget": 30000, "max_tokens": 8000 })), "test-v", "claude-sonnet-4-5-thinking", &config);
+        assert_eq!(result["request"]["generationConfig"]["thinkingConfig"]["thinkingBudget"], 7999);
+        let result = transform_openai_request(&make_req(json!({ "thinking_budget": 0 })), "test-v", "claude-sonnet-4-5-thinking", &config);
+        assert!(result["request"]["generationConfig"].get("thinkingConfig").is_none());
+    }
+
+    #[test]
+    fn test_response_format_json_schema() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "Give me a person" }],
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "person",
+                    "strict": true,
+                    "schema": {
+                        "type": "object",
+                        "properties": { "name": { "type": "string" } },
+                        "required": ["name"],
+                        "additionalProperties": false
+                    }
+                }
+            }
+        })).unwrap();
+
+        assert!(req.response_format.as_ref().unwrap().strict_schema().is_some());
+
+        let result = transform_openai_request(&req, "test-v", "gemini-2.5-flash", &Default::default());
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+        assert_eq!(gen_config["responseSchema"]["type"], "OBJECT");
+        assert_eq!(gen_config["responseSchema"]["properties"]["name"]["type"], "STRING");
+        assert!(gen_config["responseSchema"].get("additionalProperties").is_none());
+    }
 }
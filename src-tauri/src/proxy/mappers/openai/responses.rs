@@ -0,0 +1,1095 @@
+// OpenAI Responses API (/v1/responses) ↔ Gemini 转换
+// 输入 items 先转换为 Chat 消息，复用 transform_openai_request；
+// 输出由 ResponsesStreamState 将 Gemini SSE 转换为 Responses 事件流，并同时累积最终的 response 对象。
+
+use super::models::*;
+use super::streaming::store_thought_signature;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+
+/// Responses API 请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResponsesRequest {
+    pub model: String,
+    /// 字符串或 item 数组
+    #[serde(default)]
+    pub input: Option<Value>,
+    #[serde(default)]
+    pub instructions: Option<String>,
+    #[serde(default)]
+    pub previous_response_id: Option<String>,
+    #[serde(default)]
+    pub stream: bool,
+    /// 是否保存到本地响应存储 (默认 true)
+    #[serde(default)]
+    pub store: Option<bool>,
+    #[serde(default)]
+    pub tools: Option<Vec<Value>>,
+    #[serde(default)]
+    pub tool_choice: Option<Value>,
+    #[serde(default)]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(default)]
+    pub max_output_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub reasoning: Option<ReasoningParams>,
+    #[serde(default)]
+    pub text: Option<TextParams>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReasoningParams {
+    #[serde(default)]
+    pub effort: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TextParams {
+    /// {"type": "text"} / {"type": "json_object"} / {"type": "json_schema", "name", "schema", "strict"}
+    #[serde(default)]
+    pub format: Option<Value>,
+}
+
+/// 输入/输出 item (Responses API 允许将上一轮的输出 item 原样作为输入)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseItem {
+    Message {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        role: String,
+        content: ItemContent,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<String>,
+    },
+    FunctionCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        call_id: String,
+        name: String,
+        #[serde(default)]
+        arguments: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<String>,
+    },
+    FunctionCallOutput {
+        call_id: String,
+        output: Value,
+    },
+    CustomToolCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        call_id: String,
+        name: String,
+        #[serde(default)]
+        input: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<String>,
+    },
+    CustomToolCallOutput {
+        call_id: String,
+        output: Value,
+    },
+    LocalShellCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        call_id: Option<String>,
+        action: Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<String>,
+    },
+    LocalShellCallOutput {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        call_id: Option<String>,
+        output: Value,
+    },
+    Reasoning {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default)]
+        summary: Vec<SummaryPart>,
+    },
+    /// 暂不支持的 item 类型 (忽略)
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ItemContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    InputText {
+        text: String,
+    },
+    OutputText {
+        text: String,
+        #[serde(default)]
+        annotations: Vec<Value>,
+    },
+    InputImage {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image_url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<String>,
+    },
+    InputFile {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_data: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filename: Option<String>,
+    },
+    Refusal {
+        refusal: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SummaryPart {
+    SummaryText { text: String },
+}
+
+/// 解析 input 字段: 字符串视为单条 user 消息；省略 type 的 {role, content} 视为 message
+pub fn parse_input_items(input: &Option<Value>) -> Result<Vec<ResponseItem>, String> {
+    match input {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(text)) => Ok(vec![ResponseItem::Message {
+            id: None,
+            role: "user".to_string(),
+            content: ItemContent::Text(text.clone()),
+            status: None,
+        }]),
+        Some(Value::Array(raw_items)) => {
+            let mut items = Vec::with_capacity(raw_items.len());
+            for raw in raw_items {
+                let mut raw = raw.clone();
+                if let Some(obj) = raw.as_object_mut() {
+                    if !obj.contains_key("type") && obj.contains_key("role") {
+                        obj.insert("type".to_string(), json!("message"));
+                    }
+                }
+                let item: ResponseItem = serde_json::from_value(raw)
+                    .map_err(|e| format!("Invalid input item: {}", e))?;
+                if item != ResponseItem::Unknown {
+                    items.push(item);
+                }
+            }
+            Ok(items)
+        }
+        Some(other) => Err(format!("Invalid input: expected string or array, got {}", other)),
+    }
+}
+
+/// 将 Responses 请求 (含历史 items) 转换为 Chat 请求，以复用 transform_openai_request
+pub fn build_chat_request(request: &ResponsesRequest, items: &[ResponseItem]) -> OpenAIRequest {
+    OpenAIRequest {
+        model: request.model.clone(),
+        messages: items_to_messages(request.instructions.as_deref(), items),
+        prompt: None,
+        stream: request.stream,
+        n: None,
+        max_tokens: request.max_output_tokens,
+        temperature: request.temperature,
+        top_p: request.top_p,
+        stop: None,
+        response_format: request
+            .text
+            .as_ref()
+            .and_then(|t| t.format.as_ref())
+            .and_then(text_format_to_response_format),
+        tools: normalize_tools(&request.tools),
+        tool_choice: request.tool_choice.clone(),
+        parallel_tool_calls: request.parallel_tool_calls,
+        reasoning_effort: request.reasoning.as_ref().and_then(|r| r.effort.clone()),
+        thinking_budget: None,
+        instructions: None,
+        input: None,
+    }
+}
+
+fn items_to_messages(instructions: Option<&str>, items: &[ResponseItem]) -> Vec<OpenAIMessage> {
+    let mut messages = Vec::new();
+
+    if let Some(inst) = instructions.filter(|s| !s.is_empty()) {
+        messages.push(simple_message("system", Some(OpenAIContent::String(inst.to_string()))));
+    }
+
+    // Pass 1: call_id -> 工具名 (tool 消息需要 name)
+    let mut call_names: HashMap<String, String> = HashMap::new();
+    for item in items {
+        match item {
+            ResponseItem::FunctionCall { call_id, name, .. }
+            | ResponseItem::CustomToolCall { call_id, name, .. } => {
+                call_names.insert(call_id.clone(), name.clone());
+            }
+            ResponseItem::LocalShellCall { id, call_id, .. } => {
+                if let Some(cid) = call_id.as_ref().or(id.as_ref()) {
+                    call_names.insert(cid.clone(), "shell".to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Pass 2: items -> messages
+    for item in items {
+        match item {
+            ResponseItem::Message { role, content, .. } => {
+                let role = if role == "developer" { "system" } else { role.as_str() };
+                messages.push(simple_message(role, Some(item_content_to_openai(content))));
+            }
+            ResponseItem::FunctionCall { call_id, name, arguments, .. } => {
+                let args = if arguments.is_empty() { "{}".to_string() } else { arguments.clone() };
+                push_tool_call(&mut messages, call_id, name, args);
+            }
+            ResponseItem::CustomToolCall { call_id, name, input, .. } => {
+                push_tool_call(&mut messages, call_id, name, json!({ "input": input }).to_string());
+            }
+            ResponseItem::LocalShellCall { id, call_id, action, .. } => {
+                let cid = call_id.as_ref().or(id.as_ref()).cloned().unwrap_or_default();
+                push_tool_call(&mut messages, &cid, "shell", shell_action_to_args(action));
+            }
+            ResponseItem::FunctionCallOutput { call_id, output }
+            | ResponseItem::CustomToolCallOutput { call_id, output } => {
+                messages.push(tool_message(call_id, &call_names, output));
+            }
+            ResponseItem::LocalShellCallOutput { id, call_id, output } => {
+                let cid = call_id.as_ref().or(id.as_ref()).cloned().unwrap_or_default();
+                messages.push(tool_message(&cid, &call_names, output));
+            }
+            // 思考摘要无需回传 (Gemini 签名由全局存储处理)
+            ResponseItem::Reasoning { .. } | ResponseItem::Unknown => {}
+        }
+    }
+
+    messages
+}
+
+fn simple_message(role: &str, content: Option<OpenAIContent>) -> OpenAIMessage {
+    OpenAIMessage {
+        role: role.to_string(),
+        content,
+        reasoning_content: None,
+        tool_calls: None,
+        tool_call_id: None,
+        name: None,
+    }
+}
+
+fn tool_message(call_id: &str, call_names: &HashMap<String, String>, output: &Value) -> OpenAIMessage {
+    let mut msg = simple_message("tool", Some(OpenAIContent::String(output_to_string(output))));
+    msg.tool_call_id = Some(call_id.to_string());
+    msg.name = Some(call_names.get(call_id).cloned().unwrap_or_else(|| {
+        tracing::warn!("[Responses] Unknown tool name for call_id {}, defaulting to 'shell'", call_id);
+        "shell".to_string()
+    }));
+    msg
+}
+
+/// 工具调用并入上一条 assistant 消息 (同一轮的文本与并行调用属于同一个 model turn)
+fn push_tool_call(messages: &mut Vec<OpenAIMessage>, call_id: &str, name: &str, arguments: String) {
+    let call = ToolCall {
+        id: call_id.to_string(),
+        r#type: "function".to_string(),
+        function: ToolFunction { name: name.to_string(), arguments },
+    };
+    if let Some(last) = messages.last_mut().filter(|m| m.role == "assistant") {
+        last.tool_calls.get_or_insert_with(Vec::new).push(call);
+        return;
+    }
+    let mut msg = simple_message("assistant", None);
+    msg.tool_calls = Some(vec![call]);
+    messages.push(msg);
+}
+
+fn item_content_to_openai(content: &ItemContent) -> OpenAIContent {
+    let parts = match content {
+        ItemContent::Text(text) => return OpenAIContent::String(text.clone()),
+        ItemContent::Parts(parts) => parts,
+    };
+
+    let mut blocks = Vec::new();
+    for part in parts {
+        match part {
+            ContentPart::InputText { text } | ContentPart::OutputText { text, .. } => {
+                blocks.push(OpenAIContentBlock::Text { text: text.clone() });
+            }
+            ContentPart::Refusal { refusal } => {
+                blocks.push(OpenAIContentBlock::Text { text: refusal.clone() });
+            }
+            ContentPart::InputImage { image_url: Some(url), detail } => {
+                blocks.push(OpenAIContentBlock::ImageUrl {
+                    image_url: OpenAIImageUrl { url: url.clone(), detail: detail.clone() },
+                });
+            }
+            ContentPart::InputFile { file_data, file_id, file_url, filename } => {
+                // file_url 与 file_data 中的 http(s) 链接一样由 MediaFetcher 下载
+                blocks.push(OpenAIContentBlock::File {
+                    file: OpenAIFileContent {
+                        file_data: file_data.clone().or_else(|| file_url.clone()),
+                        file_id: file_id.clone(),
+                        filename: filename.clone(),
+                    },
+                });
+            }
+            ContentPart::InputImage { image_url: None, .. } | ContentPart::Unknown => {}
+        }
+    }
+
+    // 纯文本时合并为字符串，保持与 Chat 路径一致
+    if blocks.iter().all(|b| matches!(b, OpenAIContentBlock::Text { .. })) {
+        let text = blocks
+            .iter()
+            .filter_map(|b| match b {
+                OpenAIContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        return OpenAIContent::String(text);
+    }
+    OpenAIContent::Array(blocks)
+}
+
+fn output_to_string(output: &Value) -> String {
+    match output {
+        Value::String(s) => s.clone(),
+        // 新版 SDK 允许 output 为 content parts 数组
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Object(obj) => obj
+            .get("content")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| output.to_string()),
+        _ => output.to_string(),
+    }
+}
+
+/// local_shell_call.action.exec -> shell 工具参数 (command 必须为数组)
+fn shell_action_to_args(action: &Value) -> String {
+    let exec = action.get("exec").unwrap_or(action);
+    let mut args = serde_json::Map::new();
+    if let Some(cmd) = exec.get("command") {
+        let cmd = if cmd.is_string() { json!([cmd]) } else { cmd.clone() };
+        args.insert("command".to_string(), cmd);
+    }
+    if let Some(wd) = exec.get("working_directory").or(exec.get("workdir")) {
+        args.insert("workdir".to_string(), wd.clone());
+    }
+    Value::Object(args).to_string()
+}
+
+fn text_format_to_response_format(format: &Value) -> Option<ResponseFormat> {
+    match format.get("type").and_then(|v| v.as_str())? {
+        "json_object" => Some(ResponseFormat { r#type: "json_object".to_string(), json_schema: None }),
+        "json_schema" => Some(ResponseFormat {
+            r#type: "json_schema".to_string(),
+            json_schema: Some(JsonSchemaFormat {
+                name: format.get("name").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                description: format.get("description").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                schema: format.get("schema").cloned(),
+                strict: format.get("strict").and_then(|v| v.as_bool()),
+            }),
+        }),
+        _ => None,
+    }
+}
+
+/// Responses 工具定义 -> 扁平 function 工具 (transform_openai_request 可直接处理)
+/// - function: 原样保留
+/// - local_shell: 转为 shell(command: string[], workdir) 函数
+/// - custom: 转为单个 input 字符串参数的函数
+/// - web_search(_preview): 转为 web_search 以触发 googleSearch 注入
+fn normalize_tools(tools: &Option<Vec<Value>>) -> Option<Vec<Value>> {
+    let tools = tools.as_ref()?;
+    let mut normalized = Vec::new();
+    for tool in tools {
+        match tool.get("type").and_then(|v| v.as_str()).unwrap_or("function") {
+            "function" => normalized.push(tool.clone()),
+            "local_shell" => normalized.push(json!({
+                "type": "function",
+                "name": "shell",
+                "description": "Runs a shell command and returns its output.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "command": { "type": "array", "items": { "type": "string" } },
+                        "workdir": { "type": "string" }
+                    },
+                    "required": ["command"]
+                }
+            })),
+            "custom" => normalized.push(json!({
+                "type": "function",
+                "name": tool.get("name").cloned().unwrap_or(json!("custom_tool")),
+                "description": tool.get("description").cloned().unwrap_or(json!("")),
+                "parameters": {
+                    "type": "object",
+                    "properties": { "input": { "type": "string" } },
+                    "required": ["input"]
+                }
+            })),
+            "web_search" | "web_search_preview" => {
+                normalized.push(json!({ "type": "function", "name": "web_search" }))
+            }
+            other => tracing::debug!("[Responses] Skipping unsupported tool type: {}", other),
+        }
+    }
+    Some(normalized)
+}
+
+/// 当前正在输出的 item
+enum OpenItem {
+    Text { item_id: String, text: String },
+    Reasoning { item_id: String, text: String },
+}
+
+/// Responses 流式状态机
+pub struct ResponsesStreamState {
+    response_id: String,
+    model: String,
+    created_at: u64,
+    previous_response_id: Option<String>,
+    sequence_number: u64,
+    output: Vec<ResponseItem>,
+    open_item: Option<OpenItem>,
+    local_shell: bool,
+    custom_tools: HashSet<String>,
+    emitted_calls: HashSet<String>,
+    finish_reason: Option<String>,
+    usage_metadata: Option<Value>,
+}
+
+impl ResponsesStreamState {
+    pub fn new(request: &ResponsesRequest) -> Self {
+        let tools = request.tools.as_deref().unwrap_or(&[]);
+        let tool_type = |t: &Value| t.get("type").and_then(|v| v.as_str()).map(|s| s.to_string());
+        Self {
+            response_id: format!("resp_{}", uuid::Uuid::new_v4().simple()),
+            model: request.model.clone(),
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            previous_response_id: request.previous_response_id.clone(),
+            sequence_number: 0,
+            output: Vec::new(),
+            open_item: None,
+            local_shell: tools.iter().any(|t| tool_type(t).as_deref() == Some("local_shell")),
+            custom_tools: tools
+                .iter()
+                .filter(|t| tool_type(t).as_deref() == Some("custom"))
+                .filter_map(|t| t.get("name").and_then(|v| v.as_str()).map(|s| s.to_string()))
+                .collect(),
+            emitted_calls: HashSet::new(),
+            finish_reason: None,
+            usage_metadata: None,
+        }
+    }
+
+    pub fn response_id(&self) -> &str {
+        &self.response_id
+    }
+
+    /// 已完成的输出 items
+    pub fn output_items(&self) -> &[ResponseItem] {
+        &self.output
+    }
+
+    /// 构建 SSE 事件 (自动附加 type 与 sequence_number)
+    fn event(&mut self, event_type: &str, mut data: Value) -> Bytes {
+        data["type"] = json!(event_type);
+        data["sequence_number"] = json!(self.sequence_number);
+        self.sequence_number += 1;
+        Bytes::from(format!(
+            "event: {}\ndata: {}\n\n",
+            event_type,
+            serde_json::to_string(&data).unwrap_or_default()
+        ))
+    }
+
+    /// 当前 response 对象快照
+    pub fn response(&self) -> Value {
+        let finished = self.finish_reason.is_some();
+        let incomplete = self.finish_reason.as_deref() == Some("MAX_TOKENS");
+        let status = if incomplete {
+            "incomplete"
+        } else if finished {
+            "completed"
+        } else {
+            "in_progress"
+        };
+
+        json!({
+            "id": self.response_id,
+            "object": "response",
+            "created_at": self.created_at,
+            "status": status,
+            "model": self.model,
+            "output": self.output,
+            "previous_response_id": self.previous_response_id,
+            "incomplete_details": if incomplete { json!({ "reason": "max_output_tokens" }) } else { Value::Null },
+            "error": null,
+            "usage": self.usage(),
+        })
+    }
+
+    fn usage(&self) -> Value {
+        let Some(u) = &self.usage_metadata else {
+            return Value::Null;
+        };
+        let field = |k: &str| u.get(k).and_then(|v| v.as_u64()).unwrap_or(0);
+        let reasoning_tokens = field("thoughtsTokenCount");
+        let output_tokens = field("candidatesTokenCount") + reasoning_tokens;
+        json!({
+            "input_tokens": field("promptTokenCount"),
+            "input_tokens_details": { "cached_tokens": field("cachedContentTokenCount") },
+            "output_tokens": output_tokens,
+            "output_tokens_details": { "reasoning_tokens": reasoning_tokens },
+            "total_tokens": field("totalTokenCount"),
+        })
+    }
+
+    /// response.created + response.in_progress
+    pub fn start(&mut self) -> Vec<Bytes> {
+        let response = self.response();
+        vec![
+            self.event("response.created", json!({ "response": response })),
+            self.event("response.in_progress", json!({ "response": response })),
+        ]
+    }
+
+    /// 处理一行 Gemini SSE
+    pub fn process_sse_line(&mut self, line: &str) -> Vec<Bytes> {
+        let Some(data) = line.strip_prefix("data: ").map(|s| s.trim()) else {
+            return Vec::new();
+        };
+        if data.is_empty() || data == "[DONE]" {
+            return Vec::new();
+        }
+        match serde_json::from_str::<Value>(data) {
+            Ok(mut json) => {
+                let chunk = json.get_mut("response").map(|v| v.take()).unwrap_or(json);
+                self.process_chunk(&chunk)
+            }
+            Err(e) => {
+                tracing::debug!("[Responses-SSE] JSON parse error: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// 处理一个 Gemini 响应块
+    pub fn process_chunk(&mut self, chunk: &Value) -> Vec<Bytes> {
+        let mut events = Vec::new();
+
+        if let Some(usage) = chunk.get("usageMetadata") {
+            self.usage_metadata = Some(usage.clone());
+        }
+
+        let Some(candidate) = chunk.get("candidates").and_then(|c| c.get(0)) else {
+            return events;
+        };
+
+        if let Some(parts) = candidate
+            .get("content")
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array())
+        {
+            for part in parts {
+                // 捕获 thoughtSignature (Gemini 3 工具调用必需)，存入全局状态供下一轮请求使用
+                if let Some(sig) = part
+                    .get("thoughtSignature")
+                    .or(part.get("thought_signature"))
+                    .and_then(|s| s.as_str())
+                {
+                    store_thought_signature(sig);
+                }
+
+                if let Some(fc) = part.get("functionCall") {
+                    events.extend(self.emit_function_call(fc));
+                } else if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                    if text.is_empty() {
+                        continue;
+                    }
+                    if part.get("thought").and_then(|v| v.as_bool()).unwrap_or(false) {
+                        events.extend(self.emit_reasoning_delta(text));
+                    } else {
+                        events.extend(self.emit_text_delta(text));
+                    }
+                } else if let Some(img) = part.get("inlineData") {
+                    let mime_type = img.get("mimeType").and_then(|v| v.as_str()).unwrap_or("image/png");
+                    let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
+                    if !data.is_empty() {
+                        events.extend(self.emit_text_delta(&format!("![image](data:{};base64,{})", mime_type, data)));
+                    }
+                }
+            }
+        }
+
+        if let Some(reason) = candidate.get("finishReason").and_then(|r| r.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+        }
+
+        events
+    }
+
+    fn emit_text_delta(&mut self, delta: &str) -> Vec<Bytes> {
+        let mut events = Vec::new();
+        if !matches!(self.open_item, Some(OpenItem::Text { .. })) {
+            events.extend(self.close_open_item());
+            let item_id = format!("msg_{}", uuid::Uuid::new_v4().simple());
+            let output_index = self.output.len();
+            events.push(self.event("response.output_item.added", json!({
+                "output_index": output_index,
+                "item": { "type": "message", "id": item_id, "status": "in_progress", "role": "assistant", "content": [] }
+            })));
+            events.push(self.event("response.content_part.added", json!({
+                "item_id": item_id,
+                "output_index": output_index,
+                "content_index": 0,
+                "part": { "type": "output_text", "text": "", "annotations": [] }
+            })));
+            self.open_item = Some(OpenItem::Text { item_id, text: String::new() });
+        }
+
+        let item_id = match &mut self.open_item {
+            Some(OpenItem::Text { item_id, text }) => {
+                text.push_str(delta);
+                item_id.clone()
+            }
+            _ => unreachable!(),
+        };
+        let output_index = self.output.len();
+        events.push(self.event("response.output_text.delta", json!({
+            "item_id": item_id,
+            "output_index": output_index,
+            "content_index": 0,
+            "delta": delta
+        })));
+        events
+    }
+
+    fn emit_reasoning_delta(&mut self, delta: &str) -> Vec<Bytes> {
+        let mut events = Vec::new();
+        if !matches!(self.open_item, Some(OpenItem::Reasoning { .. })) {
+            events.extend(self.close_open_item());
+            let item_id = format!("rs_{}", uuid::Uuid::new_v4().simple());
+            let output_index = self.output.len();
+            events.push(self.event("response.output_item.added", json!({
+                "output_index": output_index,
+                "item": { "type": "reasoning", "id": item_id, "summary": [] }
+            })));
+            events.push(self.event("response.reasoning_summary_part.added", json!({
+                "item_id": item_id,
+                "output_index": output_index,
+                "summary_index": 0,
+                "part": { "type": "summary_text", "text": "" }
+            })));
+            self.open_item = Some(OpenItem::Reasoning { item_id, text: String::new() });
+        }
+
+        let item_id = match &mut self.open_item {
+            Some(OpenItem::Reasoning { item_id, text }) => {
+                text.push_str(delta);
+                item_id.clone()
+            }
+            _ => unreachable!(),
+        };
+        let output_index = self.output.len();
+        events.push(self.event("response.reasoning_summary_text.delta", json!({
+            "item_id": item_id,
+            "output_index": output_index,
+            "summary_index": 0,
+            "delta": delta
+        })));
+        events
+    }
+
+    /// 结束当前文本/思考 item
+    fn close_open_item(&mut self) -> Vec<Bytes> {
+        let Some(open) = self.open_item.take() else {
+            return Vec::new();
+        };
+        let output_index = self.output.len();
+        let mut events = Vec::new();
+
+        match open {
+            OpenItem::Text { item_id, text } => {
+                let part = ContentPart::OutputText { text: text.clone(), annotations: Vec::new() };
+                events.push(self.event("response.output_text.done", json!({
+                    "item_id": item_id,
+                    "output_index": output_index,
+                    "content_index": 0,
+                    "text": text
+                })));
+                events.push(self.event("response.content_part.done", json!({
+                    "item_id": item_id,
+                    "output_index": output_index,
+                    "content_index": 0,
+                    "part": part
+                })));
+                let item = ResponseItem::Message {
+                    id: Some(item_id),
+                    role: "assistant".to_string(),
+                    content: ItemContent::Parts(vec![part]),
+                    status: Some("completed".to_string()),
+                };
+                events.push(self.event("response.output_item.done", json!({ "output_index": output_index, "item": item })));
+                self.output.push(item);
+            }
+            OpenItem::Reasoning { item_id, text } => {
+                let part = SummaryPart::SummaryText { text: text.clone() };
+                events.push(self.event("response.reasoning_summary_text.done", json!({
+                    "item_id": item_id,
+                    "output_index": output_index,
+                    "summary_index": 0,
+                    "text": text
+                })));
+                events.push(self.event("response.reasoning_summary_part.done", json!({
+                    "item_id": item_id,
+                    "output_index": output_index,
+                    "summary_index": 0,
+                    "part": part
+                })));
+                let item = ResponseItem::Reasoning { id: Some(item_id), summary: vec![part] };
+                events.push(self.event("response.output_item.done", json!({ "output_index": output_index, "item": item })));
+                self.output.push(item);
+            }
+        }
+        events
+    }
+
+    fn emit_function_call(&mut self, fc: &Value) -> Vec<Bytes> {
+        // 上游偶尔会在多个块中重复下发同一调用
+        let call_key = serde_json::to_string(fc).unwrap_or_default();
+        if !self.emitted_calls.insert(call_key) {
+            return Vec::new();
+        }
+
+        let mut events = self.close_open_item();
+        let name = fc.get("name").and_then(|v| v.as_str()).unwrap_or("unknown").to_string();
+        let args = fc.get("args").cloned().unwrap_or_else(|| json!({}));
+        let call_id = fc
+            .get("id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()));
+        let item_id = format!("fc_{}", uuid::Uuid::new_v4().simple());
+        let output_index = self.output.len();
+
+        if self.local_shell && (name == "shell" || name == "local_shell") {
+            let mut action = json!({ "type": "exec", "command": shell_command_vec(&args) });
+            if let Some(wd) = args.get("workdir").or(args.get("working_directory")) {
+                action["working_directory"] = wd.clone();
+            }
+            let item = ResponseItem::LocalShellCall {
+                id: Some(item_id),
+                call_id: Some(call_id),
+                action,
+                status: Some("completed".to_string()),
+            };
+            events.push(self.event("response.output_item.added", json!({ "output_index": output_index, "item": item })));
+            events.push(self.event("response.output_item.done", json!({ "output_index": output_index, "item": item })));
+            self.output.push(item);
+            return events;
+        }
+
+        if self.custom_tools.contains(&name) {
+            let input = args
+                .get("input")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| args.to_string());
+            events.push(self.event("response.output_item.added", json!({
+                "output_index": output_index,
+                "item": { "type": "custom_tool_call", "id": item_id, "call_id": call_id, "name": name, "input": "", "status": "in_progress" }
+            })));
+            events.push(self.event("response.custom_tool_call_input.delta", json!({
+                "item_id": item_id,
+                "output_index": output_index,
+                "delta": input
+            })));
+            events.push(self.event("response.custom_tool_call_input.done", json!({
+                "item_id": item_id,
+                "output_index": output_index,
+                "input": input
+            })));
+            let item = ResponseItem::CustomToolCall {
+                id: Some(item_id),
+                call_id,
+                name,
+                input,
+                status: Some("completed".to_string()),
+            };
+            events.push(self.event("response.output_item.done", json!({ "output_index": output_index, "item": item })));
+            self.output.push(item);
+            return events;
+        }
+
+        let arguments = args.to_string();
+        events.push(self.event("response.output_item.added", json!({
+            "output_index": output_index,
+            "item": { "type": "function_call", "id": item_id, "call_id": call_id, "name": name, "arguments": "", "status": "in_progress" }
+        })));
+        events.push(self.event("response.function_call_arguments.delta", json!({
+            "item_id": item_id,
+            "output_index": output_index,
+            "delta": arguments
+        })));
+        events.push(self.event("response.function_call_arguments.done", json!({
+            "item_id": item_id,
+            "output_index": output_index,
+            "arguments": arguments
+        })));
+        let item = ResponseItem::FunctionCall {
+            id: Some(item_id),
+            call_id,
+            name,
+            arguments,
+            status: Some("completed".to_string()),
+        };
+        events.push(self.event("response.output_item.done", json!({ "output_index": output_index, "item": item })));
+        self.output.push(item);
+        events
+    }
+
+    /// 结束所有 item 并发送终止事件
+    /// 上游因 MAX_TOKENS 截断时发送 response.incomplete (incomplete_details.reason = max_output_tokens)，否则为 response.completed
+    pub fn finish(&mut self) -> Vec<Bytes> {
+        let mut events = self.close_open_item();
+        if self.finish_reason.is_none() {
+            self.finish_reason = Some("STOP".to_string());
+        }
+        let response = self.response();
+        let event = if response["status"] == "incomplete" {
+            "response.incomplete"
+        } else {
+            "response.completed"
+        };
+        events.push(self.event(event, json!({ "response": response })));
+        events
+    }
+
+    /// 上游中断时发送 response.failed
+    pub fn fail(&mut self, message: &str) -> Vec<Bytes> {
+        let mut events = self.close_open_item();
+        let mut response = self.response();
+        response["status"] = json!("failed");
+        response["error"] = json!({ "code": "server_error", "message": message });
+        events.push(self.event("response.failed", json!({ "response": response })));
+        events
+    }
+}
+
+/// shell 参数 -> local_shell_call.action.command (与 Codex 路径保持一致)
+fn shell_command_vec(args: &Value) -> Vec<String> {
+    if let Some(arr) = args.get("command").and_then(|v| v.as_array()) {
+        arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect()
+    } else if let Some(cmd) = args.get("command").and_then(|v| v.as_str()) {
+        if cmd.contains(' ') {
+            vec!["powershell.exe".to_string(), "-Command".to_string(), cmd.to_string()]
+        } else {
+            vec![cmd.to_string()]
+        }
+    } else {
+        vec!["powershell.exe".to_string(), "-Command".to_string(), "exit 0".to_string()]
+    }
+}
+
+/// 创建 Gemini SSE -> Responses SSE 流
+/// 流结束后以最终状态调用 on_complete (用于写入本地响应存储)
+pub fn create_responses_sse_stream<F>(
+    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    mut state: ResponsesStreamState,
+    on_complete: F,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>
+where
+    F: FnOnce(&ResponsesStreamState) + Send + 'static,
+{
+    Box::pin(async_stream::stream! {
+        let mut buffer = BytesMut::new();
+        for ev in state.start() {
+            yield Ok::<Bytes, String>(ev);
+        }
+
+        while let Some(item) = gemini_stream.next().await {
+            match item {
+                Ok(bytes) => {
+                    buffer.extend_from_slice(&bytes);
+                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                        let line_raw = buffer.split_to(pos + 1);
+                        if let Ok(line) = std::str::from_utf8(&line_raw) {
+                            for ev in state.process_sse_line(line.trim()) {
+                                yield Ok::<Bytes, String>(ev);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("[Responses-SSE] Upstream error: {}", e);
+                    for ev in state.fail(&format!("Upstream error: {}", e)) {
+                        yield Ok::<Bytes, String>(ev);
+                    }
+                    return;
+                }
+            }
+        }
+
+        for ev in state.finish() {
+            yield Ok::<Bytes, String>(ev);
+        }
+        on_complete(&state);
+    })
+}
+
+/// 非流式: 消费 Gemini SSE 并返回最终状态
+pub async fn collect_responses_stream(
+    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    mut state: ResponsesStreamState,
+) -> Result<ResponsesStreamState, String> {
+    let mut buffer = BytesMut::new();
+    while let Some(item) = gemini_stream.next().await {
+        let bytes = item.map_err(|e| format!("Upstream error: {}", e))?;
+        buffer.extend_from_slice(&bytes);
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line_raw = buffer.split_to(pos + 1);
+            if let Ok(line) = std::str::from_utf8(&line_raw) {
+                state.process_sse_line(line.trim());
+            }
+        }
+    }
+    if !buffer.is_empty() {
+        if let Ok(line) = std::str::from_utf8(&buffer) {
+            state.process_sse_line(line.trim());
+        }
+    }
+    state.finish();
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events_text(events: &[Bytes]) -> String {
+        events.iter().map(|b| String::from_utf8_lossy(b).to_string()).collect()
+    }
+
+    #[test]
+    fn test_input_items_to_messages() {
+        let req: ResponsesRequest = serde_json::from_value(json!({
+            "model": "gpt-5",
+            "instructions": "Be brief",
+            "input": [
+                { "role": "user", "content": "list files" },
+                { "type": "reasoning", "id": "rs_1", "summary": [] },
+                { "type": "function_call", "call_id": "call_1", "name": "ls", "arguments": "{\"path\":\".\"}" },
+                { "type": "function_call_output", "call_id": "call_1", "output": "a.txt" },
+                { "type": "message", "role": "assistant", "content": [{ "type": "output_text", "text": "a.txt", "annotations": [] }] },
+                { "type": "item_reference", "id": "x" }
+            ],
+            "tools": [
+                { "type": "function", "name": "ls", "parameters": { "type": "object", "properties": {} } },
+                { "type": "local_shell" }
+            ],
+            "max_output_tokens": 512,
+            "reasoning": { "effort": "low" }
+        })).unwrap();
+
+        let items = parse_input_items(&req.input).unwrap();
+        assert_eq!(items.len(), 5);
+
+        let chat = build_chat_request(&req, &items);
+        let roles: Vec<&str> = chat.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["system", "user", "assistant", "tool", "assistant"]);
+        assert_eq!(chat.messages[2].tool_calls.as_ref().unwrap()[0].function.name, "ls");
+        assert_eq!(chat.messages[3].name.as_deref(), Some("ls"));
+        assert_eq!(chat.max_tokens, Some(512));
+        assert_eq!(chat.reasoning_effort.as_deref(), Some("low"));
+
+        let tools = chat.tools.unwrap();
+        assert_eq!(tools[1]["name"], "shell");
+    }
+
+    #[test]
+    fn test_stream_events() {
+        let req: ResponsesRequest = serde_json::from_value(json!({ "model": "gpt-5", "input": "hi" })).unwrap();
+        let mut state = ResponsesStreamState::new(&req);
+
+        let mut events = state.start();
+        events.extend(state.process_sse_line(r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"thinking","thought":true}]}}]}}"#));
+        events.extend(state.process_sse_line(r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"Hello"}]}}]}}"#));
+        events.extend(state.process_sse_line(r#"data: {"response":{"candidates":[{"content":{"parts":[{"functionCall":{"name":"get_weather","args":{"city":"Paris"}}}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":10,"candidatesTokenCount":5,"thoughtsTokenCount":3,"totalTokenCount":18}}}"#));
+        events.extend(state.finish());
+
+        let text = events_text(&events);
+        assert!(text.starts_with("event: response.created\n"));
+        assert!(text.contains("event: response.reasoning_summary_text.delta"));
+        assert!(text.contains("event: response.output_text.delta"));
+        assert!(text.contains("event: response.function_call_arguments.delta"));
+        assert!(text.contains("event: response.completed"));
+
+        let items = state.output_items();
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[0], ResponseItem::Reasoning { .. }));
+        assert!(matches!(items[1], ResponseItem::Message { .. }));
+        match &items[2] {
+            ResponseItem::FunctionCall { name, arguments, .. } => {
+                assert_eq!(name, "get_weather");
+                assert_eq!(arguments, r#"{"city":"Paris"}"#);
+            }
+            other => panic!("Expected function_call, got {:?}", other),
+        }
+
+        let response = state.response();
+        assert_eq!(response["status"], "completed");
+        assert_eq!(response["usage"]["output_tokens"], 8);
+        assert_eq!(response["usage"]["output_tokens_details"]["reasoning_tokens"], 3);
+        assert_eq!(response["output"][1]["content"][0]["type"], "output_text");
+    }
+
+    #[test]
+    fn test_output_items_roundtrip_as_input() {
+        // 上一轮的输出 items 可直接作为下一轮输入
+        let req: ResponsesRequest = serde_json::from_value(json!({ "model": "gpt-5", "input": "hi" })).unwrap();
+        let mut state = ResponsesStreamState::new(&req);
+        state.process_sse_line(r#"data: {"candidates":[{"content":{"parts":[{"text":"Hello"}]},"finishReason":"MAX_TOKENS"}]}"#);
+        let text = events_text(&state.finish());
+        assert!(text.contains("event: response.incomplete\n"));
+        assert!(text.contains(r#""incomplete_details":{"reason":"max_output_tokens"}"#));
+        assert!(!text.contains("response.completed"));
+        assert_eq!(state.response()["status"], "incomplete");
+
+        let as_input = json!(state.output_items());
+        let parsed = parse_input_items(&Some(as_input)).unwrap();
+        assert_eq!(parsed, state.output_items());
+    }
+}
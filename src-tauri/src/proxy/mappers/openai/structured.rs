@@ -0,0 +1,195 @@
+// Structured Outputs (response_format: json_schema, strict: true) 响应校验
+use super::models::*;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+
+use crate::proxy::common::json_schema::validate_json_schema;
+
+/// 校验单个候选文本是否符合原始 Schema
+pub fn validate_structured_text(schema: &Value, text: &str) -> Result<(), String> {
+    let trimmed = text.trim();
+    // 兼容模型偶发输出的 ```json 代码块包裹
+    let trimmed = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|s| s.strip_suffix("```"))
+        .map(|s| s.trim())
+        .unwrap_or(trimmed);
+
+    let instance: Value = serde_json::from_str(trimmed)
+        .map_err(|e| format!("response is not valid JSON: {}", e))?;
+    validate_json_schema(schema, &instance)
+}
+
+/// 校验完整响应中的所有 choices (带工具调用的 choice 跳过)
+pub fn validate_structured_response(schema: &Value, response: &OpenAIResponse) -> Result<(), String> {
+    for choice in &response.choices {
+        if choice.message.tool_calls.is_some() {
+            continue;
+        }
+        let text = match &choice.message.content {
+            Some(OpenAIContent::String(s)) => s.as_str(),
+            _ => "",
+        };
+        validate_structured_text(schema, text)
+            .map_err(|e| format!("choice {}: {}", choice.index, e))?;
+    }
+    Ok(())
+}
+
+/// 构建 OpenAI 协议格式的错误体
+pub fn structured_output_error(message: &str) -> Value {
+    json!({
+        "error": {
+            "message": format!("Model output does not match the requested json_schema: {}", message),
+            "type": "invalid_response_error",
+            "param": "response_format",
+            "code": "json_schema_validation_failed"
+        }
+    })
+}
+
+/// 从一行 SSE 数据中累积各 choice 的 content，返回是否为 [DONE] 结束标记
+fn collect_sse_line(line: &str, contents: &mut BTreeMap<u64, String>) -> bool {
+    let Some(data) = line.trim().strip_prefix("data: ") else { return false };
+    if data == "[DONE]" {
+        return true;
+    }
+    let Ok(chunk) = serde_json::from_str::<Value>(data) else { return false };
+    for choice in chunk.get("choices").and_then(|c| c.as_array()).into_iter().flatten() {
+        let idx = choice.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+        if let Some(delta) = choice.get("delta").and_then(|d| d.get("content")).and_then(|c| c.as_str()) {
+            contents.entry(idx).or_default().push_str(delta);
+        }
+    }
+    false
+}
+
+/// 包装 OpenAI SSE 流: 按行缓冲 (事件可能跨 chunk 拆分) 并累积每个 choice 的 content，
+/// 上游结束后校验完整输出，不匹配时在全部内容之后、[DONE] 之前插入一条 OpenAI 格式的 error 事件
+pub fn create_schema_validated_stream(
+    mut inner: Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>,
+    schema: Value,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+    let stream = async_stream::stream! {
+        let mut contents: BTreeMap<u64, String> = BTreeMap::new();
+        let mut pending: Vec<u8> = Vec::new();
+        let mut saw_done = false;
+
+        while let Some(item) = inner.next().await {
+            let bytes = match item {
+                Ok(b) => b,
+                Err(e) => {
+                    yield Err(e);
+                    continue;
+                }
+            };
+            if saw_done {
+                continue;
+            }
+
+            // 只转发完整的行，[DONE] 暂缓到校验之后发送
+            pending.extend_from_slice(&bytes);
+            let mut out = Vec::new();
+            while let Some(pos) = pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=pos).collect();
+                if collect_sse_line(&String::from_utf8_lossy(&line), &mut contents) {
+                    saw_done = true;
+                    pending.clear();
+                    break;
+                }
+                out.extend_from_slice(&line);
+            }
+            if !out.is_empty() {
+                yield Ok(Bytes::from(out));
+            }
+        }
+
+        if !pending.is_empty() {
+            if collect_sse_line(&String::from_utf8_lossy(&pending), &mut contents) {
+                saw_done = true;
+            } else {
+                yield Ok(Bytes::from(std::mem::take(&mut pending)));
+            }
+        }
+
+        let failure = contents
+            .iter()
+            .find_map(|(idx, text)| validate_structured_text(&schema, text).err().map(|e| format!("choice {}: {}", idx, e)));
+        if let Some(err) = failure {
+            tracing::warn!("[OpenAI-Structured] Streamed output failed schema validation: {}", err);
+            yield Ok(Bytes::from(format!("data: {}\n\n", structured_output_error(&err))));
+        }
+        if saw_done {
+            yield Ok(Bytes::from("data: [DONE]\n\n"));
+        }
+    };
+
+    Box::pin(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_structured_text() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "answer": { "type": "integer" } },
+            "required": ["answer"],
+            "additionalProperties": false
+        });
+
+        assert!(validate_structured_text(&schema, "{\"answer\": 42}").is_ok());
+        assert!(validate_structured_text(&schema, "```json\n{\"answer\": 42}\n```").is_ok());
+        assert!(validate_structured_text(&schema, "{\"answer\": \"42\"}").is_err());
+        assert!(validate_structured_text(&schema, "not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schema_validated_stream_emits_error() {
+        let schema = json!({ "type": "object", "required": ["answer"] });
+        let chunks = vec![
+            Ok(Bytes::from("data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"{}\"}}]}\n\n")),
+            Ok(Bytes::from("data: [DONE]\n\n")),
+        ];
+        let inner: Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> =
+            Box::pin(futures::stream::iter(chunks));
+
+        let out: Vec<String> = create_schema_validated_stream(inner, schema)
+            .map(|r| String::from_utf8_lossy(&r.unwrap()).to_string())
+            .collect()
+            .await;
+
+        assert_eq!(out.len(), 3);
+        assert!(out[1].contains("json_schema_validation_failed"));
+        assert_eq!(out[2], "data: [DONE]\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_schema_validated_stream_buffers_split_events() {
+        let schema = json!({ "type": "object", "required": ["answer"] });
+        // 同一事件拆分在两个 chunk 中，[DONE] 与内容在同一 chunk
+        let chunks = vec![
+            Ok(Bytes::from("data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"{\\\"ans")),
+            Ok(Bytes::from("wer\\\": 1}\"}}]}\n\ndata: [DONE]\n\n")),
+        ];
+        let inner: Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> =
+            Box::pin(futures::stream::iter(chunks));
+
+        let out: String = create_schema_validated_stream(inner, schema)
+            .map(|r| String::from_utf8_lossy(&r.unwrap()).to_string())
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+
+        assert!(!out.contains("json_schema_validation_failed"));
+        assert!(out.contains("answer"));
+        assert!(out.ends_with("data: [DONE]\n\n"));
+        assert_eq!(out.matches("[DONE]").count(), 1);
+    }
+}
@@ -0,0 +1,704 @@
+// 远程媒体下载
+// 上游无法访问任意 URL，http(s) 图像/文档/音频/视频由服务端下载 (限制超时、大小、协议/主机、重定向，DNS 解析结果过滤内网地址)，
+// 按实际内容识别 MIME 后以 base64 内联；结果按 URL 缓存，过期后通过 ETag 重新验证
+
+use base64::Engine as _;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::proxy::config::MediaFetchConfig;
+
+/// 期望的媒体类别 (用于内容校验与 MIME 识别)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Image,
+    Document,
+    Audio,
+    Video,
+}
+
+impl MediaKind {
+    fn accepts(&self, mime_type: &str) -> bool {
+        match self {
+            Self::Image => mime_type.starts_with("image/"),
+            Self::Audio => mime_type.starts_with("audio/"),
+            Self::Video => mime_type.starts_with("video/"),
+            Self::Document => mime_type == "application/pdf" || mime_type.starts_with("text/"),
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Image => "image",
+            Self::Document => "document",
+            Self::Audio => "audio",
+            Self::Video => "video",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchedMedia {
+    pub mime_type: String,
+    pub data: Bytes,
+}
+
+impl FetchedMedia {
+    pub fn to_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+
+    pub fn to_data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.to_base64())
+    }
+}
+
+struct CacheEntry {
+    media: FetchedMedia,
+    etag: Option<String>,
+    validated_at: Instant,
+}
+
+pub struct MediaFetcher {
+    cache: Mutex<HashMap<String, CacheEntry>>,
+}
+
+/// 按文件头识别 MIME 类型
+pub fn sniff_mime(data: &[u8]) -> Option<&'static str> {
+    if let Ok(format) = image::guess_format(data) {
+        return Some(format.to_mime_type());
+    }
+    if data.starts_with(b"%PDF-") {
+        return Some("application/pdf");
+    }
+    if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WAVE" {
+        return Some("audio/wav");
+    }
+    if data.starts_with(b"ID3") || (data.len() >= 2 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0) {
+        return Some("audio/mp3");
+    }
+    if data.starts_with(b"OggS") {
+        return Some("audio/ogg");
+    }
+    if data.starts_with(b"fLaC") {
+        return Some("audio/flac");
+    }
+    if data.len() >= 12 && &data[0..4] == b"FORM" && matches!(&data[8..12], b"AIFF" | b"AIFC") {
+        return Some("audio/aiff");
+    }
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        return match &data[8..12] {
+            b"heic" | b"heix" => Some("image/heic"),
+            b"mif1" | b"msf1" => Some("image/heif"),
+            b"M4A " => Some("audio/aac"),
+            b"qt  " => Some("video/quicktime"),
+            b"isom" | b"iso2" | b"mp41" | b"mp42" | b"avc1" | b"dash" | b"M4V " => Some("video/mp4"),
+            _ => None,
+        };
+    }
+    // EBML (WebM / Matroska)
+    if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        return Some("video/webm");
+    }
+    None
+}
+
+/// 根据扩展名推断文档/视频 MIME 类型 (图像与音频见各自模块)
+pub fn media_mime_from_extension(path: &str) -> Option<&'static str> {
+    let path = path.split(['?', '#']).next().unwrap_or(path).to_lowercase();
+    let ext = path.rsplit_once('.')?.1;
+    match ext {
+        "pdf" => Some("application/pdf"),
+        "txt" | "log" => Some("text/plain"),
+        "md" | "markdown" => Some("text/markdown"),
+        "csv" => Some("text/csv"),
+        "html" | "htm" => Some("text/html"),
+        "xml" => Some("text/xml"),
+        "mp4" | "m4v" => Some("video/mp4"),
+        "mov" => Some("video/quicktime"),
+        "webm" => Some("video/webm"),
+        "mpeg" | "mpg" => Some("video/mpeg"),
+        _ => None,
+    }
+}
+
+/// 确定最终 MIME 类型: 文件头 > Content-Type > URL 扩展名，且必须符合期望类别
+/// 只有无法识别文件头且 Content-Type 缺失/为通用二进制时才按扩展名推断 (避免把 HTML 错误页当作图像)
+/// 文档最后兜底: 合法 UTF-8 内容视为 text/plain
+fn resolve_mime(kind: MediaKind, data: &[u8], header_mime: Option<&str>, url: &str) -> Result<String, String> {
+    let accept = |mime: &str| {
+        if kind.accepts(mime) {
+            Ok(mime.to_string())
+        } else {
+            Err(format!("{} 的内容 ({}) 不是有效的 {}", url, mime, kind.label()))
+        }
+    };
+
+    if let Some(mime) = sniff_mime(data) {
+        return accept(mime);
+    }
+    if let Some(mime) = header_mime.filter(|m| !matches!(*m, "application/octet-stream" | "binary/octet-stream")) {
+        return accept(mime);
+    }
+
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    [
+        crate::proxy::mappers::image_preprocess::mime_from_extension(path).map(str::to_string),
+        crate::proxy::audio::AudioProcessor::detect_mime_type(path).ok(),
+        media_mime_from_extension(path).map(str::to_string),
+        (kind == MediaKind::Document && std::str::from_utf8(data).is_ok()).then(|| "text/plain".to_string()),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|m| kind.accepts(m))
+    .ok_or_else(|| format!("无法识别 {} 的 {} 类型", url, kind.label()))
+}
+
+/// 校验内联媒体 (data URL 或纯 base64): 大小限制 + 按内容识别 MIME，返回规范化的 data URL
+/// name 为文件名 (可选)，仅在无法识别文件头时用于按扩展名推断
+pub fn normalize_inline_media(
+    source: &str,
+    kind: MediaKind,
+    name: Option<&str>,
+    config: &MediaFetchConfig,
+) -> Result<String, String> {
+    let (declared, b64) = match source.trim().strip_prefix("data:") {
+        Some(rest) => {
+            let (header, data) = rest
+                .split_once(',')
+                .ok_or_else(|| format!("{} data URL 格式无效", kind.label()))?;
+            (header.split(';').next().filter(|m| !m.is_empty()), data)
+        }
+        None => (None, source.trim()),
+    };
+
+    // base64 长度约为原始数据的 4/3，先按编码长度快速拒绝
+    let max_bytes = config.max_mb.saturating_mul(1024 * 1024) as usize;
+    if b64.len() / 4 * 3 > max_bytes {
+        return Err(format!("内联 {} 超过 {} MB 限制", kind.label(), config.max_mb));
+    }
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(b64)
+        .map_err(|e| format!("{} base64 解码失败: {}", kind.label(), e))?;
+    if data.len() > max_bytes {
+        return Err(format!("内联 {} 超过 {} MB 限制", kind.label(), config.max_mb));
+    }
+
+    let mime_type = resolve_mime(kind, &data, declared, name.unwrap_or("inline data"))?;
+    Ok(format!("data:{};base64,{}", mime_type, b64))
+}
+
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let octets = v4.octets();
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                // 0.0.0.0/8 ("本网络"，部分系统会路由到本机)
+                || octets[0] == 0
+                // 100.64.0.0/10 (CGNAT)
+                || (octets[0] == 100 && (octets[1] & 0xC0) == 64)
+                // 198.18.0.0/15 (基准测试网段，常被用作内网)
+                || (octets[0] == 198 && (octets[1] & 0xFE) == 18)
+        }
+        IpAddr::V6(v6) => {
+            let segments = v6.segments();
+            let embedded_v4 = |hi: u16, lo: u16| Ipv4Addr::new((hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8);
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_private_ip(IpAddr::V4(v4));
+            }
+            // IPv4 兼容地址 ::a.b.c.d (:: 与 ::1 已在下方处理)
+            if segments[..6].iter().all(|s| *s == 0) && !v6.is_loopback() && !v6.is_unspecified() {
+                return is_private_ip(IpAddr::V4(embedded_v4(segments[6], segments[7])));
+            }
+            // 64:ff9b::/96 (NAT64)，按内嵌 IPv4 判断
+            if segments[..6] == [0x64, 0xFF9B, 0, 0, 0, 0] {
+                return is_private_ip(IpAddr::V4(embedded_v4(segments[6], segments[7])));
+            }
+            // 2002::/16 (6to4)，内嵌 IPv4 位于第 2-3 段
+            if segments[0] == 0x2002 {
+                return is_private_ip(IpAddr::V4(embedded_v4(segments[1], segments[2])));
+            }
+            let first = segments[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || (first & 0xFE00) == 0xFC00 // fc00::/7 unique local
+                || (first & 0xFFC0) == 0xFE80 // fe80::/10 link local
+        }
+    }
+}
+
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host == suffix || host.ends_with(&format!(".{}", suffix)),
+        None => host == pattern,
+    }
+}
+
+/// 校验 URL 的协议、主机白名单与字面内网地址 (重定向的每一跳都会校验)
+pub fn check_url(url: &reqwest::Url, config: &MediaFetchConfig) -> Result<(), String> {
+    if !config.allowed_schemes.iter().any(|s| s.eq_ignore_ascii_case(url.scheme())) {
+        return Err(format!("不允许的协议: {}", url.scheme()));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("URL 缺少主机: {}", url))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_lowercase();
+
+    if !config.allowed_hosts.is_empty() && !config.allowed_hosts.iter().any(|p| host_matches(&host, p)) {
+        return Err(format!("主机不在允许列表中: {}", host));
+    }
+    if config.block_private_networks {
+        let is_private = host == "localhost"
+            || host.ends_with(".localhost")
+            || host.parse::<IpAddr>().map(is_private_ip).unwrap_or(false);
+        if is_private {
+            return Err(format!("禁止访问内网地址: {}", host));
+        }
+    }
+    Ok(())
+}
+
+/// 只返回公网地址的 DNS 解析器，解析结果全部为内网地址时拒绝连接
+struct PublicOnlyResolver;
+
+impl reqwest::dns::Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let public: Vec<SocketAddr> = addrs.iter().copied().filter(|a| !is_private_ip(a.ip())).collect();
+            if public.is_empty() {
+                let resolved: Vec<String> = addrs.iter().map(|a| a.ip().to_string()).collect();
+                return Err(format!("禁止访问内网地址: {} -> [{}]", host, resolved.join(", ")).into());
+            }
+            let addrs: reqwest::dns::Addrs = Box::new(public.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+impl MediaFetcher {
+    fn new() -> Self {
+        Self {
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Global singleton instance
+    pub fn global() -> &'static MediaFetcher {
+        static INSTANCE: OnceLock<MediaFetcher> = OnceLock::new();
+        INSTANCE.get_or_init(MediaFetcher::new)
+    }
+
+    /// 下载远程媒体 (带缓存)
+    pub async fn fetch(&self, url: &str, kind: MediaKind, config: &MediaFetchConfig) -> Result<FetchedMedia, String> {
+        if !config.enabled {
+            return Err("远程媒体下载已禁用 (media_fetch.enabled = false)".to_string());
+        }
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("无效的 URL {}: {}", url, e))?;
+        check_url(&parsed, config)?;
+
+        let ttl = Duration::from_secs(config.cache_ttl_secs);
+        let cached = self.cache_lookup(url);
+        if let Some((media, _, validated_at)) = &cached {
+            if validated_at.elapsed() < ttl && kind.accepts(&media.mime_type) {
+                tracing::debug!("[MediaFetcher] Cache hit: {}", url);
+                return Ok(media.clone());
+            }
+        }
+
+        let client = Self::build_client(config)?;
+        let mut request = client.get(parsed);
+        if let Some((_, Some(etag), _)) = &cached {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag.as_str());
+        }
+        let mut response = request.send().await.map_err(|e| format!("下载 {} 失败: {}", url, e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            if let Some((media, etag, _)) = cached {
+                tracing::debug!("[MediaFetcher] Revalidated via ETag: {}", url);
+                self.cache_store(url, media.clone(), etag, config);
+                return Ok(media);
+            }
+        }
+        if !response.status().is_success() {
+            return Err(format!("下载 {} 失败: HTTP {}", url, response.status()));
+        }
+
+        let max_bytes = config.max_mb.saturating_mul(1024 * 1024) as usize;
+        if response.content_length().is_some_and(|len| len as usize > max_bytes) {
+            return Err(format!("{} 超过 {} MB 限制", url, config.max_mb));
+        }
+        let header_mime = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.split(';').next().unwrap_or("").trim().to_lowercase())
+            .filter(|s| !s.is_empty());
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        // 流式读取，超过限制时提前中止
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| format!("下载 {} 失败: {}", url, e))? {
+            data.extend_from_slice(&chunk);
+            if data.len() > max_bytes {
+                return Err(format!("{} 超过 {} MB 限制", url, config.max_mb));
+            }
+        }
+
+        let mime_type = resolve_mime(kind, &data, header_mime.as_deref(), url)?;
+        let media = FetchedMedia {
+            mime_type,
+            data: Bytes::from(data),
+        };
+        tracing::debug!("[MediaFetcher] Fetched {} ({}, {} bytes)", url, media.mime_type, media.data.len());
+        self.cache_store(url, media.clone(), etag, config);
+        Ok(media)
+    }
+
+    fn build_client(config: &MediaFetchConfig) -> Result<reqwest::Client, String> {
+        Self::client_builder(config)
+            .build()
+            .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))
+    }
+
+    fn client_builder(config: &MediaFetchConfig) -> reqwest::ClientBuilder {
+        let redirect_config = config.clone();
+        let policy = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > redirect_config.max_redirects {
+                return attempt.error("too many redirects");
+            }
+            match check_url(attempt.url(), &redirect_config) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        });
+        let builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+            .redirect(policy);
+        if config.block_private_networks {
+            // 域名解析统一经过过滤，重定向的每一跳与实际建立的连接都使用同一份结果 (无 DNS rebinding 窗口)
+            // 经系统代理 (HTTP(S)_PROXY) 访问时由代理解析目标主机，会绕过过滤，因此直连
+            builder.no_proxy().dns_resolver(std::sync::Arc::new(PublicOnlyResolver))
+        } else {
+            builder
+        }
+    }
+
+    fn cache_lookup(&self, url: &str) -> Option<(FetchedMedia, Option<String>, Instant)> {
+        let cache = self.cache.lock().ok()?;
+        cache
+            .get(url)
+            .map(|e| (e.media.clone(), e.etag.clone(), e.validated_at))
+    }
+
+    fn cache_store(&self, url: &str, media: FetchedMedia, etag: Option<String>, config: &MediaFetchConfig) {
+        if config.cache_ttl_secs == 0 {
+            return;
+        }
+        let max_bytes = config.cache_max_mb.saturating_mul(1024 * 1024) as usize;
+        if media.data.len() > max_bytes {
+            return;
+        }
+        let Ok(mut cache) = self.cache.lock() else {
+            return;
+        };
+        cache.insert(
+            url.to_string(),
+            CacheEntry {
+                media,
+                etag,
+                validated_at: Instant::now(),
+            },
+        );
+        // 超出容量时淘汰最久未验证的条目
+        while cache.values().map(|e| e.media.data.len()).sum::<usize>() > max_bytes {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, e)| e.validated_at)
+                .map(|(k, _)| k.clone());
+            match oldest {
+                Some(key) => cache.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    /// 按本地文件访问策略读取媒体 (local_files 默认禁用)
+    pub fn read_local(source: &str, kind: MediaKind, config: &MediaFetchConfig) -> Result<FetchedMedia, String> {
+        let (data, path) = crate::proxy::local_media::read_local_file(source, &config.local_files)?;
+        let mime_type = resolve_mime(kind, &data, None, &path.to_string_lossy())?;
+        Ok(FetchedMedia {
+            mime_type,
+            data: Bytes::from(data),
+        })
+    }
+
+    /// OpenAI 消息: http(s) 与本地文件的 image_url / audio_url / video_url 替换为 data URL
+    /// 远程下载受 enabled 控制 (禁用时保留原 URL)，本地文件受 local_files 策略控制
+    /// file (PDF / 文本) 与内联视频在此校验大小并按内容修正 MIME
+    pub async fn inline_openai_media(
+        &self,
+        messages: &mut [crate::proxy::mappers::openai::OpenAIMessage],
+        config: &MediaFetchConfig,
+    ) -> Result<(), String> {
+        use crate::proxy::mappers::openai::{OpenAIContent, OpenAIContentBlock};
+
+        for msg in messages.iter_mut() {
+            let Some(OpenAIContent::Array(blocks)) = msg.content.as_mut() else {
+                continue;
+            };
+            for block in blocks.iter_mut() {
+                let (url, kind) = match block {
+                    OpenAIContentBlock::ImageUrl { image_url } => (&mut image_url.url, MediaKind::Image),
+                    OpenAIContentBlock::AudioUrl { audio_url } => (&mut audio_url.url, MediaKind::Audio),
+                    OpenAIContentBlock::VideoUrl { video_url } => (&mut video_url.url, MediaKind::Video),
+                    OpenAIContentBlock::File { file } => {
+                        self.inline_openai_file(file, config).await?;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if is_remote(url) {
+                    if config.enabled {
+                        *url = self.fetch(url, kind, config).await?.to_data_url();
+                    }
+                } else if !url.starts_with("data:") {
+                    *url = Self::read_local(url, kind, config)?.to_data_url();
+                } else if kind == MediaKind::Video {
+                    *url = normalize_inline_media(url, kind, None, config)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// OpenAI file 块: file_data 规范化为 data URL (http(s) 链接按文档下载)
+    async fn inline_openai_file(
+        &self,
+        file: &mut crate::proxy::mappers::openai::OpenAIFileContent,
+        config: &MediaFetchConfig,
+    ) -> Result<(), String> {
+        let Some(file_data) = file.file_data.as_mut() else {
+            return Err(match &file.file_id {
+                Some(id) => format!("不支持 file_id ({})，请使用 file_data 内联文件内容", id),
+                None => "file 缺少 file_data".to_string(),
+            });
+        };
+        *file_data = if is_remote(file_data) {
+            self.fetch(file_data, MediaKind::Document, config).await?.to_data_url()
+        } else {
+            normalize_inline_media(file_data, MediaKind::Document, file.filename.as_deref(), config)?
+        };
+        Ok(())
+    }
+
+    /// Claude 消息: 下载 url 类型的 image / document source 并转为 base64
+    pub async fn inline_claude_media(
+        &self,
+        messages: &mut [crate::proxy::mappers::claude::Message],
+        config: &MediaFetchConfig,
+    ) -> Result<(), String> {
+        use crate::proxy::mappers::claude::{ContentBlock, MessageContent};
+
+        if !config.enabled {
+            return Ok(());
+        }
+        for msg in messages.iter_mut() {
+            let MessageContent::Array(blocks) = &mut msg.content else {
+                continue;
+            };
+            for block in blocks.iter_mut() {
+                let (source_type, media_type, data, url, kind) = match block {
+                    ContentBlock::Image { source, .. } => (
+                        &mut source.source_type,
+                        &mut source.media_type,
+                        &mut source.data,
+                        &mut source.url,
+                        MediaKind::Image,
+                    ),
+                    ContentBlock::Document { source, .. } => (
+                        &mut source.source_type,
+                        &mut source.media_type,
+                        &mut source.data,
+                        &mut source.url,
+                        MediaKind::Document,
+                    ),
+                    _ => continue,
+                };
+                if source_type != "url" {
+                    continue;
+                }
+                let Some(remote) = url.take() else {
+                    return Err(format!("{} source 缺少 url", kind.label()));
+                };
+                let media = self.fetch(&remote, kind, config).await?;
+                *source_type = "base64".to_string();
+                *media_type = media.mime_type.clone();
+                *data = media.to_base64();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_remote(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_url_policy() {
+        let config = MediaFetchConfig::default();
+        let check = |u: &str, c: &MediaFetchConfig| check_url(&reqwest::Url::parse(u).unwrap(), c);
+
+        assert!(check("https://example.com/a.png", &config).is_ok());
+        assert!(check("ftp://example.com/a.png", &config).is_err());
+        assert!(check("http://127.0.0.1:8045/x", &config).is_err());
+        assert!(check("http://192.168.1.2/x", &config).is_err());
+        assert!(check("http://[::1]/x", &config).is_err());
+        assert!(check("http://localhost/x", &config).is_err());
+
+        for ip in [
+            "0.1.2.3", "198.18.0.1", "198.19.255.1", "::127.0.0.1", "::ffff:10.0.0.1",
+            "64:ff9b::a9fe:a9fe", "2002:c0a8:0101::1", "2002:7f00:1::",
+        ] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{} should be private", ip);
+        }
+        for ip in ["198.20.0.1", "64:ff9b::808:808", "2002:808:808::1", "2001:4860:4860::8888"] {
+            assert!(!is_private_ip(ip.parse().unwrap()), "{} should be public", ip);
+        }
+
+        let restricted = MediaFetchConfig {
+            allowed_hosts: vec!["*.githubusercontent.com".to_string()],
+            ..Default::default()
+        };
+        assert!(check("https://raw.githubusercontent.com/a.png", &restricted).is_ok());
+        assert!(check("https://example.com/a.png", &restricted).is_err());
+
+        let open = MediaFetchConfig {
+            block_private_networks: false,
+            ..Default::default()
+        };
+        assert!(check("http://127.0.0.1/x", &open).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resolver_rejects_private_hosts() {
+        use reqwest::dns::Resolve;
+        assert!(PublicOnlyResolver.resolve("localhost".parse().unwrap()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_private_host_is_blocked() {
+        use axum::{response::Redirect, routing::get, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let secret_hits = Arc::new(AtomicUsize::new(0));
+        let hits = secret_hits.clone();
+        let app = Router::new()
+            .route("/start", get(move || async move { Redirect::temporary(&format!("http://localhost:{}/secret", addr.port())) }))
+            .route("/rebind", get(move || async move { Redirect::temporary(&format!("http://127.0.0.1:{}/secret", addr.port())) }))
+            .route(
+                "/secret",
+                get(move || {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    async { "internal" }
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // 首跳域名固定解析到本地测试服务器 (模拟公网主机)，重定向目标走正常的校验与解析
+        let config = MediaFetchConfig::default();
+        let client = MediaFetcher::client_builder(&config)
+            .resolve("media.test", addr)
+            .build()
+            .unwrap();
+        for path in ["start", "rebind"] {
+            let result = client.get(format!("http://media.test:{}/{}", addr.port(), path)).send().await;
+            assert!(result.is_err(), "redirect via /{} should be rejected", path);
+        }
+        assert_eq!(secret_hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_resolve_mime_sniffs_content() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        // 文件头优先于错误的 Content-Type
+        assert_eq!(resolve_mime(MediaKind::Image, png, Some("image/jpeg"), "https://x/a.jpg").unwrap(), "image/png");
+        assert_eq!(
+            resolve_mime(MediaKind::Document, b"%PDF-1.7", Some("application/octet-stream"), "https://x/doc").unwrap(),
+            "application/pdf"
+        );
+        assert_eq!(resolve_mime(MediaKind::Audio, b"ID3\x04", None, "https://x/a").unwrap(), "audio/mp3");
+        // HTML 错误页不能当作图像
+        assert!(resolve_mime(MediaKind::Image, b"<html>", Some("text/html"), "https://x/a.png").is_err());
+        // 无 Content-Type 时按扩展名推断
+        assert_eq!(
+            resolve_mime(MediaKind::Image, b"????", Some("application/octet-stream"), "https://x/a.webp?v=1").unwrap(),
+            "image/webp"
+        );
+    }
+
+    #[test]
+    fn test_normalize_inline_media() {
+        let config = MediaFetchConfig::default();
+        let b64 = |d: &[u8]| base64::engine::general_purpose::STANDARD.encode(d);
+
+        // 声明的 MIME 以文件头为准
+        let pdf = normalize_inline_media(&format!("data:text/plain;base64,{}", b64(b"%PDF-1.7")), MediaKind::Document, None, &config);
+        assert!(pdf.unwrap().starts_with("data:application/pdf;base64,"));
+        // 纯 base64 文本按文件名 / UTF-8 推断
+        let md = normalize_inline_media(&b64(b"# title"), MediaKind::Document, Some("README.md"), &config);
+        assert!(md.unwrap().starts_with("data:text/markdown;"));
+        let txt = normalize_inline_media(&b64(b"plain"), MediaKind::Document, None, &config);
+        assert!(txt.unwrap().starts_with("data:text/plain;"));
+        assert!(normalize_inline_media(&b64(&[0xFF, 0x00, 0xFE]), MediaKind::Document, None, &config).is_err());
+
+        let mp4 = normalize_inline_media(&b64(b"\0\0\0\x18ftypisom"), MediaKind::Video, None, &config);
+        assert!(mp4.unwrap().starts_with("data:video/mp4;"));
+        let webm = normalize_inline_media(&b64(&[0x1A, 0x45, 0xDF, 0xA3, 0x01]), MediaKind::Video, None, &config);
+        assert!(webm.unwrap().starts_with("data:video/webm;"));
+
+        // 超过大小限制
+        let tiny = MediaFetchConfig { max_mb: 0, ..Default::default() };
+        assert!(normalize_inline_media(&b64(b"%PDF-1.7"), MediaKind::Document, None, &tiny).is_err());
+    }
+
+    #[test]
+    fn test_cache_eviction() {
+        let fetcher = MediaFetcher::new();
+        let config = MediaFetchConfig {
+            cache_max_mb: 1,
+            ..Default::default()
+        };
+        let media = |n: usize| FetchedMedia {
+            mime_type: "image/png".to_string(),
+            data: Bytes::from(vec![0u8; n]),
+        };
+        fetcher.cache_store("a", media(600 * 1024), Some("\"v1\"".to_string()), &config);
+        fetcher.cache_store("b", media(600 * 1024), None, &config);
+        assert!(fetcher.cache_lookup("a").is_none());
+        let (_, etag, _) = fetcher.cache_lookup("b").unwrap();
+        assert!(etag.is_none());
+    }
+}
@@ -90,6 +90,12 @@ pub async fn monitor_middleware(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
+    // Extract background-task downgrade from X-Background-Downgrade header if present
+    let background_downgrade = response
+        .headers()
+        .get("X-Background-Downgrade")
+        .map(|v| String::from_utf8_lossy(v.as_bytes()).to_string());
+
     let monitor = state.monitor.clone();
     let mut log = ProxyRequestLog {
         id: uuid::Uuid::new_v4().to_string(),
@@ -106,6 +112,7 @@ pub async fn monitor_middleware(
         response_body: None,
         input_tokens: None,
         output_tokens: None,
+        background_downgrade,
     };
 
     if content_type.contains("text/event-stream") {
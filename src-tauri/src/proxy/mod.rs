@@ -22,6 +22,12 @@ pub mod sticky_config;     // 粘性调度配置
 pub mod session_manager;   // 会话指纹管理
 pub mod audio;             // 音频处理模块 (PR #311)
 pub mod signature_cache;   // Signature Cache (v3.3.16)
+pub mod response_store;    // Responses API 本地响应存储
+pub mod image_jobs;        // 异步图像生成任务
+pub mod image_store;       // 生成图像存储 (签名链接)
+pub mod media_fetcher;     // 远程媒体下载与缓存
+pub mod local_media;       // 本地文件访问策略
+pub mod hooks;             // 请求/响应脚本钩子
 
 
 pub use config::ProxyConfig;
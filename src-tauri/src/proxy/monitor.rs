@@ -20,6 +20,7 @@ pub struct ProxyRequestLog {
     pub response_body: Option<String>,
     pub input_tokens: Option<u32>,
     pub output_tokens: Option<u32>,
+    pub background_downgrade: Option<String>, // 后台任务降级说明 (类别: 原模型 -> 目标模型)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
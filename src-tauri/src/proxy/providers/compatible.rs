@@ -0,0 +1,161 @@
+// 按配置接入的 Anthropic / OpenAI 兼容服务商 (DeepSeek、本地 vLLM 等)，请求体原样透传
+
+use axum::http::Method;
+use futures::future::BoxFuture;
+use serde_json::Value;
+
+use super::passthrough::{forward_json, next_api_key, AuthStyle, PassthroughRequest};
+use super::{ProviderOutcome, ProviderRequest, UpstreamProvider};
+use crate::proxy::common::model_mapping::{lookup_wildcard, wildcard_match};
+use crate::proxy::config::{ProviderDispatchMode, UpstreamProviderConfig, UpstreamProviderKind};
+use crate::proxy::server::AppState;
+
+/// 模型映射: 精确匹配 > 通配符 (字面字符多者优先) > default_model > 原样透传
+fn map_configured_model(config: &UpstreamProviderConfig, model: &str) -> String {
+    lookup_wildcard(&config.model_mapping, model)
+        .cloned()
+        .or_else(|| config.default_model.clone().filter(|m| !m.is_empty()))
+        .unwrap_or_else(|| model.to_string())
+}
+
+fn serves_configured_model(config: &UpstreamProviderConfig, model: &str) -> bool {
+    config.models.is_empty() || config.models.iter().any(|p| wildcard_match(p, model))
+}
+
+async fn forward_configured(
+    config: &UpstreamProviderConfig,
+    auth: AuthStyle,
+    state: &AppState,
+    request: ProviderRequest<'_>,
+) -> ProviderOutcome {
+    let mut body = request.body;
+    if body.get("model").is_some() {
+        body["model"] = Value::String(request.model);
+    }
+    ProviderOutcome::Responded(
+        forward_json(
+            state,
+            PassthroughRequest {
+                provider: &config.id,
+                method: Method::POST,
+                base_url: &config.base_url,
+                path: request.path,
+                incoming_headers: request.headers,
+                api_key: next_api_key(&config.id, &config.api_keys),
+                auth,
+                body,
+            },
+        )
+        .await,
+    )
+}
+
+/// 根据 kind 创建服务商
+pub fn from_config(config: &UpstreamProviderConfig) -> std::sync::Arc<dyn UpstreamProvider> {
+    match config.kind {
+        UpstreamProviderKind::Anthropic => std::sync::Arc::new(AnthropicProvider { config: config.clone() }),
+        UpstreamProviderKind::Openai => std::sync::Arc::new(OpenAIProvider { config: config.clone() }),
+    }
+}
+
+/// Anthropic Messages 兼容端点
+pub struct AnthropicProvider {
+    config: UpstreamProviderConfig,
+}
+
+impl UpstreamProvider for AnthropicProvider {
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    fn supports(&self, protocol: &str) -> bool {
+        protocol == "claude"
+    }
+
+    fn dispatch_mode(&self) -> ProviderDispatchMode {
+        self.config.dispatch_mode
+    }
+
+    fn weight(&self) -> usize {
+        self.config.weight as usize
+    }
+
+    fn serves_model(&self, model: &str) -> bool {
+        serves_configured_model(&self.config, model)
+    }
+
+    fn map_model(&self, _protocol: &str, model: &str) -> String {
+        map_configured_model(&self.config, model)
+    }
+
+    fn forward<'a>(&'a self, state: &'a AppState, request: ProviderRequest<'a>) -> BoxFuture<'a, ProviderOutcome> {
+        Box::pin(forward_configured(&self.config, AuthStyle::MatchIncoming, state, request))
+    }
+}
+
+/// OpenAI Chat Completions 兼容端点
+pub struct OpenAIProvider {
+    config: UpstreamProviderConfig,
+}
+
+impl UpstreamProvider for OpenAIProvider {
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    fn supports(&self, protocol: &str) -> bool {
+        protocol == "openai"
+    }
+
+    fn dispatch_mode(&self) -> ProviderDispatchMode {
+        self.config.dispatch_mode
+    }
+
+    fn weight(&self) -> usize {
+        self.config.weight as usize
+    }
+
+    fn serves_model(&self, model: &str) -> bool {
+        serves_configured_model(&self.config, model)
+    }
+
+    fn map_model(&self, _protocol: &str, model: &str) -> String {
+        map_configured_model(&self.config, model)
+    }
+
+    fn forward<'a>(&'a self, state: &'a AppState, request: ProviderRequest<'a>) -> BoxFuture<'a, ProviderOutcome> {
+        Box::pin(forward_configured(&self.config, AuthStyle::Bearer, state, request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_model_mapping() {
+        let config = UpstreamProviderConfig {
+            id: "deepseek".to_string(),
+            model_mapping: [
+                ("gpt-4o".to_string(), "deepseek-chat".to_string()),
+                ("o*".to_string(), "deepseek-reasoner".to_string()),
+                ("o3-mini*".to_string(), "deepseek-chat".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            models: vec!["gpt-*".to_string(), "o*".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(map_configured_model(&config, "gpt-4o"), "deepseek-chat");
+        assert_eq!(map_configured_model(&config, "o1"), "deepseek-reasoner");
+        assert_eq!(map_configured_model(&config, "o3-mini-high"), "deepseek-chat");
+        assert_eq!(map_configured_model(&config, "qwen3"), "qwen3");
+        assert_eq!(
+            map_configured_model(&UpstreamProviderConfig { default_model: Some("local".to_string()), ..config.clone() }, "qwen3"),
+            "local"
+        );
+
+        assert!(serves_configured_model(&config, "gpt-4.1"));
+        assert!(!serves_configured_model(&config, "claude-sonnet-4-5"));
+    }
+}
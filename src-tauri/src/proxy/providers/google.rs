@@ -0,0 +1,50 @@
+// 内置 Google v1internal 账号池
+// 协议转换、账号轮换与重试都在各协议 handler 内完成，因此 forward 交还请求体由 handler 继续处理
+
+use futures::future::BoxFuture;
+
+use super::{ProviderOutcome, ProviderRequest, UpstreamProvider, GOOGLE_PROVIDER_ID};
+use crate::proxy::config::ProviderDispatchMode;
+use crate::proxy::server::AppState;
+
+pub struct GoogleProvider {
+    /// 当前可用账号数 (pooled 调度中每个账号权重为 1)
+    accounts: usize,
+}
+
+impl GoogleProvider {
+    pub fn new(accounts: usize) -> Self {
+        Self { accounts }
+    }
+}
+
+impl UpstreamProvider for GoogleProvider {
+    fn id(&self) -> &str {
+        GOOGLE_PROVIDER_ID
+    }
+
+    fn supports(&self, _protocol: &str) -> bool {
+        true
+    }
+
+    fn dispatch_mode(&self) -> ProviderDispatchMode {
+        ProviderDispatchMode::Pooled
+    }
+
+    fn weight(&self) -> usize {
+        self.accounts
+    }
+
+    fn serves_model(&self, _model: &str) -> bool {
+        true
+    }
+
+    /// 模型映射由 handler 内的路由解析完成
+    fn map_model(&self, _protocol: &str, model: &str) -> String {
+        model.to_string()
+    }
+
+    fn forward<'a>(&'a self, _state: &'a AppState, request: ProviderRequest<'a>) -> BoxFuture<'a, ProviderOutcome> {
+        Box::pin(async move { ProviderOutcome::Continue(request.body) })
+    }
+}
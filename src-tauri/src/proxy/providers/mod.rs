@@ -1,2 +1,425 @@
-pub mod zai_anthropic;
+// 上游服务商抽象
+// Google v1internal 账号池与按配置接入的 Anthropic / OpenAI 兼容端点统一实现 UpstreamProvider，
+// 由 dispatch 按路由规则 (provider 字段) 与各服务商的调度方式选择
+// /v1/responses 与 /v1/completions 仅由 Google 账号池提供 (其他服务商没有对应端点)，见 require_google
+// 转发给其他服务商的请求同样应用参数策略与脚本钩子 (forward_with_policy)；模型回退与后台任务降级仅适用于账号池
 
+pub mod compatible;
+pub mod google;
+pub mod passthrough;
+pub mod zai;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::proxy::common::model_mapping::{client_key_from_headers, find_route_rule, RouteContext};
+use crate::proxy::common::param_policy::ResolvedPolicy;
+use crate::proxy::config::{HookPoint, ModelRoutingRule, ProviderDispatchMode};
+use crate::proxy::hooks::HookRunner;
+use crate::proxy::server::AppState;
+
+/// 内置账号池的服务商 id
+pub const GOOGLE_PROVIDER_ID: &str = "google";
+
+/// 执行 BeforeResponse 钩子时缓冲的服务商响应上限
+const MAX_HOOKED_RESPONSE_SIZE: usize = 32 * 1024 * 1024;
+
+/// 交给服务商的请求
+pub struct ProviderRequest<'a> {
+    /// 入口协议: claude / openai / gemini
+    pub protocol: &'a str,
+    /// 上游路径 (如 `/v1/messages`)，追加在服务商 base_url 之后
+    pub path: &'a str,
+    pub headers: &'a HeaderMap,
+    /// 映射后的上游模型
+    pub model: String,
+    /// 客户端请求体 (已完成协议解析后的清理)
+    pub body: Value,
+}
+
+pub enum ProviderOutcome {
+    /// 服务商已生成响应
+    Responded(Response),
+    /// 交还请求体，由 handler 内的 v1internal 流程继续处理
+    Continue(Value),
+}
+
+pub trait UpstreamProvider: Send + Sync {
+    fn id(&self) -> &str;
+    /// 是否能处理该入口协议 (claude / openai / gemini) 的请求
+    fn supports(&self, protocol: &str) -> bool;
+    fn dispatch_mode(&self) -> ProviderDispatchMode;
+    /// pooled 调度权重
+    fn weight(&self) -> usize;
+    /// pooled 调度中固定占用的流量百分比，设置后不再按权重分配
+    fn pool_percent(&self) -> Option<u32> {
+        None
+    }
+    /// 是否参与该模型的自动调度
+    fn serves_model(&self, model: &str) -> bool;
+    /// 客户端模型 -> 上游模型
+    fn map_model(&self, protocol: &str, model: &str) -> String;
+    fn forward<'a>(&'a self, state: &'a AppState, request: ProviderRequest<'a>) -> BoxFuture<'a, ProviderOutcome>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DispatchReason {
+    /// 路由规则指定
+    Rule,
+    Exclusive,
+    Fallback,
+    Pooled,
+    /// 没有其他可用服务商
+    Default,
+}
+
+/// 服务商选择结果
+pub struct Dispatch {
+    pub provider: Arc<dyn UpstreamProvider>,
+    pub reason: DispatchReason,
+    pub protocol: String,
+    /// 交给服务商映射的模型 (规则目标或客户端原始模型)
+    pub model: String,
+}
+
+impl Dispatch {
+    pub fn provider_id(&self) -> &str {
+        self.provider.id()
+    }
+
+    pub fn is_google(&self) -> bool {
+        self.provider.id() == GOOGLE_PROVIDER_ID
+    }
+
+    pub fn upstream_model(&self) -> String {
+        self.provider.map_model(&self.protocol, &self.model)
+    }
+
+    pub async fn forward(&self, state: &AppState, path: &str, headers: &HeaderMap, body: Value) -> ProviderOutcome {
+        if !self.is_google() {
+            tracing::info!(
+                "[Provider] {} -> {} ({:?}, model: {})",
+                path,
+                self.provider_id(),
+                self.reason,
+                self.upstream_model()
+            );
+        }
+        let request = ProviderRequest {
+            protocol: &self.protocol,
+            path,
+            headers,
+            model: self.upstream_model(),
+            body,
+        };
+        self.provider.forward(state, request).await
+    }
+
+    /// 与账号池流程一致地转发: 按上游模型与客户端 Key 应用参数策略，执行 BeforeUpstream 钩子，
+    /// 响应为 SSE 时逐事件执行 UpstreamEvent，JSON 响应执行 BeforeResponse
+    /// 交还账号池时返回未修改的请求体 (账号池流程在协议转换后重新应用)
+    pub async fn forward_with_policy(
+        &self,
+        state: &AppState,
+        path: &str,
+        headers: &HeaderMap,
+        body: Value,
+        hooks: &HookRunner,
+    ) -> ProviderOutcome {
+        if self.is_google() {
+            return ProviderOutcome::Continue(body);
+        }
+        let upstream_model = self.upstream_model();
+        let client_key = client_key_from_headers(headers);
+        let policy = ResolvedPolicy::resolve(&upstream_model, client_key.as_deref(), &state.parameter_policies.read().await);
+        let hooks = hooks.with_route(self.provider_id(), &upstream_model);
+        let mut upstream_body = body.clone();
+        policy.apply_raw(&self.protocol, &mut upstream_body);
+        hooks.run(HookPoint::BeforeUpstream, &mut upstream_body);
+
+        match self.forward(state, path, headers, upstream_body).await {
+            ProviderOutcome::Continue(_) => ProviderOutcome::Continue(body),
+            ProviderOutcome::Responded(response) => {
+                ProviderOutcome::Responded(with_response_hooks(response, &hooks, &upstream_model).await)
+            }
+        }
+    }
+}
+
+/// 服务商响应: 添加 X-Mapped-Model 响应头并执行响应侧钩子 (错误响应原样返回)
+async fn with_response_hooks(response: Response, hooks: &HookRunner, upstream_model: &str) -> Response {
+    let (mut parts, body) = response.into_parts();
+    if let Ok(value) = HeaderValue::from_str(upstream_model) {
+        parts.headers.insert("X-Mapped-Model", value);
+    }
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if !parts.status.is_success() {
+        return Response::from_parts(parts, body);
+    }
+    if content_type.starts_with("text/event-stream") {
+        let stream = hooks.wrap_upstream_stream(Box::pin(body.into_data_stream()));
+        return Response::from_parts(parts, Body::from_stream(stream));
+    }
+    if !content_type.contains("json") || !hooks.is_active(HookPoint::BeforeResponse) {
+        return Response::from_parts(parts, body);
+    }
+
+    let bytes = match axum::body::to_bytes(body, MAX_HOOKED_RESPONSE_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::BAD_GATEWAY, format!("Failed to read upstream response: {}", e)).into_response(),
+    };
+    match serde_json::from_slice::<Value>(&bytes) {
+        Ok(value) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(hooks.before_response(&value).to_string()))
+        }
+        Err(_) => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
+
+/// 当前可用的服务商 (Google 账号池在首位)
+pub async fn registry(state: &AppState) -> Vec<Arc<dyn UpstreamProvider>> {
+    let mut providers: Vec<Arc<dyn UpstreamProvider>> =
+        vec![Arc::new(google::GoogleProvider::new(state.token_manager.len()))];
+    if let Some(zai) = zai::ZaiProvider::from_config(&*state.zai.read().await) {
+        providers.push(Arc::new(zai));
+    }
+    providers.extend(
+        state
+            .providers
+            .read()
+            .await
+            .iter()
+            .filter(|p| p.enabled && !p.id.is_empty() && p.id != GOOGLE_PROVIDER_ID)
+            .map(compatible::from_config),
+    );
+    providers
+}
+
+/// 为请求选择服务商
+pub async fn dispatch(state: &AppState, protocol: &str, original_model: &str, ctx: &RouteContext) -> Dispatch {
+    dispatch_with(state, protocol, original_model, ctx, &state.provider_rr).await
+}
+
+/// 预览下一次选择结果，不推进轮询游标 (调试接口)
+pub async fn preview_dispatch(state: &AppState, protocol: &str, original_model: &str, ctx: &RouteContext) -> Dispatch {
+    let rr = AtomicUsize::new(state.provider_rr.load(Ordering::Relaxed));
+    dispatch_with(state, protocol, original_model, ctx, &rr).await
+}
+
+/// 仅由 Google 账号池提供的路由 (/v1/responses、/v1/completions) 的调度检查
+/// pooled 调度时直接使用账号池；若路由规则或 exclusive 模式要求由其他服务商独占该模型，
+/// 返回 400 而不是静默发往 Google，保证调度方式在各入口一致
+pub async fn require_google(
+    state: &AppState,
+    path: &str,
+    original_model: &str,
+    ctx: &RouteContext,
+) -> Result<(), (axum::http::StatusCode, String)> {
+    let dispatch = preview_dispatch(state, "openai", original_model, ctx).await;
+    if dispatch.is_google() || !matches!(dispatch.reason, DispatchReason::Rule | DispatchReason::Exclusive) {
+        return Ok(());
+    }
+    Err((
+        axum::http::StatusCode::BAD_REQUEST,
+        format!(
+            "{} is only served by the Google account pool, but model '{}' is routed to provider '{}' ({:?}); use /v1/chat/completions instead",
+            path,
+            original_model,
+            dispatch.provider_id(),
+            dispatch.reason
+        ),
+    ))
+}
+
+async fn dispatch_with(
+    state: &AppState,
+    protocol: &str,
+    original_model: &str,
+    ctx: &RouteContext,
+    rr: &AtomicUsize,
+) -> Dispatch {
+    let providers = registry(state).await;
+    let rules = state.routing_rules.read().await;
+    select(&providers, protocol, original_model, find_route_rule(original_model, &rules, ctx), rr)
+}
+
+/// 选择优先级: 路由规则指定 > exclusive > fallback (账号池为空) > pooled 加权轮询 > Google 账号池
+pub fn select(
+    providers: &[Arc<dyn UpstreamProvider>],
+    protocol: &str,
+    original_model: &str,
+    rule: Option<&ModelRoutingRule>,
+    rr: &AtomicUsize,
+) -> Dispatch {
+    let google = providers
+        .iter()
+        .find(|p| p.id() == GOOGLE_PROVIDER_ID)
+        .cloned()
+        .unwrap_or_else(|| Arc::new(google::GoogleProvider::new(0)));
+    let pick = |provider: &Arc<dyn UpstreamProvider>, reason: DispatchReason, model: &str| Dispatch {
+        provider: provider.clone(),
+        reason,
+        protocol: protocol.to_string(),
+        model: model.to_string(),
+    };
+
+    if let Some((rule, id)) = rule.and_then(|r| r.provider.as_deref().filter(|id| !id.is_empty()).map(|id| (r, id))) {
+        match providers.iter().find(|p| p.id() == id) {
+            Some(provider) if provider.supports(protocol) => return pick(provider, DispatchReason::Rule, &rule.target),
+            Some(_) => tracing::warn!("[Provider] 规则 {} 指定的服务商 {} 不支持 {} 协议", rule.label(), id, protocol),
+            None => tracing::warn!("[Provider] 规则 {} 指定的服务商 {} 不存在或未启用", rule.label(), id),
+        }
+    }
+
+    let candidates: Vec<&Arc<dyn UpstreamProvider>> = providers
+        .iter()
+        .filter(|p| p.supports(protocol) && p.serves_model(original_model))
+        .collect();
+    let with_mode = |mode: ProviderDispatchMode| candidates.iter().copied().filter(move |p| p.dispatch_mode() == mode);
+
+    if let Some(provider) = with_mode(ProviderDispatchMode::Exclusive).next() {
+        return pick(provider, DispatchReason::Exclusive, original_model);
+    }
+    if google.weight() == 0 {
+        if let Some(provider) = with_mode(ProviderDispatchMode::Fallback).next() {
+            return pick(provider, DispatchReason::Fallback, original_model);
+        }
+    }
+
+    // pooled: 固定百分比的服务商先按比例分配，剩余流量按权重轮询
+    let pooled: Vec<_> = with_mode(ProviderDispatchMode::Pooled).collect();
+    let fixed: Vec<_> = pooled
+        .iter()
+        .filter_map(|p| p.pool_percent().map(|percent| (*p, percent.min(100) as usize)))
+        .collect();
+    let weighted: Vec<_> = pooled
+        .iter()
+        .filter(|p| p.pool_percent().is_none())
+        .map(|p| (*p, p.weight()))
+        .collect();
+    let fixed_total = fixed.iter().map(|(_, percent)| percent).sum::<usize>().min(100);
+    let weight_total: usize = weighted.iter().map(|(_, weight)| weight).sum();
+    let has_others = fixed_total > 0 || weighted.iter().any(|(p, weight)| *weight > 0 && p.id() != GOOGLE_PROVIDER_ID);
+    if has_others && fixed_total + weight_total > 0 {
+        let r = rr.fetch_add(1, Ordering::Relaxed);
+        let picked = if weight_total == 0 {
+            pick_slot(&fixed, r % fixed_total)
+        } else if r % 100 < fixed_total {
+            pick_slot(&fixed, r % 100)
+        } else {
+            pick_slot(&weighted, r % weight_total)
+        };
+        if let Some(provider) = picked {
+            return pick(provider, DispatchReason::Pooled, original_model);
+        }
+    }
+
+    pick(&google, DispatchReason::Default, original_model)
+}
+
+fn pick_slot<'p>(entries: &[(&'p Arc<dyn UpstreamProvider>, usize)], mut slot: usize) -> Option<&'p Arc<dyn UpstreamProvider>> {
+    for (provider, weight) in entries {
+        if slot < *weight {
+            return Some(provider);
+        }
+        slot -= weight;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config::{UpstreamProviderConfig, UpstreamProviderKind};
+
+    fn provider(id: &str, kind: UpstreamProviderKind, mode: ProviderDispatchMode, weight: u32) -> Arc<dyn UpstreamProvider> {
+        compatible::from_config(&UpstreamProviderConfig {
+            id: id.to_string(),
+            enabled: true,
+            kind,
+            base_url: "http://127.0.0.1:8000".to_string(),
+            dispatch_mode: mode,
+            weight,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_select_provider() {
+        let rr = AtomicUsize::new(0);
+        let providers = vec![
+            Arc::new(google::GoogleProvider::new(2)) as Arc<dyn UpstreamProvider>,
+            provider("vllm", UpstreamProviderKind::Openai, ProviderDispatchMode::RuleOnly, 1),
+            provider("deepseek", UpstreamProviderKind::Openai, ProviderDispatchMode::Pooled, 2),
+            provider("backup", UpstreamProviderKind::Anthropic, ProviderDispatchMode::Fallback, 1),
+        ];
+
+        // 规则指定服务商，使用规则目标模型
+        let mut rule = ModelRoutingRule::simple("qwen*", "Qwen/Qwen3-32B");
+        rule.provider = Some("vllm".to_string());
+        let d = select(&providers, "openai", "qwen3", Some(&rule), &rr);
+        assert_eq!((d.provider_id(), d.reason, d.model.as_str()), ("vllm", DispatchReason::Rule, "Qwen/Qwen3-32B"));
+
+        // 协议不兼容时回到自动调度
+        let d = select(&providers, "claude", "qwen3", Some(&rule), &rr);
+        assert_eq!((d.provider_id(), d.reason), ("google", DispatchReason::Default));
+
+        // pooled: 2 个 Google 账号 + deepseek 权重 2
+        let picks: Vec<String> = (0..4)
+            .map(|_| select(&providers, "openai", "gpt-4o", None, &rr).provider_id().to_string())
+            .collect();
+        assert_eq!(picks, ["google", "google", "deepseek", "deepseek"]);
+
+        // 账号池为空时使用 fallback
+        let mut empty = providers.clone();
+        empty[0] = Arc::new(google::GoogleProvider::new(0));
+        let d = select(&empty, "claude", "claude-sonnet-4-5", None, &rr);
+        assert_eq!((d.provider_id(), d.reason), ("backup", DispatchReason::Fallback));
+    }
+
+    #[test]
+    fn test_select_pooled_share() {
+        let zai = |pool_weight: u32, pool_percent: Option<u32>| -> Arc<dyn UpstreamProvider> {
+            Arc::new(
+                zai::ZaiProvider::from_config(&crate::proxy::ZaiConfig {
+                    enabled: true,
+                    dispatch_mode: crate::proxy::ZaiDispatchMode::Pooled,
+                    pool_weight,
+                    pool_percent,
+                    ..Default::default()
+                })
+                .unwrap(),
+            )
+        };
+        let count = |providers: &[Arc<dyn UpstreamProvider>], rounds: usize| {
+            let rr = AtomicUsize::new(0);
+            (0..rounds)
+                .filter(|_| select(providers, "openai", "gpt-4o", None, &rr).provider_id() == "zai")
+                .count()
+        };
+        let google: Arc<dyn UpstreamProvider> = Arc::new(google::GoogleProvider::new(30));
+
+        // 权重: 30 个账号 + z.ai 权重 10
+        assert_eq!(count(&[google.clone(), zai(10, None)], 40), 10);
+        // 百分比: 与账号数无关
+        assert_eq!(count(&[google.clone(), zai(1, Some(25))], 100), 25);
+        // 账号池为空时百分比服务商承接全部流量
+        assert_eq!(count(&[Arc::new(google::GoogleProvider::new(0)), zai(1, Some(25))], 10), 10);
+    }
+}
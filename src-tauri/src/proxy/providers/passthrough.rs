@@ -0,0 +1,207 @@
+// 兼容协议服务商共用的 HTTP 透传 (请求头过滤、鉴权、响应流式回传)
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::StreamExt;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::time::Duration;
+
+use crate::proxy::server::AppState;
+
+/// 各服务商的 API Key 轮询游标 (按服务商 id)
+static KEY_CURSORS: Lazy<DashMap<String, AtomicUsize>> = Lazy::new(DashMap::new);
+
+/// 上游鉴权方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthStyle {
+    /// 沿用客户端的方式: x-api-key 或 Authorization (Anthropic 兼容端点)
+    MatchIncoming,
+    /// Authorization: Bearer (OpenAI 兼容端点)
+    Bearer,
+}
+
+/// 一次透传请求
+pub struct PassthroughRequest<'a> {
+    /// 服务商 id (日志)
+    pub provider: &'a str,
+    pub method: Method,
+    pub base_url: &'a str,
+    pub path: &'a str,
+    pub incoming_headers: &'a HeaderMap,
+    pub api_key: Option<&'a str>,
+    pub auth: AuthStyle,
+    pub body: Value,
+}
+
+/// 轮询选择下一个 API Key，未配置时返回 None
+pub fn next_api_key<'a>(provider: &str, keys: &'a [String]) -> Option<&'a str> {
+    let keys: Vec<&String> = keys.iter().filter(|k| !k.trim().is_empty()).collect();
+    if keys.is_empty() {
+        return None;
+    }
+    let cursor = KEY_CURSORS.entry(provider.to_string()).or_insert_with(|| AtomicUsize::new(0));
+    let index = cursor.fetch_add(1, Ordering::Relaxed) % keys.len();
+    Some(keys[index].as_str())
+}
+
+pub fn join_base_url(base: &str, path: &str) -> String {
+    let base = base.trim_end_matches('/');
+    if path.starts_with('/') {
+        format!("{}{}", base, path)
+    } else {
+        format!("{}/{}", base, path)
+    }
+}
+
+fn build_client(
+    upstream_proxy: Option<crate::proxy::config::UpstreamProxyConfig>,
+    timeout_secs: u64,
+) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(timeout_secs.max(5)));
+
+    if let Some(config) = upstream_proxy {
+        if config.enabled && !config.url.is_empty() {
+            let proxy = reqwest::Proxy::all(&config.url)
+                .map_err(|e| format!("Invalid upstream proxy url: {}", e))?;
+            builder = builder.proxy(proxy);
+        }
+    }
+
+    builder
+        .tcp_nodelay(true) // [FIX #307] Disable Nagle's algorithm to improve latency for small requests
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+fn copy_passthrough_headers(incoming: &HeaderMap) -> HeaderMap {
+    // Only forward a conservative set of headers to avoid leaking the local proxy key or cookies.
+    let mut out = HeaderMap::new();
+
+    for (k, v) in incoming.iter() {
+        let key = k.as_str().to_ascii_lowercase();
+        match key.as_str() {
+            "content-type" | "accept" | "anthropic-version" | "user-agent" => {
+                out.insert(k.clone(), v.clone());
+            }
+            // Some clients use these for streaming; safe to pass through.
+            "accept-encoding" | "cache-control" => {
+                out.insert(k.clone(), v.clone());
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+fn set_auth(headers: &mut HeaderMap, incoming: &HeaderMap, api_key: &str, style: AuthStyle) {
+    let bearer = || HeaderValue::from_str(&format!("Bearer {}", api_key));
+    match style {
+        AuthStyle::Bearer => {
+            if let Ok(v) = bearer() {
+                headers.insert(header::AUTHORIZATION, v);
+            }
+        }
+        AuthStyle::MatchIncoming => {
+            // Prefer to keep the same auth scheme as the incoming request:
+            // - If the client used x-api-key (Anthropic style), replace it.
+            // - Else if it used Authorization, replace it with Bearer.
+            // - Else default to x-api-key.
+            let has_x_api_key = incoming.contains_key("x-api-key");
+            let has_auth = incoming.contains_key(header::AUTHORIZATION);
+
+            if has_x_api_key || !has_auth {
+                if let Ok(v) = HeaderValue::from_str(api_key) {
+                    headers.insert("x-api-key", v);
+                }
+            }
+
+            if has_auth {
+                if let Ok(v) = bearer() {
+                    headers.insert(header::AUTHORIZATION, v);
+                }
+            }
+        }
+    }
+}
+
+/// 发送请求并将上游响应 (SSE 或普通 JSON) 原样流式返回
+pub async fn forward_json(state: &AppState, request: PassthroughRequest<'_>) -> Response {
+    match send_json(state, request).await {
+        Ok(resp) => stream_response(resp),
+        Err(response) => response,
+    }
+}
+
+/// 发送请求，连接失败等错误直接转为响应
+pub async fn send_json(state: &AppState, request: PassthroughRequest<'_>) -> Result<reqwest::Response, Response> {
+    let url = join_base_url(request.base_url, request.path);
+
+    let timeout_secs = state.request_timeout.max(5);
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    let client = match build_client(Some(upstream_proxy), timeout_secs) {
+        Ok(c) => c,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e).into_response()),
+    };
+
+    let mut headers = copy_passthrough_headers(request.incoming_headers);
+    if let Some(api_key) = request.api_key {
+        set_auth(&mut headers, request.incoming_headers, api_key, request.auth);
+    }
+
+    // Ensure JSON content type.
+    headers
+        .entry(header::CONTENT_TYPE)
+        .or_insert(HeaderValue::from_static("application/json"));
+
+    // [FIX #307] Explicitly serialize body to Vec<u8> to ensure Content-Length is set correctly.
+    // This avoids "Transfer-Encoding: chunked" for small bodies which caused connection errors.
+    let body_bytes = serde_json::to_vec(&request.body).unwrap_or_default();
+
+    tracing::debug!(
+        "Forwarding request to provider {} (len: {} bytes): {}",
+        request.provider,
+        body_bytes.len(),
+        url
+    );
+
+    let req = client.request(request.method, &url)
+        .headers(headers)
+        .body(body_bytes); // Use .body(Vec<u8>) instead of .json()
+
+    req.send().await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            format!("Upstream request failed: {}", e),
+        )
+            .into_response()
+    })
+}
+
+/// 将上游响应原样流式返回 (保留状态码与 Content-Type)
+pub fn stream_response(resp: reqwest::Response) -> Response {
+    let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+
+    let mut out = Response::builder().status(status);
+    if let Some(ct) = resp.headers().get(header::CONTENT_TYPE) {
+        out = out.header(header::CONTENT_TYPE, ct.clone());
+    }
+
+    // Stream response body to the client (covers SSE and non-SSE).
+    let stream = resp.bytes_stream().map(|chunk| match chunk {
+        Ok(b) => Ok::<Bytes, std::io::Error>(b),
+        Err(e) => Ok(Bytes::from(format!("Upstream stream error: {}", e))),
+    });
+
+    out.body(Body::from_stream(stream)).unwrap_or_else(|_| {
+        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to build response").into_response()
+    })
+}
@@ -0,0 +1,404 @@
+// z.ai 服务商: 由 ZaiConfig 生成，保留其模型族映射与调度方式
+// Anthropic 协议走 Anthropic 兼容端点；OpenAI 协议走 OpenAI 兼容的 PaaS 端点；
+// Gemini 协议转换为 Chat Completions 后发送到 PaaS 端点，响应再转换回 Gemini 格式
+// 多个 Key 轮询使用，429 的 Key 按 RateLimitTracker 规则锁定；失败时可交还 Google 账号池处理
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use bytes::{Bytes, BytesMut};
+use futures::{future::BoxFuture, StreamExt};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::passthrough::{send_json, stream_response, AuthStyle, PassthroughRequest};
+use super::{ProviderOutcome, ProviderRequest, UpstreamProvider};
+use crate::proxy::config::ProviderDispatchMode;
+use crate::proxy::mappers::gemini::openai_compat::{
+    gemini_to_openai_request, openai_to_gemini_response, OpenAIStreamToGemini,
+};
+use crate::proxy::rate_limit::RateLimitTracker;
+use crate::proxy::server::AppState;
+use crate::proxy::{ZaiConfig, ZaiDispatchMode};
+
+pub const ZAI_PROVIDER_ID: &str = "zai";
+
+/// PaaS 端点的 Chat Completions 路径
+const ZAI_CHAT_COMPLETIONS_PATH: &str = "/chat/completions";
+
+fn map_model_for_zai(original: &str, protocol: &str, state: &ZaiConfig) -> String {
+    let m = original.to_lowercase();
+    if let Some(mapped) = state.model_mapping.get(original) {
+        return mapped.clone();
+    }
+    if let Some(mapped) = state.model_mapping.get(&m) {
+        return mapped.clone();
+    }
+    if m.starts_with("zai:") {
+        return original[4..].to_string();
+    }
+    if m.starts_with("glm-") {
+        return original.to_string();
+    }
+    if !m.starts_with("claude-") {
+        // Anthropic 客户端可能直接使用 z.ai 的模型名；OpenAI / Gemini 客户端的模型名 z.ai 无法识别，按档位映射
+        if protocol == "claude" {
+            return original.to_string();
+        }
+        if ["flash", "mini", "lite"].iter().any(|k| m.contains(k)) {
+            return state.models.haiku.clone();
+        }
+        return state.models.sonnet.clone();
+    }
+    if m.contains("opus") {
+        return state.models.opus.clone();
+    }
+    if m.contains("haiku") {
+        return state.models.haiku.clone();
+    }
+    state.models.sonnet.clone()
+}
+
+/// Recursively remove cache_control from all nested objects/arrays
+/// [FIX #290] This is a defensive fix that works regardless of serde annotations
+pub fn deep_remove_cache_control(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("cache_control");
+            for v in map.values_mut() {
+                deep_remove_cache_control(v);
+            }
+        }
+        Value::Array(arr) => {
+            for v in arr {
+                deep_remove_cache_control(v);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 各 z.ai Key 的限流状态 (与 Google 账号相同的锁定/退避规则)
+static KEY_LIMITS: Lazy<RateLimitTracker> = Lazy::new(RateLimitTracker::new);
+static KEY_CURSOR: AtomicUsize = AtomicUsize::new(0);
+
+/// 限流跟踪使用的 Key 标识: 完整 Key 的 SHA-256，避免前后缀相同的 Key 共用锁定状态
+fn key_id(key: &str) -> String {
+    format!("zai:{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// 日志中使用的 Key 标识 (不暴露完整 Key)
+fn key_label(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 10 {
+        return format!("zai:{}", "*".repeat(chars.len()));
+    }
+    let head: String = chars[..6].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("zai:{}...{}", head, tail)
+}
+
+/// 回退到 Google 账号池的响应状态: 限流、上游故障、连接失败
+fn should_fallback(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn rotated_keys(keys: &[String]) -> Vec<&str> {
+    if keys.is_empty() {
+        return Vec::new();
+    }
+    let start = KEY_CURSOR.fetch_add(1, Ordering::Relaxed) % keys.len();
+    keys[start..]
+        .iter()
+        .chain(keys[..start].iter())
+        .map(String::as_str)
+        .filter(|key| !KEY_LIMITS.is_rate_limited(&key_id(key)))
+        .collect()
+}
+
+/// MCP 转发与视觉工具等单次请求使用的 Key: 与服务商共用轮询游标，优先未被限流的 Key
+/// 全部限流时仍返回第一个 Key，由上游返回限流错误；未配置任何 Key 时返回 None
+pub fn pick_api_key(config: &ZaiConfig) -> Option<String> {
+    let keys = config.all_api_keys();
+    rotated_keys(&keys)
+        .first()
+        .map(|key| key.to_string())
+        .or_else(|| keys.into_iter().next())
+}
+
+pub struct ZaiProvider {
+    config: ZaiConfig,
+    keys: Vec<String>,
+}
+
+impl ZaiProvider {
+    /// 未启用或调度方式为 off 时返回 None
+    pub fn from_config(config: &ZaiConfig) -> Option<Self> {
+        if !config.enabled || config.dispatch_mode == ZaiDispatchMode::Off {
+            return None;
+        }
+        Some(Self {
+            config: config.clone(),
+            keys: config.all_api_keys(),
+        })
+    }
+
+    /// 本次请求依次尝试的 Key: 从轮询位置开始，跳过仍在锁定中的 Key
+    fn key_order(&self) -> Vec<&str> {
+        rotated_keys(&self.keys)
+    }
+
+    fn passthrough<'a>(
+        &'a self,
+        protocol: &str,
+        path: &'a str,
+        headers: &'a HeaderMap,
+        api_key: &'a str,
+        body: Value,
+    ) -> PassthroughRequest<'a> {
+        let (base_url, path, auth) = match protocol {
+            "claude" => (self.config.base_url.as_str(), path, AuthStyle::MatchIncoming),
+            _ => (self.config.openai_base_url.as_str(), ZAI_CHAT_COMPLETIONS_PATH, AuthStyle::Bearer),
+        };
+        PassthroughRequest {
+            provider: ZAI_PROVIDER_ID,
+            method: Method::POST,
+            base_url,
+            path,
+            incoming_headers: headers,
+            api_key: Some(api_key),
+            auth,
+            body,
+        }
+    }
+
+    /// 按 Key 顺序发送，遇到 429 时记录该 Key 的锁定并换下一个 Key
+    async fn send_with_keys(
+        &self,
+        state: &AppState,
+        protocol: &str,
+        path: &str,
+        headers: &HeaderMap,
+        body: Value,
+    ) -> Result<reqwest::Response, Response> {
+        let keys = self.key_order();
+        if keys.is_empty() {
+            let wait = self.keys.iter().map(|k| KEY_LIMITS.get_remaining_wait(&key_id(k))).min().unwrap_or(0);
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("All z.ai api keys are rate limited, retry in {}s", wait),
+            )
+                .into_response());
+        }
+
+        let mut last_limited = None;
+        for key in keys {
+            let label = key_label(key);
+            let id = key_id(key);
+            let resp = send_json(state, self.passthrough(protocol, path, headers, key, body.clone())).await?;
+            let status = resp.status();
+            if status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                if status.is_success() {
+                    KEY_LIMITS.mark_success(&id);
+                }
+                return Ok(resp);
+            }
+
+            let retry_after = resp
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let text = resp.text().await.unwrap_or_default();
+            KEY_LIMITS.parse_from_error(&id, status.as_u16(), retry_after.as_deref(), &text);
+            tracing::warn!("[Provider] z.ai key {} rate limited, trying next key", label);
+            last_limited = Some(text);
+        }
+
+        Err((StatusCode::TOO_MANY_REQUESTS, last_limited.unwrap_or_default()).into_response())
+    }
+
+    async fn forward_passthrough(&self, state: &AppState, request: ProviderRequest<'_>) -> Response {
+        let ProviderRequest { protocol, path, headers, model, mut body } = request;
+        if body.get("model").is_some() {
+            body["model"] = Value::String(model);
+        }
+        if protocol == "claude" {
+            // [FIX #290] Clean cache_control before sending to Anthropic API
+            // This prevents "Extra inputs are not permitted" errors
+            deep_remove_cache_control(&mut body);
+        }
+
+        match self.send_with_keys(state, protocol, path, headers, body).await {
+            Ok(resp) => stream_response(resp),
+            Err(response) => response,
+        }
+    }
+
+    async fn forward_gemini(&self, state: &AppState, request: ProviderRequest<'_>) -> Response {
+        let stream = request.path.ends_with(":streamGenerateContent");
+        let body = gemini_to_openai_request(&request.body, &request.model, stream);
+        let resp = match self.send_with_keys(state, request.protocol, request.path, request.headers, body).await {
+            Ok(resp) => resp,
+            Err(response) => return response,
+        };
+        if !resp.status().is_success() {
+            return stream_response(resp);
+        }
+
+        if !stream {
+            return match resp.json::<Value>().await {
+                Ok(value) => Json(openai_to_gemini_response(&value, &request.model)).into_response(),
+                Err(e) => (StatusCode::BAD_GATEWAY, format!("Invalid upstream response: {}", e)).into_response(),
+            };
+        }
+
+        let mut converter = OpenAIStreamToGemini::new(&request.model);
+        let mut upstream = resp.bytes_stream();
+        let stream = async_stream::stream! {
+            let mut buffer = BytesMut::new();
+            while let Some(chunk) = upstream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        buffer.extend_from_slice(&bytes);
+                        while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                            let line = buffer.split_to(pos + 1);
+                            // 非 data 行与 [DONE] 无需转换
+                            let event = std::str::from_utf8(&line)
+                                .ok()
+                                .and_then(|l| l.trim().strip_prefix("data:").map(str::trim))
+                                .and_then(|payload| serde_json::from_str::<Value>(payload).ok())
+                                .and_then(|chunk| converter.convert(&chunk));
+                            if let Some(event) = event {
+                                yield Ok::<Bytes, std::io::Error>(Bytes::from(format!("data: {}\n\n", event)));
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        yield Err(std::io::Error::other(format!("Upstream stream error: {}", e)));
+                        break;
+                    }
+                }
+            }
+        };
+
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::from_stream(stream))
+            .unwrap_or_else(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to build response").into_response())
+    }
+}
+
+impl UpstreamProvider for ZaiProvider {
+    fn id(&self) -> &str {
+        ZAI_PROVIDER_ID
+    }
+
+    fn supports(&self, protocol: &str) -> bool {
+        matches!(protocol, "claude" | "openai" | "gemini")
+    }
+
+    fn dispatch_mode(&self) -> ProviderDispatchMode {
+        match self.config.dispatch_mode {
+            ZaiDispatchMode::Off => ProviderDispatchMode::RuleOnly,
+            ZaiDispatchMode::Exclusive => ProviderDispatchMode::Exclusive,
+            ZaiDispatchMode::Pooled => ProviderDispatchMode::Pooled,
+            ZaiDispatchMode::Fallback => ProviderDispatchMode::Fallback,
+        }
+    }
+
+    fn weight(&self) -> usize {
+        self.config.pool_weight as usize
+    }
+
+    fn pool_percent(&self) -> Option<u32> {
+        self.config.pool_percent
+    }
+
+    fn serves_model(&self, _model: &str) -> bool {
+        true
+    }
+
+    fn map_model(&self, protocol: &str, model: &str) -> String {
+        map_model_for_zai(model, protocol, &self.config)
+    }
+
+    fn forward<'a>(&'a self, state: &'a AppState, request: ProviderRequest<'a>) -> BoxFuture<'a, ProviderOutcome> {
+        Box::pin(async move {
+            if self.keys.is_empty() {
+                return ProviderOutcome::Responded(
+                    (StatusCode::BAD_REQUEST, "z.ai api_key is not set").into_response(),
+                );
+            }
+
+            // 失败时交还原始请求体给 Google 账号池 (需显式开启；exclusive 模式与账号池为空时不回退)
+            let fallback_body = (self.config.fallback_to_google
+                && self.config.dispatch_mode != ZaiDispatchMode::Exclusive
+                && state.token_manager.len() > 0)
+                .then(|| request.body.clone());
+
+            let response = if request.protocol == "gemini" {
+                self.forward_gemini(state, request).await
+            } else {
+                self.forward_passthrough(state, request).await
+            };
+
+            match fallback_body {
+                Some(body) if should_fallback(response.status()) => {
+                    tracing::warn!("[Provider] z.ai failed with {}, falling back to Google pool", response.status());
+                    ProviderOutcome::Continue(body)
+                }
+                _ => ProviderOutcome::Responded(response),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_model_by_protocol() {
+        let config = ZaiConfig::default();
+        assert_eq!(map_model_for_zai("claude-opus-4-5", "claude", &config), config.models.opus);
+        assert_eq!(map_model_for_zai("glm-4.6", "openai", &config), "glm-4.6");
+        assert_eq!(map_model_for_zai("my-model", "claude", &config), "my-model");
+        assert_eq!(map_model_for_zai("gpt-4o", "openai", &config), config.models.sonnet);
+        assert_eq!(map_model_for_zai("gemini-2.5-flash", "gemini", &config), config.models.haiku);
+    }
+
+    #[test]
+    fn test_key_order_skips_limited_keys() {
+        let provider = ZaiProvider::from_config(&ZaiConfig {
+            enabled: true,
+            dispatch_mode: ZaiDispatchMode::Pooled,
+            api_key: "key-test-order-aaaa".to_string(),
+            api_keys: vec![" key-test-order-bbbb ".to_string(), "key-test-order-aaaa".to_string(), String::new()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(provider.keys, ["key-test-order-aaaa", "key-test-order-bbbb"]);
+        assert_eq!(key_label("key-test-order-aaaa"), "zai:key-te...aaaa");
+
+        // 前后缀相同的 Key 不共用锁定状态
+        assert_eq!(key_label("key-test-order-bbbb"), key_label("key-test-xxxxx-bbbb"));
+        assert_ne!(key_id("key-test-order-bbbb"), key_id("key-test-xxxxx-bbbb"));
+
+        KEY_LIMITS.set_lockout_until(
+            &key_id("key-test-order-bbbb"),
+            std::time::SystemTime::now() + std::time::Duration::from_secs(60),
+            crate::proxy::rate_limit::RateLimitReason::RateLimitExceeded,
+            None,
+        );
+        for _ in 0..3 {
+            assert_eq!(provider.key_order(), ["key-test-order-aaaa"]);
+        }
+    }
+}
@@ -35,13 +35,35 @@ pub struct RateLimitInfo {
     pub model: Option<String>,
 }
 
+impl RateLimitInfo {
+    fn is_active(&self) -> bool {
+        self.reset_time > SystemTime::now()
+    }
+
+    /// 是否作用于指定模型: 账号级别限流对所有模型生效；未指定模型时任何限流记录都生效
+    fn applies_to(&self, model: Option<&str>) -> bool {
+        match (self.model.as_deref(), model) {
+            (Some(locked), Some(requested)) => locked == requested,
+            _ => true,
+        }
+    }
+}
+
 /// 限流跟踪器
+/// 同一账号可同时存在一条账号级别记录与多条模型级别记录 (Google 配额按模型计算)
 pub struct RateLimitTracker {
-    limits: DashMap<String, RateLimitInfo>,
-    /// 连续失败计数（用于智能指数退避）
+    limits: DashMap<String, Vec<RateLimitInfo>>,
+    /// 连续失败计数（用于智能指数退避），按账号 + 模型分别计数
     failure_counts: DashMap<String, u32>,
 }
 
+fn failure_key(account_id: &str, model: Option<&str>) -> String {
+    match model {
+        Some(m) => format!("{}\n{}", account_id, m),
+        None => account_id.to_string(),
+    }
+}
+
 impl RateLimitTracker {
     pub fn new() -> Self {
         Self {
@@ -52,13 +74,15 @@ impl RateLimitTracker {
     
     /// 获取账号剩余的等待时间(秒)
     pub fn get_remaining_wait(&self, account_id: &str) -> u64 {
-        if let Some(info) = self.limits.get(account_id) {
-            let now = SystemTime::now();
-            if info.reset_time > now {
-                return info.reset_time.duration_since(now).unwrap_or(Duration::from_secs(0)).as_secs();
-            }
-        }
-        0
+        self.get_remaining_wait_for_model(account_id, None)
+    }
+
+    /// 获取账号对指定模型剩余的等待时间(秒)
+    pub fn get_remaining_wait_for_model(&self, account_id: &str, model: Option<&str>) -> u64 {
+        self.get_for_model(account_id, model)
+            .and_then(|info| info.reset_time.duration_since(SystemTime::now()).ok())
+            .unwrap_or(Duration::from_secs(0))
+            .as_secs()
     }
     
     /// 标记账号请求成功，重置连续失败计数
@@ -66,11 +90,34 @@ impl RateLimitTracker {
     /// 当账号成功完成请求后调用此方法，将其失败计数归零，
     /// 这样下次失败时会从最短的锁定时间（60秒）开始。
     pub fn mark_success(&self, account_id: &str) {
-        if self.failure_counts.remove(account_id).is_some() {
+        self.mark_success_for_model(account_id, None);
+    }
+
+    /// 标记账号在指定模型上请求成功
+    /// 只清除账号级别与该模型的限流记录，其他模型的限流不受影响；未指定模型时清除该账号全部记录
+    pub fn mark_success_for_model(&self, account_id: &str, model: Option<&str>) {
+        let model_prefix = failure_key(account_id, Some(""));
+        let before = self.failure_counts.len();
+        self.failure_counts.retain(|key, _| match model {
+            Some(_) => key != account_id && *key != failure_key(account_id, model),
+            None => key != account_id && !key.starts_with(&model_prefix),
+        });
+        if self.failure_counts.len() < before {
             tracing::debug!("账号 {} 请求成功，已重置失败计数", account_id);
         }
         // 同时清除限流记录（如果有）
-        self.limits.remove(account_id);
+        if model.is_none() {
+            self.limits.remove(account_id);
+        } else if let Some(mut entries) = self.limits.get_mut(account_id) {
+            entries.retain(|info| info.model.is_some() && info.model.as_deref() != model);
+        }
+    }
+
+    /// 保存限流记录，替换同一账号同一作用范围的旧记录
+    fn store(&self, account_id: &str, info: RateLimitInfo) {
+        let mut entries = self.limits.entry(account_id.to_string()).or_default();
+        entries.retain(|existing| existing.model != info.model);
+        entries.push(info);
     }
     
     /// 精确锁定账号到指定时间点
@@ -95,7 +142,7 @@ impl RateLimitTracker {
             model: model.clone(),  // 🆕 支持模型级别限流
         };
         
-        self.limits.insert(account_id.to_string(), info);
+        self.store(account_id, info);
         
         if let Some(m) = &model {
             tracing::info!(
@@ -151,6 +198,19 @@ impl RateLimitTracker {
         status: u16,
         retry_after_header: Option<&str>,
         body: &str,
+    ) -> Option<RateLimitInfo> {
+        self.parse_from_error_for_model(account_id, status, retry_after_header, body, None)
+    }
+
+    /// 从错误响应解析限流信息，并只锁定该账号的指定模型
+    /// model 为 None 时锁定整个账号 (仅用于与模型无关的账号级错误)
+    pub fn parse_from_error_for_model(
+        &self,
+        account_id: &str,
+        status: u16,
+        retry_after_header: Option<&str>,
+        body: &str,
+        model: Option<&str>,
     ) -> Option<RateLimitInfo> {
         // 支持 429 (限流) 以及 500/503/529 (后端故障软避让)
         if status != 429 && status != 500 && status != 503 && status != 529 {
@@ -188,7 +248,7 @@ impl RateLimitTracker {
             None => {
                 // 获取连续失败次数，用于指数退避
                 let failure_count = {
-                    let mut count = self.failure_counts.entry(account_id.to_string()).or_insert(0);
+                    let mut count = self.failure_counts.entry(failure_key(account_id, model)).or_insert(0);
                     *count += 1;
                     *count
                 };
@@ -247,17 +307,18 @@ impl RateLimitTracker {
             retry_after_sec: retry_sec,
             detected_at: SystemTime::now(),
             reason,
-            model: None,  // 默认账号级别限流
+            model: model.map(str::to_string),
         };
         
         // 存储
-        self.limits.insert(account_id.to_string(), info.clone());
+        self.store(account_id, info.clone());
         
         tracing::warn!(
-            "账号 {} [{}] 限流类型: {:?}, 重置延时: {}秒",
+            "账号 {} [{}] 限流类型: {:?}, 模型: {}, 重置延时: {}秒",
             account_id,
             status,
             reason,
+            model.unwrap_or("*"),
             retry_sec
         );
         
@@ -434,30 +495,43 @@ impl RateLimitTracker {
         None
     }
     
-    /// 获取账号的限流信息
+    /// 获取账号仍生效的限流信息 (多条记录时取最晚重置的一条)
     pub fn get(&self, account_id: &str) -> Option<RateLimitInfo> {
-        self.limits.get(account_id).map(|r| r.clone())
+        self.get_for_model(account_id, None)
+    }
+
+    /// 获取账号对指定模型生效的限流信息 (账号级别记录与该模型的记录中最晚重置的一条)
+    pub fn get_for_model(&self, account_id: &str, model: Option<&str>) -> Option<RateLimitInfo> {
+        self.limits.get(account_id).and_then(|entries| {
+            entries
+                .iter()
+                .filter(|info| info.is_active() && info.applies_to(model))
+                .max_by_key(|info| info.reset_time)
+                .cloned()
+        })
     }
     
     /// 检查账号是否仍在限流中
     pub fn is_rate_limited(&self, account_id: &str) -> bool {
-        if let Some(info) = self.get(account_id) {
-            info.reset_time > SystemTime::now()
-        } else {
-            false
-        }
+        self.get(account_id).is_some()
     }
     
+    /// 检查账号对指定模型是否仍在限流中
+    /// 账号级别限流对所有模型生效；模型级别限流只影响对应模型 (未指定模型时同样视为限流)
+    pub fn is_rate_limited_for_model(&self, account_id: &str, model: Option<&str>) -> bool {
+        self.get_for_model(account_id, model).is_some()
+    }
+
     /// 获取距离限流重置还有多少秒
     pub fn get_reset_seconds(&self, account_id: &str) -> Option<u64> {
-        if let Some(info) = self.get(account_id) {
-            info.reset_time
-                .duration_since(SystemTime::now())
-                .ok()
-                .map(|d| d.as_secs())
-        } else {
-            None
-        }
+        self.get_reset_seconds_for_model(account_id, None)
+    }
+
+    /// 获取账号对指定模型距离限流重置还有多少秒
+    pub fn get_reset_seconds_for_model(&self, account_id: &str, model: Option<&str>) -> Option<u64> {
+        self.get_for_model(account_id, model)
+            .and_then(|info| info.reset_time.duration_since(SystemTime::now()).ok())
+            .map(|d| d.as_secs())
     }
     
     /// 清除过期的限流记录
@@ -466,13 +540,16 @@ impl RateLimitTracker {
         let now = SystemTime::now();
         let mut count = 0;
         
-        self.limits.retain(|_k, v| {
-            if v.reset_time <= now {
-                count += 1;
-                false
-            } else {
-                true
-            }
+        self.limits.retain(|_k, entries| {
+            entries.retain(|info| {
+                if info.reset_time <= now {
+                    count += 1;
+                    false
+                } else {
+                    true
+                }
+            });
+            !entries.is_empty()
         });
         
         if count > 0 {
@@ -493,7 +570,7 @@ impl RateLimitTracker {
     /// 用于乐观重置机制,当所有账号都被限流但等待时间很短时,
     /// 清除所有限流记录以解决时序竞争条件
     pub fn clear_all(&self) {
-        let count = self.limits.len();
+        let count: usize = self.limits.iter().map(|entries| entries.len()).sum();
         self.limits.clear();
         tracing::warn!("🔄 Optimistic reset: Cleared all {} rate limit record(s)", count);
     }
@@ -570,4 +647,39 @@ mod tests {
         // 应该被识别为 RateLimitExceeded，而不是 QuotaExhausted
         assert_eq!(reason, RateLimitReason::RateLimitExceeded);
     }
+
+    #[test]
+    fn test_model_scoped_rate_limit() {
+        let tracker = RateLimitTracker::new();
+        let reset = SystemTime::now() + Duration::from_secs(60);
+        tracker.set_lockout_until("acc1", reset, RateLimitReason::QuotaExhausted, Some("claude-opus-4-5-thinking".to_string()));
+        tracker.set_lockout_until("acc2", reset, RateLimitReason::QuotaExhausted, None);
+
+        // 模型级限流只影响该模型
+        assert!(tracker.is_rate_limited_for_model("acc1", Some("claude-opus-4-5-thinking")));
+        assert!(!tracker.is_rate_limited_for_model("acc1", Some("claude-sonnet-4-5")));
+        assert!(tracker.is_rate_limited_for_model("acc1", None));
+        // 账号级限流影响所有模型
+        assert!(tracker.is_rate_limited_for_model("acc2", Some("claude-sonnet-4-5")));
+        assert!(!tracker.is_rate_limited_for_model("acc3", None));
+    }
+
+    #[test]
+    fn test_model_locks_are_independent() {
+        let tracker = RateLimitTracker::new();
+        tracker.parse_from_error_for_model("acc1", 429, Some("60"), "", Some("claude-opus-4-5-thinking"));
+        tracker.parse_from_error_for_model("acc1", 429, Some("30"), "", Some("gemini-3-pro-high"));
+
+        // 两个模型的限流同时存在，互不覆盖
+        assert!(tracker.is_rate_limited_for_model("acc1", Some("claude-opus-4-5-thinking")));
+        assert!(tracker.is_rate_limited_for_model("acc1", Some("gemini-3-pro-high")));
+        assert!(!tracker.is_rate_limited_for_model("acc1", Some("gemini-3-flash")));
+        assert!(tracker.get_remaining_wait_for_model("acc1", Some("gemini-3-pro-high")) <= 30);
+        assert!(tracker.get_remaining_wait("acc1") > 30);
+
+        // 某个模型请求成功只清除该模型的记录
+        tracker.mark_success_for_model("acc1", Some("gemini-3-pro-high"));
+        assert!(!tracker.is_rate_limited_for_model("acc1", Some("gemini-3-pro-high")));
+        assert!(tracker.is_rate_limited_for_model("acc1", Some("claude-opus-4-5-thinking")));
+    }
 }
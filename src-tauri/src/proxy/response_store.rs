@@ -0,0 +1,123 @@
+use crate::proxy::mappers::openai::responses::ResponseItem;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+// 与 OpenAI 不同，本地存储只保留有限时间，足够覆盖一次交互式会话
+const RESPONSE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+const MAX_STORED_RESPONSES: usize = 512;
+
+/// 已保存的响应
+#[derive(Clone, Debug)]
+pub struct StoredResponse {
+    /// 最终 response 对象 (供 GET /v1/responses/:id 返回)
+    pub response: Value,
+    /// 完整对话 items (历史输入 + 本轮输出)，供 previous_response_id 续接
+    pub items: Vec<ResponseItem>,
+    /// 创建该响应的客户端 Key，仅允许同一 Key 读取、续接与删除
+    pub client_key: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct CacheEntry {
+    data: StoredResponse,
+    timestamp: SystemTime,
+}
+
+impl CacheEntry {
+    fn is_expired(&self) -> bool {
+        self.timestamp.elapsed().unwrap_or(Duration::ZERO) > RESPONSE_TTL
+    }
+}
+
+/// Responses API 本地响应存储 (previous_response_id 支持)
+pub struct ResponseStore {
+    responses: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl ResponseStore {
+    fn new() -> Self {
+        Self {
+            responses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Global singleton instance
+    pub fn global() -> &'static ResponseStore {
+        static INSTANCE: OnceLock<ResponseStore> = OnceLock::new();
+        INSTANCE.get_or_init(ResponseStore::new)
+    }
+
+    pub fn insert(&self, id: &str, response: StoredResponse) {
+        if let Ok(mut cache) = self.responses.lock() {
+            cache.retain(|_, v| !v.is_expired());
+            // 超出容量时淘汰最旧的条目
+            while cache.len() >= MAX_STORED_RESPONSES {
+                let oldest = cache
+                    .iter()
+                    .min_by_key(|(_, v)| v.timestamp)
+                    .map(|(k, _)| k.clone());
+                match oldest {
+                    Some(key) => cache.remove(&key),
+                    None => break,
+                };
+            }
+            tracing::debug!("[ResponseStore] Storing response {} ({} items)", id, response.items.len());
+            cache.insert(
+                id.to_string(),
+                CacheEntry {
+                    data: response,
+                    timestamp: SystemTime::now(),
+                },
+            );
+        }
+    }
+
+    /// 读取响应；不属于该客户端 Key 的响应视为不存在
+    pub fn get(&self, id: &str, client_key: Option<&str>) -> Option<StoredResponse> {
+        let cache = self.responses.lock().ok()?;
+        cache
+            .get(id)
+            .filter(|entry| !entry.is_expired() && entry.data.client_key.as_deref() == client_key)
+            .map(|entry| entry.data.clone())
+    }
+
+    pub fn remove(&self, id: &str, client_key: Option<&str>) -> bool {
+        let Ok(mut cache) = self.responses.lock() else {
+            return false;
+        };
+        if cache.get(id).is_some_and(|entry| entry.data.client_key.as_deref() == client_key) {
+            cache.remove(id);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_store_and_evict() {
+        let store = ResponseStore::new();
+        let owned = |response: Value| StoredResponse { response, items: Vec::new(), client_key: Some("sk-a".to_string()) };
+        store.insert("resp_1", owned(json!({"id": "resp_1"})));
+        assert_eq!(store.get("resp_1", Some("sk-a")).unwrap().response["id"], "resp_1");
+        assert!(store.get("resp_missing", Some("sk-a")).is_none());
+        // 其他客户端 (或无 Key) 不能读取、删除
+        assert!(store.get("resp_1", Some("sk-b")).is_none());
+        assert!(store.get("resp_1", None).is_none());
+        assert!(!store.remove("resp_1", Some("sk-b")));
+
+        for i in 0..MAX_STORED_RESPONSES {
+            store.insert(&format!("resp_x{}", i), owned(json!({})));
+        }
+        assert_eq!(store.responses.lock().unwrap().len(), MAX_STORED_RESPONSES);
+
+        assert!(store.remove("resp_x10", Some("sk-a")));
+        assert!(!store.remove("resp_x10", Some("sk-a")));
+    }
+}
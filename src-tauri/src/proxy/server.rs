@@ -7,7 +7,7 @@ use axum::{
     Router,
 };
 use std::sync::Arc;
-use tokio::sync::oneshot;
+use tokio::sync::watch;
 use tower_http::trace::TraceLayer;
 use tower_http::services::{ServeDir, ServeFile};
 use tracing::{debug, error};
@@ -18,7 +18,7 @@ use std::sync::atomic::AtomicUsize;
 #[derive(Clone)]
 pub struct AppState {
     pub token_manager: Arc<TokenManager>,
-    pub custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    pub routing_rules: Arc<tokio::sync::RwLock<Vec<crate::proxy::config::ModelRoutingRule>>>,
     #[allow(dead_code)]
     pub request_timeout: u64, // API 请求超时(秒)
     #[allow(dead_code)]
@@ -31,6 +31,16 @@ pub struct AppState {
     pub zai_vision_mcp: Arc<crate::proxy::zai_vision_mcp::ZaiVisionMcpState>,
     pub monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
     pub experimental: Arc<RwLock<crate::proxy::config::ExperimentalConfig>>,
+    pub thinking: Arc<RwLock<crate::proxy::config::ThinkingBudgetConfig>>,
+    pub image_storage: Arc<RwLock<crate::proxy::config::ImageStorageConfig>>,
+    pub image_generation: Arc<RwLock<crate::proxy::config::ImageGenerationConfig>>,
+    pub image_preprocess: Arc<RwLock<crate::proxy::config::ImagePreprocessConfig>>,
+    pub media_fetch: Arc<RwLock<crate::proxy::config::MediaFetchConfig>>,
+    pub model_fallback: Arc<RwLock<crate::proxy::config::ModelFallbackConfig>>,
+    pub background_tasks: Arc<RwLock<crate::proxy::config::BackgroundTaskConfig>>,
+    pub parameter_policies: Arc<RwLock<Vec<crate::proxy::config::ParameterPolicy>>>,
+    pub hooks: Arc<RwLock<crate::proxy::config::HookConfig>>,
+    pub providers: Arc<RwLock<Vec<crate::proxy::config::UpstreamProviderConfig>>>,
     pub security_state: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
     pub sessions: crate::proxy::handlers::web_auth::SessionStore, // Web 登录 session
 }
@@ -38,9 +48,9 @@ pub struct AppState {
 impl AppState {
     /// 更新模型映射配置
     pub async fn update_mapping(&self, config: &crate::proxy::config::ProxyConfig) {
-        let mut m = self.custom_mapping.write().await;
-        *m = config.custom_mapping.clone();
-        tracing::debug!("模型映射 (Custom) 已通过 Admin API 热更新");
+        let mut m = self.routing_rules.write().await;
+        *m = config.routing_rules.clone();
+        tracing::debug!("模型路由规则已通过 Admin API 热更新");
     }
 }
 
@@ -53,20 +63,30 @@ impl axum::extract::FromRef<AppState> for crate::proxy::handlers::web_auth::Sess
 
 /// Axum 服务器实例
 pub struct AxumServer {
-    shutdown_tx: Option<oneshot::Sender<()>>,
-    custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    shutdown_tx: Option<watch::Sender<bool>>,
+    routing_rules: Arc<tokio::sync::RwLock<Vec<crate::proxy::config::ModelRoutingRule>>>,
     proxy_state: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
     security_state: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
     zai_state: Arc<RwLock<crate::proxy::ZaiConfig>>,
+    thinking_state: Arc<RwLock<crate::proxy::config::ThinkingBudgetConfig>>,
+    image_storage_state: Arc<RwLock<crate::proxy::config::ImageStorageConfig>>,
+    image_generation_state: Arc<RwLock<crate::proxy::config::ImageGenerationConfig>>,
+    image_preprocess_state: Arc<RwLock<crate::proxy::config::ImagePreprocessConfig>>,
+    media_fetch_state: Arc<RwLock<crate::proxy::config::MediaFetchConfig>>,
+    model_fallback_state: Arc<RwLock<crate::proxy::config::ModelFallbackConfig>>,
+    background_tasks_state: Arc<RwLock<crate::proxy::config::BackgroundTaskConfig>>,
+    parameter_policies_state: Arc<RwLock<Vec<crate::proxy::config::ParameterPolicy>>>,
+    hooks_state: Arc<RwLock<crate::proxy::config::HookConfig>>,
+    providers_state: Arc<RwLock<Vec<crate::proxy::config::UpstreamProviderConfig>>>,
 }
 
 impl AxumServer {
     pub async fn update_mapping(&self, config: &crate::proxy::config::ProxyConfig) {
         {
-            let mut m = self.custom_mapping.write().await;
-            *m = config.custom_mapping.clone();
+            let mut m = self.routing_rules.write().await;
+            *m = config.routing_rules.clone();
         }
-        tracing::debug!("模型映射 (Custom) 已全量热更新");
+        tracing::debug!("模型路由规则已全量热更新");
     }
 
     /// 更新代理配置
@@ -87,21 +107,91 @@ impl AxumServer {
         *zai = config.zai.clone();
         tracing::info!("z.ai 配置已热更新");
     }
+
+    pub async fn update_thinking(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut thinking = self.thinking_state.write().await;
+        *thinking = config.thinking.clone();
+        tracing::info!("推理预算配置已热更新");
+    }
+
+    pub async fn update_image_storage(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut storage = self.image_storage_state.write().await;
+        *storage = config.image_storage.clone();
+        tracing::info!("图像存储配置已热更新");
+    }
+
+    pub async fn update_image_generation(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut generation = self.image_generation_state.write().await;
+        *generation = config.image_generation.clone();
+        tracing::info!("文生图配置已热更新");
+    }
+
+    pub async fn update_image_preprocess(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut preprocess = self.image_preprocess_state.write().await;
+        *preprocess = config.image_preprocess.clone();
+        tracing::info!("图像预处理配置已热更新");
+    }
+
+    pub async fn update_media_fetch(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut media_fetch = self.media_fetch_state.write().await;
+        *media_fetch = config.media_fetch.clone();
+        tracing::info!("远程媒体下载配置已热更新");
+    }
+
+    pub async fn update_model_fallback(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut fallback = self.model_fallback_state.write().await;
+        *fallback = config.model_fallback.clone();
+        tracing::info!("模型回退链配置已热更新");
+    }
+
+    pub async fn update_background_tasks(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut background_tasks = self.background_tasks_state.write().await;
+        *background_tasks = config.background_tasks.clone();
+        tracing::info!("后台任务降级规则已热更新");
+    }
+
+    pub async fn update_parameter_policies(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut policies = self.parameter_policies_state.write().await;
+        *policies = config.parameter_policies.clone();
+        tracing::info!("参数策略已热更新");
+    }
+
+    pub async fn update_hooks(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut hooks = self.hooks_state.write().await;
+        *hooks = config.hooks.clone();
+        tracing::info!("脚本钩子配置已热更新");
+    }
+
+    pub async fn update_providers(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut providers = self.providers_state.write().await;
+        *providers = config.providers.clone();
+        tracing::info!("上游服务商配置已热更新");
+    }
     /// 启动 Axum 服务器
     pub async fn start(
         host: String,
         port: u16,
         token_manager: Arc<TokenManager>,
-        custom_mapping: std::collections::HashMap<String, String>,
+        routing_rules: Vec<crate::proxy::config::ModelRoutingRule>,
         _request_timeout: u64,
         upstream_proxy: crate::proxy::config::UpstreamProxyConfig,
         security_config: crate::proxy::ProxySecurityConfig,
         zai_config: crate::proxy::ZaiConfig,
         monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
         experimental_config: crate::proxy::config::ExperimentalConfig,
-
+        thinking_config: crate::proxy::config::ThinkingBudgetConfig,
+        image_storage_config: crate::proxy::config::ImageStorageConfig,
+        image_generation_config: crate::proxy::config::ImageGenerationConfig,
+        image_preprocess_config: crate::proxy::config::ImagePreprocessConfig,
+        media_fetch_config: crate::proxy::config::MediaFetchConfig,
+        model_fallback_config: crate::proxy::config::ModelFallbackConfig,
+        background_tasks_config: crate::proxy::config::BackgroundTaskConfig,
+        parameter_policies: Vec<crate::proxy::config::ParameterPolicy>,
+        hooks_config: crate::proxy::config::HookConfig,
+        providers_config: Vec<crate::proxy::config::UpstreamProviderConfig>,
+        admin_listener: crate::proxy::config::AdminListenerConfig,
     ) -> Result<(Self, tokio::task::JoinHandle<()>), String> {
-        let custom_mapping_state = Arc::new(tokio::sync::RwLock::new(custom_mapping));
+        let routing_rules_state = Arc::new(tokio::sync::RwLock::new(routing_rules));
 	        let proxy_state = Arc::new(tokio::sync::RwLock::new(upstream_proxy.clone()));
 	        let security_state = Arc::new(RwLock::new(security_config));
 	        let zai_state = Arc::new(RwLock::new(zai_config));
@@ -109,10 +199,20 @@ impl AxumServer {
 	        let zai_vision_mcp_state =
 	            Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
 	        let experimental_state = Arc::new(RwLock::new(experimental_config));
+	        let thinking_state = Arc::new(RwLock::new(thinking_config));
+	        let image_storage_state = Arc::new(RwLock::new(image_storage_config));
+	        let image_generation_state = Arc::new(RwLock::new(image_generation_config));
+	        let image_preprocess_state = Arc::new(RwLock::new(image_preprocess_config));
+	        let media_fetch_state = Arc::new(RwLock::new(media_fetch_config));
+	        let model_fallback_state = Arc::new(RwLock::new(model_fallback_config));
+	        let background_tasks_state = Arc::new(RwLock::new(background_tasks_config));
+	        let parameter_policies_state = Arc::new(RwLock::new(parameter_policies));
+	        let hooks_state = Arc::new(RwLock::new(hooks_config));
+	        let providers_state = Arc::new(RwLock::new(providers_config));
 
 	        let state = AppState {
 	            token_manager: token_manager.clone(),
-	            custom_mapping: custom_mapping_state.clone(),
+	            routing_rules: routing_rules_state.clone(),
 	            request_timeout: 300, // 5分钟超时
             thought_signature_map: Arc::new(tokio::sync::Mutex::new(
                 std::collections::HashMap::new(),
@@ -126,183 +226,91 @@ impl AxumServer {
             zai_vision_mcp: zai_vision_mcp_state,
             monitor: monitor.clone(),
             experimental: experimental_state,
+            thinking: thinking_state.clone(),
+            image_storage: image_storage_state.clone(),
+            image_generation: image_generation_state.clone(),
+            image_preprocess: image_preprocess_state.clone(),
+            media_fetch: media_fetch_state.clone(),
+            model_fallback: model_fallback_state.clone(),
+            background_tasks: background_tasks_state.clone(),
+            parameter_policies: parameter_policies_state.clone(),
+            hooks: hooks_state.clone(),
+            providers: providers_state.clone(),
             security_state: security_state.clone(),
             sessions: Arc::new(RwLock::new(std::collections::HashMap::new())),
         };
 
 
-        // 构建路由 - 使用新架构的 handlers！
-        use crate::proxy::handlers;
-        // 构建路由
-        let app = Router::new()
-            // OpenAI Protocol
-            .route("/v1/models", get(handlers::openai::handle_list_models))
-            .route(
-                "/v1/chat/completions",
-                post(handlers::openai::handle_chat_completions),
-            )
-            .route(
-                "/v1/completions",
-                post(handlers::openai::handle_completions),
-            )
-            .route("/v1/responses", post(handlers::openai::handle_completions)) // 兼容 Codex CLI
-            .route(
-                "/v1/images/generations",
-                post(handlers::openai::handle_images_generations),
-            ) // 图像生成 API
-            .route(
-                "/v1/images/edits",
-                post(handlers::openai::handle_images_edits),
-            ) // 图像编辑 API
-            .route(
-                "/v1/audio/transcriptions",
-                post(handlers::audio::handle_audio_transcription),
-            ) // 音频转录 API (PR #311)
-            // Claude Protocol
-            .route("/v1/messages", post(handlers::claude::handle_messages))
-            .route(
-                "/v1/messages/count_tokens",
-                post(handlers::claude::handle_count_tokens),
-            )
-            .route(
-                "/v1/models/claude",
-                get(handlers::claude::handle_list_models),
-            )
-            // z.ai MCP (optional reverse-proxy)
-            .route(
-                "/mcp/web_search_prime/mcp",
-                any(handlers::mcp::handle_web_search_prime),
-            )
-	            .route(
-	                "/mcp/web_reader/mcp",
-	                any(handlers::mcp::handle_web_reader),
-	            )
-	            .route(
-	                "/mcp/zai-mcp-server/mcp",
-	                any(handlers::mcp::handle_zai_mcp_server),
-	            )
-	            // Gemini Protocol (Native)
-	            .route("/v1beta/models", get(handlers::gemini::handle_list_models))
-            // Handle both GET (get info) and POST (generateContent with colon) at the same route
-            .route(
-                "/v1beta/models/:model",
-                get(handlers::gemini::handle_get_model).post(handlers::gemini::handle_generate),
-            )
-            .route(
-                "/v1beta/models/:model/countTokens",
-                post(handlers::gemini::handle_count_tokens),
-            ) // Specific route priority
-            .route("/v1/models/detect", post(handlers::common::handle_detect_model))
-            .route("/v1/api/event_logging/batch", post(silent_ok_handler))
-            .route("/v1/api/event_logging", post(silent_ok_handler))
-            // Admin API (Web Control)
-            .route("/api/admin/accounts", get(handlers::admin::handle_list_accounts).post(handlers::admin::handle_add_account))
-            .route("/api/admin/accounts/current", get(handlers::admin::handle_get_current_account))
-            .route("/api/admin/accounts/batch_delete", post(handlers::admin::handle_delete_accounts))
-            .route("/api/admin/accounts/:id", axum::routing::delete(handlers::admin::handle_delete_account))
-            .route("/api/admin/accounts/switch", post(handlers::admin::handle_switch_account))
-            .route("/api/admin/accounts/reorder", post(handlers::admin::handle_reorder_accounts))
-            .route("/api/admin/accounts/:id/toggle_proxy", post(handlers::admin::handle_toggle_proxy))
-            .route("/api/admin/config", get(handlers::admin::handle_load_config).post(handlers::admin::handle_save_config))
-            .route("/api/admin/quota/refresh", post(handlers::admin::handle_refresh_all_quotas))
-            .route("/api/admin/quota/:id", post(handlers::admin::handle_refresh_account_quota))
-            // Proxy Control
-            .route("/api/admin/proxy/status", get(handlers::proxy_control::handle_get_proxy_status))
-            .route("/api/admin/proxy/mapping", post(handlers::proxy_control::handle_update_model_mapping))
-            .route("/api/admin/proxy/fetch_models", post(handlers::proxy_control::handle_fetch_zai_models))
-            .route("/api/admin/proxy/sessions", axum::routing::delete(handlers::proxy_control::handle_clear_session_bindings))
-            .route("/api/admin/utils/generate_key", post(handlers::proxy_control::handle_generate_api_key))
-            // Monitor
-            .route("/api/admin/monitor/stats", get(handlers::proxy_control::handle_get_proxy_stats))
-            .route("/api/admin/monitor/logs", get(handlers::proxy_control::handle_get_proxy_logs).delete(handlers::proxy_control::handle_clear_proxy_logs))
-            .route("/api/admin/monitor/enable", post(handlers::proxy_control::handle_set_monitor_enabled))
-            // Stub control
-            .route("/api/admin/proxy/start", post(handlers::proxy_control::handle_start_stop_stub))
-            .route("/api/admin/proxy/stop", post(handlers::proxy_control::handle_start_stop_stub))
-            .route("/api/admin/proxy/restart", post(handlers::proxy_control::handle_restart_server))
-            // Auth API (Web Login)
-            .route("/api/auth/status", get(handlers::web_auth::handle_auth_status))
-            .route("/api/auth/setup", post(handlers::web_auth::handle_setup_password))
-            .route("/api/auth/login", post(handlers::web_auth::handle_login))
-            .route("/api/auth/logout", post(handlers::web_auth::handle_logout))
-            // Web OAuth API (手动 Code 流程)
-            .route("/api/oauth/url", get(handlers::web_oauth::get_google_auth_url))
-            .route("/api/oauth/exchange", post(handlers::web_oauth::exchange_google_code))
-            
-            .route("/healthz", get(health_check_handler))
-            .fallback_service(
-                ServeDir::new("dist")
-                    .not_found_service(ServeFile::new("dist/index.html"))
-            )
-            .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
-            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::monitor::monitor_middleware))
-            .layer(TraceLayer::new_for_http())
-            // Admin 页面 session 认证中间件（先添加，后执行）
-            .layer(axum::middleware::from_fn_with_state(
-                state.sessions.clone(),
-                crate::proxy::middleware::admin_auth_middleware,
-            ))
-            // API Key 认证中间件（后添加，先执行）
-            .layer(axum::middleware::from_fn_with_state(
-                security_state.clone(),
-                crate::proxy::middleware::auth_middleware,
-            ))
-            .layer(crate::proxy::middleware::cors_layer())
-            .with_state(state);
+        let proxy_routes = build_proxy_routes();
+        let admin_routes = build_admin_routes();
+
+        // 创建关闭通道 (多个监听循环共享)
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
         // 绑定地址
         let addr = format!("{}:{}", host, port);
-        let listener = tokio::net::TcpListener::bind(&addr)
-            .await
-            .map_err(|e| format!("地址 {} 绑定失败: {}", addr, e))?;
+        let listener = BoundListener::bind_tcp(&addr).await?;
+
+        let mut tasks = Vec::new();
+        if admin_listener.enabled {
+            // 独立管理监听: 主端口仅提供代理 API，管理界面与 Admin API 走独立监听
+            let admin_bound = match admin_listener.unix_socket.as_deref().filter(|p| !p.trim().is_empty()) {
+                Some(path) => BoundListener::bind_unix(path)?,
+                None => {
+                    let admin_addr = format!("{}:{}", admin_listener.host, admin_listener.port);
+                    BoundListener::bind_tcp(&admin_addr).await?
+                }
+            };
 
-        tracing::info!("反代服务器启动在 http://{}", addr);
+            let public_app = apply_layers(proxy_routes, &state, &security_state);
+            // /healthz 已在代理路由中注册，仅独立监听时在管理路由补充
+            let admin_app = apply_layers(
+                admin_routes
+                    .route("/healthz", get(health_check_handler))
+                    .fallback_service(static_dist_service()),
+                &state,
+                &security_state,
+            );
 
-        // 创建关闭通道
-        let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
+            tracing::info!("反代服务器启动在 {} (仅代理 API)", listener.describe());
+            tracing::info!("管理界面启动在 {}", admin_bound.describe());
+
+            tasks.push(tokio::spawn(serve(listener, public_app, shutdown_rx.clone())));
+            tasks.push(tokio::spawn(serve(admin_bound, admin_app, shutdown_rx)));
+        } else {
+            let app = apply_layers(
+                proxy_routes.merge(admin_routes).fallback_service(static_dist_service()),
+                &state,
+                &security_state,
+            );
+
+            tracing::info!("反代服务器启动在 {}", listener.describe());
+
+            tasks.push(tokio::spawn(serve(listener, app, shutdown_rx)));
+        }
 
         let server_instance = Self {
             shutdown_tx: Some(shutdown_tx),
-            custom_mapping: custom_mapping_state.clone(),
+            routing_rules: routing_rules_state.clone(),
             proxy_state,
             security_state,
             zai_state,
+            thinking_state,
+            image_storage_state,
+            image_generation_state,
+            image_preprocess_state,
+            media_fetch_state,
+            model_fallback_state,
+            background_tasks_state,
+            parameter_policies_state,
+            hooks_state,
+            providers_state,
         };
 
-        // 在新任务中启动服务器
+        // 等待所有监听循环结束
         let handle = tokio::spawn(async move {
-            use hyper::server::conn::http1;
-            use hyper_util::rt::TokioIo;
-            use hyper_util::service::TowerToHyperService;
-
-            loop {
-                tokio::select! {
-                    res = listener.accept() => {
-                        match res {
-                            Ok((stream, _)) => {
-                                let io = TokioIo::new(stream);
-                                let service = TowerToHyperService::new(app.clone());
-
-                                tokio::task::spawn(async move {
-                                    if let Err(err) = http1::Builder::new()
-                                        .serve_connection(io, service)
-                                        .with_upgrades() // 支持 WebSocket (如果以后需要)
-                                        .await
-                                    {
-                                        debug!("连接处理结束或出错: {:?}", err);
-                                    }
-                                });
-                            }
-                            Err(e) => {
-                                error!("接收连接失败: {:?}", e);
-                            }
-                        }
-                    }
-                    _ = &mut shutdown_rx => {
-                        tracing::info!("反代服务器停止监听");
-                        break;
-                    }
-                }
+            for task in tasks {
+                let _ = task.await;
             }
         });
 
@@ -312,11 +320,274 @@ impl AxumServer {
     /// 停止服务器
     pub fn stop(mut self) {
         if let Some(tx) = self.shutdown_tx.take() {
-            let _ = tx.send(());
+            let _ = tx.send(true);
         }
     }
 }
 
+/// 代理 API 路由 (`/v1`、`/v1beta`、`/mcp`)
+fn build_proxy_routes() -> Router<AppState> {
+    use crate::proxy::handlers;
+
+    Router::new()
+        // OpenAI Protocol
+        .route("/v1/models", get(handlers::openai::handle_list_models))
+        .route(
+            "/v1/chat/completions",
+            post(handlers::openai::handle_chat_completions),
+        )
+        .route(
+            "/v1/completions",
+            post(handlers::openai::handle_completions),
+        )
+        .route("/v1/responses", post(handlers::openai::handle_responses)) // 兼容 Codex CLI
+        .route(
+            "/v1/responses/:id",
+            get(handlers::openai::handle_get_response).delete(handlers::openai::handle_delete_response),
+        )
+        .route(
+            "/v1/images/generations",
+            post(handlers::openai::handle_images_generations),
+        ) // 图像生成 API
+        .route("/v1/images/jobs", post(handlers::openai::handle_create_image_job)) // 异步图像生成
+        .route("/v1/images/jobs/:id", get(handlers::openai::handle_get_image_job))
+        .route(
+            "/v1/images/edits",
+            post(handlers::openai::handle_images_edits),
+        ) // 图像编辑 API
+        .route(
+            "/v1/embeddings",
+            post(handlers::embeddings::handle_embeddings),
+        ) // Embeddings API
+        .route(
+            "/v1/audio/transcriptions",
+            post(handlers::audio::handle_audio_transcription),
+        ) // 音频转录 API (PR #311)
+        .route(
+            "/v1/audio/translations",
+            post(handlers::audio::handle_audio_translation),
+        ) // 音频翻译 API
+        .route(
+            "/v1/audio/speech",
+            post(handlers::audio::handle_audio_speech),
+        ) // 语音合成 API
+        // Claude Protocol
+        .route("/v1/messages", post(handlers::claude::handle_messages))
+        .route(
+            "/v1/messages/count_tokens",
+            post(handlers::claude::handle_count_tokens),
+        )
+        .route(
+            "/v1/models/claude",
+            get(handlers::claude::handle_list_models),
+        )
+        // z.ai MCP (optional reverse-proxy)
+        .route(
+            "/mcp/web_search_prime/mcp",
+            any(handlers::mcp::handle_web_search_prime),
+        )
+        .route(
+            "/mcp/web_reader/mcp",
+            any(handlers::mcp::handle_web_reader),
+        )
+        .route(
+            "/mcp/zai-mcp-server/mcp",
+            any(handlers::mcp::handle_zai_mcp_server),
+        )
+        // Gemini Protocol (Native)
+        .route("/v1beta/models", get(handlers::gemini::handle_list_models))
+        // Handle both GET (get info) and POST (generateContent with colon) at the same route
+        .route(
+            "/v1beta/models/:model",
+            get(handlers::gemini::handle_get_model).post(handlers::gemini::handle_generate),
+        )
+        .route(
+            "/v1beta/models/:model/countTokens",
+            post(handlers::gemini::handle_count_tokens),
+        ) // Specific route priority
+        .route("/v1/models/detect", post(handlers::common::handle_detect_model))
+        .route("/v1/api/event_logging/batch", post(silent_ok_handler))
+        .route("/v1/api/event_logging", post(silent_ok_handler))
+        .route("/files/images/:id", get(handlers::files::handle_get_image)) // 签名链接，无需 API Key
+        .route("/healthz", get(health_check_handler))
+}
+
+/// 管理路由 (Admin API、Web 登录、Web OAuth)
+fn build_admin_routes() -> Router<AppState> {
+    use crate::proxy::handlers;
+
+    Router::new()
+        // Admin API (Web Control)
+        .route("/api/admin/accounts", get(handlers::admin::handle_list_accounts).post(handlers::admin::handle_add_account))
+        .route("/api/admin/accounts/current", get(handlers::admin::handle_get_current_account))
+        .route("/api/admin/accounts/batch_delete", post(handlers::admin::handle_delete_accounts))
+        .route("/api/admin/accounts/:id", axum::routing::delete(handlers::admin::handle_delete_account))
+        .route("/api/admin/accounts/switch", post(handlers::admin::handle_switch_account))
+        .route("/api/admin/accounts/reorder", post(handlers::admin::handle_reorder_accounts))
+        .route("/api/admin/accounts/:id/toggle_proxy", post(handlers::admin::handle_toggle_proxy))
+        .route("/api/admin/config", get(handlers::admin::handle_load_config).post(handlers::admin::handle_save_config))
+        .route("/api/admin/quota/refresh", post(handlers::admin::handle_refresh_all_quotas))
+        .route("/api/admin/quota/:id", post(handlers::admin::handle_refresh_account_quota))
+        // Proxy Control
+        .route("/api/admin/proxy/status", get(handlers::proxy_control::handle_get_proxy_status))
+        .route("/api/admin/proxy/mapping", post(handlers::proxy_control::handle_update_model_mapping))
+        .route("/api/admin/proxy/fetch_models", post(handlers::proxy_control::handle_fetch_zai_models))
+        .route("/api/admin/proxy/sessions", axum::routing::delete(handlers::proxy_control::handle_clear_session_bindings))
+        .route("/api/admin/utils/generate_key", post(handlers::proxy_control::handle_generate_api_key))
+        // Monitor
+        .route("/api/admin/monitor/stats", get(handlers::proxy_control::handle_get_proxy_stats))
+        .route("/api/admin/monitor/logs", get(handlers::proxy_control::handle_get_proxy_logs).delete(handlers::proxy_control::handle_clear_proxy_logs))
+        .route("/api/admin/monitor/enable", post(handlers::proxy_control::handle_set_monitor_enabled))
+        .route("/api/admin/images", get(handlers::admin::handle_list_images))
+        .route("/api/admin/images/:id", axum::routing::delete(handlers::admin::handle_delete_image))
+        // Debug
+        .route("/api/admin/debug/explain", post(handlers::debug::handle_explain))
+        // Stub control
+        .route("/api/admin/proxy/start", post(handlers::proxy_control::handle_start_stop_stub))
+        .route("/api/admin/proxy/stop", post(handlers::proxy_control::handle_start_stop_stub))
+        .route("/api/admin/proxy/restart", post(handlers::proxy_control::handle_restart_server))
+        // Auth API (Web Login)
+        .route("/api/auth/status", get(handlers::web_auth::handle_auth_status))
+        .route("/api/auth/setup", post(handlers::web_auth::handle_setup_password))
+        .route("/api/auth/login", post(handlers::web_auth::handle_login))
+        .route("/api/auth/logout", post(handlers::web_auth::handle_logout))
+        // Web OAuth API (手动 Code 流程)
+        .route("/api/oauth/url", get(handlers::web_oauth::get_google_auth_url))
+        .route("/api/oauth/exchange", post(handlers::web_oauth::exchange_google_code))
+}
+
+/// Web 前端静态资源 (dist)
+fn static_dist_service() -> ServeDir<tower_http::set_status::SetStatus<ServeFile>> {
+    ServeDir::new("dist").not_found_service(ServeFile::new("dist/index.html"))
+}
+
+/// 挂载公共中间件并注入状态
+fn apply_layers(
+    router: Router<AppState>,
+    state: &AppState,
+    security_state: &Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
+) -> Router {
+    router
+        .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::monitor::monitor_middleware))
+        .layer(TraceLayer::new_for_http())
+        // Admin 页面 session 认证中间件（先添加，后执行）
+        .layer(axum::middleware::from_fn_with_state(
+            state.sessions.clone(),
+            crate::proxy::middleware::admin_auth_middleware,
+        ))
+        // API Key 认证中间件（后添加，先执行）
+        .layer(axum::middleware::from_fn_with_state(
+            security_state.clone(),
+            crate::proxy::middleware::auth_middleware,
+        ))
+        .layer(crate::proxy::middleware::cors_layer())
+        .with_state(state.clone())
+}
+
+/// 已绑定的监听器 (TCP 或 Unix socket)
+enum BoundListener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, String),
+}
+
+impl BoundListener {
+    async fn bind_tcp(addr: &str) -> Result<Self, String> {
+        tokio::net::TcpListener::bind(addr)
+            .await
+            .map(Self::Tcp)
+            .map_err(|e| format!("地址 {} 绑定失败: {}", addr, e))
+    }
+
+    #[cfg(unix)]
+    fn bind_unix(path: &str) -> Result<Self, String> {
+        // 清理上次运行残留的 socket 文件
+        if std::path::Path::new(path).exists() {
+            let _ = std::fs::remove_file(path);
+        }
+        tokio::net::UnixListener::bind(path)
+            .map(|l| Self::Unix(l, path.to_string()))
+            .map_err(|e| format!("Unix socket {} 绑定失败: {}", path, e))
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(path: &str) -> Result<Self, String> {
+        Err(format!("当前平台不支持 Unix socket 监听: {}", path))
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Tcp(l) => l
+                .local_addr()
+                .map(|a| format!("http://{}", a))
+                .unwrap_or_else(|_| "tcp".to_string()),
+            #[cfg(unix)]
+            Self::Unix(_, path) => format!("unix:{}", path),
+        }
+    }
+
+    /// 接收一个连接并交给 hyper 处理
+    async fn accept_and_serve(&self, app: &Router) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(l) => {
+                let (stream, _) = l.accept().await?;
+                serve_connection(stream, app.clone());
+            }
+            #[cfg(unix)]
+            Self::Unix(l, _) => {
+                let (stream, _) = l.accept().await?;
+                serve_connection(stream, app.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn serve_connection<S>(stream: S, app: Router)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    use hyper::server::conn::http1;
+    use hyper_util::rt::TokioIo;
+    use hyper_util::service::TowerToHyperService;
+
+    let io = TokioIo::new(stream);
+    let service = TowerToHyperService::new(app);
+
+    tokio::task::spawn(async move {
+        if let Err(err) = http1::Builder::new()
+            .serve_connection(io, service)
+            .with_upgrades() // 支持 WebSocket (如果以后需要)
+            .await
+        {
+            debug!("连接处理结束或出错: {:?}", err);
+        }
+    });
+}
+
+/// 监听循环，收到关闭信号后退出
+async fn serve(listener: BoundListener, app: Router, mut shutdown_rx: watch::Receiver<bool>) {
+    loop {
+        tokio::select! {
+            res = listener.accept_and_serve(&app) => {
+                if let Err(e) = res {
+                    error!("接收连接失败: {:?}", e);
+                }
+            }
+            _ = shutdown_rx.changed() => {
+                tracing::info!("{} 停止监听", listener.describe());
+                break;
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    if let BoundListener::Unix(_, path) = &listener {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
 // ===== API 处理器 (旧代码已移除，由 src/proxy/handlers/* 接管) =====
 
 /// 健康检查处理器
@@ -331,3 +602,15 @@ async fn health_check_handler() -> Response {
 async fn silent_ok_handler() -> Response {
     StatusCode::OK.into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merged_routes_build() {
+        // 未启用独立管理监听时两组路由合并到同一端口，不能有重复路由
+        let _ = build_proxy_routes().merge(build_admin_routes()).fallback_service(static_dist_service());
+        let _ = build_admin_routes().route("/healthz", get(health_check_handler));
+    }
+}
@@ -25,8 +25,10 @@ mod tests {
             ],
             system: None,
             tools: None, // 无工具调用
+            tool_choice: None,
             stream: false,
             max_tokens: None,
+            stop_sequences: None,
             temperature: None,
             top_p: None,
             top_k: None,
@@ -21,6 +21,52 @@ pub struct ProxyToken {
     pub subscription_tier: Option<String>, // "FREE" | "PRO" | "ULTRA"
 }
 
+/// 账号选择预览 (调试用，见 `TokenManager::preview_token`)
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TokenSelectionPreview {
+    /// 将被选中的账号 (邮箱)，None 表示没有可用账号
+    pub account: Option<String>,
+    #[serde(skip)]
+    pub project_id: Option<String>,
+    /// 选择原因
+    pub reason: String,
+    /// 按调度顺序 (订阅等级) 排列的候选账号状态
+    pub candidates: Vec<TokenCandidate>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TokenCandidate {
+    pub email: String,
+    pub subscription_tier: Option<String>,
+    /// 对目标模型是否处于限流
+    pub rate_limited: bool,
+    pub reset_seconds: Option<u64>,
+    /// access_token 即将过期 (实际请求时会先刷新)
+    pub needs_refresh: bool,
+}
+
+/// 一次账号选择的输入 (get_token_internal 与 preview_token 共用)
+struct SelectionContext<'a> {
+    /// 按订阅等级排序的账号快照
+    tokens: &'a [ProxyToken],
+    scheduling: StickySessionConfig,
+    session_id: Option<&'a str>,
+    model: Option<&'a str>,
+    /// 无状态请求 (图像/Embedding/语音) 不参与 60s 账号锁定
+    stateless_group: bool,
+    last_used: Option<(String, std::time::Instant)>,
+    /// true: 实际请求 (推进轮询游标、建立/解除会话绑定)；false: 仅预览
+    commit: bool,
+}
+
+/// 账号选择结果
+struct TokenPick {
+    token: ProxyToken,
+    reason: String,
+    /// 轮询新选中的账号，需要写回 last_used_account (60s 锁定)
+    lock_last_used: bool,
+}
+
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>,  // account_id -> ProxyToken
     current_index: Arc<AtomicUsize>,
@@ -182,22 +228,29 @@ impl TokenManager {
     /// 参数 `force_rotate` 为 true 时将忽略锁定，强制切换账号
     /// 参数 `session_id` 用于跨请求维持会话粘性
     pub async fn get_token(&self, quota_group: &str, force_rotate: bool, session_id: Option<&str>) -> Result<(String, String, String), String> {
+        self.get_token_for_model(quota_group, force_rotate, session_id, None).await
+    }
+
+    /// 按目标模型获取 Token：仅针对其他模型的限流记录不会排除账号
+    /// (用于模型回退链，例如 opus 额度耗尽后同一账号仍可服务 sonnet)
+    pub async fn get_token_for_model(
+        &self,
+        quota_group: &str,
+        force_rotate: bool,
+        session_id: Option<&str>,
+        model: Option<&str>,
+    ) -> Result<(String, String, String), String> {
         // 【优化 Issue #284】添加 5 秒超时，防止死锁
         let timeout_duration = std::time::Duration::from_secs(5);
-        match tokio::time::timeout(timeout_duration, self.get_token_internal(quota_group, force_rotate, session_id)).await {
+        match tokio::time::timeout(timeout_duration, self.get_token_internal(quota_group, force_rotate, session_id, model)).await {
             Ok(result) => result,
             Err(_) => Err("Token acquisition timeout (5s) - system too busy or deadlock detected".to_string()),
         }
     }
 
-    /// 内部实现：获取 Token 的核心逻辑
-    async fn get_token_internal(&self, quota_group: &str, force_rotate: bool, session_id: Option<&str>) -> Result<(String, String, String), String> {
+    /// 账号快照，按订阅等级排序
+    fn sorted_snapshot(&self) -> Vec<ProxyToken> {
         let mut tokens_snapshot: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
-        let total = tokens_snapshot.len();
-        if total == 0 {
-            return Err("Token pool is empty".to_string());
-        }
-
         // ===== 【优化】根据订阅等级排序 (优先级: ULTRA > PRO > FREE) =====
         // 理由: ULTRA/PRO 重置快，优先消耗；FREE 重置慢，用于兜底
         tokens_snapshot.sort_by(|a, b| {
@@ -209,117 +262,251 @@ impl TokenManager {
             };
             tier_priority(&a.subscription_tier).cmp(&tier_priority(&b.subscription_tier))
         });
+        tokens_snapshot
+    }
 
-        // 0. 读取当前调度配置
-        let scheduling = self.sticky_config.read().await.clone();
-        use crate::proxy::sticky_config::SchedulingMode;
+    /// 预览 get_token 将选择的账号及原因
+    /// 与 get_token_internal 共用 pick_token 的选择逻辑，但不推进轮询、不建立/解除会话绑定、不刷新 Token
+    pub async fn preview_token(
+        &self,
+        quota_group: &str,
+        force_rotate: bool,
+        session_id: Option<&str>,
+        model: Option<&str>,
+    ) -> TokenSelectionPreview {
+        let tokens_snapshot = self.sorted_snapshot();
+        let now = chrono::Utc::now().timestamp();
+        let candidates = tokens_snapshot
+            .iter()
+            .map(|t| TokenCandidate {
+                email: t.email.clone(),
+                subscription_tier: t.subscription_tier.clone(),
+                rate_limited: self.is_token_limited(t, model),
+                reset_seconds: self.token_reset_seconds(t, model),
+                needs_refresh: now >= t.timestamp - 300,
+            })
+            .collect();
 
-        // 【优化 Issue #284】将锁操作移到循环外，避免重复获取锁
-        // 预先获取 last_used_account 的快照，避免在循环中多次加锁
-        let last_used_account_id = if quota_group != "image_gen" {
-            let last_used = self.last_used_account.lock().await;
-            last_used.clone()
-        } else {
+        if tokens_snapshot.is_empty() {
+            return TokenSelectionPreview {
+                account: None,
+                project_id: None,
+                reason: "Token pool is empty".to_string(),
+                candidates,
+            };
+        }
+
+        let ctx = self.selection_context(&tokens_snapshot, quota_group, session_id, model, false).await;
+        let mut notes = Vec::new();
+        let mut preview = match self.pick_token(&ctx, &HashSet::new(), force_rotate, &mut notes) {
+            Some(pick) => TokenSelectionPreview {
+                account: Some(pick.token.email.clone()),
+                project_id: pick.token.project_id.clone(),
+                reason: pick.reason,
+                candidates: Vec::new(),
+            },
+            None => {
+                let min_wait = tokens_snapshot
+                    .iter()
+                    .filter_map(|t| self.token_reset_seconds(t, model))
+                    .min();
+                TokenSelectionPreview {
+                    account: None,
+                    project_id: None,
+                    reason: match min_wait {
+                        Some(wait) => format!("all accounts are rate-limited, shortest wait {}s", wait),
+                        None => "all accounts failed or unhealthy".to_string(),
+                    },
+                    candidates: Vec::new(),
+                }
+            }
+        };
+
+        if !notes.is_empty() {
+            preview.reason = format!("{}; {}", notes.join("; "), preview.reason);
+        }
+        preview.candidates = candidates;
+        preview
+    }
+
+    /// 读取调度配置与 last_used_account 快照，构建一次选择的上下文
+    async fn selection_context<'a>(
+        &self,
+        tokens: &'a [ProxyToken],
+        quota_group: &str,
+        session_id: Option<&'a str>,
+        model: Option<&'a str>,
+        commit: bool,
+    ) -> SelectionContext<'a> {
+        // 图像生成、Embedding 与语音合成为无状态请求，不参与 60s 账号锁定
+        let stateless_group = matches!(quota_group, "image_gen" | "embedding" | "tts");
+        // 【优化 Issue #284】预先获取 last_used_account 的快照，避免在循环中多次加锁
+        let last_used = if stateless_group {
             None
+        } else {
+            self.last_used_account.lock().await.clone()
         };
+        SelectionContext {
+            tokens,
+            scheduling: self.sticky_config.read().await.clone(),
+            session_id,
+            model,
+            stateless_group,
+            last_used,
+            commit,
+        }
+    }
 
-        let mut attempted: HashSet<String> = HashSet::new();
-        let mut last_error: Option<String> = None;
-        let mut need_update_last_used: Option<(String, std::time::Instant)> = None;
+    /// 选择账号 (粘性会话 > 60s 全局锁定 > 轮询)，get_token_internal 与 preview_token 共用
+    /// ctx.commit 为 false 时只读取状态: 不推进轮询游标、不建立/解除会话绑定
+    fn pick_token(
+        &self,
+        ctx: &SelectionContext<'_>,
+        attempted: &HashSet<String>,
+        rotate: bool,
+        notes: &mut Vec<String>,
+    ) -> Option<TokenPick> {
+        use crate::proxy::sticky_config::SchedulingMode;
 
-        for attempt in 0..total {
-            let rotate = force_rotate || attempt > 0;
+        let total = ctx.tokens.len();
+        if total == 0 {
+            return None;
+        }
+        let sticky_sid = ctx.session_id.filter(|_| ctx.scheduling.mode != SchedulingMode::PerformanceFirst);
 
-            // ===== 【核心】粘性会话与智能调度逻辑 =====
-            let mut target_token: Option<ProxyToken> = None;
-            
-            // 模式 A: 粘性会话处理 (CacheFirst 或 Balance 且有 session_id)
-            if !rotate && session_id.is_some() && scheduling.mode != SchedulingMode::PerformanceFirst {
-                let sid = session_id.unwrap();
-                
-                // 1. 检查会话是否已绑定账号
-                if let Some(bound_id) = self.session_accounts.get(sid).map(|v| v.clone()) {
-                    // 2. 检查绑定的账号是否限流 (使用精准的剩余时间接口)
-                    let reset_sec = self.rate_limit_tracker.get_remaining_wait(&bound_id);
-                    if reset_sec > 0 {
-                        // 【修复 Issue #284】立即解绑并切换账号，不再阻塞等待
-                        // 原因：阻塞等待会导致并发请求时客户端 socket 超时 (UND_ERR_SOCKET)
+        // 模式 A: 粘性会话处理 (CacheFirst 或 Balance 且有 session_id)
+        if let Some(sid) = sticky_sid.filter(|_| !rotate) {
+            // 1. 检查会话是否已绑定账号
+            if let Some(bound_id) = self.session_accounts.get(sid).map(|v| v.clone()) {
+                let bound = ctx.tokens.iter().find(|t| t.account_id == bound_id);
+                // 2. 检查绑定的账号对本次模型是否限流 (使用精准的剩余时间接口)
+                let reset_sec = bound
+                    .and_then(|t| self.token_reset_seconds(t, ctx.model))
+                    .unwrap_or_else(|| self.rate_limit_tracker.get_remaining_wait_for_model(&bound_id, ctx.model));
+                if reset_sec > 0 {
+                    // 【修复 Issue #284】立即解绑并切换账号，不再阻塞等待
+                    // 原因：阻塞等待会导致并发请求时客户端 socket 超时 (UND_ERR_SOCKET)
+                    if ctx.commit {
                         tracing::warn!("Session {} bound account {} is rate-limited ({}s remaining). Unbinding and switching to next available account.", sid, bound_id, reset_sec);
                         self.session_accounts.remove(sid);
-                    } else if !attempted.contains(&bound_id) {
-                        // 3. 账号可用且未被标记为尝试失败，优先复用
-                        if let Some(found) = tokens_snapshot.iter().find(|t| t.account_id == bound_id) {
-                            tracing::debug!("Sticky Session: Successfully reusing bound account {} for session {}", found.email, sid);
-                            target_token = Some(found.clone());
-                        }
+                    }
+                    let account = bound.map(|t| t.email.as_str()).unwrap_or(&bound_id);
+                    notes.push(format!("session {} was bound to {} but it is rate-limited ({}s), unbound", sid, account, reset_sec));
+                } else if !attempted.contains(&bound_id) {
+                    // 3. 账号可用且未被标记为尝试失败，优先复用
+                    if let Some(found) = bound {
+                        tracing::debug!("Sticky Session: Successfully reusing bound account {} for session {}", found.email, sid);
+                        return Some(TokenPick {
+                            token: found.clone(),
+                            reason: format!("sticky session {} is bound to this account", sid),
+                            lock_last_used: false,
+                        });
                     }
                 }
             }
+        }
 
-            // 模式 B: 原子化 60s 全局锁定 (针对无 session_id 情况的默认保护)
-            if target_token.is_none() && !rotate && quota_group != "image_gen" {
-                // 【优化】使用预先获取的快照，不再在循环内加锁
-                if let Some((account_id, last_time)) = &last_used_account_id {
-                    if last_time.elapsed().as_secs() < 60 && !attempted.contains(account_id) {
-                        if let Some(found) = tokens_snapshot.iter().find(|t| &t.account_id == account_id) {
-                            tracing::debug!("60s Window: Force reusing last account: {}", found.email);
-                            target_token = Some(found.clone());
-                        }
+        // 模式 B: 原子化 60s 全局锁定 (针对无 session_id 情况的默认保护)
+        let windowed = !rotate && !ctx.stateless_group;
+        if windowed {
+            if let Some((account_id, last_time)) = &ctx.last_used {
+                let elapsed = last_time.elapsed().as_secs();
+                // 锁定账号对本次模型已限流时不复用，交给轮询选择其他账号
+                if elapsed < 60 && !attempted.contains(account_id) {
+                    if let Some(found) = ctx
+                        .tokens
+                        .iter()
+                        .find(|t| &t.account_id == account_id && !self.is_token_limited(t, ctx.model))
+                    {
+                        tracing::debug!("60s Window: Force reusing last account: {}", found.email);
+                        return Some(TokenPick {
+                            token: found.clone(),
+                            reason: format!("reusing last account within 60s window (used {}s ago)", elapsed),
+                            lock_last_used: false,
+                        });
                     }
                 }
-                
-                // 若无锁定，则轮询选择新账号
-                if target_token.is_none() {
-                    let start_idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
-                    for offset in 0..total {
-                        let idx = (start_idx + offset) % total;
-                        let candidate = &tokens_snapshot[idx];
-                        if attempted.contains(&candidate.account_id) {
-                            continue;
-                        }
+            }
+        }
 
-                        // 【新增】主动避开限流或 5xx 锁定的账号 (来自 PR #28 的高可用思路)
-                        if self.is_rate_limited(&candidate.account_id) {
-                            continue;
-                        }
+        // 轮询选择新账号 (模式 B 无锁定时) 或 模式 C: 纯轮询 / 强制轮换
+        let cursor = if ctx.commit {
+            self.current_index.fetch_add(1, Ordering::SeqCst)
+        } else {
+            self.current_index.load(Ordering::SeqCst)
+        };
+        let start_idx = cursor % total;
+        let mut skipped = 0;
+        for offset in 0..total {
+            let candidate = &ctx.tokens[(start_idx + offset) % total];
+            if attempted.contains(&candidate.account_id) {
+                continue;
+            }
+            // 【新增】主动避开限流或 5xx 锁定的账号 (来自 PR #28 的高可用思路)
+            if self.is_token_limited(candidate, ctx.model) {
+                skipped += 1;
+                continue;
+            }
 
-                        target_token = Some(candidate.clone());
-                        // 【优化】标记需要更新，稍后统一写回
-                        need_update_last_used = Some((candidate.account_id.clone(), std::time::Instant::now()));
-                        
-                        // 如果是会话首次分配且需要粘性，在此建立绑定
-                        if let Some(sid) = session_id {
-                            if scheduling.mode != SchedulingMode::PerformanceFirst {
-                                self.session_accounts.insert(sid.to_string(), candidate.account_id.clone());
-                                tracing::debug!("Sticky Session: Bound new account {} to session {}", candidate.email, sid);
-                            }
-                        }
-                        break;
-                    }
+            let mut reason = format!("round-robin at index {}", start_idx);
+            if skipped > 0 {
+                reason.push_str(&format!(", skipped {} rate-limited account(s)", skipped));
+            }
+            if rotate {
+                tracing::debug!("Force Rotation: Switched to account: {}", candidate.email);
+                reason.push_str(", forced rotation");
+            }
+            // 如果是会话首次分配且需要粘性，在此建立绑定
+            if let Some(sid) = sticky_sid.filter(|_| windowed) {
+                if ctx.commit {
+                    self.session_accounts.insert(sid.to_string(), candidate.account_id.clone());
+                    tracing::debug!("Sticky Session: Bound new account {} to session {}", candidate.email, sid);
                 }
-            } else if target_token.is_none() {
-                // 模式 C: 纯轮询模式 (Round-robin) 或强制轮换
-                let start_idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
-                for offset in 0..total {
-                    let idx = (start_idx + offset) % total;
-                    let candidate = &tokens_snapshot[idx];
-                    if attempted.contains(&candidate.account_id) {
-                        continue;
-                    }
+                reason.push_str(&format!(", binds to session {}", sid));
+            }
+            return Some(TokenPick {
+                token: candidate.clone(),
+                reason,
+                lock_last_used: windowed,
+            });
+        }
+        None
+    }
 
-                    // 【新增】主动避开限流或 5xx 锁定的账号
-                    if self.is_rate_limited(&candidate.account_id) {
-                        continue;
-                    }
+    /// 内部实现：获取 Token 的核心逻辑
+    async fn get_token_internal(
+        &self,
+        quota_group: &str,
+        force_rotate: bool,
+        session_id: Option<&str>,
+        model: Option<&str>,
+    ) -> Result<(String, String, String), String> {
+        let tokens_snapshot = self.sorted_snapshot();
+        let total = tokens_snapshot.len();
+        if total == 0 {
+            return Err("Token pool is empty".to_string());
+        }
 
-                    target_token = Some(candidate.clone());
-                    
-                    if rotate {
-                        tracing::debug!("Force Rotation: Switched to account: {}", candidate.email);
-                    }
-                    break;
+        // 0. 读取当前调度配置与 last_used_account 快照 (循环外只加锁一次)
+        let ctx = self.selection_context(&tokens_snapshot, quota_group, session_id, model, true).await;
+        let stateless_group = ctx.stateless_group;
+        let last_used_account_id = ctx.last_used.clone();
+
+        let mut attempted: HashSet<String> = HashSet::new();
+        let mut last_error: Option<String> = None;
+        let mut need_update_last_used: Option<(String, std::time::Instant)> = None;
+
+        for attempt in 0..total {
+            let rotate = force_rotate || attempt > 0;
+
+            // ===== 【核心】粘性会话与智能调度逻辑 (见 pick_token) =====
+            let target_token = self.pick_token(&ctx, &attempted, rotate, &mut Vec::new()).map(|pick| {
+                if pick.lock_last_used {
+                    // 【优化】标记需要更新，稍后统一写回
+                    need_update_last_used = Some((pick.token.account_id.clone(), std::time::Instant::now()));
                 }
-            }
+                pick.token
+            });
             
             let mut token = match target_token {
                 Some(t) => t,
@@ -329,7 +516,7 @@ impl TokenManager {
                     
                     // 计算最短等待时间
                     let min_wait = tokens_snapshot.iter()
-                        .filter_map(|t| self.rate_limit_tracker.get_reset_seconds(&t.account_id))
+                        .filter_map(|t| self.token_reset_seconds(t, model))
                         .min();
                     
                     // Layer 1: 如果最短等待时间 <= 2秒,执行缓冲延迟
@@ -345,7 +532,7 @@ impl TokenManager {
                             
                             // 重新尝试选择账号
                             let retry_token = tokens_snapshot.iter()
-                                .find(|t| !attempted.contains(&t.account_id) && !self.is_rate_limited(&t.account_id));
+                                .find(|t| !attempted.contains(&t.account_id) && !self.is_token_limited(t, model));
                             
                             if let Some(t) = retry_token {
                                 tracing::info!("✅ Buffer delay successful! Found available account: {}", t.email);
@@ -430,10 +617,10 @@ impl TokenManager {
                         attempted.insert(token.account_id.clone());
 
                         // 【优化】标记需要清除锁定，避免在循环内加锁
-                        if quota_group != "image_gen" {
-                            if matches!(&last_used_account_id, Some((id, _)) if id == &token.account_id) {
-                                need_update_last_used = Some((String::new(), std::time::Instant::now())); // 空字符串表示需要清除
-                            }
+                        if !stateless_group
+                            && matches!(&last_used_account_id, Some((id, _)) if id == &token.account_id)
+                        {
+                            need_update_last_used = Some((String::new(), std::time::Instant::now())); // 空字符串表示需要清除
                         }
                         continue;
                     }
@@ -459,10 +646,10 @@ impl TokenManager {
                         attempted.insert(token.account_id.clone());
 
                         // 【优化】标记需要清除锁定，避免在循环内加锁
-                        if quota_group != "image_gen" {
-                            if matches!(&last_used_account_id, Some((id, _)) if id == &token.account_id) {
-                                need_update_last_used = Some((String::new(), std::time::Instant::now())); // 空字符串表示需要清除
-                            }
+                        if !stateless_group
+                            && matches!(&last_used_account_id, Some((id, _)) if id == &token.account_id)
+                        {
+                            need_update_last_used = Some((String::new(), std::time::Instant::now())); // 空字符串表示需要清除
                         }
                         continue;
                     }
@@ -471,7 +658,7 @@ impl TokenManager {
 
             // 【优化】在成功返回前，统一更新 last_used_account（如果需要）
             if let Some((new_account_id, new_time)) = need_update_last_used {
-                if quota_group != "image_gen" {
+                if !stateless_group {
                     let mut last_used = self.last_used_account.lock().await;
                     if new_account_id.is_empty() {
                         // 空字符串表示需要清除锁定
@@ -565,18 +752,21 @@ impl TokenManager {
     // ===== 限流管理方法 =====
     
     /// 标记账号限流(从外部调用,通常在 handler 中)
+    /// model 为本次请求实际使用的上游模型，只锁定该模型；None 表示账号级别错误
     pub fn mark_rate_limited(
         &self,
         account_id: &str,
         status: u16,
         retry_after_header: Option<&str>,
         error_body: &str,
+        model: Option<&str>,
     ) {
-        self.rate_limit_tracker.parse_from_error(
+        self.rate_limit_tracker.parse_from_error_for_model(
             account_id,
             status,
             retry_after_header,
             error_body,
+            model,
         );
     }
     
@@ -585,6 +775,20 @@ impl TokenManager {
         self.rate_limit_tracker.is_rate_limited(account_id)
     }
     
+    /// 检查账号对指定模型是否限流
+    /// handler 以邮箱标记限流，调度以 account_id 为键，两者都需检查
+    fn is_token_limited(&self, token: &ProxyToken, model: Option<&str>) -> bool {
+        self.rate_limit_tracker.is_rate_limited_for_model(&token.account_id, model)
+            || self.rate_limit_tracker.is_rate_limited_for_model(&token.email, model)
+    }
+
+    /// 账号对指定模型距离限流重置的秒数 (同时检查 account_id 与邮箱)
+    fn token_reset_seconds(&self, token: &ProxyToken, model: Option<&str>) -> Option<u64> {
+        let by_id = self.rate_limit_tracker.get_reset_seconds_for_model(&token.account_id, model);
+        let by_email = self.rate_limit_tracker.get_reset_seconds_for_model(&token.email, model);
+        by_id.max(by_email)
+    }
+
     /// 获取距离限流重置还有多少秒
     #[allow(dead_code)]
     pub fn get_rate_limit_reset_seconds(&self, account_id: &str) -> Option<u64> {
@@ -607,8 +811,8 @@ impl TokenManager {
     /// 
     /// 在请求成功完成后调用，将该账号的失败计数归零，
     /// 下次失败时从最短的锁定时间开始（智能限流）。
-    pub fn mark_account_success(&self, account_id: &str) {
-        self.rate_limit_tracker.mark_success(account_id);
+    pub fn mark_account_success(&self, account_id: &str, model: Option<&str>) {
+        self.rate_limit_tracker.mark_success_for_model(account_id, model);
     }
     
     /// 从账号文件获取配额刷新时间
@@ -767,11 +971,12 @@ impl TokenManager {
             } else {
                 tracing::debug!("账号 {} 的 429 响应包含 quotaResetDelay,直接使用 API 返回的时间", account_id);
             }
-            self.rate_limit_tracker.parse_from_error(
+            self.rate_limit_tracker.parse_from_error_for_model(
                 account_id,
                 status,
                 retry_after_header,
                 error_body,
+                model,
             );
             return;
         }
@@ -805,11 +1010,12 @@ impl TokenManager {
         
         // 都失败了,回退到指数退避策略
         tracing::warn!("账号 {} 无法获取配额刷新时间,使用指数退避策略", account_id);
-        self.rate_limit_tracker.parse_from_error(
+        self.rate_limit_tracker.parse_from_error_for_model(
             account_id,
             status,
             retry_after_header,
             error_body,
+            model,
         );
     }
 
@@ -847,3 +1053,97 @@ fn truncate_reason(reason: &str, max_len: usize) -> String {
     s.push('…');
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(id: &str, tier: &str) -> ProxyToken {
+        ProxyToken {
+            account_id: id.to_string(),
+            access_token: format!("at-{}", id),
+            refresh_token: format!("rt-{}", id),
+            expires_in: 3600,
+            timestamp: chrono::Utc::now().timestamp() + 3600,
+            email: format!("{}@example.com", id),
+            account_path: PathBuf::new(),
+            project_id: Some(format!("project-{}", id)),
+            subscription_tier: Some(tier.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preview_token_has_no_side_effects() {
+        let manager = TokenManager::new(std::env::temp_dir());
+        assert!(manager.preview_token("agent", false, None, None).await.account.is_none());
+
+        manager.tokens.insert("free".to_string(), token("free", "FREE"));
+        manager.tokens.insert("pro".to_string(), token("pro", "PRO"));
+
+        // 轮询从订阅等级最高的账号开始，且不推进轮询、不绑定会话
+        let preview = manager.preview_token("agent", false, Some("sid-1"), Some("gemini-3-flash")).await;
+        assert_eq!(preview.account.as_deref(), Some("pro@example.com"));
+        assert_eq!(preview.project_id.as_deref(), Some("project-pro"));
+        assert!(preview.reason.starts_with("round-robin"));
+        assert_eq!(preview.candidates.len(), 2);
+        assert_eq!(manager.current_index.load(Ordering::SeqCst), 0);
+        assert!(manager.session_accounts.get("sid-1").is_none());
+
+        // 限流账号被跳过
+        manager.mark_rate_limited("pro@example.com", 429, Some("60"), "", None);
+        let preview = manager.preview_token("agent", false, None, Some("gemini-3-flash")).await;
+        assert_eq!(preview.account.as_deref(), Some("free@example.com"));
+        assert!(preview.reason.contains("skipped 1"));
+        assert!(preview.candidates[0].rate_limited);
+
+        // 已绑定的会话优先
+        manager.session_accounts.insert("sid-2".to_string(), "free".to_string());
+        let preview = manager.preview_token("agent", false, Some("sid-2"), None).await;
+        assert_eq!(preview.account.as_deref(), Some("free@example.com"));
+        assert!(preview.reason.contains("sticky session sid-2"));
+    }
+
+    #[tokio::test]
+    async fn test_preview_matches_actual_selection() {
+        let manager = TokenManager::new(std::env::temp_dir());
+        for (id, tier) in [("ultra", "ULTRA"), ("pro", "PRO"), ("free", "FREE")] {
+            manager.tokens.insert(id.to_string(), token(id, tier));
+        }
+        manager.mark_rate_limited("ultra@example.com", 429, Some("60"), "", Some("gemini-3-flash"));
+
+        for (force_rotate, session_id) in [(false, None), (false, Some("sid-1")), (true, None), (false, Some("sid-1"))] {
+            let preview = manager.preview_token("agent", force_rotate, session_id, Some("gemini-3-flash")).await;
+            let (_, _, email) = manager
+                .get_token_for_model("agent", force_rotate, session_id, Some("gemini-3-flash"))
+                .await
+                .unwrap();
+            assert_eq!(preview.account.as_deref(), Some(email.as_str()), "{}", preview.reason);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_model_lock_leaves_fallback_model_available() {
+        let manager = TokenManager::new(std::env::temp_dir());
+        manager.tokens.insert("free".to_string(), token("free", "FREE"));
+        manager.tokens.insert("pro".to_string(), token("pro", "PRO"));
+        for email in ["free@example.com", "pro@example.com"] {
+            manager.mark_rate_limited(email, 429, Some("60"), "", Some("claude-opus-4-5-thinking"));
+        }
+
+        // 主模型在所有账号上都已限流
+        let primary = manager.get_token_for_model("agent", false, None, Some("claude-opus-4-5-thinking")).await;
+        assert!(primary.is_err());
+
+        // 回退模型仍可使用同一批账号，且 60s 复用窗口不会选中已限流的账号
+        let (_, _, first) = manager
+            .get_token_for_model("agent", false, None, Some("gemini-3-pro-high"))
+            .await
+            .unwrap();
+        manager.mark_rate_limited(&first, 429, Some("60"), "", Some("gemini-3-pro-high"));
+        let (_, _, second) = manager
+            .get_token_for_model("agent", false, None, Some("gemini-3-pro-high"))
+            .await
+            .unwrap();
+        assert_ne!(first, second);
+    }
+}
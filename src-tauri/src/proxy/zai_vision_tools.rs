@@ -2,7 +2,7 @@ use base64::Engine;
 use serde_json::{json, Value};
 use tokio::time::Duration;
 
-use crate::proxy::config::UpstreamProxyConfig;
+use crate::proxy::config::{LocalFileAccessConfig, UpstreamProxyConfig};
 use crate::proxy::ZaiConfig;
 
 const ZAI_PAAZ_CHAT_COMPLETIONS_URL: &str = "https://api.z.ai/api/paas/v4/chat/completions";
@@ -48,13 +48,31 @@ fn file_ext(path: &std::path::Path) -> Option<String> {
         .map(|s| s.to_string())
 }
 
-fn encode_file_as_data_url(path: &std::path::Path, mime: &str) -> Result<String, String> {
-    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+/// 按本地文件访问策略读取文件 (工具自身的大小上限与策略上限取较小值)
+fn read_local_media(
+    source: &str,
+    max_size_mb: u64,
+    policy: &LocalFileAccessConfig,
+    kind: &str,
+) -> Result<(Vec<u8>, std::path::PathBuf), String> {
+    let policy = LocalFileAccessConfig {
+        max_mb: policy.max_mb.min(max_size_mb),
+        ..policy.clone()
+    };
+    crate::proxy::local_media::read_local_file(source, &policy)
+        .map_err(|e| format!("{} file rejected: {}", kind, e))
+}
+
+fn encode_data_url(bytes: &[u8], mime: &str) -> String {
     let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
-    Ok(format!("data:{};base64,{}", mime, encoded))
+    format!("data:{};base64,{}", mime, encoded)
 }
 
-fn image_source_to_content(image_source: &str, max_size_mb: u64) -> Result<Value, String> {
+fn image_source_to_content(
+    image_source: &str,
+    max_size_mb: u64,
+    policy: &LocalFileAccessConfig,
+) -> Result<Value, String> {
     if is_http_url(image_source) {
         return Ok(json!({
             "type": "image_url",
@@ -62,27 +80,20 @@ fn image_source_to_content(image_source: &str, max_size_mb: u64) -> Result<Value
         }));
     }
 
-    let path = std::path::Path::new(image_source);
-    let meta = std::fs::metadata(path).map_err(|_| "Image file not found".to_string())?;
-    let max_size = max_size_mb * 1024 * 1024;
-    if meta.len() > max_size {
-        return Err(format!(
-            "Image file too large ({} bytes), max {} MB",
-            meta.len(),
-            max_size_mb
-        ));
-    }
-
-    let ext = file_ext(path).ok_or("Unsupported image format".to_string())?;
+    let (bytes, path) = read_local_media(image_source, max_size_mb, policy, "Image")?;
+    let ext = file_ext(&path).ok_or("Unsupported image format".to_string())?;
     let mime = mime_for_image_extension(&ext).ok_or("Unsupported image format".to_string())?;
-    let data_url = encode_file_as_data_url(path, mime)?;
     Ok(json!({
         "type": "image_url",
-        "image_url": { "url": data_url }
+        "image_url": { "url": encode_data_url(&bytes, mime) }
     }))
 }
 
-fn video_source_to_content(video_source: &str, max_size_mb: u64) -> Result<Value, String> {
+fn video_source_to_content(
+    video_source: &str,
+    max_size_mb: u64,
+    policy: &LocalFileAccessConfig,
+) -> Result<Value, String> {
     if is_http_url(video_source) {
         return Ok(json!({
             "type": "video_url",
@@ -90,23 +101,12 @@ fn video_source_to_content(video_source: &str, max_size_mb: u64) -> Result<Value
         }));
     }
 
-    let path = std::path::Path::new(video_source);
-    let meta = std::fs::metadata(path).map_err(|_| "Video file not found".to_string())?;
-    let max_size = max_size_mb * 1024 * 1024;
-    if meta.len() > max_size {
-        return Err(format!(
-            "Video file too large ({} bytes), max {} MB",
-            meta.len(),
-            max_size_mb
-        ));
-    }
-
-    let ext = file_ext(path).ok_or("Unsupported video format".to_string())?;
+    let (bytes, path) = read_local_media(video_source, max_size_mb, policy, "Video")?;
+    let ext = file_ext(&path).ok_or("Unsupported video format".to_string())?;
     let mime = mime_for_video_extension(&ext).ok_or("Unsupported video format".to_string())?;
-    let data_url = encode_file_as_data_url(path, mime)?;
     Ok(json!({
         "type": "video_url",
-        "video_url": { "url": data_url }
+        "video_url": { "url": encode_data_url(&bytes, mime) }
     }))
 }
 
@@ -273,14 +273,13 @@ pub fn tool_specs() -> Vec<Value> {
 pub async fn call_tool(
     zai: &ZaiConfig,
     upstream_proxy: UpstreamProxyConfig,
+    local_files: &LocalFileAccessConfig,
     timeout_secs: u64,
     tool_name: &str,
     arguments: &Value,
 ) -> Result<Value, String> {
-    let api_key = zai.api_key.trim();
-    if api_key.is_empty() {
-        return Err("z.ai api_key is missing".to_string());
-    }
+    let api_key = crate::proxy::providers::zai::pick_api_key(zai).ok_or("z.ai api_key is missing")?;
+    let api_key = api_key.as_str();
 
     let client = build_client(upstream_proxy, timeout_secs)?;
 
@@ -304,7 +303,7 @@ pub async fn call_tool(
                 _ => return Err("Invalid output_type".to_string()),
             };
 
-            let image = image_source_to_content(image_source, 5)?;
+            let image = image_source_to_content(image_source, 5, local_files)?;
             vision_chat_completion(&client, api_key, system_prompt, vec![image], prompt).await?
         }
         "extract_text_from_screenshot" => {
@@ -318,7 +317,7 @@ pub async fn call_tool(
                     prompt.push_str(&format!("\n\nLanguage hint: {}", lang.trim()));
                 }
             }
-            let image = image_source_to_content(image_source, 5)?;
+            let image = image_source_to_content(image_source, 5, local_files)?;
             let system_prompt = "Extract text from the screenshot accurately. Preserve code formatting. If unsure, say what is uncertain.";
             vision_chat_completion(&client, api_key, system_prompt, vec![image], &prompt).await?
         }
@@ -333,7 +332,7 @@ pub async fn call_tool(
                     prompt.push_str(&format!("\n\nContext: {}", ctx.trim()));
                 }
             }
-            let image = image_source_to_content(image_source, 5)?;
+            let image = image_source_to_content(image_source, 5, local_files)?;
             let system_prompt = "Diagnose the error shown in the screenshot. Identify root cause, propose fixes and verification steps.";
             vision_chat_completion(&client, api_key, system_prompt, vec![image], &prompt).await?
         }
@@ -348,7 +347,7 @@ pub async fn call_tool(
                     prompt.push_str(&format!("\n\nDiagram type: {}", diagram_type.trim()));
                 }
             }
-            let image = image_source_to_content(image_source, 5)?;
+            let image = image_source_to_content(image_source, 5, local_files)?;
             let system_prompt = "Explain the technical diagram. Describe components, relationships, data flows, and key assumptions.";
             vision_chat_completion(&client, api_key, system_prompt, vec![image], &prompt).await?
         }
@@ -363,7 +362,7 @@ pub async fn call_tool(
                     prompt.push_str(&format!("\n\nFocus: {}", focus.trim()));
                 }
             }
-            let image = image_source_to_content(image_source, 5)?;
+            let image = image_source_to_content(image_source, 5, local_files)?;
             let system_prompt = "Analyze the chart/dashboard and extract insights, trends, anomalies, and recommendations.";
             vision_chat_completion(&client, api_key, system_prompt, vec![image], &prompt).await?
         }
@@ -378,8 +377,8 @@ pub async fn call_tool(
                 .ok_or("Missing actual_image_source")?;
             let prompt = arguments.get("prompt").and_then(|v| v.as_str()).ok_or("Missing prompt")?;
 
-            let expected_img = image_source_to_content(expected, 5)?;
-            let actual_img = image_source_to_content(actual, 5)?;
+            let expected_img = image_source_to_content(expected, 5, local_files)?;
+            let actual_img = image_source_to_content(actual, 5, local_files)?;
             let system_prompt = "Compare the two UI screenshots and report differences grouped by severity. Include actionable fix suggestions.";
             vision_chat_completion(
                 &client,
@@ -396,7 +395,7 @@ pub async fn call_tool(
                 .and_then(|v| v.as_str())
                 .ok_or("Missing image_source")?;
             let prompt = arguments.get("prompt").and_then(|v| v.as_str()).ok_or("Missing prompt")?;
-            let image = image_source_to_content(image_source, 5)?;
+            let image = image_source_to_content(image_source, 5, local_files)?;
             let system_prompt = "Analyze the image. Be precise and include relevant details.";
             vision_chat_completion(&client, api_key, system_prompt, vec![image], prompt).await?
         }
@@ -406,7 +405,7 @@ pub async fn call_tool(
                 .and_then(|v| v.as_str())
                 .ok_or("Missing video_source")?;
             let prompt = arguments.get("prompt").and_then(|v| v.as_str()).ok_or("Missing prompt")?;
-            let video = video_source_to_content(video_source, 8)?;
+            let video = video_source_to_content(video_source, 8, local_files)?;
             let system_prompt = "Analyze the video content according to the user's request.";
             vision_chat_completion(&client, api_key, system_prompt, vec![video], prompt).await?
         }
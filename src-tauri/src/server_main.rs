@@ -32,6 +32,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             proxy_config.port = p;
         }
     }
+    // 设置 ADMIN_PORT 时启用独立管理端口 (Admin UI/API 不再暴露在主端口上)
+    if let Ok(port_str) = std::env::var("ADMIN_PORT") {
+        if let Ok(p) = port_str.parse() {
+            proxy_config.admin_listener.enabled = true;
+            proxy_config.admin_listener.port = p;
+        }
+    }
+    if let Ok(host) = std::env::var("ADMIN_HOST") {
+        proxy_config.admin_listener.host = host;
+    }
     
     // 3. Init TokenManager
     // Use get_data_dir() (not get_accounts_dir()) - TokenManager appends "/accounts" internally
@@ -60,13 +70,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         bind_addr.to_string(),
         proxy_config.port,
         token_manager,
-        proxy_config.custom_mapping.clone(),
+        proxy_config.routing_rules.clone(),
         proxy_config.request_timeout,
         proxy_config.upstream_proxy.clone(),
         proxy::ProxySecurityConfig::from_proxy_config(&proxy_config),
         proxy_config.zai.clone(),
         monitor,
         proxy_config.experimental.clone(),
+        proxy_config.thinking.clone(),
+        proxy_config.image_storage.clone(),
+        proxy_config.image_generation.clone(),
+        proxy_config.image_preprocess.clone(),
+        proxy_config.media_fetch.clone(),
+        proxy_config.model_fallback.clone(),
+        proxy_config.background_tasks.clone(),
+        proxy_config.parameter_policies.clone(),
+        proxy_config.hooks.clone(),
+        proxy_config.providers.clone(),
+        proxy_config.admin_listener.clone(),
     ).await.map_err(|e| format!("Server start failed: {}", e))?;
 
     println!("Server is valid and running!");
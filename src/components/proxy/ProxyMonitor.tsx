@@ -22,6 +22,7 @@ interface ProxyRequestLog {
     input_tokens?: number;
     output_tokens?: number;
     account_email?: string;
+    background_downgrade?: string;
 }
 
 interface ProxyStats {
@@ -282,6 +283,12 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
                                         <span className="font-mono font-semibold text-gray-900 dark:text-white text-xs">{selectedLog.account_email}</span>
                                     </div>
                                 )}
+                                {selectedLog.background_downgrade && (
+                                    <div className="mt-5 pt-5 border-t border-gray-200 dark:border-slate-700">
+                                        <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-2">{t('monitor.details.background_downgrade')}</span>
+                                        <span className="font-mono font-semibold text-amber-600 dark:text-amber-400 text-xs break-all">{selectedLog.background_downgrade}</span>
+                                    </div>
+                                )}
                             </div>
 
                             {/* Payloads */}
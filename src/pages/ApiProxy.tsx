@@ -22,9 +22,10 @@ import {
     Activity,
     Check,
     X,
-    Edit2
+    Edit2,
+    ArrowUp
 } from 'lucide-react';
-import { AppConfig, ProxyConfig, StickySessionConfig } from '../types/config';
+import { AppConfig, ModelRoutingRule, ProxyConfig, StickySessionConfig } from '../types/config';
 import HelpTooltip from '../components/common/HelpTooltip';
 import ModalDialog from '../components/common/ModalDialog';
 import { showToast } from '../components/common/ToastContainer';
@@ -32,6 +33,21 @@ import { cn } from '../utils/cn';
 import { useProxyModels } from '../hooks/useProxyModels';
 import GroupedSelect, { SelectOption } from '../components/common/GroupedSelect';
 
+// 规则是否带有附加条件 (条件只能在配置文件中编辑，列表中仅做标记)
+const hasConditions = (rule: ModelRoutingRule) => {
+    const c = rule.conditions;
+    if (!c) return false;
+    return !!(
+        c.client_keys?.length ||
+        c.protocols?.length ||
+        c.has_tools != null ||
+        c.has_images != null ||
+        c.min_prompt_tokens != null ||
+        c.max_prompt_tokens != null ||
+        (c.headers && Object.keys(c.headers).length > 0)
+    );
+};
+
 interface ProxyStatus {
     running: boolean;
     port: number;
@@ -152,7 +168,7 @@ export default function ApiProxy() {
     const [zaiNewMappingFrom, setZaiNewMappingFrom] = useState('');
     const [zaiNewMappingTo, setZaiNewMappingTo] = useState('');
     const [customMappingValue, setCustomMappingValue] = useState(''); // 自定义映射表单的选中值
-    const [editingKey, setEditingKey] = useState<string | null>(null);
+    const [editingIndex, setEditingIndex] = useState<number | null>(null);
     const [editingValue, setEditingValue] = useState<string>('');
 
     // Modal states
@@ -215,26 +231,57 @@ export default function ApiProxy() {
         }
     };
 
-    // 专门处理模型映射的热更新 (全量)
-    const handleMappingUpdate = async (type: 'custom', key: string, value: string) => {
+    // 专门处理路由规则的热更新 (全量)
+    const updateRoutingRules = async (rules: ModelRoutingRule[], successMessage?: string) => {
         if (!appConfig) return;
 
-        console.log('[DEBUG] handleMappingUpdate called:', { type, key, value });
-
-        const newConfig = { ...appConfig.proxy };
-        newConfig.custom_mapping = { ...(newConfig.custom_mapping || {}), [key]: value };
+        const newConfig = { ...appConfig.proxy, routing_rules: rules };
 
         try {
             await invoke('update_model_mapping', { config: newConfig });
             setAppConfig({ ...appConfig, proxy: newConfig });
-            console.log('[DEBUG] Mapping updated successfully');
-            showToast(t('common.saved'), 'success');
+            if (successMessage) showToast(successMessage, 'success');
         } catch (error) {
-            console.error('Failed to update mapping:', error);
+            console.error('Failed to update routing rules:', error);
             showToast(`${t('common.error')}: ${error}`, 'error');
         }
     };
 
+    const simpleRule = (pattern: string, target: string): ModelRoutingRule => ({
+        enabled: true,
+        pattern,
+        pattern_type: 'glob',
+        target,
+    });
+
+    // 添加映射: 已有同名的无条件通配符规则时更新目标，否则追加到末尾
+    const handleMappingUpdate = async (pattern: string, target: string) => {
+        if (!appConfig) return;
+        const rules = [...(appConfig.proxy.routing_rules || [])];
+        const index = rules.findIndex(r => r.pattern === pattern && r.pattern_type === 'glob' && !hasConditions(r));
+        if (index >= 0) {
+            rules[index] = { ...rules[index], target };
+        } else {
+            rules.push(simpleRule(pattern, target));
+        }
+        await updateRoutingRules(rules, t('common.saved'));
+    };
+
+    const handleRuleTargetUpdate = async (index: number, target: string) => {
+        if (!appConfig) return;
+        const rules = [...(appConfig.proxy.routing_rules || [])];
+        rules[index] = { ...rules[index], target };
+        await updateRoutingRules(rules, t('common.saved'));
+    };
+
+    // 规则按顺序匹配，上移即提高优先级
+    const handleMoveRuleUp = async (index: number) => {
+        if (!appConfig || index <= 0) return;
+        const rules = [...(appConfig.proxy.routing_rules || [])];
+        [rules[index - 1], rules[index]] = [rules[index], rules[index - 1]];
+        await updateRoutingRules(rules);
+    };
+
     const handleResetMapping = () => {
         if (!appConfig) return;
         setIsResetConfirmOpen(true);
@@ -244,69 +291,47 @@ export default function ApiProxy() {
         if (!appConfig) return;
         setIsResetConfirmOpen(false);
 
-        // 恢复到默认映射值 (空映射)
-        const newConfig = {
-            ...appConfig.proxy,
-            custom_mapping: {}
-        };
-
-        try {
-            await invoke('update_model_mapping', { config: newConfig });
-            setAppConfig({ ...appConfig, proxy: newConfig });
-            showToast(t('common.success'), 'success');
-        } catch (error) {
-            console.error('Failed to reset mapping:', error);
-            showToast(`${t('common.error')}: ${error}`, 'error');
-        }
+        // 恢复到默认映射值 (无路由规则)
+        await updateRoutingRules([], t('common.success'));
     };
 
 
-    // 应用预设映射 (通配符)
+    // 应用预设映射 (通配符，更具体的规则在前)
     const handleApplyPresets = async () => {
         if (!appConfig) return;
 
-        const presets: Record<string, string> = {
+        const presets: [string, string][] = [
             // OpenAI (通配符)
-            "gpt-4*": "gemini-3-pro-high",
-            "gpt-4o*": "gemini-3-flash",
-            "gpt-3.5*": "gemini-2.5-flash",
-            "o1-*": "gemini-3-pro-high",
-            "o3-*": "gemini-3-pro-high",
+            ["gpt-4o*", "gemini-3-flash"],
+            ["gpt-4*", "gemini-3-pro-high"],
+            ["gpt-3.5*", "gemini-2.5-flash"],
+            ["o1-*", "gemini-3-pro-high"],
+            ["o3-*", "gemini-3-pro-high"],
 
             // Claude (通配符)
-            "claude-3-5-sonnet-*": "claude-sonnet-4-5",
-            "claude-3-opus-*": "claude-opus-4-5-thinking",
-            "claude-opus-4-*": "claude-opus-4-5-thinking",
-            "claude-haiku-*": "gemini-2.5-flash-lite",
-            "claude-3-haiku-*": "gemini-2.5-flash-lite",
-        };
-
-        const newConfig = {
-            ...appConfig.proxy,
-            custom_mapping: { ...appConfig.proxy.custom_mapping, ...presets }
-        };
-
-        try {
-            await invoke('update_model_mapping', { config: newConfig });
-            setAppConfig({ ...appConfig, proxy: newConfig });
-            showToast(t('proxy.router.presets_applied'), 'success');
-        } catch (error) {
-            console.error('Failed to apply presets:', error);
-            showToast(`${t('common.error')}: ${error}`, 'error');
+            ["claude-3-5-sonnet-*", "claude-sonnet-4-5"],
+            ["claude-3-opus-*", "claude-opus-4-5-thinking"],
+            ["claude-opus-4-*", "claude-opus-4-5-thinking"],
+            ["claude-haiku-*", "gemini-2.5-flash-lite"],
+            ["claude-3-haiku-*", "gemini-2.5-flash-lite"],
+        ];
+
+        const rules = [...(appConfig.proxy.routing_rules || [])];
+        for (const [pattern, target] of presets) {
+            const index = rules.findIndex(r => r.pattern === pattern && r.pattern_type === 'glob');
+            if (index >= 0) {
+                rules[index] = { ...rules[index], target };
+            } else {
+                rules.push(simpleRule(pattern, target));
+            }
         }
+        await updateRoutingRules(rules, t('proxy.router.presets_applied'));
     };
 
-    const handleRemoveCustomMapping = async (key: string) => {
-        if (!appConfig || !appConfig.proxy.custom_mapping) return;
-        const newCustom = { ...appConfig.proxy.custom_mapping };
-        delete newCustom[key];
-        const newConfig = { ...appConfig.proxy, custom_mapping: newCustom };
-        try {
-            await invoke('update_model_mapping', { config: newConfig });
-            setAppConfig({ ...appConfig, proxy: newConfig });
-        } catch (error) {
-            console.error('Failed to remove custom mapping:', error);
-        }
+    const handleRemoveCustomMapping = async (index: number) => {
+        if (!appConfig || !appConfig.proxy.routing_rules) return;
+        const rules = appConfig.proxy.routing_rules.filter((_, i) => i !== index);
+        await updateRoutingRules(rules);
     };
 
     const updateProxyConfig = (updates: Partial<ProxyConfig>) => {
@@ -351,6 +376,10 @@ export default function ApiProxy() {
         }
     };
 
+    // api_key 与 api_keys 任一非空即可 (与后端 ZaiConfig::all_api_keys 一致)
+    const zaiHasApiKey = !!appConfig?.proxy.zai?.api_key?.trim()
+        || (appConfig?.proxy.zai?.api_keys ?? []).some(key => key.trim());
+
     const refreshZaiModels = async () => {
         if (!appConfig?.proxy.zai) return;
         setZaiModelsLoading(true);
@@ -962,7 +991,7 @@ print(response.text)`;
                                     <div className="space-y-1">
                                         <label className="text-[11px] font-medium text-gray-500 dark:text-gray-400 flex items-center justify-between">
                                             <span>{t('proxy.config.zai.api_key')}</span>
-                                            {!(appConfig.proxy.zai?.api_key) && (
+                                            {!zaiHasApiKey && (
                                                 <span className="text-amber-500 text-[10px] flex items-center gap-1">
                                                     <HelpTooltip text={t('proxy.config.zai.warning')} />
                                                     {t('common.required')}
@@ -986,7 +1015,7 @@ print(response.text)`;
                                             </h4>
                                             <button
                                                 onClick={refreshZaiModels}
-                                                disabled={zaiModelsLoading || !appConfig.proxy.zai?.api_key}
+                                                disabled={zaiModelsLoading || !zaiHasApiKey}
                                                 className="btn btn-ghost btn-xs gap-1"
                                             >
                                                 <RefreshCw size={12} className={zaiModelsLoading ? 'animate-spin' : ''} />
@@ -1295,14 +1324,20 @@ print(response.text)`;
                                             </div>
                                             <div className="overflow-y-auto max-h-[180px] border border-gray-100 dark:border-white/5 rounded-lg bg-gray-50/10 dark:bg-white/5 p-3" data-custom-mapping-list>
                                                 <div className="grid grid-cols-1 md:grid-cols-2 gap-x-6 gap-y-2">
-                                                    {appConfig.proxy.custom_mapping && Object.entries(appConfig.proxy.custom_mapping).length > 0 ? (
-                                                        Object.entries(appConfig.proxy.custom_mapping).map(([key, val]) => (
-                                                            <div key={key} className={`flex items-center justify-between p-1.5 rounded-md transition-all border group ${editingKey === key ? 'bg-blue-50/80 dark:bg-blue-900/15 border-blue-300/50 dark:border-blue-500/30 shadow-sm' : 'border-transparent hover:bg-gray-100 dark:hover:bg-white/5 hover:border-gray-200 dark:hover:border-white/10'}`}>
+                                                    {appConfig.proxy.routing_rules && appConfig.proxy.routing_rules.length > 0 ? (
+                                                        appConfig.proxy.routing_rules.map((rule, index) => (
+                                                            <div key={`${index}-${rule.pattern}`} className={`flex items-center justify-between p-1.5 rounded-md transition-all border group ${editingIndex === index ? 'bg-blue-50/80 dark:bg-blue-900/15 border-blue-300/50 dark:border-blue-500/30 shadow-sm' : 'border-transparent hover:bg-gray-100 dark:hover:bg-white/5 hover:border-gray-200 dark:hover:border-white/10'} ${rule.enabled ? '' : 'opacity-50'}`}>
                                                                 <div className="flex items-center gap-2.5 overflow-hidden flex-1">
-                                                                    <span className="font-mono text-[10px] font-bold text-blue-600 dark:text-blue-400 truncate max-w-[140px]" title={key}>{key}</span>
+                                                                    <span className="font-mono text-[9px] text-gray-400 dark:text-gray-600 shrink-0">{index + 1}</span>
+                                                                    <span className="font-mono text-[10px] font-bold text-blue-600 dark:text-blue-400 truncate max-w-[140px]" title={rule.name || rule.pattern}>
+                                                                        {rule.pattern_type === 'regex' ? `/${rule.pattern}/` : rule.pattern}
+                                                                    </span>
+                                                                    {hasConditions(rule) && (
+                                                                        <span className="text-[9px] px-1 rounded bg-amber-100 dark:bg-amber-900/30 text-amber-600 dark:text-amber-400 shrink-0" title={JSON.stringify(rule.conditions)}>if</span>
+                                                                    )}
                                                                     <ArrowRight size={10} className="text-gray-300 dark:text-gray-600 shrink-0" />
 
-                                                                    {editingKey === key ? (
+                                                                    {editingIndex === index ? (
                                                                         <div className="flex-1 mr-2">
                                                                             <GroupedSelect
                                                                                 value={editingValue}
@@ -1314,19 +1349,19 @@ print(response.text)`;
                                                                         </div>
                                                                     ) : (
                                                                         <span className="font-mono text-[10px] text-gray-500 dark:text-gray-400 truncate cursor-pointer hover:text-blue-500"
-                                                                            onClick={() => { setEditingKey(key); setEditingValue(val); }}
-                                                                            title={val}>{val}</span>
+                                                                            onClick={() => { setEditingIndex(index); setEditingValue(rule.target); }}
+                                                                            title={rule.target}>{rule.target}</span>
                                                                     )}
                                                                 </div>
 
                                                                 <div className="flex items-center gap-1.5 shrink-0">
-                                                                    {editingKey === key ? (
+                                                                    {editingIndex === index ? (
                                                                         <div className="flex items-center gap-1 bg-white dark:bg-gray-800 rounded-md border border-blue-200 dark:border-blue-800 p-0.5 shadow-sm">
                                                                             <button
                                                                                 className="btn btn-ghost btn-xs text-primary hover:bg-blue-50 dark:hover:bg-blue-900/30 p-0 h-6 w-6 min-h-0"
                                                                                 onClick={() => {
-                                                                                    handleMappingUpdate('custom', key, editingValue);
-                                                                                    setEditingKey(null);
+                                                                                    handleRuleTargetUpdate(index, editingValue);
+                                                                                    setEditingIndex(null);
                                                                                 }}
                                                                                 title={t('common.save') || 'Save'}
                                                                             >
@@ -1335,7 +1370,7 @@ print(response.text)`;
                                                                             <div className="w-[1px] h-3 bg-gray-200 dark:bg-gray-700" />
                                                                             <button
                                                                                 className="btn btn-ghost btn-xs text-gray-400 hover:bg-gray-100 dark:hover:bg-gray-700 p-0 h-6 w-6 min-h-0"
-                                                                                onClick={() => setEditingKey(null)}
+                                                                                onClick={() => setEditingIndex(null)}
                                                                                 title={t('common.cancel') || 'Cancel'}
                                                                             >
                                                                                 <X size={14} strokeWidth={3} />
@@ -1343,16 +1378,25 @@ print(response.text)`;
                                                                         </div>
                                                                     ) : (
                                                                         <div className="flex items-center gap-1 opacity-0 group-hover:opacity-100 transition-opacity">
+                                                                            {index > 0 && (
+                                                                                <button
+                                                                                    className="btn btn-ghost btn-xs text-gray-400 hover:text-blue-500 hover:bg-blue-50 dark:hover:bg-white/10 p-0 h-6 w-6 min-h-0"
+                                                                                    onClick={() => handleMoveRuleUp(index)}
+                                                                                    title={t('proxy.router.move_up') || 'Move up'}
+                                                                                >
+                                                                                    <ArrowUp size={12} />
+                                                                                </button>
+                                                                            )}
                                                                             <button
                                                                                 className="btn btn-ghost btn-xs text-gray-400 hover:text-blue-500 hover:bg-blue-50 dark:hover:bg-white/10 p-0 h-6 w-6 min-h-0"
-                                                                                onClick={() => { setEditingKey(key); setEditingValue(val); }}
+                                                                                onClick={() => { setEditingIndex(index); setEditingValue(rule.target); }}
                                                                                 title={t('common.edit') || 'Edit'}
                                                                             >
                                                                                 <Edit2 size={12} />
                                                                             </button>
                                                                             <button
                                                                                 className="btn btn-ghost btn-xs text-error hover:bg-red-50 dark:hover:bg-red-900/20 p-0 h-6 w-6 min-h-0"
-                                                                                onClick={() => handleRemoveCustomMapping(key)}
+                                                                                onClick={() => handleRemoveCustomMapping(index)}
                                                                                 title={t('common.delete') || 'Delete'}
                                                                             >
                                                                                 <Trash2 size={12} />
@@ -1399,7 +1443,7 @@ print(response.text)`;
                                                         const k = (document.getElementById('custom-key') as HTMLInputElement).value;
                                                         const v = customMappingValue;
                                                         if (k && v) {
-                                                            handleMappingUpdate('custom', k, v);
+                                                            handleMappingUpdate(k, v);
                                                             (document.getElementById('custom-key') as HTMLInputElement).value = '';
                                                             setCustomMappingValue(''); // 清空选择
                                                         }
@@ -10,12 +10,198 @@ export interface ProxyConfig {
     port: number;
     api_key: string;
     auto_start: boolean;
-    custom_mapping?: Record<string, string>;
+    routing_rules?: ModelRoutingRule[];
     request_timeout: number;
     enable_logging: boolean;
     upstream_proxy: UpstreamProxyConfig;
     zai?: ZaiConfig;
     scheduling?: StickySessionConfig;
+    thinking?: ThinkingBudgetConfig;
+    image_storage?: ImageStorageConfig;
+    image_generation?: ImageGenerationConfig;
+    image_preprocess?: ImagePreprocessConfig;
+    media_fetch?: MediaFetchConfig;
+    model_fallback?: ModelFallbackConfig;
+    background_tasks?: BackgroundTaskConfig;
+    parameter_policies?: ParameterPolicy[];
+    hooks?: HookConfig;
+    providers?: UpstreamProviderConfig[];
+    admin_listener?: AdminListenerConfig;
+}
+
+export interface RouteConditions {
+    client_keys?: string[];
+    // openai / claude / gemini
+    protocols?: string[];
+    has_tools?: boolean | null;
+    has_images?: boolean | null;
+    min_prompt_tokens?: number | null;
+    max_prompt_tokens?: number | null;
+    // 请求头名称 -> 值 (支持 * 通配符)
+    headers?: Record<string, string>;
+}
+
+export interface RouteParamOverrides {
+    temperature?: number | null;
+    top_p?: number | null;
+    max_tokens?: number | null;
+    thinking_budget?: number | null;
+}
+
+// 模型路由规则: 按顺序匹配，首个命中的规则生效
+export interface ModelRoutingRule {
+    name?: string | null;
+    enabled: boolean;
+    pattern: string;
+    pattern_type: 'glob' | 'regex';
+    target: string;
+    // 上游服务商 id ('google' 或 providers 中的 id)，为空时自动调度
+    provider?: string | null;
+    conditions?: RouteConditions;
+    overrides?: RouteParamOverrides;
+}
+
+export interface ThinkingBudgetConfig {
+    // 模型 (支持 * 通配符，精确匹配优先，其次字面字符最多的模式) -> 默认 thinkingBudget, 0 表示关闭
+    model_defaults: Record<string, number>;
+}
+
+export interface ImageStorageConfig {
+    // 0 表示永久保留
+    retention_hours: number;
+    url_ttl_secs: number;
+    public_base_url?: string | null;
+}
+
+export interface ImageGenerationConfig {
+    // 单个账号最多承担的图像数量
+    images_per_account: number;
+    // 同时运行的异步任务上限
+    max_concurrent_jobs: number;
+}
+
+export interface ImagePreprocessConfig {
+    enabled: boolean;
+    max_dimension: number;
+    low_detail_dimension: number;
+    jpeg_quality: number;
+    reencode_threshold_kb: number;
+}
+
+export interface MediaFetchConfig {
+    enabled: boolean;
+    timeout_secs: number;
+    max_mb: number;
+    allowed_schemes: string[];
+    // 支持 *.example.com，为空表示不限制
+    allowed_hosts: string[];
+    block_private_networks: boolean;
+    max_redirects: number;
+    // 0 表示不缓存
+    cache_ttl_secs: number;
+    cache_max_mb: number;
+    local_files?: LocalFileAccessConfig;
+}
+
+export interface LocalFileAccessConfig {
+    enabled: boolean;
+    // 绝对路径，为空时拒绝所有本地文件
+    allowed_roots: string[];
+    max_mb: number;
+}
+
+export interface ModelFallbackConfig {
+    enabled: boolean;
+    // 模型 (支持 * 通配符) -> 按顺序尝试的回退模型
+    chains: Record<string, string[]>;
+}
+
+export interface BackgroundTaskConfig {
+    enabled: boolean;
+    // 最后一条用户消息超过该长度 (字节) 时不做识别
+    max_message_length: number;
+    // 按顺序匹配，先命中的类别生效
+    categories: BackgroundTaskCategory[];
+}
+
+export interface BackgroundTaskCategory {
+    name: string;
+    enabled: boolean;
+    keywords: string[];
+    // 正则表达式 (在消息中搜索)
+    patterns: string[];
+    target_model: string;
+}
+
+// 请求/响应脚本钩子 (Rhai)，脚本读写 `data`，只读 `ctx`
+export interface HookConfig {
+    enabled: boolean;
+    // 单个脚本执行超时 (毫秒)；upstream_event 钩子每个 SSE 事件同步执行，上限 5ms
+    timeout_ms: number;
+    hooks: ScriptHook[];
+}
+
+export type HookPoint = 'request_parsed' | 'before_upstream' | 'upstream_event' | 'before_response';
+
+export interface ScriptHook {
+    name: string;
+    enabled: boolean;
+    points: HookPoint[];
+    // 内联脚本，未设置时读取 file
+    script?: string;
+    file?: string;
+}
+
+// 额外的上游服务商 (Anthropic / OpenAI 兼容端点)
+export interface UpstreamProviderConfig {
+    // 唯一标识，'google' 保留给内置账号池
+    id: string;
+    enabled: boolean;
+    kind: 'anthropic' | 'openai';
+    base_url: string;
+    // 轮询使用，本地服务可为空
+    api_keys: string[];
+    // 客户端模型 (支持 * 通配符) -> 上游模型
+    model_mapping: Record<string, string>;
+    default_model?: string | null;
+    // 参与自动调度的模型 (支持通配符)，为空表示所有模型
+    models: string[];
+    dispatch_mode: 'rule_only' | 'exclusive' | 'pooled' | 'fallback';
+    // pooled 模式权重 (每个 Google 账号为 1)
+    weight: number;
+}
+
+// 生成参数策略，多条命中时按顺序应用
+export interface ParameterPolicy {
+    name: string;
+    enabled: boolean;
+    // 映射后模型名 (支持通配符)，为空表示所有模型
+    models: string[];
+    // 客户端 API Key，为空表示所有客户端
+    client_keys: string[];
+    temperature?: ParamRule;
+    top_p?: ParamRule;
+    top_k?: ParamRule;
+    max_tokens?: ParamRule;
+    system_prepend?: string;
+    system_append?: string;
+    // OFF / LOW / MEDIUM / HIGH / NONE
+    safety_threshold?: string;
+}
+
+// 取值顺序: force > 客户端值 > default，之后按 min/max 截断
+export interface ParamRule {
+    default?: number;
+    min?: number;
+    max?: number;
+    force?: number;
+}
+
+export interface AdminListenerConfig {
+    enabled: boolean;
+    host: string;
+    port: number;
+    unix_socket?: string | null;
 }
 
 export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst';
@@ -43,8 +229,18 @@ export interface ZaiModelDefaults {
 export interface ZaiConfig {
     enabled: boolean;
     base_url: string;
+    // OpenAI 兼容 PaaS 端点 (OpenAI / Gemini 协议客户端使用)
+    openai_base_url?: string;
     api_key: string;
+    // 额外的 Key，与 api_key 一起轮询，429 的 Key 暂时跳过
+    api_keys?: string[];
     dispatch_mode: ZaiDispatchMode;
+    // pooled 模式权重 (每个 Google 账号为 1)
+    pool_weight?: number;
+    // pooled 模式固定流量百分比 (0-100)，设置后忽略 pool_weight
+    pool_percent?: number | null;
+    // z.ai 失败 (全部 Key 限流 / 5xx / 连接失败) 时回退到 Google 账号池 (默认关闭，exclusive 模式下不生效)
+    fallback_to_google?: boolean;
     model_mapping?: Record<string, string>;
     models: ZaiModelDefaults;
     mcp: ZaiMcpConfig;